@@ -1,4 +1,5 @@
 use candid::CandidType;
+use ic_mple_utils::store::StorageAccessError;
 use serde::{Deserialize, Serialize};
 use thiserror::Error;
 
@@ -9,4 +10,7 @@ pub enum AuthError {
 
     #[error("AnonimousUserNotAllowed")]
     AnonimousUserNotAllowed,
+
+    #[error("StorageAccess: {0}")]
+    StorageAccess(#[from] StorageAccessError),
 }
@@ -0,0 +1,241 @@
+use std::collections::HashMap;
+use std::hash::Hash;
+
+use candid::{CandidType, Principal};
+use ic_mple_utils::store::Storage;
+use serde::de::DeserializeOwned;
+
+use crate::{AuthService, AuthServiceStorage};
+
+/// The result of the evaluation of an `InspectPolicy`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum InspectDecision {
+    Accept,
+    Reject(String),
+}
+
+impl InspectDecision {
+    /// Accepts the message if the decision is `Accept`, otherwise it rejects it with the reason.
+    /// This must be called in the `canister_inspect_message` hook.
+    pub fn apply(self) {
+        match self {
+            InspectDecision::Accept => ic_cdk::api::accept_message(),
+            InspectDecision::Reject(reason) => ic_cdk::trap(reason),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+enum MethodRule<T> {
+    Allow,
+    RequirePermission(T),
+}
+
+/// The rules to accept the update calls in the `canister_inspect_message` hook.
+/// The methods not explicitly allowed are rejected.
+///
+/// ```ignore
+/// let policy = InspectPolicy::new()
+///     .deny_anonymous()
+///     .allow_method("get_counter")
+///     .require_permission("schedule_tasks", Permission::Scheduler);
+///
+/// #[ic_cdk::inspect_message]
+/// fn inspect_message() {
+///     AUTH.with_borrow(|auth| policy.inspect(auth));
+/// }
+/// ```
+#[derive(Debug, Clone)]
+pub struct InspectPolicy<T> {
+    methods: HashMap<String, MethodRule<T>>,
+    deny_anonymous: bool,
+    max_arg_size: Option<usize>,
+}
+
+impl<T> Default for InspectPolicy<T> {
+    fn default() -> Self {
+        Self {
+            methods: HashMap::new(),
+            deny_anonymous: false,
+            max_arg_size: None,
+        }
+    }
+}
+
+impl<T> InspectPolicy<T>
+where
+    T: PartialEq + CandidType + Eq + serde::Serialize + Hash + Clone + std::fmt::Debug,
+    T: DeserializeOwned,
+{
+    /// Creates a policy that rejects all the methods
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Accepts the calls to the method from any caller
+    pub fn allow_method(mut self, method: impl Into<String>) -> Self {
+        self.methods.insert(method.into(), MethodRule::Allow);
+        self
+    }
+
+    /// Accepts the calls to the method only from the callers with the permission
+    pub fn require_permission(mut self, method: impl Into<String>, permission: T) -> Self {
+        self.methods
+            .insert(method.into(), MethodRule::RequirePermission(permission));
+        self
+    }
+
+    /// Rejects all the calls from the anonymous principal
+    pub fn deny_anonymous(mut self) -> Self {
+        self.deny_anonymous = true;
+        self
+    }
+
+    /// Rejects all the calls with an argument larger than `max_arg_size` bytes
+    pub fn max_arg_size(mut self, max_arg_size: usize) -> Self {
+        self.max_arg_size = Some(max_arg_size);
+        self
+    }
+
+    /// Evaluates the policy for a call to `method` from `caller` with an argument of `arg_size` bytes
+    pub fn evaluate<S: Storage<AuthServiceStorage<T>>>(
+        &self,
+        auth: &AuthService<S, T>,
+        method: &str,
+        caller: Principal,
+        arg_size: usize,
+    ) -> InspectDecision {
+        if let Some(max_arg_size) = self.max_arg_size
+            && arg_size > max_arg_size
+        {
+            return InspectDecision::Reject(format!(
+                "the argument of {method} is larger than {max_arg_size} bytes"
+            ));
+        }
+
+        if self.deny_anonymous && caller == Principal::anonymous() {
+            return InspectDecision::Reject("anonymous callers are not allowed".to_string());
+        }
+
+        match self.methods.get(method) {
+            Some(MethodRule::Allow) => InspectDecision::Accept,
+            Some(MethodRule::RequirePermission(permission)) => {
+                match auth.try_has_all_permissions(&caller, std::slice::from_ref(permission)) {
+                    Ok(true) => InspectDecision::Accept,
+                    Ok(false) => InspectDecision::Reject(format!(
+                        "the caller {caller} is not authorized to call {method}"
+                    )),
+                    Err(err) => InspectDecision::Reject(err.to_string()),
+                }
+            }
+            None => InspectDecision::Reject(format!("the method {method} is not allowed")),
+        }
+    }
+
+    /// Evaluates the policy for the current message and applies the decision.
+    /// This must be called in the `canister_inspect_message` hook.
+    pub fn inspect<S: Storage<AuthServiceStorage<T>>>(&self, auth: &AuthService<S, T>) {
+        let method = ic_cdk::api::msg_method_name();
+        let arg_size = ic_cdk::api::msg_arg_data().len();
+        self.evaluate(auth, &method, ic_cdk::api::msg_caller(), arg_size)
+            .apply();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::cell::RefCell;
+
+    use ic_stable_structures::memory_manager::{MemoryId, MemoryManager};
+    use ic_stable_structures::{BTreeMap, DefaultMemoryImpl};
+    use serde::{Deserialize, Serialize};
+
+    use super::*;
+
+    #[derive(Debug, Clone, CandidType, Serialize, Deserialize, Hash, PartialEq, Eq)]
+    enum Permission {
+        Scheduler,
+    }
+
+    type TestAuthService = AuthService<RefCell<AuthServiceStorage<Permission>>, Permission>;
+
+    fn auth_service() -> TestAuthService {
+        AuthService::new(RefCell::new(BTreeMap::new(
+            MemoryManager::init(DefaultMemoryImpl::default()).get(MemoryId::new(1)),
+        )))
+    }
+
+    fn policy() -> InspectPolicy<Permission> {
+        InspectPolicy::new()
+            .allow_method("get_counter")
+            .require_permission("schedule_tasks", Permission::Scheduler)
+            .max_arg_size(1024)
+    }
+
+    #[test]
+    fn should_accept_the_allowed_methods() {
+        let auth = auth_service();
+        let caller = Principal::from_slice(&[1; 29]);
+
+        assert_eq!(
+            policy().evaluate(&auth, "get_counter", caller, 10),
+            InspectDecision::Accept
+        );
+        assert_eq!(
+            policy().evaluate(&auth, "get_counter", Principal::anonymous(), 10),
+            InspectDecision::Accept
+        );
+        assert!(matches!(
+            policy().evaluate(&auth, "reset", caller, 10),
+            InspectDecision::Reject(_)
+        ));
+    }
+
+    #[test]
+    fn should_require_the_permission() {
+        let mut auth = auth_service();
+        let scheduler = Principal::from_slice(&[1; 29]);
+        let stranger = Principal::from_slice(&[2; 29]);
+        auth.add_permissions(scheduler, vec![Permission::Scheduler])
+            .unwrap();
+
+        assert_eq!(
+            policy().evaluate(&auth, "schedule_tasks", scheduler, 10),
+            InspectDecision::Accept
+        );
+        assert!(matches!(
+            policy().evaluate(&auth, "schedule_tasks", stranger, 10),
+            InspectDecision::Reject(_)
+        ));
+    }
+
+    #[test]
+    fn should_deny_anonymous_callers() {
+        let auth = auth_service();
+        let policy = policy().deny_anonymous();
+
+        assert!(matches!(
+            policy.evaluate(&auth, "get_counter", Principal::anonymous(), 10),
+            InspectDecision::Reject(_)
+        ));
+        assert_eq!(
+            policy.evaluate(&auth, "get_counter", Principal::from_slice(&[1; 29]), 10),
+            InspectDecision::Accept
+        );
+    }
+
+    #[test]
+    fn should_reject_large_arguments() {
+        let auth = auth_service();
+        let caller = Principal::from_slice(&[1; 29]);
+
+        assert_eq!(
+            policy().evaluate(&auth, "get_counter", caller, 1024),
+            InspectDecision::Accept
+        );
+        assert!(matches!(
+            policy().evaluate(&auth, "get_counter", caller, 1025),
+            InspectDecision::Reject(_)
+        ));
+    }
+}
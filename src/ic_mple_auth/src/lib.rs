@@ -18,6 +18,7 @@ use serde::de::DeserializeOwned;
 use crate::error::AuthError;
 
 pub mod error;
+pub mod inspect;
 
 #[derive(Debug, CandidType, PartialEq, Eq, serde::Serialize, serde::Deserialize, Clone)]
 pub struct PermissionList<
@@ -108,7 +109,7 @@ where
         principal: &Principal,
         permission: T,
     ) -> Result<(), AuthError> {
-        if self.has_all_permissions(principal, &[permission]) {
+        if self.try_has_all_permissions(principal, &[permission])? {
             Ok(())
         } else {
             Err(AuthError::NotAuthorized)
@@ -121,7 +122,7 @@ where
         principal: &Principal,
         permissions: &[T],
     ) -> Result<(), AuthError> {
-        if self.has_all_permissions(principal, permissions) {
+        if self.try_has_all_permissions(principal, permissions)? {
             Ok(())
         } else {
             Err(AuthError::NotAuthorized)
@@ -131,23 +132,31 @@ where
     /// Returns whether the user has all the required permissions
     pub fn has_all_permissions(&self, principal: &Principal, permissions: &[T]) -> bool {
         self.permission_storage.with_borrow(|permission_storage| {
-            if let Some(permissions_list) = permission_storage.get(principal) {
-                permissions
-                    .iter()
-                    .all(|item| permissions_list.permissions.contains(item))
-            } else {
-                permissions.is_empty()
-            }
+            Self::contains_all_permissions(permission_storage, principal, permissions)
         })
     }
 
+    /// Returns whether the user has all the required permissions,
+    /// or the StorageAccess error if the storage cannot be borrowed
+    pub fn try_has_all_permissions(
+        &self,
+        principal: &Principal,
+        permissions: &[T],
+    ) -> Result<bool, AuthError> {
+        Ok(self
+            .permission_storage
+            .try_with_borrow(|permission_storage| {
+                Self::contains_all_permissions(permission_storage, principal, permissions)
+            })?)
+    }
+
     /// Returns NotAuthorized error if the user does not have at least one of the permissions
     pub fn check_has_any_permission(
         &self,
         principal: &Principal,
         permissions: &[T],
     ) -> Result<(), AuthError> {
-        if self.has_any_permission(principal, permissions) {
+        if self.try_has_any_permission(principal, permissions)? {
             Ok(())
         } else {
             Err(AuthError::NotAuthorized)
@@ -157,17 +166,24 @@ where
     /// Return whether the user has at least one of the required permissions
     pub fn has_any_permission(&self, principal: &Principal, permissions: &[T]) -> bool {
         self.permission_storage.with_borrow(|permission_storage| {
-            if let Some(permissions_list) = permission_storage.get(principal) {
-                permissions
-                    .iter()
-                    .any(|item| permissions_list.permissions.contains(item))
-                    || permissions.is_empty()
-            } else {
-                permissions.is_empty()
-            }
+            Self::contains_any_permission(permission_storage, principal, permissions)
         })
     }
 
+    /// Return whether the user has at least one of the required permissions,
+    /// or the StorageAccess error if the storage cannot be borrowed
+    pub fn try_has_any_permission(
+        &self,
+        principal: &Principal,
+        permissions: &[T],
+    ) -> Result<bool, AuthError> {
+        Ok(self
+            .permission_storage
+            .try_with_borrow(|permission_storage| {
+                Self::contains_any_permission(permission_storage, principal, permissions)
+            })?)
+    }
+
     /// Add permissions to a user
     pub fn add_permissions(
         &mut self,
@@ -175,8 +191,9 @@ where
         permissions: Vec<T>,
     ) -> Result<PermissionList<T>, AuthError> {
         self.check_anonymous_principal(&principal)?;
-        self.permission_storage
-            .with_borrow_mut(|permission_storage| {
+        Ok(self
+            .permission_storage
+            .try_with_borrow_mut(|permission_storage| {
                 info!(
                     "Adding permissions {:?} to principal {}",
                     permissions, principal
@@ -188,8 +205,8 @@ where
                     existing_permissions.permissions.insert(permission);
                 }
                 permission_storage.insert(principal, existing_permissions.clone());
-                Ok(existing_permissions)
-            })
+                existing_permissions
+            })?)
     }
 
     /// Remove permissions from a user
@@ -199,8 +216,9 @@ where
         permissions: &[T],
     ) -> Result<PermissionList<T>, AuthError> {
         self.check_anonymous_principal(&principal)?;
-        self.permission_storage
-            .with_borrow_mut(|permission_storage| {
+        Ok(self
+            .permission_storage
+            .try_with_borrow_mut(|permission_storage| {
                 let mut existing_permissions =
                     permission_storage.get(&principal).unwrap_or_default();
 
@@ -217,8 +235,8 @@ where
                 } else {
                     permission_storage.remove(&principal);
                 }
-                Ok(existing_permissions)
-            })
+                existing_permissions
+            })?)
     }
 
     /// Return the user permissions
@@ -227,12 +245,54 @@ where
             .with_borrow(|permission_storage| permission_storage.get(principal).unwrap_or_default())
     }
 
+    /// Return the user permissions,
+    /// or the StorageAccess error if the storage cannot be borrowed
+    pub fn try_get_permissions(
+        &self,
+        principal: &Principal,
+    ) -> Result<PermissionList<T>, AuthError> {
+        Ok(self
+            .permission_storage
+            .try_with_borrow(|permission_storage| {
+                permission_storage.get(principal).unwrap_or_default()
+            })?)
+    }
+
     /// Clear the Whitelist state
     pub fn clear(&mut self) {
         self.permission_storage
             .with_borrow_mut(|permission_storage| permission_storage.clear_new())
     }
 
+    fn contains_all_permissions(
+        permission_storage: &AuthServiceStorage<T>,
+        principal: &Principal,
+        permissions: &[T],
+    ) -> bool {
+        if let Some(permissions_list) = permission_storage.get(principal) {
+            permissions
+                .iter()
+                .all(|item| permissions_list.permissions.contains(item))
+        } else {
+            permissions.is_empty()
+        }
+    }
+
+    fn contains_any_permission(
+        permission_storage: &AuthServiceStorage<T>,
+        principal: &Principal,
+        permissions: &[T],
+    ) -> bool {
+        if let Some(permissions_list) = permission_storage.get(principal) {
+            permissions
+                .iter()
+                .any(|item| permissions_list.permissions.contains(item))
+                || permissions.is_empty()
+        } else {
+            permissions.is_empty()
+        }
+    }
+
     fn check_anonymous_principal(&self, principal: &Principal) -> Result<(), AuthError> {
         if principal == &Principal::anonymous() {
             return Err(AuthError::AnonimousUserNotAllowed);
@@ -244,7 +304,19 @@ where
 #[cfg(test)]
 mod tests {
 
-    use std::{cell::RefCell, collections::HashSet};
+    use std::{
+        cell::RefCell,
+        collections::HashSet,
+        panic::AssertUnwindSafe,
+        rc::Rc,
+        sync::{Arc, Mutex},
+    };
+
+    use ic_mple_log::LogSettings;
+    use ic_mple_log::service::{LoggerConfigService, LoggerServiceStorage};
+    use ic_mple_utils::ic_api::{IcTrait, mock::IcMock};
+    use ic_mple_utils::store::{StorageAccessError, init_storage, is_initialized};
+    use ic_stable_structures::StableCell;
 
     use ic_stable_structures::memory_manager::{MemoryId, MemoryManager};
     use serde::Deserialize;
@@ -796,11 +868,223 @@ mod tests {
         );
     }
 
-    fn new_permission_service() -> TestPermissionService {
-        let store = RefCell::new(BTreeMap::new(
-            MemoryManager::init(DefaultMemoryImpl::default()).get(MemoryId::new(1)),
+    fn new_permission_storage() -> AuthServiceStorage<TestPermission> {
+        BTreeMap::new(MemoryManager::init(DefaultMemoryImpl::default()).get(MemoryId::new(1)))
+    }
+
+    fn check_storage_backend<S: Storage<AuthServiceStorage<TestPermission>>>(store: S) {
+        let mut permissions = AuthService::new(store);
+        let principal = Principal::from_slice(&[1; 29]);
+
+        permissions
+            .add_permissions(principal, vec![TestPermission::ReadLogs])
+            .unwrap();
+
+        assert!(permissions.has_all_permissions(&principal, &[TestPermission::ReadLogs]));
+        assert!(!permissions.has_any_permission(&principal, &[TestPermission::Admin]));
+
+        permissions
+            .remove_permissions(principal, &[TestPermission::ReadLogs])
+            .unwrap();
+
+        assert!(!permissions.has_any_permission(&principal, &[TestPermission::ReadLogs]));
+    }
+
+    #[test]
+    fn should_use_the_tokio_locks_as_storage() {
+        check_storage_backend(tokio::sync::Mutex::new(new_permission_storage()));
+        check_storage_backend(Arc::new(tokio::sync::Mutex::new(new_permission_storage())));
+        check_storage_backend(tokio::sync::RwLock::new(new_permission_storage()));
+        check_storage_backend(Arc::new(tokio::sync::RwLock::new(new_permission_storage())));
+    }
+
+    #[test]
+    fn should_use_the_parking_lot_locks_as_storage() {
+        check_storage_backend(parking_lot::Mutex::new(new_permission_storage()));
+        check_storage_backend(Arc::new(parking_lot::Mutex::new(new_permission_storage())));
+        check_storage_backend(parking_lot::RwLock::new(new_permission_storage()));
+        check_storage_backend(Arc::new(parking_lot::RwLock::new(new_permission_storage())));
+    }
+
+    thread_local! {
+        static LAZY_PERMISSIONS: RefCell<Option<AuthServiceStorage<TestPermission>>> = const { RefCell::new(None) };
+    }
+
+    #[test]
+    fn should_use_a_lazily_initialized_storage() {
+        assert!(!is_initialized(&LAZY_PERMISSIONS));
+
+        init_storage(&LAZY_PERMISSIONS, new_permission_storage());
+
+        assert!(is_initialized(&LAZY_PERMISSIONS));
+        check_storage_backend(&LAZY_PERMISSIONS);
+    }
+
+    #[test]
+    #[should_panic(expected = "storage not initialized: call init_storage first")]
+    fn should_panic_if_the_lazy_storage_is_not_initialized() {
+        let permissions = AuthService::new(&LAZY_PERMISSIONS);
+        permissions.has_all_permissions(&Principal::from_slice(&[1; 29]), &[TestPermission::Admin]);
+    }
+
+    #[test]
+    fn should_return_an_error_if_the_lazy_storage_is_not_initialized() {
+        let mut permissions = AuthService::new(&LAZY_PERMISSIONS);
+        let principal = Principal::from_slice(&[1; 29]);
+
+        assert_eq!(
+            permissions.try_get_permissions(&principal),
+            Err(AuthError::StorageAccess(StorageAccessError::Uninitialized))
+        );
+        assert_eq!(
+            permissions.add_permissions(principal, vec![TestPermission::Admin]),
+            Err(AuthError::StorageAccess(StorageAccessError::Uninitialized))
+        );
+    }
+
+    #[test]
+    fn should_return_an_error_if_the_storage_is_poisoned() {
+        let store = Arc::new(Mutex::new(new_permission_storage()));
+        let mut permissions = AuthService::new(store.clone());
+        let principal = Principal::from_slice(&[1; 29]);
+
+        permissions
+            .add_permissions(principal, vec![TestPermission::ReadLogs])
+            .unwrap();
+
+        // poison the mutex
+        let result = std::panic::catch_unwind(AssertUnwindSafe(|| {
+            let _guard = store.lock().unwrap();
+            panic!("poisoning the storage");
+        }));
+        assert!(result.is_err());
+
+        let poisoned = Err(AuthError::StorageAccess(StorageAccessError::Poisoned));
+        assert_eq!(
+            permissions.try_has_all_permissions(&principal, &[TestPermission::ReadLogs]),
+            poisoned
+        );
+        assert_eq!(
+            permissions.try_has_any_permission(&principal, &[TestPermission::ReadLogs]),
+            poisoned
+        );
+        assert_eq!(
+            permissions.check_has_permission(&principal, TestPermission::ReadLogs),
+            Err(AuthError::StorageAccess(StorageAccessError::Poisoned))
+        );
+        assert_eq!(
+            permissions.try_get_permissions(&principal),
+            Err(AuthError::StorageAccess(StorageAccessError::Poisoned))
+        );
+        assert_eq!(
+            permissions.add_permissions(principal, vec![TestPermission::Admin]),
+            Err(AuthError::StorageAccess(StorageAccessError::Poisoned))
+        );
+        assert_eq!(
+            permissions.remove_permissions(principal, &[TestPermission::ReadLogs]),
+            Err(AuthError::StorageAccess(StorageAccessError::Poisoned))
+        );
+    }
+
+    #[test]
+    fn should_return_an_error_if_the_storage_is_already_borrowed() {
+        let store = Rc::new(RefCell::new(new_permission_storage()));
+        let permissions = AuthService::new(store.clone());
+        let principal = Principal::from_slice(&[1; 29]);
+
+        let _borrowed = store.borrow_mut();
+
+        assert_eq!(
+            permissions.try_has_all_permissions(&principal, &[]),
+            Err(AuthError::StorageAccess(
+                StorageAccessError::AlreadyBorrowed
+            ))
+        );
+    }
+
+    #[test]
+    fn should_check_the_permissions_of_the_msg_caller() {
+        let mut ic = IcMock::default();
+        let mut permissions = new_permission_service();
+        let admin = Principal::from_slice(&[1; 29]);
+        let user = Principal::from_slice(&[2; 29]);
+
+        permissions
+            .add_permissions(admin, vec![TestPermission::Admin])
+            .unwrap();
+
+        assert_eq!(
+            permissions.check_has_permission(&ic.msg_caller(), TestPermission::Admin),
+            Err(AuthError::NotAuthorized)
+        );
+
+        ic.set_msg_caller(admin);
+        assert!(
+            permissions
+                .check_has_permission(&ic.msg_caller(), TestPermission::Admin)
+                .is_ok()
+        );
+
+        ic.with_caller(user, || {
+            assert_eq!(
+                permissions.check_has_permission(&ic.msg_caller(), TestPermission::Admin),
+                Err(AuthError::NotAuthorized)
+            );
+        });
+        assert_eq!(ic.msg_caller(), admin);
+    }
+
+    struct State {
+        permissions: AuthServiceStorage<TestPermission>,
+        log_settings: LoggerServiceStorage,
+    }
+
+    thread_local! {
+        static STATE: RefCell<State> = RefCell::new(State {
+            permissions: BTreeMap::new(
+                MemoryManager::init(DefaultMemoryImpl::default()).get(MemoryId::new(1)),
+            ),
+            log_settings: StableCell::new(
+                MemoryManager::init(DefaultMemoryImpl::default()).get(MemoryId::new(2)),
+                LogSettings::default(),
+            ),
+        });
+    }
+
+    #[test]
+    fn should_share_a_single_state_between_services() {
+        let mut permissions = AuthService::new((&STATE).map(
+            |state: &State| &state.permissions,
+            |state: &mut State| &mut state.permissions,
+        ));
+        let mut logger_config_service = LoggerConfigService::new((&STATE).map(
+            |state: &State| &state.log_settings,
+            |state: &mut State| &mut state.log_settings,
         ));
-        AuthService::new(store)
+        let principal = Principal::from_slice(&[1; 29]);
+
+        permissions
+            .add_permissions(principal, vec![TestPermission::ReadLogs])
+            .unwrap();
+        logger_config_service
+            .log_settings_store
+            .with_borrow_mut(|store| {
+                store.set(LogSettings {
+                    log_filter: "debug".to_string(),
+                    ..Default::default()
+                })
+            });
+
+        assert!(permissions.has_all_permissions(&principal, &[TestPermission::ReadLogs]));
+        assert_eq!(logger_config_service.get_logger_filter(), "debug");
+        STATE.with_borrow(|state| {
+            assert!(state.permissions.contains_key(&principal));
+            assert_eq!(state.log_settings.get().log_filter, "debug");
+        });
+    }
+
+    fn new_permission_service() -> TestPermissionService {
+        AuthService::new(RefCell::new(new_permission_storage()))
     }
 
     type TestPermissionService =
@@ -1,16 +1,22 @@
 pub mod identity;
+pub mod request;
+pub mod retry;
 
 use std::path::{Path, PathBuf};
-use std::time::Duration;
+use std::time::{Duration, SystemTime};
 
 use candid::utils::ArgumentEncoder;
 use candid::{CandidType, Decode, Principal, encode_args};
+use ic_agent::agent::CallResponse;
 use ic_agent::identity::PemError;
 use serde::Deserialize;
 use serde::de::DeserializeOwned;
 use thiserror::Error;
 
-use crate::client::CanisterClient;
+use crate::agent::request::{SubmittedUpdate, decode_request_status};
+use crate::agent::retry::TransportRetries;
+use crate::client::{CallMode, CanisterClient};
+use crate::error::check_response_size;
 use crate::{CanisterClientError, CanisterClientResult};
 
 #[derive(Error, Debug)]
@@ -27,10 +33,16 @@ pub enum AgentError {
 
 pub type Result<T> = std::result::Result<T, AgentError>;
 
+/// The time after which a submitted update is dropped by the IC if it was not received yet.
+const UPDATE_INGRESS_EXPIRY: Duration = Duration::from_secs(3 * 60);
+
 #[derive(Clone)]
 pub struct IcAgentClient {
     pub canister_id: Principal,
     agent: ic_agent::Agent,
+    transport_retries: TransportRetries,
+    // the max size of a response. None means unlimited.
+    max_response_bytes: Option<usize>,
 }
 
 impl IcAgentClient {
@@ -42,10 +54,7 @@ impl IcAgentClient {
         timeout: Option<Duration>,
     ) -> Result<Self> {
         let agent = identity::init_agent(identity_path, network, timeout).await?;
-        Ok(Self {
-            canister_id: canister,
-            agent,
-        })
+        Ok(Self::with_agent(canister, agent))
     }
 
     /// Initialize an IC Agent with an existing agent
@@ -53,43 +62,200 @@ impl IcAgentClient {
         Self {
             canister_id: canister,
             agent,
+            transport_retries: TransportRetries::default(),
+            max_response_bytes: None,
         }
     }
-}
 
-impl CanisterClient for IcAgentClient {
-    async fn query<T, R>(&self, method: &str, args: T) -> CanisterClientResult<R>
+    /// Sets the max size in bytes of the responses accepted by this client.
+    /// A larger response is rejected with a `CanisterClientError::ResponseTooLarge` error
+    /// before attempting to decode it.
+    /// By default, the size of the responses is unlimited.
+    pub fn with_max_response_bytes(mut self, max_response_bytes: usize) -> Self {
+        self.max_response_bytes = Some(max_response_bytes);
+        self
+    }
+
+    /// Retries the calls that fail because of a transient HTTP-level error
+    /// (e.g. the boundary node returns 429, 502, 503 or 504)
+    /// up to `max_retries` times, waiting `delay` between two attempts.
+    ///
+    /// Queries are retried on all these errors, while update calls are retried only on 429 and 503,
+    /// which prove that the request was not accepted: after a 502 or 504 the update could have
+    /// been executed, so a non-idempotent update is never sent twice.
+    pub fn with_transport_retries(mut self, max_retries: u32, delay: Duration) -> Self {
+        self.transport_retries = TransportRetries { max_retries, delay };
+        self
+    }
+
+    /// Submits an update call without waiting for its result.
+    ///
+    /// The returned value can be persisted and used later, even by another process,
+    /// to poll the result of the update with `poll_update`.
+    pub async fn submit_update<T>(
+        &self,
+        method: &str,
+        args: T,
+    ) -> CanisterClientResult<SubmittedUpdate>
+    where
+        T: ArgumentEncoder + Send + Sync,
+    {
+        let args = encode_args(args)
+            .map_err(|e| CanisterClientError::from(e).with_context(self.canister_id, method))?;
+
+        // The expiry is set explicitly, so that the pending update knows when it can be dropped
+        let ingress_expiry = SystemTime::now() + UPDATE_INGRESS_EXPIRY;
+        let response = self
+            .transport_retries
+            .execute_update(|| {
+                self.agent
+                    .update(&self.canister_id, method)
+                    .with_arg(args.clone())
+                    .expire_at(ingress_expiry)
+                    .call()
+            })
+            .await
+            .map_err(|e| {
+                CanisterClientError::IcAgentError(e).with_context(self.canister_id, method)
+            })?;
+
+        let method = method.to_string();
+        Ok(match response {
+            CallResponse::Response((reply, _certificate)) => {
+                SubmittedUpdate::Replied { method, reply }
+            }
+            CallResponse::Poll(request_id) => SubmittedUpdate::Pending {
+                method,
+                request_id: request_id.into(),
+                ingress_expiry_nanos: nanos_since_epoch(ingress_expiry),
+            },
+        })
+    }
+
+    /// Polls the status of an update submitted by `submit_update`.
+    ///
+    /// Returns the decoded result if the update was replied,
+    /// or `None` if the update is still in progress.
+    /// Returns an error if the IC did not receive the update before its ingress expiry.
+    pub async fn poll_update<R>(&self, update: &SubmittedUpdate) -> CanisterClientResult<Option<R>>
+    where
+        R: DeserializeOwned + CandidType,
+    {
+        let result = match update {
+            SubmittedUpdate::Replied { reply, .. } => {
+                check_response_size(self.max_response_bytes, reply).and_then(|_| {
+                    Decode!(reply, R)
+                        .map(Some)
+                        .map_err(CanisterClientError::from)
+                })
+            }
+            SubmittedUpdate::Pending {
+                request_id,
+                ingress_expiry_nanos,
+                ..
+            } => match self
+                .agent
+                .request_status_raw(&(*request_id).into(), self.canister_id)
+                .await
+            {
+                Ok(status) => decode_request_status(
+                    *request_id,
+                    *ingress_expiry_nanos,
+                    nanos_since_epoch(SystemTime::now()),
+                    self.max_response_bytes,
+                    status,
+                ),
+                Err(e) => Err(CanisterClientError::IcAgentError(e)),
+            },
+        };
+
+        result.map_err(|e| e.with_context(self.canister_id, update.method()))
+    }
+
+    /// Performs a query call with the given arguments.
+    async fn query_call<T, R>(&self, method: &str, args: T) -> CanisterClientResult<R>
     where
         T: ArgumentEncoder + Send + Sync,
         R: DeserializeOwned + CandidType + Send,
     {
-        let args = encode_args(args)?;
+        let args = encode_args(args)
+            .map_err(|e| CanisterClientError::from(e).with_context(self.canister_id, method))?;
 
-        self.agent
-            .query(&self.canister_id, method)
-            .with_arg(args)
-            .call()
+        self.transport_retries
+            .execute(|| {
+                self.agent
+                    .query(&self.canister_id, method)
+                    .with_arg(args.clone())
+                    .call()
+            })
             .await
             .map_err(CanisterClientError::IcAgentError)
-            .map(|r| decode(&r))
+            .and_then(|r| {
+                check_response_size(self.max_response_bytes, &r)?;
+                Ok(decode(&r))
+            })
+            .map_err(|e| e.with_context(self.canister_id, method))
     }
 
-    async fn update<T, R>(&self, method: &str, args: T) -> CanisterClientResult<R>
+    /// Performs an update call with the given arguments.
+    async fn update_call<T, R>(&self, method: &str, args: T) -> CanisterClientResult<R>
     where
         T: ArgumentEncoder + Send + Sync,
         R: DeserializeOwned + CandidType + Send,
     {
-        let args = encode_args(args)?;
-        self.agent
-            .update(&self.canister_id, method)
-            .with_arg(args)
-            .call_and_wait()
-            .await
+        let args = encode_args(args)
+            .map_err(|e| CanisterClientError::from(e).with_context(self.canister_id, method))?;
+
+        // Only the rejected submissions are retried: once the request is accepted, it must not be sent again
+        let response = self
+            .transport_retries
+            .execute_update(|| {
+                self.agent
+                    .update(&self.canister_id, method)
+                    .with_arg(args.clone())
+                    .call()
+            })
+            .await;
+
+        let response = match response {
+            Ok(CallResponse::Response((response, _certificate))) => Ok(response),
+            Ok(CallResponse::Poll(request_id)) => self
+                .agent
+                .wait(&request_id, self.canister_id)
+                .await
+                .map(|(response, _certificate)| response),
+            Err(e) => Err(e),
+        };
+
+        response
             .map_err(CanisterClientError::IcAgentError)
-            .map(|r| decode(&r))
+            .and_then(|r| {
+                check_response_size(self.max_response_bytes, &r)?;
+                Ok(decode(&r))
+            })
+            .map_err(|e| e.with_context(self.canister_id, method))
     }
 }
 
+impl CanisterClient for IcAgentClient {
+    async fn call<T, R>(&self, mode: CallMode, method: &str, args: T) -> CanisterClientResult<R>
+    where
+        T: ArgumentEncoder + Send + Sync,
+        R: DeserializeOwned + CandidType + Send,
+    {
+        match mode {
+            CallMode::Query | CallMode::CompositeQuery => self.query_call(method, args).await,
+            CallMode::Update => self.update_call(method, args).await,
+        }
+    }
+}
+
+fn nanos_since_epoch(time: SystemTime) -> u64 {
+    time.duration_since(SystemTime::UNIX_EPOCH)
+        .expect("the time is before the epoch")
+        .as_nanos() as u64
+}
+
 #[inline]
 fn decode<'a, T: CandidType + Deserialize<'a>>(bytes: &'a [u8]) -> T {
     Decode!(bytes, T).expect("failed to decode item from candid")
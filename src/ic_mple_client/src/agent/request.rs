@@ -0,0 +1,241 @@
+use std::fmt;
+use std::str::FromStr;
+
+use candid::{CandidType, Decode};
+use ic_agent::RequestId;
+use ic_agent::agent::RequestStatusResponse;
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+
+use crate::error::check_response_size;
+use crate::{CanisterClientError, CanisterClientResult};
+
+/// The id of an update request submitted to the IC.
+/// It can be persisted, e.g. in its hex string form, to poll the request status later.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, CandidType, Serialize, Deserialize)]
+pub struct AgentRequestId(pub [u8; 32]);
+
+impl From<RequestId> for AgentRequestId {
+    fn from(request_id: RequestId) -> Self {
+        Self(*request_id)
+    }
+}
+
+impl From<AgentRequestId> for RequestId {
+    fn from(request_id: AgentRequestId) -> Self {
+        RequestId::new(&request_id.0)
+    }
+}
+
+impl fmt::Display for AgentRequestId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for byte in self.0 {
+            write!(f, "{byte:02x}")?;
+        }
+        Ok(())
+    }
+}
+
+impl FromStr for AgentRequestId {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if s.len() != 64 || !s.is_ascii() {
+            return Err(format!(
+                "invalid request id [{s}]: expected 64 hex characters"
+            ));
+        }
+        let mut bytes = [0u8; 32];
+        for (i, byte) in bytes.iter_mut().enumerate() {
+            *byte = u8::from_str_radix(&s[i * 2..i * 2 + 2], 16)
+                .map_err(|e| format!("invalid request id [{s}]: {e}"))?;
+        }
+        Ok(Self(bytes))
+    }
+}
+
+/// An update call submitted to the IC by `IcAgentClient::submit_update`.
+#[derive(Debug, Clone, PartialEq, Eq, CandidType, Serialize, Deserialize)]
+pub enum SubmittedUpdate {
+    /// The update was already answered when submitted. It contains the candid encoded reply.
+    Replied { method: String, reply: Vec<u8> },
+    /// The update was accepted and its status must be polled.
+    /// The request is dropped by the IC if it is not received before the ingress expiry,
+    /// in nanoseconds since the epoch.
+    Pending {
+        method: String,
+        request_id: AgentRequestId,
+        ingress_expiry_nanos: u64,
+    },
+}
+
+impl SubmittedUpdate {
+    /// Returns the method of the update.
+    pub fn method(&self) -> &str {
+        match self {
+            SubmittedUpdate::Replied { method, .. } | SubmittedUpdate::Pending { method, .. } => {
+                method
+            }
+        }
+    }
+}
+
+/// Returns the decoded reply if the request was replied, or `None` if it is still in progress.
+///
+/// The status of a request is `Unknown` until the IC receives it, so an `Unknown` status
+/// after the ingress expiry means that the request was dropped and will never be executed.
+pub(crate) fn decode_request_status<R>(
+    request_id: AgentRequestId,
+    ingress_expiry_nanos: u64,
+    now_nanos: u64,
+    max_response_bytes: Option<usize>,
+    status: RequestStatusResponse,
+) -> CanisterClientResult<Option<R>>
+where
+    R: DeserializeOwned + CandidType,
+{
+    match status {
+        RequestStatusResponse::Unknown if now_nanos > ingress_expiry_nanos => Err(
+            CanisterClientError::IcAgentRequestExpired(request_id.to_string()),
+        ),
+        RequestStatusResponse::Unknown
+        | RequestStatusResponse::Received
+        | RequestStatusResponse::Processing => Ok(None),
+        RequestStatusResponse::Replied(reply) => {
+            check_response_size(max_response_bytes, &reply.arg)?;
+            Ok(Some(Decode!(&reply.arg, R)?))
+        }
+        RequestStatusResponse::Rejected(reject) => {
+            Err(CanisterClientError::IcAgentRequestRejected(reject))
+        }
+        RequestStatusResponse::Done => Err(CanisterClientError::IcAgentRequestDone(
+            request_id.to_string(),
+        )),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use candid::Encode;
+    use ic_agent::agent::ReplyResponse;
+
+    use super::*;
+
+    #[test]
+    fn should_convert_request_id_to_and_from_hex() {
+        let request_id = AgentRequestId([171; 32]);
+
+        let hex = request_id.to_string();
+
+        assert_eq!(hex, "ab".repeat(32));
+        assert_eq!(AgentRequestId::from_str(&hex).unwrap(), request_id);
+    }
+
+    #[test]
+    fn should_not_parse_invalid_request_id() {
+        assert!(AgentRequestId::from_str("ab").is_err());
+        assert!(AgentRequestId::from_str(&"zz".repeat(32)).is_err());
+    }
+
+    #[test]
+    fn should_convert_request_id_to_and_from_agent_request_id() {
+        let request_id = AgentRequestId([7; 32]);
+        let agent_request_id: RequestId = request_id.into();
+        assert_eq!(AgentRequestId::from(agent_request_id), request_id);
+    }
+
+    const EXPIRY: u64 = 1_000;
+
+    #[test]
+    fn should_serialize_submitted_update() {
+        let submitted = SubmittedUpdate::Pending {
+            method: "update".to_string(),
+            request_id: AgentRequestId([3; 32]),
+            ingress_expiry_nanos: EXPIRY,
+        };
+        let encoded = Encode!(&submitted).unwrap();
+        assert_eq!(Decode!(&encoded, SubmittedUpdate).unwrap(), submitted);
+    }
+
+    #[test]
+    fn should_return_none_while_request_is_in_progress() {
+        for status in [
+            RequestStatusResponse::Unknown,
+            RequestStatusResponse::Received,
+            RequestStatusResponse::Processing,
+        ] {
+            let result =
+                decode_request_status::<u64>(AgentRequestId([0; 32]), EXPIRY, EXPIRY, None, status);
+            assert!(result.unwrap().is_none());
+        }
+    }
+
+    #[test]
+    fn should_fail_if_request_is_unknown_after_the_ingress_expiry() {
+        let result = decode_request_status::<u64>(
+            AgentRequestId([0; 32]),
+            EXPIRY,
+            EXPIRY + 1,
+            None,
+            RequestStatusResponse::Unknown,
+        );
+
+        assert!(matches!(
+            result,
+            Err(CanisterClientError::IcAgentRequestExpired(_))
+        ));
+
+        // the request was received before the expiry, so it will be executed
+        let result = decode_request_status::<u64>(
+            AgentRequestId([0; 32]),
+            EXPIRY,
+            EXPIRY + 1,
+            None,
+            RequestStatusResponse::Processing,
+        );
+        assert!(result.unwrap().is_none());
+    }
+
+    #[test]
+    fn should_decode_replied_request() {
+        let status = RequestStatusResponse::Replied(ReplyResponse {
+            arg: Encode!(&42u64).unwrap(),
+        });
+
+        let result = decode_request_status::<u64>(AgentRequestId([0; 32]), EXPIRY, 0, None, status);
+
+        assert_eq!(result.unwrap(), Some(42));
+    }
+
+    #[test]
+    fn should_not_decode_a_reply_larger_than_the_limit() {
+        let arg = Encode!(&42u64).unwrap();
+        let actual = arg.len();
+        let status = RequestStatusResponse::Replied(ReplyResponse { arg });
+
+        let result =
+            decode_request_status::<u64>(AgentRequestId([0; 32]), EXPIRY, 0, Some(1), status);
+
+        assert!(matches!(
+            result,
+            Err(CanisterClientError::ResponseTooLarge { limit: 1, actual: a }) if a == actual
+        ));
+    }
+
+    #[test]
+    fn should_fail_if_request_is_done() {
+        let result = decode_request_status::<u64>(
+            AgentRequestId([0; 32]),
+            EXPIRY,
+            0,
+            None,
+            RequestStatusResponse::Done,
+        );
+
+        assert!(matches!(
+            result,
+            Err(CanisterClientError::IcAgentRequestDone(_))
+        ));
+    }
+}
@@ -0,0 +1,224 @@
+use std::future::Future;
+use std::time::Duration;
+
+use ic_agent::AgentError;
+
+/// HTTP status codes returned by the boundary nodes that identify a transient failure.
+const TRANSIENT_HTTP_STATUSES: [u16; 4] = [429, 502, 503, 504];
+
+/// HTTP status codes of the transient failures that prove the request was not accepted.
+/// A 502 or 504 could be returned after the request was forwarded to the replica,
+/// so an update could be executed twice if it were retried on them.
+const REJECTED_HTTP_STATUSES: [u16; 2] = [429, 503];
+
+/// Defines how many times a call failed because of a transient transport error is retried.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct TransportRetries {
+    /// The max number of retries. Zero means no retries.
+    pub max_retries: u32,
+    /// The time to wait between two consecutive attempts.
+    pub delay: Duration,
+}
+
+impl TransportRetries {
+    /// Executes the operation, retrying it while it fails with a transient error
+    /// and the max number of retries is not reached.
+    pub(crate) async fn execute<T, F, Fut>(&self, operation: F) -> Result<T, AgentError>
+    where
+        F: Fn() -> Fut,
+        Fut: Future<Output = Result<T, AgentError>>,
+    {
+        self.execute_while(operation, is_transient_error).await
+    }
+
+    /// Executes the submission of an update, retrying it only while it fails with
+    /// a transient error proving that the request was not accepted, see `is_rejected_error`.
+    pub(crate) async fn execute_update<T, F, Fut>(&self, operation: F) -> Result<T, AgentError>
+    where
+        F: Fn() -> Fut,
+        Fut: Future<Output = Result<T, AgentError>>,
+    {
+        self.execute_while(operation, is_rejected_error).await
+    }
+
+    async fn execute_while<T, F, Fut>(
+        &self,
+        operation: F,
+        is_retryable: fn(&AgentError) -> bool,
+    ) -> Result<T, AgentError>
+    where
+        F: Fn() -> Fut,
+        Fut: Future<Output = Result<T, AgentError>>,
+    {
+        let mut failed_attempts = 0;
+        loop {
+            match operation().await {
+                Err(error) if failed_attempts < self.max_retries && is_retryable(&error) => {
+                    failed_attempts += 1;
+                    tokio::time::sleep(self.delay).await;
+                }
+                result => return result,
+            }
+        }
+    }
+}
+
+/// Returns true if the error is an HTTP-level failure that is expected to be transient,
+/// e.g. the boundary node is rate limiting the requests or is temporarily unavailable.
+pub fn is_transient_error(error: &AgentError) -> bool {
+    match error {
+        AgentError::HttpError(payload) => TRANSIENT_HTTP_STATUSES.contains(&payload.status),
+        _ => false,
+    }
+}
+
+/// Returns true if the error is a transient HTTP-level failure returned before the request
+/// was accepted, e.g. the boundary node is rate limiting the requests.
+/// Only these errors are safe to retry for the update calls.
+pub fn is_rejected_error(error: &AgentError) -> bool {
+    match error {
+        AgentError::HttpError(payload) => REJECTED_HTTP_STATUSES.contains(&payload.status),
+        _ => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use std::sync::atomic::{AtomicU32, Ordering};
+
+    use ic_agent::agent::agent_error::HttpErrorPayload;
+
+    use super::*;
+
+    fn http_error(status: u16) -> AgentError {
+        AgentError::HttpError(HttpErrorPayload {
+            status,
+            content_type: None,
+            content: vec![],
+        })
+    }
+
+    #[test]
+    fn should_classify_transient_http_errors() {
+        assert!(is_transient_error(&http_error(429)));
+        assert!(is_transient_error(&http_error(502)));
+        assert!(is_transient_error(&http_error(503)));
+        assert!(is_transient_error(&http_error(504)));
+    }
+
+    #[test]
+    fn should_not_classify_other_http_errors_as_transient() {
+        assert!(!is_transient_error(&http_error(400)));
+        assert!(!is_transient_error(&http_error(404)));
+        assert!(!is_transient_error(&http_error(500)));
+    }
+
+    #[test]
+    fn should_classify_only_the_rejections_as_safe_for_updates() {
+        assert!(is_rejected_error(&http_error(429)));
+        assert!(is_rejected_error(&http_error(503)));
+        assert!(!is_rejected_error(&http_error(502)));
+        assert!(!is_rejected_error(&http_error(504)));
+        assert!(!is_rejected_error(&http_error(500)));
+    }
+
+    #[tokio::test]
+    async fn should_not_retry_an_update_that_could_have_been_accepted() {
+        let retries = TransportRetries {
+            max_retries: 2,
+            delay: Duration::ZERO,
+        };
+
+        for status in [502, 504] {
+            let attempts = AtomicU32::new(0);
+            let result: Result<(), AgentError> = retries
+                .execute_update(|| async {
+                    attempts.fetch_add(1, Ordering::SeqCst);
+                    Err(http_error(status))
+                })
+                .await;
+
+            assert!(result.is_err());
+            assert_eq!(attempts.load(Ordering::SeqCst), 1);
+        }
+
+        let attempts = AtomicU32::new(0);
+        let result: Result<(), AgentError> = retries
+            .execute_update(|| async {
+                attempts.fetch_add(1, Ordering::SeqCst);
+                Err(http_error(429))
+            })
+            .await;
+
+        assert!(result.is_err());
+        assert_eq!(attempts.load(Ordering::SeqCst), 3);
+    }
+
+    #[test]
+    fn should_not_classify_non_http_errors_as_transient() {
+        assert!(!is_transient_error(&AgentError::InvalidReplicaUrl(
+            "invalid".to_string()
+        )));
+    }
+
+    #[tokio::test]
+    async fn should_retry_transient_errors_up_to_max_retries() {
+        let retries = TransportRetries {
+            max_retries: 2,
+            delay: Duration::ZERO,
+        };
+        let attempts = AtomicU32::new(0);
+
+        let result: Result<(), AgentError> = retries
+            .execute(|| async {
+                attempts.fetch_add(1, Ordering::SeqCst);
+                Err(http_error(503))
+            })
+            .await;
+
+        assert!(result.is_err());
+        assert_eq!(attempts.load(Ordering::SeqCst), 3);
+    }
+
+    #[tokio::test]
+    async fn should_not_retry_non_transient_errors() {
+        let retries = TransportRetries {
+            max_retries: 2,
+            delay: Duration::ZERO,
+        };
+        let attempts = AtomicU32::new(0);
+
+        let result: Result<(), AgentError> = retries
+            .execute(|| async {
+                attempts.fetch_add(1, Ordering::SeqCst);
+                Err(http_error(400))
+            })
+            .await;
+
+        assert!(result.is_err());
+        assert_eq!(attempts.load(Ordering::SeqCst), 1);
+    }
+
+    #[tokio::test]
+    async fn should_return_the_first_success() {
+        let retries = TransportRetries {
+            max_retries: 5,
+            delay: Duration::ZERO,
+        };
+        let attempts = AtomicU32::new(0);
+
+        let result = retries
+            .execute(|| async {
+                if attempts.fetch_add(1, Ordering::SeqCst) < 2 {
+                    Err(http_error(429))
+                } else {
+                    Ok(42)
+                }
+            })
+            .await;
+
+        assert_eq!(result.unwrap(), 42);
+        assert_eq!(attempts.load(Ordering::SeqCst), 3);
+    }
+}
@@ -1,10 +1,22 @@
 use std::future::Future;
+use std::time::Duration;
 
 use candid::CandidType;
 use candid::utils::ArgumentEncoder;
 use serde::de::DeserializeOwned;
 
-use crate::CanisterClientResult;
+use crate::{CanisterClientError, CanisterClientResult};
+
+/// The kind of canister method to call.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CallMode {
+    /// A query method.
+    Query,
+    /// A composite query method.
+    CompositeQuery,
+    /// An update method.
+    Update,
+}
 
 /// Generic client for interacting with a canister.
 /// This is used to abstract away the differences between the IC Agent and the
@@ -12,6 +24,28 @@ use crate::CanisterClientResult;
 /// The IC Agent is used for interaction through the dfx tool, while the IC
 /// Canister is used for interacting with the EVM canister in wasm environments.
 pub trait CanisterClient: Send + Clone {
+    /// Call a method on the canister.
+    /// Each client maps the call mode to the call type appropriate for its backend.
+    ///
+    /// # Arguments
+    ///
+    /// * `mode` - The kind of method to call.
+    /// * `method` - The method name.
+    /// * `args` - The arguments to the method.
+    ///
+    /// # Returns
+    ///
+    /// The result of the method call.
+    fn call<T, R>(
+        &self,
+        mode: CallMode,
+        method: &str,
+        args: T,
+    ) -> impl Future<Output = CanisterClientResult<R>> + Send
+    where
+        T: ArgumentEncoder + Send + Sync,
+        R: DeserializeOwned + CandidType + Send;
+
     /// Call an update method on the canister.
     ///
     /// # Arguments
@@ -29,7 +63,10 @@ pub trait CanisterClient: Send + Clone {
     ) -> impl Future<Output = CanisterClientResult<R>> + Send
     where
         T: ArgumentEncoder + Send + Sync,
-        R: DeserializeOwned + CandidType + Send;
+        R: DeserializeOwned + CandidType + Send,
+    {
+        self.call(CallMode::Update, method, args)
+    }
 
     /// Call a query method on the canister.
     ///
@@ -48,5 +85,108 @@ pub trait CanisterClient: Send + Clone {
     ) -> impl Future<Output = CanisterClientResult<R>> + Send
     where
         T: ArgumentEncoder + Send + Sync,
-        R: DeserializeOwned + CandidType + Send;
+        R: DeserializeOwned + CandidType + Send,
+    {
+        self.call(CallMode::Query, method, args)
+    }
+
+    /// Call a method on the canister, failing with `CanisterClientError::Timeout`
+    /// if the call does not complete within the deadline.
+    ///
+    /// On timeout, the in-flight call future is dropped and its resources released.
+    /// In wasm environments, the call is simply delegated, as the IC has its own timeout semantics.
+    ///
+    /// # Arguments
+    ///
+    /// * `mode` - The kind of method to call.
+    /// * `method` - The method name.
+    /// * `args` - The arguments to the method.
+    /// * `deadline` - The max duration of the call.
+    ///
+    /// # Returns
+    ///
+    /// The result of the method call.
+    fn call_with_deadline<T, R>(
+        &self,
+        mode: CallMode,
+        method: &str,
+        args: T,
+        deadline: Duration,
+    ) -> impl Future<Output = CanisterClientResult<R>> + Send
+    where
+        T: ArgumentEncoder + Send + Sync,
+        R: DeserializeOwned + CandidType + Send,
+    {
+        with_deadline(self.call(mode, method, args), deadline)
+    }
+
+    /// Call an update method on the canister with a deadline.
+    /// See `call_with_deadline` for details.
+    ///
+    /// # Arguments
+    ///
+    /// * `method` - The method name.
+    /// * `args` - The arguments to the method.
+    /// * `deadline` - The max duration of the call.
+    ///
+    /// # Returns
+    ///
+    /// The result of the method call.
+    fn update_with_deadline<T, R>(
+        &self,
+        method: &str,
+        args: T,
+        deadline: Duration,
+    ) -> impl Future<Output = CanisterClientResult<R>> + Send
+    where
+        T: ArgumentEncoder + Send + Sync,
+        R: DeserializeOwned + CandidType + Send,
+    {
+        self.call_with_deadline(CallMode::Update, method, args, deadline)
+    }
+
+    /// Call a query method on the canister with a deadline.
+    /// See `call_with_deadline` for details.
+    ///
+    /// # Arguments
+    ///
+    /// * `method` - The method name.
+    /// * `args` - The arguments to the method.
+    /// * `deadline` - The max duration of the call.
+    ///
+    /// # Returns
+    ///
+    /// The result of the method call.
+    fn query_with_deadline<T, R>(
+        &self,
+        method: &str,
+        args: T,
+        deadline: Duration,
+    ) -> impl Future<Output = CanisterClientResult<R>> + Send
+    where
+        T: ArgumentEncoder + Send + Sync,
+        R: DeserializeOwned + CandidType + Send,
+    {
+        self.call_with_deadline(CallMode::Query, method, args, deadline)
+    }
+}
+
+/// Awaits the call, failing if it does not complete within the deadline.
+#[cfg(not(target_family = "wasm"))]
+async fn with_deadline<R>(
+    call: impl Future<Output = CanisterClientResult<R>>,
+    deadline: Duration,
+) -> CanisterClientResult<R> {
+    tokio::time::timeout(deadline, call)
+        .await
+        .map_err(|_| CanisterClientError::Timeout(deadline))?
+}
+
+/// Awaits the call. In wasm environments, the IC has its own timeout semantics.
+#[cfg(target_family = "wasm")]
+async fn with_deadline<R>(
+    call: impl Future<Output = CanisterClientResult<R>>,
+    _deadline: Duration,
+) -> CanisterClientResult<R> {
+    call.await
 }
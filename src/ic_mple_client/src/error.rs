@@ -1,3 +1,7 @@
+use std::fmt;
+use std::time::Duration;
+
+use candid::Principal;
 use thiserror::Error;
 
 #[derive(Debug, Error)]
@@ -12,9 +16,96 @@ pub enum CanisterClientError {
     #[error("ic agent error: {0}")]
     IcAgentError(#[from] ic_agent::agent::AgentError),
 
+    #[cfg(feature = "ic-agent")]
+    #[error("ic agent request rejected: {0:?}")]
+    IcAgentRequestRejected(ic_agent::agent::RejectResponse),
+
+    #[cfg(feature = "ic-agent")]
+    #[error("ic agent request [{0}] is done and its reply is no longer available")]
+    IcAgentRequestDone(String),
+
+    #[cfg(feature = "ic-agent")]
+    #[error("ic agent request [{0}] was not received before its ingress expiry")]
+    IcAgentRequestExpired(String),
+
     #[cfg(feature = "pocket-ic")]
     #[error("pocket-ic test error: {0:?}")]
     PocketIcTestError(::pocket_ic::RejectResponse),
+
+    #[cfg(feature = "pocket-ic")]
+    #[error("pocket-ic predicate not satisfied after {steps} steps")]
+    PocketIcPredicateNotSatisfied { steps: usize },
+
+    #[error("response too large: {actual} bytes exceed the limit of {limit} bytes")]
+    ResponseTooLarge { limit: usize, actual: usize },
+
+    #[error("call timed out after {0:?}")]
+    Timeout(Duration),
+
+    #[error("{context}: {source}")]
+    WithContext {
+        context: CallContext,
+        source: Box<CanisterClientError>,
+    },
+}
+
+impl CanisterClientError {
+    /// Attaches the canister id and the method of the failed call to the error.
+    /// If the error already has a context, it is returned unchanged.
+    pub fn with_context(self, canister_id: Principal, method: &str) -> Self {
+        match self {
+            CanisterClientError::WithContext { .. } => self,
+            error => CanisterClientError::WithContext {
+                context: CallContext {
+                    canister_id,
+                    method: method.to_string(),
+                },
+                source: Box::new(error),
+            },
+        }
+    }
+
+    /// Returns the context of the failed call, if available.
+    pub fn context(&self) -> Option<&CallContext> {
+        match self {
+            CanisterClientError::WithContext { context, .. } => Some(context),
+            _ => None,
+        }
+    }
+
+    /// Returns the underlying error without the call context.
+    /// This can be used to match on the actual error.
+    pub fn inner(&self) -> &CanisterClientError {
+        match self {
+            CanisterClientError::WithContext { source, .. } => source.inner(),
+            error => error,
+        }
+    }
+
+    /// Consumes the error and returns the underlying error without the call context.
+    pub fn into_inner(self) -> CanisterClientError {
+        match self {
+            CanisterClientError::WithContext { source, .. } => source.into_inner(),
+            error => error,
+        }
+    }
+}
+
+/// The context of a canister call: the target canister and the called method.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CallContext {
+    pub canister_id: Principal,
+    pub method: String,
+}
+
+impl fmt::Display for CallContext {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "call to method [{}] of canister [{}] failed",
+            self.method, self.canister_id
+        )
+    }
 }
 
 #[cfg(feature = "pocket-ic")]
@@ -26,5 +117,75 @@ impl From<::pocket_ic::RejectResponse> for CanisterClientError {
 
 pub type CanisterClientResult<T> = Result<T, CanisterClientError>;
 
+/// Returns an error if the size of the raw response exceeds the limit, if any.
+pub(crate) fn check_response_size(
+    max_response_bytes: Option<usize>,
+    response: &[u8],
+) -> CanisterClientResult<()> {
+    match max_response_bytes {
+        Some(limit) if response.len() > limit => Err(CanisterClientError::ResponseTooLarge {
+            limit,
+            actual: response.len(),
+        }),
+        _ => Ok(()),
+    }
+}
+
 /// This tuple is returned incase of IC errors such as Network, canister error.
 pub type IcError = ic_cdk::call::Error;
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    fn candid_error() -> CanisterClientError {
+        CanisterClientError::CandidError(candid::Error::msg("decoding failed"))
+    }
+
+    #[test]
+    fn should_display_the_call_context() {
+        let error = candid_error().with_context(Principal::anonymous(), "get_counter");
+
+        let message = error.to_string();
+
+        assert!(message.contains("get_counter"));
+        assert!(message.contains(&Principal::anonymous().to_text()));
+        assert!(message.contains("decoding failed"));
+    }
+
+    #[test]
+    fn should_give_access_to_the_inner_error() {
+        let error = candid_error().with_context(Principal::anonymous(), "get_counter");
+
+        assert_eq!(
+            error.context(),
+            Some(&CallContext {
+                canister_id: Principal::anonymous(),
+                method: "get_counter".to_string(),
+            })
+        );
+        assert!(matches!(error.inner(), CanisterClientError::CandidError(_)));
+        assert!(matches!(
+            error.into_inner(),
+            CanisterClientError::CandidError(_)
+        ));
+    }
+
+    #[test]
+    fn should_not_wrap_the_context_twice() {
+        let error = candid_error()
+            .with_context(Principal::anonymous(), "first")
+            .with_context(Principal::management_canister(), "second");
+
+        assert_eq!(error.context().unwrap().method, "first");
+        assert!(matches!(error.inner(), CanisterClientError::CandidError(_)));
+    }
+
+    #[test]
+    fn should_have_no_context_when_not_attached() {
+        let error = candid_error();
+        assert!(error.context().is_none());
+        assert!(matches!(error.inner(), CanisterClientError::CandidError(_)));
+    }
+}
@@ -1,10 +1,40 @@
+use std::sync::{Arc, Mutex};
+
 use candid::utils::ArgumentEncoder;
 use candid::{CandidType, Principal};
 use serde::de::DeserializeOwned;
 
-use crate::client::CanisterClient;
+use crate::client::{CallMode, CanisterClient};
+use crate::error::check_response_size;
 use crate::{CanisterClientError, CanisterClientResult};
 
+/// The wait mode used to perform a call to a canister.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CallWaitMode {
+    /// A bounded wait call with the specified timeout in seconds.
+    /// The response of this call is best-effort.
+    BoundedWait(u32),
+    /// An unbounded wait call. The response of this call is guaranteed.
+    UnboundedWait,
+}
+
+impl CallWaitMode {
+    /// Returns the wait mode for the given timeout.
+    /// If `Some`, a bounded wait with the specified timeout is selected.
+    /// If `None`, an unbounded wait is selected.
+    pub fn from_timeout(timeout_seconds: Option<u32>) -> Self {
+        match timeout_seconds {
+            Some(timeout_seconds) => CallWaitMode::BoundedWait(timeout_seconds),
+            None => CallWaitMode::UnboundedWait,
+        }
+    }
+
+    /// Returns true if a call performed with this wait mode is answered via a best-effort response.
+    pub fn is_best_effort(&self) -> bool {
+        matches!(self, CallWaitMode::BoundedWait(_))
+    }
+}
+
 /// This client is used to interact with the IC canister.
 #[derive(Debug, Clone)]
 pub struct IcCanisterClient {
@@ -12,6 +42,10 @@ pub struct IcCanisterClient {
     pub canister_id: Principal,
     // the call timeout
     timeout_seconds: Option<u32>,
+    // the wait mode of the last call completed by this client
+    last_call_mode: Arc<Mutex<Option<CallWaitMode>>>,
+    // the max size of a response. None means unlimited.
+    max_response_bytes: Option<usize>,
 }
 
 impl IcCanisterClient {
@@ -26,46 +60,165 @@ impl IcCanisterClient {
         Self {
             canister_id: canister,
             timeout_seconds,
+            last_call_mode: Default::default(),
+            max_response_bytes: None,
         }
     }
 
-    async fn call<T, R>(&self, method: &str, args: T) -> CanisterClientResult<R>
+    /// Sets the max size in bytes of the responses accepted by this client.
+    /// A larger response is rejected with a `CanisterClientError::ResponseTooLarge` error
+    /// before attempting to decode it.
+    /// By default, the size of the responses is unlimited.
+    pub fn with_max_response_bytes(mut self, max_response_bytes: usize) -> Self {
+        self.max_response_bytes = Some(max_response_bytes);
+        self
+    }
+
+    /// Returns the wait mode of the last call completed by this client, successfully or not,
+    /// or `None` if no call was completed yet.
+    ///
+    /// The mode is recorded when the response is received, so with concurrent calls
+    /// it belongs to the call that completed last, not to the one that started last.
+    /// The returned mode tells whether that call was answered via a best-effort response.
+    pub fn last_call_mode(&self) -> Option<CallWaitMode> {
+        *self.last_call_mode.lock().unwrap()
+    }
+
+    /// Call an update method on the canister overriding the default timeout of the client.
+    ///
+    /// # Parameters
+    /// - `method`: The method name.
+    /// - `args`: The arguments to the method.
+    /// - `timeout_seconds`: The timeout in seconds for this call.
+    ///   If `Some`, a bounded call with the specified timeout will be used.
+    ///   If `None`, an unbounded call will be used.
+    pub async fn update_with_timeout<T, R>(
+        &self,
+        method: &str,
+        args: T,
+        timeout_seconds: Option<u32>,
+    ) -> CanisterClientResult<R>
+    where
+        T: ArgumentEncoder + Send + Sync,
+        R: DeserializeOwned + CandidType + Send,
+    {
+        self.call_with_wait_mode(method, args, CallWaitMode::from_timeout(timeout_seconds))
+            .await
+    }
+
+    /// Call a query method on the canister overriding the default timeout of the client.
+    ///
+    /// # Parameters
+    /// - `method`: The method name.
+    /// - `args`: The arguments to the method.
+    /// - `timeout_seconds`: The timeout in seconds for this call.
+    ///   If `Some`, a bounded call with the specified timeout will be used.
+    ///   If `None`, an unbounded call will be used.
+    pub async fn query_with_timeout<T, R>(
+        &self,
+        method: &str,
+        args: T,
+        timeout_seconds: Option<u32>,
+    ) -> CanisterClientResult<R>
+    where
+        T: ArgumentEncoder + Send + Sync,
+        R: DeserializeOwned + CandidType + Send,
+    {
+        self.call_with_wait_mode(method, args, CallWaitMode::from_timeout(timeout_seconds))
+            .await
+    }
+
+    /// Returns the wait mode used by default by this client.
+    fn default_call_mode(&self) -> CallWaitMode {
+        CallWaitMode::from_timeout(self.timeout_seconds)
+    }
+
+    async fn call_with_wait_mode<T, R>(
+        &self,
+        method: &str,
+        args: T,
+        mode: CallWaitMode,
+    ) -> CanisterClientResult<R>
     where
         T: ArgumentEncoder + Send,
         R: DeserializeOwned + CandidType,
     {
-        let call = if let Some(timeout_seconds) = self.timeout_seconds {
-            ic_cdk::call::Call::bounded_wait(self.canister_id, method)
-                .change_timeout(timeout_seconds)
-                .with_args(&args)
-        } else {
-            ic_cdk::call::Call::unbounded_wait(self.canister_id, method).with_args(&args)
+        let call = match mode {
+            CallWaitMode::BoundedWait(timeout_seconds) => {
+                ic_cdk::call::Call::bounded_wait(self.canister_id, method)
+                    .change_timeout(timeout_seconds)
+                    .with_args(&args)
+            }
+            CallWaitMode::UnboundedWait => {
+                ic_cdk::call::Call::unbounded_wait(self.canister_id, method).with_args(&args)
+            }
         };
 
-        let call_result = call
-            .await
-            .map_err(|e| CanisterClientError::CanisterError(e.into()))?
+        let call_result = call.await;
+        *self.last_call_mode.lock().unwrap() = Some(mode);
+
+        let call_result = call_result
+            .map_err(|e| {
+                CanisterClientError::CanisterError(e.into()).with_context(self.canister_id, method)
+            })?
             .into_bytes();
 
+        check_response_size(self.max_response_bytes, &call_result)
+            .map_err(|e| e.with_context(self.canister_id, method))?;
+
         use candid::Decode;
-        Decode!(&call_result, R).map_err(CanisterClientError::CandidError)
+        Decode!(&call_result, R)
+            .map_err(|e| CanisterClientError::CandidError(e).with_context(self.canister_id, method))
     }
 }
 
 impl CanisterClient for IcCanisterClient {
-    async fn update<T, R>(&self, method: &str, args: T) -> CanisterClientResult<R>
+    /// All the call modes are performed as inter-canister calls.
+    async fn call<T, R>(&self, _mode: CallMode, method: &str, args: T) -> CanisterClientResult<R>
     where
         T: ArgumentEncoder + Send + Sync,
         R: DeserializeOwned + CandidType + Send,
     {
-        self.call(method, args).await
+        self.call_with_wait_mode(method, args, self.default_call_mode())
+            .await
     }
+}
 
-    async fn query<T, R>(&self, method: &str, args: T) -> CanisterClientResult<R>
-    where
-        T: ArgumentEncoder + Send + Sync,
-        R: DeserializeOwned + CandidType + Send,
-    {
-        self.call(method, args).await
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    #[test]
+    fn should_select_bounded_wait_when_timeout_is_set() {
+        assert_eq!(
+            CallWaitMode::from_timeout(Some(10)),
+            CallWaitMode::BoundedWait(10)
+        );
+        assert!(CallWaitMode::from_timeout(Some(10)).is_best_effort());
+    }
+
+    #[test]
+    fn should_select_unbounded_wait_when_timeout_is_none() {
+        assert_eq!(
+            CallWaitMode::from_timeout(None),
+            CallWaitMode::UnboundedWait
+        );
+        assert!(!CallWaitMode::from_timeout(None).is_best_effort());
+    }
+
+    #[test]
+    fn should_use_the_client_timeout_by_default() {
+        let client = IcCanisterClient::new(Principal::anonymous(), Some(25));
+        assert_eq!(client.default_call_mode(), CallWaitMode::BoundedWait(25));
+
+        let client = IcCanisterClient::new(Principal::anonymous(), None);
+        assert_eq!(client.default_call_mode(), CallWaitMode::UnboundedWait);
+    }
+
+    #[test]
+    fn should_have_no_last_call_mode_before_any_call() {
+        let client = IcCanisterClient::new(Principal::anonymous(), Some(25));
+        assert_eq!(client.last_call_mode(), None);
     }
 }
@@ -9,12 +9,14 @@ pub mod mock;
 #[cfg(feature = "pocket-ic")]
 pub mod pocket_ic;
 
+#[cfg(feature = "ic-agent")]
+pub use agent::request::{AgentRequestId, SubmittedUpdate};
 #[cfg(feature = "ic-agent")]
 pub use agent::{AgentError, IcAgentClient};
-pub use client::CanisterClient;
-pub use error::{CanisterClientError, CanisterClientResult, IcError};
+pub use client::{CallMode, CanisterClient};
+pub use error::{CallContext, CanisterClientError, CanisterClientResult, IcError};
 #[cfg(feature = "ic-agent")]
 pub use ic_agent;
-pub use ic_client::IcCanisterClient;
+pub use ic_client::{CallWaitMode, IcCanisterClient};
 #[cfg(feature = "pocket-ic")]
-pub use pocket_ic::PocketIcClient;
+pub use pocket_ic::{PendingCall, PocketIcClient};
@@ -6,16 +6,37 @@ use candid::{CandidType, Decode, Encode};
 use serde::de::DeserializeOwned;
 
 use crate::CanisterClientResult;
-use crate::client::CanisterClient;
+use crate::client::{CallMode, CanisterClient};
+use crate::error::check_response_size;
 
-type Requests =
-    HashMap<String, VecDeque<Box<dyn FnOnce() -> CanisterClientResult<Vec<u8>> + Send>>>;
+type Response = Box<dyn FnOnce() -> CanisterClientResult<Vec<u8>> + Send>;
+type Requests = HashMap<String, VecDeque<Response>>;
+
+const QUERY: &str = "query";
+const UPDATE: &str = "update";
+
+/// A response that must be consumed in registration order.
+struct SequencedResponse {
+    kind: &'static str,
+    method: String,
+    response: Response,
+}
+
+#[derive(Default)]
+struct Sequence {
+    responses: VecDeque<SequencedResponse>,
+    violations: Vec<String>,
+}
 
 /// This client is used to mock the IC canister behavior in tests.
 #[derive(Default, Clone)]
 pub struct MockCanisterClient {
     queries: Arc<Mutex<Requests>>,
     updates: Arc<Mutex<Requests>>,
+    sequence: Arc<Mutex<Sequence>>,
+    max_response_bytes: Option<usize>,
+    #[cfg(not(target_family = "wasm"))]
+    response_delay: Option<std::time::Duration>,
 }
 
 impl MockCanisterClient {
@@ -34,15 +55,7 @@ impl MockCanisterClient {
     where
         R: DeserializeOwned + CandidType + Send,
     {
-        match response {
-            Ok(response) => {
-                let response = Encode!(&response).unwrap();
-                self.add_query_fn(method, Box::new(move || Ok(response.clone())));
-            }
-            Err(err) => {
-                self.add_query_fn(method, Box::new(move || Err(err)));
-            }
-        }
+        self.add_query_fn(method, encode_response(response));
     }
 
     /// Adds a query response to the mock client.
@@ -83,15 +96,7 @@ impl MockCanisterClient {
     where
         R: DeserializeOwned + CandidType + Send,
     {
-        match response {
-            Ok(response) => {
-                let response = Encode!(&response).unwrap();
-                self.add_update_fn(method, Box::new(move || Ok(response.clone())));
-            }
-            Err(err) => {
-                self.add_update_fn(method, Box::new(move || Err(err)));
-            }
-        }
+        self.add_update_fn(method, encode_response(response));
     }
 
     /// Adds an update response to the mock client.
@@ -117,53 +122,207 @@ impl MockCanisterClient {
             .push_back(response);
     }
 
+    /// Sets the max size in bytes of the responses returned by this client.
+    /// A larger response is rejected with a `CanisterClientError::ResponseTooLarge` error
+    /// before attempting to decode it.
+    /// By default, the size of the responses is unlimited.
+    pub fn with_max_response_bytes(mut self, max_response_bytes: usize) -> Self {
+        self.max_response_bytes = Some(max_response_bytes);
+        self
+    }
+
+    /// Sets a delay applied before returning each response.
+    /// This can be used to simulate slow canister calls.
+    #[cfg(not(target_family = "wasm"))]
+    pub fn with_response_delay(mut self, delay: std::time::Duration) -> Self {
+        self.response_delay = Some(delay);
+        self
+    }
+
+    /// Returns a handle to register responses that must be consumed in registration order,
+    /// regardless of the called method.
+    ///
+    /// The sequenced responses coexist with the unordered ones: a call to a method
+    /// that has no sequenced responses uses the responses registered directly on the mock client.
+    pub fn in_sequence(&self) -> MockSequence {
+        MockSequence {
+            sequence: self.sequence.clone(),
+        }
+    }
+
     /// Clears all query and update responses from the mock client.
     pub fn clear(&self) {
         let mut queries = self.queries.lock().unwrap();
         queries.clear();
         let mut updates = self.updates.lock().unwrap();
         updates.clear();
+        let mut sequence = self.sequence.lock().unwrap();
+        sequence.responses.clear();
+        sequence.violations.clear();
     }
 }
 
-impl CanisterClient for MockCanisterClient {
-    async fn update<T, R>(&self, method: &str, _args: T) -> CanisterClientResult<R>
+/// Handle to register mock responses that must be consumed in registration order.
+/// Created by `MockCanisterClient::in_sequence`.
+#[derive(Clone)]
+pub struct MockSequence {
+    sequence: Arc<Mutex<Sequence>>,
+}
+
+impl MockSequence {
+    /// Appends a query response to the sequence.
+    pub fn add_query<R>(&self, method: &str, response: CanisterClientResult<R>) -> &Self
     where
-        T: ArgumentEncoder + Send + Sync,
         R: DeserializeOwned + CandidType + Send,
     {
-        let mut updates = self.updates.lock().unwrap();
-        let update = updates
-            .get_mut(method)
-            .and_then(|v| v.pop_front())
-            .expect("No response for update call [{method}] in mock client");
-        let response = update();
+        self.add(QUERY, method, encode_response(response))
+    }
+
+    /// Appends a query response function to the sequence.
+    pub fn add_query_fn(
+        &self,
+        method: &str,
+        response: Box<dyn FnOnce() -> CanisterClientResult<Vec<u8>> + Send>,
+    ) -> &Self {
+        self.add(QUERY, method, response)
+    }
+
+    /// Appends an update response to the sequence.
+    pub fn add_update<R>(&self, method: &str, response: CanisterClientResult<R>) -> &Self
+    where
+        R: DeserializeOwned + CandidType + Send,
+    {
+        self.add(UPDATE, method, encode_response(response))
+    }
+
+    /// Appends an update response function to the sequence.
+    pub fn add_update_fn(
+        &self,
+        method: &str,
+        response: Box<dyn FnOnce() -> CanisterClientResult<Vec<u8>> + Send>,
+    ) -> &Self {
+        self.add(UPDATE, method, response)
+    }
+
+    /// Verifies that all the sequenced responses were consumed in registration order.
+    /// Returns an error naming the expected and the actual call otherwise.
+    pub fn verify(&self) -> Result<(), String> {
+        let sequence = self.sequence.lock().unwrap();
+        if let Some(violation) = sequence.violations.first() {
+            return Err(violation.clone());
+        }
+        if let Some(expected) = sequence.responses.front() {
+            return Err(format!(
+                "Expected {} call [{}] in mock client sequence, but it was never performed",
+                expected.kind, expected.method
+            ));
+        }
+        Ok(())
+    }
+
+    fn add(&self, kind: &'static str, method: &str, response: Response) -> &Self {
+        self.sequence
+            .lock()
+            .unwrap()
+            .responses
+            .push_back(SequencedResponse {
+                kind,
+                method: method.to_string(),
+                response,
+            });
+        self
+    }
+}
+
+fn encode_response<R>(response: CanisterClientResult<R>) -> Response
+where
+    R: DeserializeOwned + CandidType + Send,
+{
+    match response {
+        Ok(response) => {
+            let response = Encode!(&response).unwrap();
+            Box::new(move || Ok(response))
+        }
+        Err(err) => Box::new(move || Err(err)),
+    }
+}
+
+impl MockCanisterClient {
+    fn respond<R>(&self, kind: &'static str, method: &str) -> CanisterClientResult<R>
+    where
+        R: DeserializeOwned + CandidType,
+    {
+        let request = match self.next_sequenced_response(kind, method) {
+            Some(request) => request,
+            None => {
+                let requests = if kind == QUERY {
+                    &self.queries
+                } else {
+                    &self.updates
+                };
+                let mut requests = requests.lock().unwrap();
+                requests
+                    .get_mut(method)
+                    .and_then(|v| v.pop_front())
+                    .unwrap_or_else(|| {
+                        panic!("No response for {kind} call [{method}] in mock client")
+                    })
+            }
+        };
+        let response = request();
         match response {
             Ok(response) => {
-                let decoded = Decode!(&response, R).expect("The mock client response for update call [{method}] cannot be decoded to the expected type");
+                check_response_size(self.max_response_bytes, &response)?;
+                let decoded = Decode!(&response, R).unwrap_or_else(|_| panic!("The mock client response for {kind} call [{method}] cannot be decoded to the expected type"));
                 Ok(decoded)
             }
             Err(err) => Err(err),
         }
     }
 
-    async fn query<T, R>(&self, method: &str, _args: T) -> CanisterClientResult<R>
+    /// Returns the next sequenced response if the method is part of the sequence.
+    /// Panics if the method is part of the sequence but it is not the next expected call.
+    fn next_sequenced_response(&self, kind: &'static str, method: &str) -> Option<Response> {
+        let mut sequence = self.sequence.lock().unwrap();
+        if !sequence
+            .responses
+            .iter()
+            .any(|r| r.kind == kind && r.method == method)
+        {
+            return None;
+        }
+
+        let expected = sequence.responses.front()?;
+        if expected.kind != kind || expected.method != method {
+            let violation = format!(
+                "Expected {} call [{}] in mock client sequence, but got {kind} call [{method}]",
+                expected.kind, expected.method
+            );
+            sequence.violations.push(violation.clone());
+            drop(sequence);
+            panic!("{violation}");
+        }
+
+        sequence.responses.pop_front().map(|r| r.response)
+    }
+}
+
+impl CanisterClient for MockCanisterClient {
+    /// Query and composite query calls consume the query responses,
+    /// update calls consume the update responses.
+    async fn call<T, R>(&self, mode: CallMode, method: &str, _args: T) -> CanisterClientResult<R>
     where
         T: ArgumentEncoder + Send + Sync,
         R: DeserializeOwned + CandidType + Send,
     {
-        let mut queries = self.queries.lock().unwrap();
-        let query = queries
-            .get_mut(method)
-            .and_then(|v| v.pop_front())
-            .expect("No response for query call [{method}] in mock client");
-        let response = query();
-        match response {
-            Ok(response) => {
-                let decoded = Decode!(&response, R).expect("The mock client response for query call [{method}] cannot be decoded to the expected type");
-                Ok(decoded)
-            }
-            Err(err) => Err(err),
+        #[cfg(not(target_family = "wasm"))]
+        if let Some(delay) = self.response_delay {
+            tokio::time::sleep(delay).await;
+        }
+
+        match mode {
+            CallMode::Query | CallMode::CompositeQuery => self.respond(QUERY, method),
+            CallMode::Update => self.respond(UPDATE, method),
         }
     }
 }
@@ -171,9 +330,12 @@ impl CanisterClient for MockCanisterClient {
 #[cfg(test)]
 mod tests {
 
+    use std::time::Duration;
+
     use serde::Deserialize;
 
     use super::*;
+    use crate::CanisterClientError;
 
     #[derive(CandidType, Deserialize, Debug)]
     struct TestCandidType {
@@ -262,6 +424,147 @@ mod tests {
         mock_client.update::<_, u64>("update", ()).await.unwrap();
     }
 
+    #[tokio::test]
+    async fn test_mock_client_call_modes() {
+        let mock_client = MockCanisterClient::default();
+        mock_client.add_query("method", Ok(1u64));
+        mock_client.add_query("method", Ok(2u64));
+        mock_client.add_update("method", Ok(3u64));
+
+        assert_eq!(
+            mock_client
+                .call::<_, u64>(CallMode::Query, "method", ())
+                .await
+                .unwrap(),
+            1
+        );
+        assert_eq!(
+            mock_client
+                .call::<_, u64>(CallMode::CompositeQuery, "method", ())
+                .await
+                .unwrap(),
+            2
+        );
+        assert_eq!(
+            mock_client
+                .call::<_, u64>(CallMode::Update, "method", ())
+                .await
+                .unwrap(),
+            3
+        );
+    }
+
+    #[tokio::test]
+    async fn test_mock_client_sequence() {
+        let mock_client = MockCanisterClient::default();
+        mock_client.add_query("status", Ok(1u64));
+        let sequence = mock_client.in_sequence();
+        sequence
+            .add_update("prepare", Ok(10u64))
+            .add_query("status", Ok(11u64))
+            .add_update("commit", Ok(12u64));
+
+        assert_eq!(
+            mock_client.update::<_, u64>("prepare", ()).await.unwrap(),
+            10
+        );
+        assert_eq!(mock_client.query::<_, u64>("status", ()).await.unwrap(), 11);
+        assert_eq!(
+            mock_client.update::<_, u64>("commit", ()).await.unwrap(),
+            12
+        );
+        // the unordered responses are used once the sequenced ones are consumed
+        assert_eq!(mock_client.query::<_, u64>("status", ()).await.unwrap(), 1);
+
+        assert!(sequence.verify().is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_mock_client_sequence_violation() {
+        let mock_client = MockCanisterClient::default();
+        let sequence = mock_client.in_sequence();
+        sequence
+            .add_update("prepare", Ok(()))
+            .add_update("commit", Ok(()));
+
+        let client = mock_client.clone();
+        let result = tokio::spawn(async move { client.update::<_, ()>("commit", ()).await }).await;
+
+        assert!(result.unwrap_err().is_panic());
+        assert_eq!(
+            sequence.verify().unwrap_err(),
+            "Expected update call [prepare] in mock client sequence, but got update call [commit]"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_mock_client_sequence_not_consumed() {
+        let mock_client = MockCanisterClient::default();
+        let sequence = mock_client.in_sequence();
+        sequence
+            .add_update("prepare", Ok(()))
+            .add_update("commit", Ok(()));
+
+        mock_client.update::<_, ()>("prepare", ()).await.unwrap();
+
+        assert_eq!(
+            sequence.verify().unwrap_err(),
+            "Expected update call [commit] in mock client sequence, but it was never performed"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_mock_client_response_under_size_limit() {
+        let mock_client = MockCanisterClient::default().with_max_response_bytes(1024);
+        mock_client.add_query("query", Ok(vec![1u8; 512]));
+
+        let response = mock_client.query::<_, Vec<u8>>("query", ()).await.unwrap();
+
+        assert_eq!(response.len(), 512);
+    }
+
+    #[tokio::test]
+    async fn test_mock_client_response_over_size_limit() {
+        let mock_client = MockCanisterClient::default().with_max_response_bytes(1024);
+        mock_client.add_query("query", Ok(vec![1u8; 2048]));
+
+        let response = mock_client.query::<_, Vec<u8>>("query", ()).await;
+
+        match response {
+            Err(CanisterClientError::ResponseTooLarge { limit, actual }) => {
+                assert_eq!(limit, 1024);
+                assert!(actual > 2048);
+            }
+            _ => panic!("Expected a ResponseTooLarge error"),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_mock_client_call_within_deadline() {
+        let mock_client =
+            MockCanisterClient::default().with_response_delay(Duration::from_millis(10));
+        mock_client.add_query("query", Ok(42u64));
+
+        let response = mock_client
+            .query_with_deadline::<_, u64>("query", (), Duration::from_secs(10))
+            .await;
+
+        assert_eq!(response.unwrap(), 42);
+    }
+
+    #[tokio::test]
+    async fn test_mock_client_call_exceeding_deadline() {
+        let mock_client =
+            MockCanisterClient::default().with_response_delay(Duration::from_secs(10));
+        mock_client.add_update("update", Ok(42u64));
+
+        let response = mock_client
+            .update_with_deadline::<_, u64>("update", (), Duration::from_millis(10))
+            .await;
+
+        assert!(matches!(response, Err(CanisterClientError::Timeout(_))));
+    }
+
     #[test]
     fn test_mock_client_clear() {
         let mock_client = MockCanisterClient::default();
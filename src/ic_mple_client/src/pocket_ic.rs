@@ -1,4 +1,6 @@
+use std::marker::PhantomData;
 use std::sync::Arc;
+use std::time::Duration;
 
 use candid::utils::ArgumentEncoder;
 use candid::{CandidType, Decode, Principal};
@@ -6,7 +8,8 @@ use pocket_ic::common::rest::RawMessageId;
 use pocket_ic::nonblocking::*;
 use serde::de::DeserializeOwned;
 
-use crate::{CanisterClient, CanisterClientResult};
+use crate::error::check_response_size;
+use crate::{CallMode, CanisterClient, CanisterClientError, CanisterClientResult};
 
 /// A client for interacting with a canister inside dfinity's PocketIc test framework.
 #[derive(Clone)]
@@ -14,6 +17,8 @@ pub struct PocketIcClient {
     client: Option<Arc<PocketIc>>,
     pub canister: Principal,
     pub caller: Principal,
+    // the max size of a response. None means unlimited.
+    max_response_bytes: Option<usize>,
 }
 
 impl PocketIcClient {
@@ -33,9 +38,48 @@ impl PocketIcClient {
             client: Some(client.into()),
             canister,
             caller,
+            max_response_bytes: None,
         }
     }
 
+    /// Sets the max size in bytes of the responses accepted by this client.
+    /// A larger response is rejected with a `CanisterClientError::ResponseTooLarge` error
+    /// before attempting to decode it.
+    /// By default, the size of the responses is unlimited.
+    pub fn with_max_response_bytes(mut self, max_response_bytes: usize) -> Self {
+        self.max_response_bytes = Some(max_response_bytes);
+        self
+    }
+
+    /// Creates a new instance of PocketIcClient that calls the canister as its first controller.
+    /// The controller is resolved from the canister settings.
+    ///
+    /// Panics if the canister has no controllers.
+    pub async fn as_controller<P: Into<Arc<PocketIc>>>(client: P, canister: Principal) -> Self {
+        let client = client.into();
+        let controller = client
+            .get_controllers(canister)
+            .await
+            .into_iter()
+            .next()
+            .unwrap_or_else(|| panic!("The canister [{canister}] has no controllers"));
+        Self::from_client(client, canister, controller)
+    }
+
+    /// Creates a new instance of PocketIcClient that calls the canister as the anonymous principal.
+    pub fn as_anonymous<P: Into<Arc<PocketIc>>>(client: P, canister: Principal) -> Self {
+        Self::from_client(client, canister, Principal::anonymous())
+    }
+
+    /// Creates a new instance of PocketIcClient that calls the canister as the given principal.
+    pub fn as_principal<P: Into<Arc<PocketIc>>>(
+        client: P,
+        canister: Principal,
+        principal: Principal,
+    ) -> Self {
+        Self::from_client(client, canister, principal)
+    }
+
     /// Returns the PocketIC client for the canister.
     pub fn client(&self) -> &PocketIc {
         self.client
@@ -49,15 +93,19 @@ impl PocketIcClient {
         T: ArgumentEncoder + Send + Sync,
         R: DeserializeOwned + CandidType,
     {
-        let args = candid::encode_args(args)?;
+        self.with_context(method, async {
+            let args = candid::encode_args(args)?;
 
-        let call_result = self
-            .client()
-            .update_call(self.canister, self.caller, method, args)
-            .await?;
+            let call_result = self
+                .client()
+                .update_call(self.canister, self.caller, method, args)
+                .await?;
 
-        let decoded = Decode!(&call_result, R)?;
-        Ok(decoded)
+            check_response_size(self.max_response_bytes, &call_result)?;
+            let decoded = Decode!(&call_result, R)?;
+            Ok::<_, CanisterClientError>(decoded)
+        })
+        .await
     }
 
     /// Performs a query call with the given arguments.
@@ -66,15 +114,19 @@ impl PocketIcClient {
         T: ArgumentEncoder + Send + Sync,
         R: DeserializeOwned + CandidType,
     {
-        let args = candid::encode_args(args)?;
+        self.with_context(method, async {
+            let args = candid::encode_args(args)?;
 
-        let call_result = self
-            .client()
-            .query_call(self.canister, self.caller, method, args)
-            .await?;
+            let call_result = self
+                .client()
+                .query_call(self.canister, self.caller, method, args)
+                .await?;
 
-        let decoded = Decode!(&call_result, R)?;
-        Ok(decoded)
+            check_response_size(self.max_response_bytes, &call_result)?;
+            let decoded = Decode!(&call_result, R)?;
+            Ok::<_, CanisterClientError>(decoded)
+        })
+        .await
     }
 
     /// Submit an update call (without executing it immediately).
@@ -82,14 +134,17 @@ impl PocketIcClient {
     where
         T: ArgumentEncoder + Send + Sync,
     {
-        let args = candid::encode_args(args)?;
+        self.with_context(method, async {
+            let args = candid::encode_args(args)?;
 
-        let msg_id = self
-            .client()
-            .submit_call(self.canister, self.caller, method, args)
-            .await?;
+            let msg_id = self
+                .client()
+                .submit_call(self.canister, self.caller, method, args)
+                .await?;
 
-        Ok(msg_id)
+            Ok::<_, CanisterClientError>(msg_id)
+        })
+        .await
     }
 
     /// Await an update call submitted previously by `submit_call`.
@@ -98,25 +153,186 @@ impl PocketIcClient {
         R: DeserializeOwned + CandidType,
     {
         let call_result = self.client().await_call(msg_id).await?;
+        check_response_size(self.max_response_bytes, &call_result)?;
         let decoded = Decode!(&call_result, R)?;
         Ok(decoded)
     }
-}
 
-impl CanisterClient for PocketIcClient {
-    async fn update<T, R>(&self, method: &str, args: T) -> CanisterClientResult<R>
+    /// Submit an update call (without executing it immediately).
+    /// The returned `PendingCall` remembers the expected return type of the call.
+    pub async fn submit<T, R>(&self, method: &str, args: T) -> CanisterClientResult<PendingCall<R>>
     where
         T: ArgumentEncoder + Send + Sync,
-        R: DeserializeOwned + CandidType + Send,
+        R: DeserializeOwned + CandidType,
     {
-        PocketIcClient::update_call(self, method, args).await
+        let msg_id = self.submit_call(method, args).await?;
+        Ok(PendingCall {
+            client: self.clone(),
+            method: method.to_string(),
+            msg_id,
+            _result: PhantomData,
+        })
     }
 
-    async fn query<T, R>(&self, method: &str, args: T) -> CanisterClientResult<R>
+    /// Executes a single round of the submitted calls with an explicit `tick()`
+    /// and then awaits the results of all the given pending calls.
+    /// The results are returned in the same order as the pending calls.
+    pub async fn join_all<R>(&self, calls: Vec<PendingCall<R>>) -> Vec<CanisterClientResult<R>>
+    where
+        R: DeserializeOwned + CandidType,
+    {
+        self.client().tick().await;
+
+        let mut results = Vec::with_capacity(calls.len());
+        for call in calls {
+            results.push(call.await_result().await);
+        }
+        results
+    }
+
+    /// Repeatedly advances the time by `step`, executes a round and performs the query,
+    /// until the predicate holds for the decoded value.
+    /// This is useful to test canisters driven by timers.
+    ///
+    /// Returns the first value for which the predicate holds,
+    /// or an error if a query fails or the predicate does not hold after `max_steps` steps.
+    /// The time is advanced by [`ic_mple_pocket_ic::advance_until`].
+    pub async fn advance_until<T, R, F>(
+        &self,
+        query_method: &str,
+        args: T,
+        predicate: F,
+        step: Duration,
+        max_steps: usize,
+    ) -> CanisterClientResult<R>
+    where
+        T: ArgumentEncoder + Send + Sync,
+        R: DeserializeOwned + CandidType,
+        F: Fn(&R) -> bool,
+    {
+        self.with_context(query_method, async {
+            let args = candid::encode_args(args)?;
+
+            // The outcome of the last query, kept to stop at the first error or satisfying value
+            let mut outcome = None;
+            ic_mple_pocket_ic::advance_until(self.client(), step, max_steps, async |env| {
+                let result: CanisterClientResult<R> = async {
+                    let call_result = env
+                        .query_call(self.canister, self.caller, query_method, args.clone())
+                        .await?;
+                    check_response_size(self.max_response_bytes, &call_result)?;
+                    Ok(Decode!(&call_result, R)?)
+                }
+                .await;
+
+                match result {
+                    Ok(decoded) if !predicate(&decoded) => false,
+                    result => {
+                        outcome = Some(result);
+                        true
+                    }
+                }
+            })
+            .await
+            .map_err(|timeout| {
+                CanisterClientError::PocketIcPredicateNotSatisfied {
+                    steps: timeout.iterations,
+                }
+            })?;
+
+            outcome.expect("the outcome is set when the predicate is satisfied")
+        })
+        .await
+    }
+
+    /// Attaches the canister id and the method to the error returned by the call, if any.
+    async fn with_context<R>(
+        &self,
+        method: &str,
+        call: impl Future<Output = CanisterClientResult<R>>,
+    ) -> CanisterClientResult<R> {
+        call.await
+            .map_err(|e| e.with_context(self.canister, method))
+    }
+}
+
+/// An update call submitted by `PocketIcClient::submit` that has not been awaited yet.
+pub struct PendingCall<R> {
+    client: PocketIcClient,
+    method: String,
+    msg_id: RawMessageId,
+    _result: PhantomData<fn() -> R>,
+}
+
+impl<R> PendingCall<R>
+where
+    R: DeserializeOwned + CandidType,
+{
+    /// Returns the id of the submitted message.
+    pub fn message_id(&self) -> &RawMessageId {
+        &self.msg_id
+    }
+
+    /// Awaits the call and decodes its result to the expected type.
+    pub async fn await_result(self) -> CanisterClientResult<R> {
+        self.client
+            .await_call(self.msg_id)
+            .await
+            .map_err(|e| e.with_context(self.client.canister, &self.method))
+    }
+}
+
+impl CanisterClient for PocketIcClient {
+    async fn call<T, R>(&self, mode: CallMode, method: &str, args: T) -> CanisterClientResult<R>
     where
         T: ArgumentEncoder + Send + Sync,
         R: DeserializeOwned + CandidType + Send,
     {
-        PocketIcClient::query_call(self, method, args).await
+        match mode {
+            CallMode::Query | CallMode::CompositeQuery => {
+                PocketIcClient::query_call(self, method, args).await
+            }
+            CallMode::Update => PocketIcClient::update_call(self, method, args).await,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use ic_mple_pocket_ic::{CanisterSettingsArgs, create_canister_with, get_pocket_ic_client};
+
+    use super::*;
+
+    #[tokio::test]
+    async fn should_resolve_the_canister_controller() {
+        let client = Arc::new(get_pocket_ic_client().await.build_async().await);
+        let controller = Principal::from_slice(&[1; 29]);
+        let canister = create_canister_with(
+            &client,
+            CanisterSettingsArgs {
+                controllers: Some(vec![controller]),
+                ..Default::default()
+            },
+            0,
+        )
+        .await;
+
+        let as_controller = PocketIcClient::as_controller(client.clone(), canister).await;
+        let as_anonymous = PocketIcClient::as_anonymous(client.clone(), canister);
+        let user = Principal::from_slice(&[2; 29]);
+        let as_user = PocketIcClient::as_principal(client.clone(), canister, user);
+
+        assert_eq!(as_controller.caller, controller);
+        assert_eq!(as_controller.canister, canister);
+        assert_eq!(as_anonymous.caller, Principal::anonymous());
+        assert_eq!(as_user.caller, user);
+
+        drop(as_controller);
+        drop(as_anonymous);
+        drop(as_user);
+        if let Ok(client) = Arc::try_unwrap(client) {
+            client.drop().await
+        }
     }
 }
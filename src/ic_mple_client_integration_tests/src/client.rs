@@ -1,4 +1,4 @@
-use ic_mple_client::{CanisterClient, CanisterClientResult};
+use ic_mple_client::{CallMode, CanisterClient, CanisterClientResult};
 
 /// Client for the test canister
 #[derive(Debug, Clone)]
@@ -18,11 +18,17 @@ impl<C: CanisterClient> TestCanisterClient<C> {
         self.client.query("get_counter", ()).await
     }
 
+    pub async fn get_counter_with_mode(&self, mode: CallMode) -> CanisterClientResult<u64> {
+        self.client.call(mode, "get_counter", ()).await
+    }
+
     pub async fn increment_counter(&self, amount: u64) -> CanisterClientResult<()> {
         self.client.update("increment_counter", (amount,)).await
     }
 
     pub async fn counter_of_other_canister(&self) -> CanisterClientResult<u64> {
-        self.client.query("counter_of_other_canister", ()).await
+        self.client
+            .call(CallMode::CompositeQuery, "counter_of_other_canister", ())
+            .await
     }
 }
@@ -1,8 +1,9 @@
 use std::cell::RefCell;
 
 use candid::{CandidType, Deserialize, Principal};
+use ic_cdk::management_canister::{HttpMethod, HttpRequestArgs, http_request};
 use ic_cdk::{query, update};
-use ic_mple_client::IcCanisterClient;
+use ic_mple_client::{CallMode, IcCanisterClient};
 
 use crate::client::TestCanisterClient;
 
@@ -40,6 +41,7 @@ fn get_counter() -> u64 {
 
 #[update]
 fn increment_counter(amount: u64) {
+    ic_cdk::println!("increment_counter: {amount}");
     COUNTER.with(|counter| *counter.borrow_mut() += amount);
 }
 
@@ -50,7 +52,23 @@ async fn counter_of_other_canister() -> u64 {
     // Use IcCanisterClient to perform an intercanister call
     let client = IcCanisterClient::new(other_canister, Some(10));
     let client = TestCanisterClient::new(client);
-    client.get_counter().await.unwrap()
+    client
+        .get_counter_with_mode(CallMode::CompositeQuery)
+        .await
+        .unwrap()
+}
+
+#[update]
+async fn http_get(url: String) -> Result<String, String> {
+    let args = HttpRequestArgs {
+        url,
+        method: HttpMethod::GET,
+        max_response_bytes: Some(10_000),
+        ..Default::default()
+    };
+
+    let response = http_request(&args).await.map_err(|e| e.to_string())?;
+    String::from_utf8(response.body).map_err(|e| e.to_string())
 }
 
 // Enable Candid export
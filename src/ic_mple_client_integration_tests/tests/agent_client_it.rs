@@ -1,6 +1,6 @@
 use candid::Principal;
 use ic_mple_client::ic_agent::{agent::AgentBuilder, export::reqwest::Url};
-use ic_mple_client::{CanisterClient, CanisterClientResult, IcAgentClient};
+use ic_mple_client::{CanisterClient, CanisterClientError, CanisterClientResult, IcAgentClient};
 
 // Address of ckUSDC canister
 const CKUSDC_ADDRESS: &str = "xevnm-gaaaa-aaaar-qafnq-cai";
@@ -33,3 +33,23 @@ async fn agent_client_should_call_the_ckusdc_canister() {
     // Assert
     assert_eq!(symbol, "ckUSDC");
 }
+
+#[tokio::test]
+async fn agent_client_should_reject_a_response_larger_than_the_limit() {
+    // Arrange
+    let url = Url::parse("https://ic0.app").unwrap();
+    let agent = AgentBuilder::default().with_url(url).build().unwrap();
+    let client = IcAgentClient::with_agent(Principal::from_text(CKUSDC_ADDRESS).unwrap(), agent)
+        .with_max_response_bytes(1);
+    let ckusdc_client = CkUsdcClient { client };
+
+    // Act
+    let result = ckusdc_client.icrc1_symbol().await;
+
+    // Assert
+    let err = result.unwrap_err();
+    assert!(matches!(
+        err.inner(),
+        CanisterClientError::ResponseTooLarge { limit: 1, .. }
+    ));
+}
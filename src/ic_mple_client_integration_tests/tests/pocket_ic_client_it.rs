@@ -1,6 +1,13 @@
+use std::time::Duration;
+
 use candid::Principal;
-use ic_mple_client::PocketIcClient;
+use ic_mple_client::{CallMode, CanisterClientError, PocketIcClient};
 use ic_mple_client_integration_tests::client::TestCanisterClient;
+use ic_mple_pocket_ic::test_context::TestContext;
+use ic_mple_pocket_ic::{
+    assert_log_contains, canister_logs, pending_http_requests, query_ic_logs, respond_http,
+    take_snapshot,
+};
 use utils::pocket_ic_test_context::with_pocket_ic_context;
 
 mod utils;
@@ -90,3 +97,264 @@ async fn ic_mple_client_should_perform_an_intercanister_call() {
     .await
     .unwrap();
 }
+
+#[tokio::test]
+async fn ic_mple_client_should_execute_submitted_calls_concurrently() {
+    with_pocket_ic_context::<_, ()>(async move |ctx| {
+        // Arrange
+        let pocket_ic_client = PocketIcClient::from_client(
+            ctx.client.clone(),
+            ctx.canister_a_principal,
+            Principal::anonymous(),
+        );
+        let client = TestCanisterClient::new(pocket_ic_client.clone());
+
+        // Act
+        let first = pocket_ic_client
+            .submit::<_, ()>("increment_counter", (10u64,))
+            .await
+            .unwrap();
+        let second = pocket_ic_client
+            .submit::<_, ()>("increment_counter", (11u64,))
+            .await
+            .unwrap();
+        let results = pocket_ic_client.join_all(vec![first, second]).await;
+        let counter = client.get_counter().await.unwrap();
+
+        // Assert
+        assert_eq!(results.len(), 2);
+        assert!(results.iter().all(|result| result.is_ok()));
+        assert_eq!(counter, 21);
+
+        Ok(())
+    })
+    .await
+    .unwrap();
+}
+
+#[tokio::test]
+async fn ic_mple_client_should_call_with_every_call_mode() {
+    with_pocket_ic_context::<_, ()>(async move |ctx| {
+        // Arrange
+        let client = TestCanisterClient::new(PocketIcClient::from_client(
+            ctx.client.clone(),
+            ctx.canister_a_principal,
+            Principal::anonymous(),
+        ));
+        client.increment_counter(7).await.unwrap();
+
+        // Act
+        let query = client.get_counter_with_mode(CallMode::Query).await.unwrap();
+        let composite_query = client
+            .get_counter_with_mode(CallMode::CompositeQuery)
+            .await
+            .unwrap();
+        let update = client
+            .get_counter_with_mode(CallMode::Update)
+            .await
+            .unwrap();
+
+        // Assert
+        assert_eq!(query, 7);
+        assert_eq!(composite_query, 7);
+        assert_eq!(update, 7);
+
+        Ok(())
+    })
+    .await
+    .unwrap();
+}
+
+#[tokio::test]
+async fn ic_mple_client_should_receive_a_mocked_http_outcall_response() {
+    with_pocket_ic_context::<_, ()>(async move |ctx| {
+        // Arrange
+        let client = PocketIcClient::from_client(
+            ctx.client.clone(),
+            ctx.canister_a_principal,
+            Principal::anonymous(),
+        );
+        let pending = client
+            .submit::<_, Result<String, String>>(
+                "http_get",
+                ("https://example.com/price".to_string(),),
+            )
+            .await
+            .unwrap();
+
+        // Act
+        ctx.client.tick().await;
+        ctx.client.tick().await;
+        let requests = pending_http_requests(&ctx.client).await;
+        assert_eq!(requests.len(), 1);
+        assert_eq!(requests[0].url, "https://example.com/price");
+        respond_http(
+            &ctx.client,
+            &requests[0],
+            200,
+            vec![("content-type".to_string(), "text/plain".to_string())],
+            b"42".to_vec(),
+        )
+        .await;
+        let response = pending.await_result().await.unwrap();
+
+        // Assert
+        assert_eq!(response, Ok("42".to_string()));
+
+        Ok(())
+    })
+    .await
+    .unwrap();
+}
+
+#[tokio::test]
+async fn ic_mple_client_should_fetch_the_canister_logs() {
+    with_pocket_ic_context::<_, ()>(async move |ctx| {
+        // Arrange
+        let client = TestCanisterClient::new(PocketIcClient::from_client(
+            ctx.client.clone(),
+            ctx.canister_a_principal,
+            Principal::anonymous(),
+        ));
+
+        // Act
+        client.increment_counter(5).await.unwrap();
+
+        // Assert
+        assert_log_contains(
+            &ctx.client,
+            ctx.canister_a_principal,
+            "increment_counter: 5",
+        )
+        .await;
+        assert!(
+            !canister_logs(&ctx.client, ctx.canister_b_principal)
+                .await
+                .iter()
+                .any(|record| record.content.starts_with(b"increment_counter"))
+        );
+
+        Ok(())
+    })
+    .await
+    .unwrap();
+}
+
+#[tokio::test]
+async fn ic_mple_client_should_restore_a_canister_snapshot() {
+    with_pocket_ic_context::<_, ()>(async move |ctx| {
+        // Arrange
+        let client = TestCanisterClient::new(PocketIcClient::from_client(
+            ctx.client.clone(),
+            ctx.canister_a_principal,
+            Principal::anonymous(),
+        ));
+        client.increment_counter(5).await.unwrap();
+        let snapshot = take_snapshot(&ctx.client, ctx.canister_a_principal)
+            .await
+            .unwrap();
+        let test_context = TestContext::from_client(ctx.client.clone());
+
+        // Act
+        client.increment_counter(10).await.unwrap();
+        let counter_before_restore = client.get_counter().await.unwrap();
+        let counter_after_restore = test_context
+            .with_snapshot(ctx.canister_a_principal, &snapshot, async |_| {
+                client.get_counter().await.unwrap()
+            })
+            .await;
+
+        // Assert
+        assert_eq!(counter_before_restore, 15);
+        assert_eq!(counter_after_restore, 5);
+
+        Ok(())
+    })
+    .await
+    .unwrap();
+}
+
+#[tokio::test]
+async fn ic_mple_client_should_query_the_typed_canister_logs() {
+    with_pocket_ic_context::<_, ()>(async move |ctx| {
+        // Arrange
+        let client = TestCanisterClient::new(PocketIcClient::from_client(
+            ctx.client.clone(),
+            ctx.canister_a_principal,
+            Principal::anonymous(),
+        ));
+
+        // Act
+        client.increment_counter(1).await.unwrap();
+        client.increment_counter(2).await.unwrap();
+
+        // Assert
+        let logs = query_ic_logs(&ctx.client, ctx.canister_a_principal)
+            .await
+            .into_iter()
+            .filter(|record| record.message.starts_with("increment_counter"))
+            .collect::<Vec<_>>();
+        assert_eq!(logs.len(), 2);
+        assert_eq!(logs[0].message, "increment_counter: 1");
+        assert_eq!(logs[1].message, "increment_counter: 2");
+        assert!(logs[0].idx < logs[1].idx);
+        assert!(logs[0].timestamp_nanos > 0);
+
+        Ok(())
+    })
+    .await
+    .unwrap();
+}
+
+#[tokio::test]
+async fn ic_mple_client_should_advance_until_the_predicate_holds() {
+    with_pocket_ic_context::<_, ()>(async move |ctx| {
+        // Arrange
+        let client = PocketIcClient::from_client(
+            ctx.client.clone(),
+            ctx.canister_a_principal,
+            Principal::anonymous(),
+        );
+        TestCanisterClient::new(client.clone())
+            .increment_counter(3)
+            .await
+            .unwrap();
+        let start = ctx.client.get_time().await;
+
+        // Act
+        let counter = client
+            .advance_until(
+                "get_counter",
+                (),
+                |counter: &u64| *counter == 3,
+                Duration::from_secs(1),
+                5,
+            )
+            .await
+            .unwrap();
+        let timeout = client
+            .advance_until(
+                "get_counter",
+                (),
+                |counter: &u64| *counter == 4,
+                Duration::from_secs(1),
+                5,
+            )
+            .await
+            .unwrap_err();
+
+        // Assert
+        let elapsed = ctx.client.get_time().await.as_nanos_since_unix_epoch()
+            - start.as_nanos_since_unix_epoch();
+        assert_eq!(counter, 3);
+        assert!(matches!(
+            timeout.inner(),
+            CanisterClientError::PocketIcPredicateNotSatisfied { steps: 5 }
+        ));
+        assert!(elapsed >= Duration::from_secs(6).as_nanos() as u64);
+
+        Ok(())
+    })
+    .await
+    .unwrap();
+}
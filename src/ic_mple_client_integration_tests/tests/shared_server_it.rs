@@ -0,0 +1,30 @@
+use std::env;
+
+use ic_mple_pocket_ic::{
+    POCKET_IC_SERVER_URL_ENV, PocketIcServer, ensure_shared_server, get_pocket_ic_client,
+};
+
+// This is the only test of this binary because it changes the environment of the process
+#[tokio::test]
+async fn should_create_the_instances_on_the_server_set_in_the_environment() {
+    // Arrange
+    let server = PocketIcServer::start().await;
+    // SAFETY: no other thread of this binary reads the environment concurrently
+    unsafe {
+        env::set_var(POCKET_IC_SERVER_URL_ENV, server.url().as_str());
+    }
+
+    // Act
+    let shared_server_url = ensure_shared_server().await;
+    let pocket_ic = get_pocket_ic_client().await.build_async().await;
+    let canister = pocket_ic.create_canister().await;
+
+    // Assert
+    assert_eq!(&shared_server_url, server.url());
+    assert_eq!(&pocket_ic.get_server_url(), server.url());
+    assert!(pocket_ic.canister_exists(canister).await);
+
+    pocket_ic.drop().await;
+    // The server is stopped when dropped
+    drop(server);
+}
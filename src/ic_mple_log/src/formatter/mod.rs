@@ -34,7 +34,7 @@ use std::{fmt, io};
 
 pub mod buffer;
 mod humantime;
-use ic_mple_utils::ic_api::{IcApi, IcTrait};
+use ic_mple_utils::ic_api::{IcApi, IcTrait, ic};
 use log::Record;
 
 use self::buffer::Buffer;
@@ -128,7 +128,7 @@ impl Builder {
                     indent: self.format_indent,
                     suffix: self.format_suffix,
                     formatter: buf,
-                    ic: IcApi::default(),
+                    ic: ic(),
                 };
 
                 fmt.write(record)
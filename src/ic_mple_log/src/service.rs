@@ -99,17 +99,17 @@ impl<S: Storage<LoggerServiceStorage>> LoggerConfigService<S> {
         }
 
         if let Some(log_settings) = log_settings {
-            self.log_settings_store.with_borrow_mut(|store| {
+            self.log_settings_store.try_with_borrow_mut(|store| {
                 store.set(log_settings.into());
-            });
+            })?;
         }
 
-        self.log_settings_store.with_borrow(|store| {
+        self.log_settings_store.try_with_borrow(|store| {
             LOGGER_CONFIG.with_borrow_mut(|logger_config| {
                 *logger_config = Some(init_log(store.get())?);
                 Ok(())
             })
-        })
+        })?
     }
 
     /// Changes the logger filter at runtime
@@ -127,12 +127,19 @@ impl<S: Storage<LoggerServiceStorage>> LoggerConfigService<S> {
             .with_borrow(|store| store.get().log_filter.clone())
     }
 
+    /// Returns the current logger filter, or an error if the storage cannot be accessed
+    pub fn try_get_logger_filter(&self) -> Result<String, LogError> {
+        Ok(self
+            .log_settings_store
+            .try_with_borrow(|store| store.get().log_filter.clone())?)
+    }
+
     fn update_log_settings(&mut self, filter: &str) -> Result<(), LogError> {
-        self.log_settings_store.with_borrow_mut(|store| {
+        self.log_settings_store.try_with_borrow_mut(|store| {
             let mut log_settings = store.get().clone();
             log_settings.log_filter = filter.to_string();
             store.set(log_settings);
-        });
+        })?;
 
         Ok(())
     }
@@ -141,6 +148,8 @@ impl<S: Storage<LoggerServiceStorage>> LoggerConfigService<S> {
 #[cfg(test)]
 mod test {
     use std::cell::RefCell;
+    use std::panic::AssertUnwindSafe;
+    use std::sync::{Arc, Mutex};
 
     use ic_stable_structures::{
         DefaultMemoryImpl,
@@ -148,6 +157,7 @@ mod test {
     };
 
     use super::*;
+    use ic_mple_utils::store::StorageAccessError;
 
     thread_local! {
         static LOG_SETTINGS_STORE: RefCell<LoggerServiceStorage> = RefCell::new(
@@ -170,4 +180,28 @@ mod test {
         let logger_config_service = LoggerConfigService::new(store);
         assert_eq!(logger_config_service.get_logger_filter(), "warn");
     }
+
+    #[test]
+    fn should_return_an_error_if_the_storage_is_poisoned() {
+        let store = Arc::new(Mutex::new(StableCell::new(
+            MemoryManager::init(DefaultMemoryImpl::default()).get(MemoryId::new(1)),
+            LogSettings::default(),
+        )));
+        let mut logger_config_service = LoggerConfigService::new(store.clone());
+
+        let result = std::panic::catch_unwind(AssertUnwindSafe(|| {
+            let _guard = store.lock().unwrap();
+            panic!("poisoning the storage");
+        }));
+        assert!(result.is_err());
+
+        assert_eq!(
+            logger_config_service.try_get_logger_filter(),
+            Err(LogError::StorageAccess(StorageAccessError::Poisoned))
+        );
+        assert_eq!(
+            logger_config_service.set_logger_filter("debug"),
+            Err(LogError::StorageAccess(StorageAccessError::Poisoned))
+        );
+    }
 }
@@ -1,5 +1,6 @@
 use candid::CandidType;
 use env_filter::ParseError;
+use ic_mple_utils::store::StorageAccessError;
 use log::SetLoggerError;
 use serde::Deserialize;
 
@@ -27,6 +28,8 @@ pub enum LogError {
     InvalidMemory,
     /// Error in the logger configuration.
     InvalidConfiguration(String),
+    /// The storage of the logger configuration cannot be accessed.
+    StorageAccess(StorageAccessError),
 }
 
 impl From<ParseError> for LogError {
@@ -35,6 +38,12 @@ impl From<ParseError> for LogError {
     }
 }
 
+impl From<StorageAccessError> for LogError {
+    fn from(value: StorageAccessError) -> Self {
+        Self::StorageAccess(value)
+    }
+}
+
 impl From<SetLoggerError> for LogError {
     fn from(_: SetLoggerError) -> Self {
         Self::AlreadyInitialized
@@ -0,0 +1,258 @@
+use std::path::{Path, PathBuf};
+use std::{env, fs, io};
+
+use log::*;
+
+/// The environment variable that overrides the directory where the PocketIC server binaries are cached.
+pub const POCKET_IC_CACHE_DIR_ENV: &str = "POCKET_IC_CACHE_DIR";
+
+/// The environment variable that, if set to a number, enables the automatic cleanup
+/// of the cached PocketIC server versions after a download, keeping the given number of versions.
+pub const POCKET_IC_CLEANUP_KEEP_ENV: &str = "POCKET_IC_CLEANUP_KEEP";
+
+/// Returns the directory where the PocketIC server binaries are cached.
+///
+/// The directory is resolved in this order:
+/// - the `POCKET_IC_CACHE_DIR` environment variable
+/// - `$XDG_CACHE_HOME/ic_mple/pocket-ic`
+/// - `$HOME/.cache/ic_mple/pocket-ic`
+/// - the `pocket-ic-server` directory in the temp directory
+pub fn pocket_ic_cache_dir() -> PathBuf {
+    resolve_cache_dir(|key| env::var(key).ok())
+}
+
+/// Resolves the cache directory reading the environment variables with the given function.
+fn resolve_cache_dir(env_var: impl Fn(&str) -> Option<String>) -> PathBuf {
+    let env_var = |key: &str| env_var(key).filter(|value| !value.trim().is_empty());
+
+    if let Some(cache_dir) = env_var(POCKET_IC_CACHE_DIR_ENV) {
+        return PathBuf::from(cache_dir);
+    }
+
+    if let Some(xdg_cache_home) = env_var("XDG_CACHE_HOME") {
+        return PathBuf::from(xdg_cache_home)
+            .join("ic_mple")
+            .join("pocket-ic");
+    }
+
+    if let Some(home) = env_var("HOME") {
+        return PathBuf::from(home)
+            .join(".cache")
+            .join("ic_mple")
+            .join("pocket-ic");
+    }
+
+    env::temp_dir().join("pocket-ic-server")
+}
+
+/// Deletes the cached PocketIC server versions, keeping the `keep` most recent ones.
+/// Returns the deleted version directories.
+///
+/// The directory of the binary pointed to by the `POCKET_IC_BIN` environment variable is never deleted.
+pub fn cleanup_old_versions(keep: usize) -> io::Result<Vec<PathBuf>> {
+    cleanup_old_versions_in(&pocket_ic_cache_dir(), keep, &current_binaries(None))
+}
+
+/// Runs the cleanup of the cached versions if the `POCKET_IC_CLEANUP_KEEP` environment variable is set.
+/// The directory of the given binary is never deleted.
+pub(crate) fn cleanup_old_versions_from_env(downloaded_binary: &Path) {
+    let Some(keep) = env::var(POCKET_IC_CLEANUP_KEEP_ENV)
+        .ok()
+        .and_then(|keep| keep.trim().parse::<usize>().ok())
+    else {
+        return;
+    };
+
+    let current_binaries = current_binaries(Some(downloaded_binary));
+    match cleanup_old_versions_in(&pocket_ic_cache_dir(), keep, &current_binaries) {
+        Ok(removed) => {
+            for dir in removed {
+                info!("removed stale pocket-ic server version [{dir:?}]");
+            }
+        }
+        Err(e) => warn!("stale pocket-ic server versions cannot be removed: {e}"),
+    }
+}
+
+/// Returns the binary pointed to by the `POCKET_IC_BIN` environment variable and the given one.
+fn current_binaries(binary: Option<&Path>) -> Vec<PathBuf> {
+    env::var("POCKET_IC_BIN")
+        .ok()
+        .map(PathBuf::from)
+        .into_iter()
+        .chain(binary.map(Path::to_path_buf))
+        .collect()
+}
+
+/// Deletes the version directories under `cache_dir`, keeping the `keep` most recent ones
+/// and the ones containing the `current_binaries`.
+/// Directories whose name is not a version are ignored.
+fn cleanup_old_versions_in(
+    cache_dir: &Path,
+    keep: usize,
+    current_binaries: &[PathBuf],
+) -> io::Result<Vec<PathBuf>> {
+    if !cache_dir.exists() {
+        return Ok(vec![]);
+    }
+
+    let mut versions = vec![];
+    for entry in fs::read_dir(cache_dir)? {
+        let entry = entry?;
+        if !entry.file_type()?.is_dir() {
+            continue;
+        }
+        if let Some(version) = entry.file_name().to_str().and_then(parse_version) {
+            versions.push((version, entry.path()));
+        }
+    }
+
+    // Most recent first
+    versions.sort_by(|(a, _), (b, _)| b.cmp(a));
+
+    let mut removed = vec![];
+    for (_, dir) in versions.into_iter().skip(keep) {
+        if current_binaries
+            .iter()
+            .any(|binary| binary.starts_with(&dir))
+        {
+            continue;
+        }
+        fs::remove_dir_all(&dir)?;
+        removed.push(dir);
+    }
+
+    Ok(removed)
+}
+
+/// Parses a `major.minor.patch` version.
+fn parse_version(version: &str) -> Option<(u64, u64, u64)> {
+    let mut parts = version.split('.');
+    let version = (
+        parts.next()?.parse().ok()?,
+        parts.next()?.parse().ok()?,
+        parts.next()?.parse().ok()?,
+    );
+    parts.next().is_none().then_some(version)
+}
+
+#[cfg(test)]
+mod tests {
+
+    use std::collections::HashMap;
+
+    use super::*;
+
+    fn resolve(vars: &[(&str, &str)]) -> PathBuf {
+        let vars: HashMap<String, String> = vars
+            .iter()
+            .map(|(key, value)| (key.to_string(), value.to_string()))
+            .collect();
+        resolve_cache_dir(|key| vars.get(key).cloned())
+    }
+
+    #[test]
+    fn should_use_the_cache_dir_env_first() {
+        let dir = resolve(&[
+            (POCKET_IC_CACHE_DIR_ENV, "/custom/cache"),
+            ("XDG_CACHE_HOME", "/xdg"),
+            ("HOME", "/home/user"),
+        ]);
+        assert_eq!(dir, PathBuf::from("/custom/cache"));
+    }
+
+    #[test]
+    fn should_use_the_xdg_cache_home() {
+        let dir = resolve(&[("XDG_CACHE_HOME", "/xdg"), ("HOME", "/home/user")]);
+        assert_eq!(dir, PathBuf::from("/xdg/ic_mple/pocket-ic"));
+    }
+
+    #[test]
+    fn should_use_the_home_cache() {
+        let dir = resolve(&[("HOME", "/home/user")]);
+        assert_eq!(dir, PathBuf::from("/home/user/.cache/ic_mple/pocket-ic"));
+    }
+
+    #[test]
+    fn should_use_the_temp_dir_as_last_resort() {
+        let dir = resolve(&[]);
+        assert_eq!(dir, env::temp_dir().join("pocket-ic-server"));
+    }
+
+    #[test]
+    fn should_keep_the_most_recent_versions() {
+        let dir =
+            cache_dir_with_versions("keep_most_recent", &["9.0.0", "12.0.0", "10.1.0", "11.0.0"]);
+
+        let mut removed = cleanup_old_versions_in(&dir, 2, &[]).unwrap();
+        removed.sort();
+
+        assert_eq!(removed, vec![dir.join("10.1.0"), dir.join("9.0.0")]);
+        assert!(dir.join("12.0.0").exists());
+        assert!(dir.join("11.0.0").exists());
+        assert!(!dir.join("10.1.0").exists());
+        assert!(!dir.join("9.0.0").exists());
+    }
+
+    #[test]
+    fn should_never_delete_the_current_binary_dir() {
+        let dir = cache_dir_with_versions("keep_current_binary", &["9.0.0", "10.0.0", "11.0.0"]);
+        let current_binary = dir.join("9.0.0").join("x86_64").join("pocket-ic");
+
+        let removed = cleanup_old_versions_in(&dir, 1, &[current_binary]).unwrap();
+
+        assert_eq!(removed, vec![dir.join("10.0.0")]);
+        assert!(dir.join("9.0.0").exists());
+        assert!(dir.join("11.0.0").exists());
+    }
+
+    #[test]
+    fn should_ignore_dirs_that_are_not_versions() {
+        let dir = cache_dir_with_versions("ignore_non_versions", &["10.0.0", "11.0.0", "custom"]);
+
+        let removed = cleanup_old_versions_in(&dir, 0, &[]).unwrap();
+
+        assert_eq!(removed.len(), 2);
+        assert!(dir.join("custom").exists());
+    }
+
+    #[test]
+    fn should_not_fail_if_the_cache_dir_does_not_exist() {
+        let dir = env::temp_dir()
+            .join("ic_mple_pocket_ic_cache_tests")
+            .join("missing");
+        assert!(cleanup_old_versions_in(&dir, 1, &[]).unwrap().is_empty());
+    }
+
+    #[test]
+    fn should_order_versions_numerically() {
+        assert!(parse_version("10.0.0") > parse_version("9.0.0"));
+        assert!(parse_version("9.10.0") > parse_version("9.9.0"));
+        assert_eq!(parse_version("9.0"), None);
+        assert_eq!(parse_version("9.0.0.1"), None);
+        assert_eq!(parse_version("latest"), None);
+    }
+
+    fn cache_dir_with_versions(name: &str, versions: &[&str]) -> PathBuf {
+        let dir = env::temp_dir()
+            .join("ic_mple_pocket_ic_cache_tests")
+            .join(name);
+        let _ = fs::remove_dir_all(&dir);
+        for version in versions {
+            let version_dir = dir.join(version).join("x86_64");
+            fs::create_dir_all(&version_dir).unwrap();
+            fs::write(version_dir.join("pocket-ic"), b"binary").unwrap();
+        }
+        dir
+    }
+
+    #[test]
+    fn should_ignore_blank_env_vars() {
+        let dir = resolve(&[
+            (POCKET_IC_CACHE_DIR_ENV, " "),
+            ("XDG_CACHE_HOME", ""),
+            ("HOME", "/home/user"),
+        ]);
+        assert_eq!(dir, PathBuf::from("/home/user/.cache/ic_mple/pocket-ic"));
+    }
+}
@@ -0,0 +1,135 @@
+use candid::{Nat, Principal};
+use pocket_ic::RejectResponse;
+use pocket_ic::management_canister::CanisterSettings;
+use pocket_ic::nonblocking::PocketIc;
+
+/// The settings of a canister created by `create_canister_with`.
+/// The settings that are not set keep the default value.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct CanisterSettingsArgs {
+    /// The controllers of the canister.
+    pub controllers: Option<Vec<Principal>>,
+    /// The compute allocation, in percent.
+    pub compute_allocation: Option<u64>,
+    /// The memory allocation, in bytes.
+    pub memory_allocation: Option<u64>,
+    /// The freezing threshold, in seconds.
+    pub freezing_threshold: Option<u64>,
+}
+
+impl From<CanisterSettingsArgs> for CanisterSettings {
+    fn from(args: CanisterSettingsArgs) -> Self {
+        CanisterSettings {
+            controllers: args.controllers,
+            compute_allocation: args.compute_allocation.map(Nat::from),
+            memory_allocation: args.memory_allocation.map(Nat::from),
+            freezing_threshold: args.freezing_threshold.map(Nat::from),
+            ..Default::default()
+        }
+    }
+}
+
+/// Creates a canister with the given settings and adds the given cycles to it.
+/// The canister is created by its first controller, if any.
+pub async fn create_canister_with(
+    env: &PocketIc,
+    settings: CanisterSettingsArgs,
+    cycles: u128,
+) -> Principal {
+    let sender = settings
+        .controllers
+        .as_ref()
+        .and_then(|controllers| controllers.first().copied());
+    let canister = env
+        .create_canister_with_settings(sender, Some(settings.into()))
+        .await;
+    if cycles > 0 {
+        env.add_cycles(canister, cycles).await;
+    }
+    canister
+}
+
+/// Updates the settings of the canister on behalf of the given sender, that must be a controller.
+pub async fn update_settings(
+    env: &PocketIc,
+    canister: Principal,
+    sender: Option<Principal>,
+    settings: CanisterSettingsArgs,
+) -> Result<(), RejectResponse> {
+    env.update_canister_settings(canister, sender, settings.into())
+        .await
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+    use crate::get_pocket_ic_client;
+
+    #[test]
+    fn should_convert_the_settings() {
+        let controller = Principal::from_slice(&[1; 29]);
+        let settings: CanisterSettings = CanisterSettingsArgs {
+            controllers: Some(vec![controller]),
+            compute_allocation: Some(10),
+            memory_allocation: None,
+            freezing_threshold: Some(3600),
+        }
+        .into();
+
+        assert_eq!(settings.controllers, Some(vec![controller]));
+        assert_eq!(settings.compute_allocation, Some(Nat::from(10u64)));
+        assert_eq!(settings.memory_allocation, None);
+        assert_eq!(settings.freezing_threshold, Some(Nat::from(3600u64)));
+    }
+
+    #[tokio::test]
+    async fn should_create_a_canister_with_settings() {
+        let env = get_pocket_ic_client().await.build_async().await;
+        let controller = Principal::from_slice(&[1; 29]);
+        let other = Principal::from_slice(&[2; 29]);
+
+        let canister = create_canister_with(
+            &env,
+            CanisterSettingsArgs {
+                controllers: Some(vec![controller]),
+                freezing_threshold: Some(3600),
+                ..Default::default()
+            },
+            10_u128.pow(12),
+        )
+        .await;
+
+        assert_eq!(env.get_controllers(canister).await, vec![controller]);
+        assert!(env.cycle_balance(canister).await >= 10_u128.pow(12));
+
+        update_settings(
+            &env,
+            canister,
+            Some(controller),
+            CanisterSettingsArgs {
+                controllers: Some(vec![controller, other]),
+                ..Default::default()
+            },
+        )
+        .await
+        .unwrap();
+
+        let mut controllers = env.get_controllers(canister).await;
+        controllers.sort();
+        assert_eq!(controllers, vec![controller, other]);
+
+        assert!(
+            update_settings(
+                &env,
+                canister,
+                Some(Principal::from_slice(&[3; 29])),
+                CanisterSettingsArgs::default()
+            )
+            .await
+            .is_err()
+        );
+
+        env.drop().await;
+    }
+}
@@ -0,0 +1,79 @@
+use std::fmt;
+
+use sha2::{Digest, Sha256};
+
+/// Error returned when the checksum of a binary does not match the expected one.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ChecksumMismatch {
+    pub expected: String,
+    pub actual: String,
+}
+
+impl fmt::Display for ChecksumMismatch {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "SHA-256 checksum mismatch: expected [{}], found [{}]",
+            self.expected, self.actual
+        )
+    }
+}
+
+impl std::error::Error for ChecksumMismatch {}
+
+/// Returns the lowercase hex encoded SHA-256 checksum of the bytes.
+pub fn sha256_hex(bytes: &[u8]) -> String {
+    Sha256::digest(bytes)
+        .iter()
+        .map(|byte| format!("{byte:02x}"))
+        .collect()
+}
+
+/// Verifies that the SHA-256 checksum of the bytes matches the expected hex encoded checksum.
+/// The comparison is case insensitive.
+pub fn verify_sha256(bytes: &[u8], expected: &str) -> Result<(), ChecksumMismatch> {
+    let actual = sha256_hex(bytes);
+    if actual.eq_ignore_ascii_case(expected.trim()) {
+        Ok(())
+    } else {
+        Err(ChecksumMismatch {
+            expected: expected.trim().to_lowercase(),
+            actual,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    // SHA-256 of "hello world"
+    const HELLO_WORLD_SHA256: &str =
+        "b94d27b9934d3e08a52e52d7da7dabfac484efe37a5380ee9088f7ace2efcde9";
+
+    #[test]
+    fn should_compute_the_sha256_checksum() {
+        assert_eq!(sha256_hex(b"hello world"), HELLO_WORLD_SHA256);
+    }
+
+    #[test]
+    fn should_verify_a_matching_checksum() {
+        assert!(verify_sha256(b"hello world", HELLO_WORLD_SHA256).is_ok());
+        assert!(verify_sha256(b"hello world", &HELLO_WORLD_SHA256.to_uppercase()).is_ok());
+    }
+
+    #[test]
+    fn should_reject_a_mismatching_checksum() {
+        let result = verify_sha256(b"hello world!", HELLO_WORLD_SHA256);
+
+        let error = result.unwrap_err();
+        assert_eq!(error.expected, HELLO_WORLD_SHA256);
+        assert_eq!(error.actual, sha256_hex(b"hello world!"));
+    }
+
+    #[test]
+    fn should_reject_truncated_bytes() {
+        assert!(verify_sha256(b"hello", HELLO_WORLD_SHA256).is_err());
+    }
+}
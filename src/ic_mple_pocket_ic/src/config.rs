@@ -0,0 +1,191 @@
+use std::env;
+
+use crate::platform::Platform;
+
+/// The default version of the PocketIC server.
+pub const POCKET_IC_SERVER_VERSION: &str = "12.0.0";
+
+/// The environment variable that overrides the default version of the PocketIC server.
+pub const POCKET_IC_SERVER_VERSION_ENV: &str = "POCKET_IC_SERVER_VERSION";
+
+/// The environment variable that sets the expected SHA-256 checksum of the PocketIC server binary.
+pub const POCKET_IC_SERVER_SHA256_ENV: &str = "POCKET_IC_SERVER_SHA256";
+
+/// The environment variable that overrides the template of the URL used to download the PocketIC server.
+pub const POCKET_IC_DOWNLOAD_URL_TEMPLATE_ENV: &str = "POCKET_IC_DOWNLOAD_URL_TEMPLATE";
+
+/// The environment variable that, if set to `1` or `true`, disables the download progress printed to stderr.
+pub const POCKET_IC_QUIET_ENV: &str = "POCKET_IC_QUIET";
+
+/// The default template of the URL used to download the PocketIC server.
+pub const POCKET_IC_DOWNLOAD_URL_TEMPLATE: &str = "https://github.com/dfinity/pocketic/releases/download/{version}/pocket-ic-{arch}-{platform}.gz";
+
+/// The configuration of the PocketIC server used by `get_pocket_ic_client_with`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PocketIcConfig {
+    /// The version of the PocketIC server to use.
+    /// It is used both in the cache path and in the download URL of the server binary.
+    pub version: String,
+    /// The expected hex encoded SHA-256 checksum of the decompressed server binary.
+    /// If set, both the downloaded and the cached binaries are verified against it.
+    pub sha256: Option<String>,
+    /// The template of the URL used to download the gzipped server binary.
+    /// The `{version}`, `{platform}` and `{arch}` placeholders are replaced with the server version,
+    /// the platform (`linux` or `darwin`) and the architecture (`x86_64` or `arm64`).
+    /// A `file://` URL can be used to copy the binary from a local mirror.
+    pub download_url_template: String,
+    /// Whether to run `pocket-ic --version` to verify the version of a cached server binary before using it.
+    pub check_version: bool,
+    /// Whether to disable the download progress printed to stderr.
+    /// The progress is always logged, instead of printed, if a logger is installed.
+    pub quiet: bool,
+}
+
+impl Default for PocketIcConfig {
+    /// Returns the default configuration.
+    /// The version is read from the `POCKET_IC_SERVER_VERSION` environment variable, if set,
+    /// the checksum from the `POCKET_IC_SERVER_SHA256` environment variable
+    /// the download URL template from the `POCKET_IC_DOWNLOAD_URL_TEMPLATE` environment variable
+    /// and the quiet flag from the `POCKET_IC_QUIET` environment variable.
+    fn default() -> Self {
+        Self {
+            version: version_or_default(env::var(POCKET_IC_SERVER_VERSION_ENV).ok()),
+            sha256: env::var(POCKET_IC_SERVER_SHA256_ENV)
+                .ok()
+                .filter(|sha256| !sha256.trim().is_empty()),
+            download_url_template: env::var(POCKET_IC_DOWNLOAD_URL_TEMPLATE_ENV)
+                .ok()
+                .filter(|template| !template.trim().is_empty())
+                .unwrap_or_else(|| POCKET_IC_DOWNLOAD_URL_TEMPLATE.to_string()),
+            check_version: false,
+            quiet: env::var(POCKET_IC_QUIET_ENV)
+                .ok()
+                .is_some_and(|quiet| is_enabled(&quiet)),
+        }
+    }
+}
+
+impl PocketIcConfig {
+    /// Sets the version of the PocketIC server.
+    pub fn with_version(mut self, version: impl Into<String>) -> Self {
+        self.version = version.into();
+        self
+    }
+
+    /// Sets the expected hex encoded SHA-256 checksum of the decompressed server binary.
+    pub fn with_sha256(mut self, sha256: impl Into<String>) -> Self {
+        self.sha256 = Some(sha256.into());
+        self
+    }
+
+    /// Sets the template of the URL used to download the server binary.
+    /// See `PocketIcConfig::download_url_template` for the supported placeholders.
+    pub fn with_download_url_template(mut self, download_url_template: impl Into<String>) -> Self {
+        self.download_url_template = download_url_template.into();
+        self
+    }
+
+    /// Sets whether to verify the version of a cached server binary by running `pocket-ic --version`.
+    pub fn with_version_check(mut self, check_version: bool) -> Self {
+        self.check_version = check_version;
+        self
+    }
+
+    /// Sets whether to disable the download progress printed to stderr.
+    pub fn with_quiet(mut self, quiet: bool) -> Self {
+        self.quiet = quiet;
+        self
+    }
+
+    /// Returns the URL to download the server binary for the given platform.
+    pub fn download_url(&self, platform: &Platform) -> String {
+        self.download_url_template
+            .replace("{version}", &self.version)
+            .replace("{platform}", platform.os)
+            .replace("{arch}", platform.arch)
+    }
+}
+
+/// Returns the given version, or the default one if the given version is missing or blank.
+fn version_or_default(version: Option<String>) -> String {
+    version
+        .map(|version| version.trim().to_string())
+        .filter(|version| !version.is_empty())
+        .unwrap_or_else(|| POCKET_IC_SERVER_VERSION.to_string())
+}
+
+/// Returns true if the value of a flag environment variable enables it.
+fn is_enabled(value: &str) -> bool {
+    matches!(value.trim().to_lowercase().as_str(), "1" | "true" | "yes")
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    #[test]
+    fn should_use_the_default_version_if_not_set() {
+        assert_eq!(version_or_default(None), POCKET_IC_SERVER_VERSION);
+        assert_eq!(
+            version_or_default(Some("  ".to_string())),
+            POCKET_IC_SERVER_VERSION
+        );
+    }
+
+    #[test]
+    fn should_use_the_version_from_the_env() {
+        assert_eq!(version_or_default(Some("13.0.0".to_string())), "13.0.0");
+    }
+
+    #[test]
+    fn should_resolve_the_default_download_url() {
+        let config = PocketIcConfig::default()
+            .with_version("13.0.0")
+            .with_download_url_template(POCKET_IC_DOWNLOAD_URL_TEMPLATE);
+        assert_eq!(
+            config.download_url(&Platform {
+                os: "linux",
+                arch: "x86_64"
+            }),
+            "https://github.com/dfinity/pocketic/releases/download/13.0.0/pocket-ic-x86_64-linux.gz"
+        );
+        assert_eq!(
+            config.download_url(&Platform {
+                os: "darwin",
+                arch: "arm64"
+            }),
+            "https://github.com/dfinity/pocketic/releases/download/13.0.0/pocket-ic-arm64-darwin.gz"
+        );
+    }
+
+    #[test]
+    fn should_resolve_a_custom_download_url() {
+        let config = PocketIcConfig::default()
+            .with_version("13.0.0")
+            .with_download_url_template("https://mirror.local/pocket-ic/{version}/{platform}.gz");
+        assert_eq!(
+            config.download_url(&Platform {
+                os: "darwin",
+                arch: "x86_64"
+            }),
+            "https://mirror.local/pocket-ic/13.0.0/darwin.gz"
+        );
+    }
+
+    #[test]
+    fn should_parse_flag_values() {
+        assert!(is_enabled("1"));
+        assert!(is_enabled("true"));
+        assert!(is_enabled(" TRUE "));
+        assert!(!is_enabled("0"));
+        assert!(!is_enabled("false"));
+        assert!(!is_enabled(""));
+    }
+
+    #[test]
+    fn should_override_the_version() {
+        let config = PocketIcConfig::default().with_version("11.0.0");
+        assert_eq!(config.version, "11.0.0");
+    }
+}
@@ -0,0 +1,515 @@
+use std::fs::{self, OpenOptions};
+use std::io::{Cursor, Read};
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use flate2::read::GzDecoder;
+use log::*;
+
+use crate::checksum::verify_sha256;
+use crate::config::PocketIcConfig;
+use crate::platform::Platform;
+use crate::progress::{read_with_progress, report_progress};
+use crate::validation::validate_binary;
+
+/// The max number of attempts to download a valid pocket-ic server binary.
+const MAX_DOWNLOAD_ATTEMPTS: usize = 3;
+
+/// The max number of attempts of an HTTP download that fails with a transient error.
+const MAX_HTTP_ATTEMPTS: u32 = 3;
+
+/// The delay before the first retry of a failed HTTP download. It doubles at each retry.
+const HTTP_RETRY_BASE_DELAY: Duration = Duration::from_secs(1);
+
+/// Error returned when the pocket-ic server binary cannot be downloaded.
+#[derive(Debug, thiserror::Error)]
+pub enum DownloadError {
+    #[error(
+        "pocket-ic server binary cannot be downloaded from [{url}] after {attempts} attempts: {source}"
+    )]
+    Http {
+        url: String,
+        attempts: u32,
+        source: reqwest::Error,
+    },
+
+    #[error("pocket-ic server binary cannot be copied from [{url}]: {source}")]
+    Io { url: String, source: std::io::Error },
+
+    #[error("pocket-ic.gz downloaded from [{url}] cannot be decompressed: {source}")]
+    Decompress { url: String, source: std::io::Error },
+
+    #[error(
+        "pocket-ic server binary downloaded from [{url}] failed the SHA-256 checksum verification {attempts} times. Expected checksum: [{expected}]"
+    )]
+    ChecksumMismatch {
+        url: String,
+        attempts: usize,
+        expected: String,
+    },
+}
+
+/// Downloads the pocket-ic server binary to the given path.
+///
+/// The download is performed holding an exclusive lock on a `.lock` file next to the binary,
+/// so that concurrent processes do not download the same binary at the same time.
+/// A process that acquires the lock after another one completed the download reuses its result.
+pub(crate) async fn download_binary(
+    binary_path: PathBuf,
+    config: &PocketIcConfig,
+    platform: &Platform,
+) -> Result<PathBuf, DownloadError> {
+    let download_url = config.download_url(platform);
+    let validation_config = config.clone();
+
+    install_binary_with_lock(
+        &binary_path,
+        move |binary_path| is_valid_cached_binary(binary_path, &validation_config),
+        || fetch_verified_binary(&download_url, config),
+    )
+    .await
+}
+
+/// Installs the binary returned by `fetch` at the given path, holding an exclusive file lock.
+/// If a valid binary is found once the lock is acquired, it is reused and `fetch` is not called.
+async fn install_binary_with_lock<V, F, Fut>(
+    binary_path: &Path,
+    is_valid: V,
+    fetch: F,
+) -> Result<PathBuf, DownloadError>
+where
+    V: FnOnce(&Path) -> bool + Send + 'static,
+    F: FnOnce() -> Fut,
+    Fut: Future<Output = Result<Vec<u8>, DownloadError>>,
+{
+    let pocket_ic_dir = binary_path
+        .parent()
+        .expect("pocket-ic server binary path should have a parent directory");
+    tokio::fs::create_dir_all(pocket_ic_dir)
+        .await
+        .expect("pocket-ic server path directories should be created");
+
+    let lock_path = binary_path.with_extension("lock");
+    debug!("acquiring the pocket-ic lock file [{lock_path:?}]");
+
+    // Waiting for the lock and validating the binary block the thread,
+    // so they must not run on the async runtime
+    let locked_binary_path = binary_path.to_path_buf();
+    let (lock_file, is_valid) = tokio::task::spawn_blocking(move || {
+        let lock_file = OpenOptions::new()
+            .create(true)
+            .truncate(false)
+            .write(true)
+            .open(&lock_path)
+            .unwrap_or_else(|e| {
+                panic!("pocket-ic lock file [{lock_path:?}] should be opened: {e}")
+            });
+        lock_file.lock().unwrap_or_else(|e| {
+            panic!("pocket-ic lock file [{lock_path:?}] should be locked: {e}")
+        });
+        let is_valid = is_valid(&locked_binary_path);
+        (lock_file, is_valid)
+    })
+    .await
+    .expect("pocket-ic lock file should be acquired");
+
+    if is_valid {
+        // Another process downloaded the binary while waiting for the lock
+        info!("reusing pocket-ic server binary [{binary_path:?}]");
+    } else {
+        let binary = fetch().await?;
+        write_binary(binary_path, binary).await;
+    }
+
+    // The lock is released when the file is closed
+    drop(lock_file);
+
+    Ok(binary_path.to_path_buf())
+}
+
+/// Returns true if the cached binary exists and is valid, without blocking the async runtime.
+/// See `is_valid_cached_binary` for details.
+pub(crate) async fn is_valid_cached_binary_async(
+    binary_path: PathBuf,
+    config: PocketIcConfig,
+) -> bool {
+    tokio::task::spawn_blocking(move || is_valid_cached_binary(&binary_path, &config))
+        .await
+        .expect("pocket-ic server binary should be validated")
+}
+
+/// Returns true if the cached binary exists and is valid.
+/// An invalid binary is deleted. See `validate_binary` for the performed validations.
+pub(crate) fn is_valid_cached_binary(binary_path: &Path, config: &PocketIcConfig) -> bool {
+    if !binary_path.exists() {
+        return false;
+    }
+
+    let expected_version = config.check_version.then_some(config.version.as_str());
+    match validate_binary(binary_path, config.sha256.as_deref(), expected_version) {
+        Ok(()) => true,
+        Err(e) => {
+            warn!("cached pocket-ic server binary is not valid: {e}. It will be downloaded again");
+            let _ = fs::remove_file(binary_path);
+            false
+        }
+    }
+}
+
+/// Downloads the pocket-ic server binary, retrying if it does not match the expected checksum.
+async fn fetch_verified_binary(
+    download_url: &str,
+    config: &PocketIcConfig,
+) -> Result<Vec<u8>, DownloadError> {
+    for attempt in 1..=MAX_DOWNLOAD_ATTEMPTS {
+        let binary = fetch_binary(download_url, config.quiet).await?;
+
+        if let Some(sha256) = &config.sha256
+            && let Err(e) = verify_sha256(&binary, sha256)
+        {
+            warn!(
+                "pocket-ic server binary downloaded from [{download_url}] is not valid (attempt {attempt}/{MAX_DOWNLOAD_ATTEMPTS}): {e}"
+            );
+            continue;
+        }
+
+        return Ok(binary);
+    }
+
+    Err(DownloadError::ChecksumMismatch {
+        url: download_url.to_string(),
+        attempts: MAX_DOWNLOAD_ATTEMPTS,
+        expected: config.sha256.clone().unwrap_or_default(),
+    })
+}
+
+/// Downloads and decompresses the pocket-ic server binary.
+/// If the URL is a `file://` URL, the binary is copied from the local file system.
+/// The download progress is printed to stderr unless `quiet` is set or a logger is installed.
+async fn fetch_binary(download_url: &str, quiet: bool) -> Result<Vec<u8>, DownloadError> {
+    let gz_binary = match download_url.strip_prefix("file://") {
+        Some(path) => {
+            info!("copying pocket-ic server binary from: {download_url}");
+            tokio::fs::read(path)
+                .await
+                .map_err(|source| DownloadError::Io {
+                    url: download_url.to_string(),
+                    source,
+                })?
+        }
+        None => http_get_with_retries(download_url, quiet).await?,
+    };
+
+    // unzip file
+    let gz_data_cursor = Cursor::new(gz_binary);
+    let mut tar = GzDecoder::new(gz_data_cursor);
+    let mut binary = vec![];
+    tar.read_to_end(&mut binary)
+        .map_err(|source| DownloadError::Decompress {
+            url: download_url.to_string(),
+            source,
+        })?;
+    Ok(binary)
+}
+
+/// Downloads the content of the URL, retrying with exponential backoff if a transient error occurs.
+async fn http_get_with_retries(download_url: &str, quiet: bool) -> Result<Vec<u8>, DownloadError> {
+    let mut attempt = 1;
+    loop {
+        report_progress(
+            &format!(
+                "downloading pocket-ic server binary from: {download_url} (attempt {attempt}/{MAX_HTTP_ATTEMPTS})"
+            ),
+            quiet,
+        );
+
+        match http_get(download_url, quiet).await {
+            Ok(bytes) => return Ok(bytes),
+            Err(e) if attempt < MAX_HTTP_ATTEMPTS && is_retryable_error(&e) => {
+                let delay = retry_delay(attempt);
+                warn!(
+                    "pocket-ic server binary download from [{download_url}] failed: {e}. Retrying in {delay:?}"
+                );
+                tokio::time::sleep(delay).await;
+                attempt += 1;
+            }
+            Err(source) => {
+                return Err(DownloadError::Http {
+                    url: download_url.to_string(),
+                    attempts: attempt,
+                    source,
+                });
+            }
+        }
+    }
+}
+
+async fn http_get(download_url: &str, quiet: bool) -> Result<Vec<u8>, reqwest::Error> {
+    let mut response = reqwest::Client::builder()
+        .timeout(Duration::from_secs(120))
+        .build()?
+        .get(download_url)
+        .send()
+        .await?
+        .error_for_status()?;
+
+    read_with_progress(
+        response.content_length(),
+        async || response.chunk().await,
+        |message| report_progress(&message, quiet),
+    )
+    .await
+}
+
+/// Returns true if the download failed for a transient reason and can be retried.
+/// Timeouts, connection errors and server errors are retryable,
+/// client errors, like a 404 for an unknown version, are not.
+fn is_retryable_error(error: &reqwest::Error) -> bool {
+    if let Some(status) = error.status() {
+        return is_retryable_status(status);
+    }
+    error.is_timeout() || error.is_connect() || error.is_request() || error.is_body()
+}
+
+fn is_retryable_status(status: reqwest::StatusCode) -> bool {
+    status.is_server_error() || status == reqwest::StatusCode::TOO_MANY_REQUESTS
+}
+
+/// Returns the delay before the retry following the given attempt,
+/// doubling at each attempt and with a random jitter of up to half of the delay.
+fn retry_delay(attempt: u32) -> Duration {
+    let delay = HTTP_RETRY_BASE_DELAY * 2u32.pow(attempt - 1);
+    let jitter_range = (delay.as_millis() as u64 / 2).max(1);
+    let nanos = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|now| now.subsec_nanos() as u64)
+        .unwrap_or_default();
+    delay + Duration::from_millis(nanos % jitter_range)
+}
+
+/// Writes the pocket-ic server binary to file and makes it executable.
+/// The binary is written to a temporary file that is then renamed,
+/// so that a partially written binary is never visible at the target path.
+async fn write_binary(binary_path: &Path, binary: Vec<u8>) {
+    info!("writing pocket-ic server binary to [{binary_path:?}]");
+
+    let temp_path = binary_path.with_extension(format!("{}.tmp", std::process::id()));
+
+    tokio::fs::write(&temp_path, binary)
+        .await
+        .expect("pocket-ic server binary should be written to file");
+
+    #[cfg(target_family = "unix")]
+    {
+        use std::os::unix::prelude::PermissionsExt;
+        let mut perms = tokio::fs::metadata(&temp_path).await.unwrap().permissions();
+        perms.set_mode(0o770);
+        tokio::fs::set_permissions(&temp_path, perms).await.unwrap();
+    }
+
+    tokio::fs::rename(&temp_path, binary_path)
+        .await
+        .expect("pocket-ic server binary should be moved to the target path");
+}
+
+#[cfg(test)]
+mod tests {
+
+    use std::env;
+    use std::sync::Arc;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    use super::*;
+    use crate::checksum::sha256_hex;
+
+    #[test]
+    fn should_accept_a_cached_binary_without_checksum() {
+        let dir = test_dir("cached_binary_without_checksum");
+        let binary_path = dir.join("pocket-ic");
+        fs::write(&binary_path, b"binary").unwrap();
+
+        assert!(is_valid_cached_binary(&binary_path, &config(None)));
+        assert!(binary_path.exists());
+    }
+
+    #[test]
+    fn should_accept_a_cached_binary_with_matching_checksum() {
+        let dir = test_dir("cached_binary_with_matching_checksum");
+        let binary_path = dir.join("pocket-ic");
+        fs::write(&binary_path, b"binary").unwrap();
+
+        assert!(is_valid_cached_binary(
+            &binary_path,
+            &config(Some(&sha256_hex(b"binary")))
+        ));
+        assert!(binary_path.exists());
+    }
+
+    #[test]
+    fn should_delete_a_corrupted_cached_binary() {
+        let dir = test_dir("corrupted_cached_binary");
+        let binary_path = dir.join("pocket-ic");
+        fs::write(&binary_path, b"bin").unwrap();
+
+        assert!(!is_valid_cached_binary(
+            &binary_path,
+            &config(Some(&sha256_hex(b"binary")))
+        ));
+        assert!(!binary_path.exists());
+    }
+
+    #[test]
+    fn should_not_accept_a_missing_cached_binary() {
+        let dir = test_dir("missing_cached_binary");
+        assert!(!is_valid_cached_binary(
+            &dir.join("pocket-ic"),
+            &config(None)
+        ));
+    }
+
+    #[test]
+    fn should_delete_an_empty_cached_binary() {
+        let dir = test_dir("empty_cached_binary");
+        let binary_path = dir.join("pocket-ic");
+        fs::write(&binary_path, b"").unwrap();
+
+        assert!(!is_valid_cached_binary(&binary_path, &config(None)));
+        assert!(!binary_path.exists());
+    }
+
+    #[tokio::test]
+    async fn should_copy_the_binary_from_a_file_url() {
+        use std::io::Write;
+
+        use flate2::Compression;
+        use flate2::write::GzEncoder;
+
+        let dir = test_dir("file_url");
+        let gz_path = dir.join("pocket-ic-13.0.0-linux.gz");
+        let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
+        encoder.write_all(b"binary").unwrap();
+        fs::write(&gz_path, encoder.finish().unwrap()).unwrap();
+
+        let config = PocketIcConfig::default()
+            .with_version("13.0.0")
+            .with_download_url_template(format!(
+                "file://{}/pocket-ic-{{version}}-{{platform}}.gz",
+                dir.display()
+            ));
+
+        let platform = Platform {
+            os: "linux",
+            arch: "x86_64",
+        };
+        let binary = fetch_binary(&config.download_url(&platform), true)
+            .await
+            .unwrap();
+
+        assert_eq!(binary, b"binary");
+    }
+
+    #[test]
+    fn should_download_only_once_when_installing_concurrently() {
+        let dir = test_dir("concurrent_install");
+        let binary_path = dir.join("pocket-ic");
+        let downloads = Arc::new(AtomicUsize::new(0));
+
+        let handles = (0..8)
+            .map(|_| {
+                let binary_path = binary_path.clone();
+                let downloads = downloads.clone();
+                std::thread::spawn(move || {
+                    tokio::runtime::Builder::new_current_thread()
+                        .build()
+                        .unwrap()
+                        .block_on(install_binary_with_lock(
+                            &binary_path,
+                            |path| path.exists(),
+                            || async move {
+                                downloads.fetch_add(1, Ordering::SeqCst);
+                                std::thread::sleep(Duration::from_millis(50));
+                                Ok(b"binary".to_vec())
+                            },
+                        ))
+                })
+            })
+            .collect::<Vec<_>>();
+
+        for handle in handles {
+            assert_eq!(handle.join().unwrap().unwrap(), binary_path);
+        }
+
+        assert_eq!(downloads.load(Ordering::SeqCst), 1);
+        assert_eq!(fs::read(&binary_path).unwrap(), b"binary");
+    }
+
+    #[test]
+    fn should_retry_server_errors() {
+        assert!(is_retryable_status(
+            reqwest::StatusCode::INTERNAL_SERVER_ERROR
+        ));
+        assert!(is_retryable_status(reqwest::StatusCode::BAD_GATEWAY));
+        assert!(is_retryable_status(
+            reqwest::StatusCode::SERVICE_UNAVAILABLE
+        ));
+        assert!(is_retryable_status(reqwest::StatusCode::GATEWAY_TIMEOUT));
+        assert!(is_retryable_status(reqwest::StatusCode::TOO_MANY_REQUESTS));
+    }
+
+    #[test]
+    fn should_not_retry_client_errors() {
+        assert!(!is_retryable_status(reqwest::StatusCode::NOT_FOUND));
+        assert!(!is_retryable_status(reqwest::StatusCode::FORBIDDEN));
+        assert!(!is_retryable_status(reqwest::StatusCode::BAD_REQUEST));
+    }
+
+    #[tokio::test]
+    async fn should_retry_connection_errors() {
+        // Nothing listens on port 1
+        let error = http_get("http://127.0.0.1:1/pocket-ic.gz", true)
+            .await
+            .unwrap_err();
+        assert!(is_retryable_error(&error));
+    }
+
+    #[tokio::test]
+    async fn should_not_retry_invalid_urls() {
+        let error = http_get("not a url", true).await.unwrap_err();
+        assert!(error.is_builder());
+        assert!(!is_retryable_error(&error));
+    }
+
+    #[tokio::test]
+    async fn should_return_an_error_if_the_file_is_missing() {
+        let dir = test_dir("missing_file_url");
+        let url = format!("file://{}/pocket-ic.gz", dir.display());
+
+        let error = fetch_binary(&url, true).await.unwrap_err();
+
+        assert!(matches!(error, DownloadError::Io { .. }));
+    }
+
+    #[test]
+    fn should_double_the_retry_delay() {
+        for attempt in 1..=3 {
+            let base = HTTP_RETRY_BASE_DELAY * 2u32.pow(attempt - 1);
+            let delay = retry_delay(attempt);
+            assert!(delay >= base);
+            assert!(delay <= base + base / 2);
+        }
+    }
+
+    fn config(sha256: Option<&str>) -> PocketIcConfig {
+        PocketIcConfig {
+            sha256: sha256.map(str::to_string),
+            ..PocketIcConfig::default()
+        }
+    }
+
+    fn test_dir(name: &str) -> PathBuf {
+        let dir = env::temp_dir().join("ic_mple_pocket_ic_tests").join(name);
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+}
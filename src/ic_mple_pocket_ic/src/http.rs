@@ -0,0 +1,61 @@
+use pocket_ic::common::rest::{
+    CanisterHttpHeader, CanisterHttpReply, CanisterHttpRequest, CanisterHttpResponse,
+    MockCanisterHttpResponse,
+};
+use pocket_ic::nonblocking::PocketIc;
+
+/// Returns the canister HTTP outcalls waiting for a response.
+pub async fn pending_http_requests(env: &PocketIc) -> Vec<CanisterHttpRequest> {
+    env.get_canister_http().await
+}
+
+/// Responds to a pending canister HTTP outcall with the given status, headers and body.
+pub async fn respond_http(
+    env: &PocketIc,
+    request: &CanisterHttpRequest,
+    status: u16,
+    headers: Vec<(String, String)>,
+    body: Vec<u8>,
+) {
+    let reply = CanisterHttpReply {
+        status,
+        headers: headers
+            .into_iter()
+            .map(|(name, value)| CanisterHttpHeader { name, value })
+            .collect(),
+        body,
+    };
+    respond_http_with(env, request, reply).await
+}
+
+/// Responds to all the pending canister HTTP outcalls accepted by the matcher with the given reply.
+/// Returns the number of outcalls that were responded.
+///
+/// It can be called after each `tick()` to serve the outcalls as soon as they are made.
+pub async fn auto_respond_http<M>(env: &PocketIc, matcher: M, reply: &CanisterHttpReply) -> usize
+where
+    M: Fn(&CanisterHttpRequest) -> bool,
+{
+    let mut responded = 0;
+    for request in pending_http_requests(env).await {
+        if matcher(&request) {
+            respond_http_with(env, &request, reply.clone()).await;
+            responded += 1;
+        }
+    }
+    responded
+}
+
+async fn respond_http_with(
+    env: &PocketIc,
+    request: &CanisterHttpRequest,
+    reply: CanisterHttpReply,
+) {
+    env.mock_canister_http_response(MockCanisterHttpResponse {
+        subnet_id: request.subnet_id,
+        request_id: request.request_id,
+        response: CanisterHttpResponse::CanisterHttpReply(reply),
+        additional_responses: vec![],
+    })
+    .await
+}
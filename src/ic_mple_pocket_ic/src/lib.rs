@@ -1,172 +1,270 @@
-use std::fs::File;
-use std::io::{Cursor, Read};
-use std::path::{Path, PathBuf};
-use std::time::Duration;
-use std::{env, fs};
+use std::collections::BTreeMap;
+use std::env;
+use std::path::PathBuf;
+use std::sync::OnceLock;
 
 use ::pocket_ic::PocketIcBuilder;
-use flate2::read::GzDecoder;
-use log::*;
-use tokio::sync::OnceCell;
+use tokio::sync::Mutex;
+
+use crate::cache::cleanup_old_versions_from_env;
+use crate::download::{download_binary, is_valid_cached_binary_async};
+use crate::server::shared_server_url;
+
+mod cache;
+mod canister;
+mod checksum;
+mod config;
+mod download;
+mod http;
+mod logs;
+mod platform;
+mod progress;
+mod server;
+mod snapshot;
+#[cfg(feature = "test-harness")]
+pub mod test_context;
+mod time;
+mod topology;
+mod validation;
+mod wasm;
+
+pub use cache::{
+    POCKET_IC_CACHE_DIR_ENV, POCKET_IC_CLEANUP_KEEP_ENV, cleanup_old_versions, pocket_ic_cache_dir,
+};
+pub use canister::{CanisterSettingsArgs, create_canister_with, update_settings};
+pub use checksum::{ChecksumMismatch, sha256_hex, verify_sha256};
+pub use config::{
+    POCKET_IC_DOWNLOAD_URL_TEMPLATE, POCKET_IC_DOWNLOAD_URL_TEMPLATE_ENV, POCKET_IC_QUIET_ENV,
+    POCKET_IC_SERVER_SHA256_ENV, POCKET_IC_SERVER_VERSION, POCKET_IC_SERVER_VERSION_ENV,
+    PocketIcConfig,
+};
+pub use download::DownloadError;
+pub use http::{auto_respond_http, pending_http_requests, respond_http};
+pub use logs::{IcLogRecord, assert_log_contains, canister_logs, query_ic_logs};
+pub use platform::Platform;
+pub use server::{POCKET_IC_SERVER_URL_ENV, PocketIcServer, ensure_shared_server};
+pub use snapshot::{SnapshotId, restore_snapshot, take_snapshot};
+pub use time::{AdvanceTimeout, advance_until};
+pub use topology::Topology;
+pub use wasm::{WasmLoadError, load_wasm};
 
 pub mod pocket_ic {
     pub use pocket_ic::*;
 }
 
-const POCKET_IC_SERVER_VERSION: &str = "12.0.0";
-
 /// Returns the pocket-ic client.
 /// If pocket-ic server binary is not present, it downloads it and sets
 /// the `POCKET_IC_BIN` environment variable accordingly.
 /// See: https://crates.io/crates/pocket-ic
 ///
-/// The temp directory is used to store the binary.
+/// The binary is stored in the cache directory returned by `pocket_ic_cache_dir`,
+/// which can be set with the `POCKET_IC_CACHE_DIR` environment variable.
 ///
 /// To use custom server binary, the `POCKET_IC_BIN` environment variable should be set and
 /// point to the binary. Also, the binary should be executable.
 ///
+/// The version of the server can be set with the `POCKET_IC_SERVER_VERSION` environment variable.
+///
+/// If the `POCKET_IC_SERVER_URL` environment variable is set, the instance is created on that server
+/// instead of spawning a new one. See `ensure_shared_server` for details.
+///
 /// It supports only linux and macos.
+///
+/// The download and the file system operations never block the async runtime,
+/// so it can be called from a `#[tokio::test]` with any runtime flavor.
 pub async fn get_pocket_ic_client() -> PocketIcBuilder {
-    static INITIALIZATION_STATUS: OnceCell<bool> = OnceCell::const_new();
-
-    let status: &bool = INITIALIZATION_STATUS
-        .get_or_init(|| async {
-            if check_custom_pocket_ic_initialized() {
-                // Custom server binary found. Let's use it.
-                return true;
-            };
-
-            if let Some(binary_path) = dbg!(check_default_pocket_ic_binary_exist()) {
-                // Default server binary found. Let's use it.
-                unsafe {
-                    env::set_var("POCKET_IC_BIN", binary_path);
-                }
-                return true;
-            }
+    get_pocket_ic_client_with(PocketIcConfig::default()).await
+}
 
-            // Server binary not found. Let's download it.
-            let mut target_dir = env::var("POCKET_IC_BIN")
-                .map(PathBuf::from)
-                .unwrap_or_else(|_| default_pocket_ic_server_binary_path());
+/// Returns the pocket-ic client using the given configuration.
+/// If the pocket-ic server binary for the configured version is not present, it downloads it and sets
+/// the `POCKET_IC_BIN` environment variable accordingly.
+///
+/// See `get_pocket_ic_client` for details.
+pub async fn get_pocket_ic_client_with(config: PocketIcConfig) -> PocketIcBuilder {
+    get_pocket_ic_client_with_config_and_topology(config, Topology::default()).await
+}
 
-            target_dir.pop();
+/// Returns the pocket-ic client with the subnets of the given topology.
+///
+/// See `get_pocket_ic_client` for details.
+pub async fn get_pocket_ic_client_with_topology(topology: Topology) -> PocketIcBuilder {
+    get_pocket_ic_client_with_config_and_topology(PocketIcConfig::default(), topology).await
+}
 
-            let binary_path = download_binary(target_dir).await;
+/// Returns the pocket-ic client using the given configuration and the subnets of the given topology.
+///
+/// See `get_pocket_ic_client` for details.
+pub async fn get_pocket_ic_client_with_config_and_topology(
+    config: PocketIcConfig,
+    topology: Topology,
+) -> PocketIcBuilder {
+    try_get_pocket_ic_client(config, topology)
+        .await
+        .unwrap_or_else(|e| panic!("{e}"))
+}
+
+/// Returns the pocket-ic client using the given configuration and the subnets of the given topology,
+/// or an error if the pocket-ic server binary cannot be downloaded.
+///
+/// See `get_pocket_ic_client` for details.
+pub async fn try_get_pocket_ic_client(
+    config: PocketIcConfig,
+    topology: Topology,
+) -> Result<PocketIcBuilder, DownloadError> {
+    if let Some(server_url) = shared_server_url() {
+        log::debug!("using pocket-ic server [{server_url}]");
+        return Ok(topology.apply(PocketIcBuilder::new().with_server_url(server_url)));
+    }
+
+    let binary_path = pocket_ic_server_binary(&config).await?;
+    Ok(topology.apply(PocketIcBuilder::new().with_server_binary(binary_path)))
+}
 
-            unsafe {
-                env::set_var("POCKET_IC_BIN", binary_path);
+/// Returns the path of the pocket-ic server binary for the configured version
+/// and sets the `POCKET_IC_BIN` environment variable accordingly.
+async fn pocket_ic_server_binary(config: &PocketIcConfig) -> Result<PathBuf, DownloadError> {
+    // The binaries already initialized by version
+    static INITIALIZED_BINARIES: Mutex<BTreeMap<String, PathBuf>> =
+        Mutex::const_new(BTreeMap::new());
+
+    let binary_path = {
+        let mut initialized = INITIALIZED_BINARIES.lock().await;
+        match initialized.get(&config.version) {
+            Some(binary_path) => binary_path.clone(),
+            None => {
+                let binary_path = init_pocket_ic_binary(config).await?;
+                initialized.insert(config.version.clone(), binary_path.clone());
+                binary_path
             }
+        }
+    };
 
-            true
-        })
-        .await;
+    log::debug!("using pocket-ic server binary [{binary_path:?}]");
 
-    if !*status {
-        panic!("pocket-ic is not initialized");
+    unsafe {
+        env::set_var("POCKET_IC_BIN", &binary_path);
     }
 
-    // We create a PocketIC instance consisting of the NNS and one application subnet.
-    // With no II subnet, there's no subnet with ECDSA keys.
-    PocketIcBuilder::new()
-        .with_nns_subnet()
-        .with_ii_subnet()
-        .with_application_subnet()
+    Ok(binary_path)
 }
 
-fn default_pocket_ic_server_dir() -> PathBuf {
-    env::temp_dir()
-        .join("pocket-ic-server")
-        .join(POCKET_IC_SERVER_VERSION)
-}
+/// Returns the path of the pocket-ic server binary, downloading it if not present.
+async fn init_pocket_ic_binary(config: &PocketIcConfig) -> Result<PathBuf, DownloadError> {
+    let platform = Platform::current(&config.version);
 
-fn default_pocket_ic_server_binary_path() -> PathBuf {
-    default_pocket_ic_server_dir().join("pocket-ic")
-}
+    if let Some(custom_binary_path) = custom_pocket_ic_binary() {
+        if custom_binary_path.exists() {
+            // Custom server binary found. Let's use it.
+            return Ok(custom_binary_path);
+        }
 
-fn check_custom_pocket_ic_initialized() -> bool {
-    if let Ok(path) = env::var("POCKET_IC_BIN") {
-        return Path::new(&path).exists();
+        // Custom server binary not found. Let's download it to the custom path.
+        return download_binary(custom_binary_path, config, &platform).await;
     }
-    false
+
+    let binary_path = default_pocket_ic_server_binary_path(&config.version, &platform);
+    if is_valid_cached_binary_async(binary_path.clone(), config.clone()).await {
+        // Default server binary found. Let's use it.
+        return Ok(binary_path);
+    }
+
+    // Server binary not found. Let's download it.
+    let binary_path = download_binary(binary_path, config, &platform).await?;
+    cleanup_old_versions_from_env(&binary_path);
+    Ok(binary_path)
 }
 
-fn check_default_pocket_ic_binary_exist() -> Option<PathBuf> {
-    let path = default_pocket_ic_server_binary_path();
-    path.exists().then_some(path)
+/// Returns the custom binary path set by the user through the `POCKET_IC_BIN` environment variable.
+/// The variable is read only once, before being overwritten by `get_pocket_ic_client_with`.
+fn custom_pocket_ic_binary() -> Option<PathBuf> {
+    static CUSTOM_BINARY: OnceLock<Option<PathBuf>> = OnceLock::new();
+    CUSTOM_BINARY
+        .get_or_init(|| env::var("POCKET_IC_BIN").ok().map(PathBuf::from))
+        .clone()
 }
 
-async fn download_binary(pocket_ic_dir: PathBuf) -> PathBuf {
-    let platform = match env::consts::OS {
-        "linux" => "linux",
-        "macos" => "darwin",
-        _ => panic!("pocket-ic requires linux or macos"),
-    };
+fn default_pocket_ic_server_dir(version: &str, platform: &Platform) -> PathBuf {
+    pocket_ic_cache_dir().join(version).join(platform.arch)
+}
 
-    let download_url = format!(
-        "https://github.com/dfinity/pocketic/releases/download/{POCKET_IC_SERVER_VERSION}/pocket-ic-x86_64-{platform}.gz"
-    );
+fn default_pocket_ic_server_binary_path(version: &str, platform: &Platform) -> PathBuf {
+    default_pocket_ic_server_dir(version, platform).join("pocket-ic")
+}
 
-    // Download file
-    let gz_binary = {
-        info!("downloading pocket-ic server binary from: {download_url}");
+/// Load wasm bytes from a file.
+#[deprecated(note = "use `load_wasm` instead")]
+pub fn load_wasm_bytes(wasm_path: &str) -> Vec<u8> {
+    load_wasm(wasm_path).unwrap_or_else(|e| panic!("{e}"))
+}
 
-        let response = reqwest::Client::builder()
-            .timeout(Duration::from_secs(120))
-            .build()
-            .unwrap()
-            .get(download_url)
-            .send()
-            .await
-            .unwrap();
+#[cfg(test)]
+mod tests {
 
-        response
-            .bytes()
-            .await
-            .expect("pocket-ic server binary should be downloaded correctly")
-    };
+    use super::*;
 
-    let gz_data_cursor = Cursor::new(gz_binary);
-    let binary_file_path = pocket_ic_dir.join("pocket-ic");
-    fs::create_dir_all(&pocket_ic_dir)
-        .expect("pocket-ic server path directories should be created");
-
-    // unzip file
-    {
-        info!("unzip pocket-ic.gz to [{binary_file_path:?}]");
-
-        let mut tar = GzDecoder::new(gz_data_cursor);
-        let mut temp = vec![];
-        tar.read_to_end(&mut temp)
-            .expect("pocket-ic.gz should be decompressed");
-
-        fs::write(&binary_file_path, temp)
-            .expect("pocket-ic server binary should be written to file");
-
-        #[cfg(target_family = "unix")]
-        {
-            use std::os::unix::prelude::PermissionsExt;
-            let mut perms = std::fs::metadata(&binary_file_path).unwrap().permissions();
-            perms.set_mode(0o770);
-            std::fs::set_permissions(&binary_file_path, perms).unwrap();
-        }
+    #[tokio::test]
+    async fn should_initialize_pocket_ic() {
+        get_pocket_ic_client().await;
     }
 
-    binary_file_path
-}
+    #[tokio::test]
+    async fn should_create_canisters_on_different_app_subnets() {
+        let topology = Topology {
+            app_subnets: 2,
+            ..Default::default()
+        };
+        let client = get_pocket_ic_client_with_topology(topology)
+            .await
+            .build_async()
+            .await;
+
+        let app_subnets = client.topology().await.get_app_subnets();
+        assert_eq!(app_subnets.len(), 2);
+
+        let first = client
+            .create_canister_on_subnet(None, None, app_subnets[0])
+            .await;
+        let second = client
+            .create_canister_on_subnet(None, None, app_subnets[1])
+            .await;
+
+        assert_eq!(client.get_subnet(first).await, Some(app_subnets[0]));
+        assert_eq!(client.get_subnet(second).await, Some(app_subnets[1]));
+        assert_ne!(
+            client.get_subnet(first).await,
+            client.get_subnet(second).await
+        );
+
+        client.drop().await;
+    }
 
-/// Load wasm bytes from a file.
-pub fn load_wasm_bytes(wasm_path: &str) -> Vec<u8> {
-    let path = PathBuf::from(wasm_path);
-    let mut f = File::open(path).expect("File does not exists");
-    let mut buffer = Vec::new();
+    const X86_64: Platform = Platform {
+        os: "linux",
+        arch: "x86_64",
+    };
 
-    f.read_to_end(&mut buffer)
-        .expect("Could not read file content");
+    const ARM64: Platform = Platform {
+        os: "linux",
+        arch: "arm64",
+    };
 
-    buffer
-}
+    #[test]
+    fn should_use_the_version_in_the_binary_path() {
+        let path = default_pocket_ic_server_binary_path("13.0.0", &X86_64);
+        assert!(path.starts_with(pocket_ic_cache_dir()));
+        assert!(path.ends_with("13.0.0/x86_64/pocket-ic"));
+        assert_ne!(
+            default_pocket_ic_server_binary_path("13.0.0", &X86_64),
+            default_pocket_ic_server_binary_path("12.0.0", &X86_64)
+        );
+    }
 
-#[tokio::test]
-async fn should_initialize_pocket_ic() {
-    get_pocket_ic_client().await;
+    #[test]
+    fn should_use_the_architecture_in_the_binary_path() {
+        assert_ne!(
+            default_pocket_ic_server_binary_path("13.0.0", &X86_64),
+            default_pocket_ic_server_binary_path("13.0.0", &ARM64)
+        );
+    }
 }
@@ -0,0 +1,111 @@
+use candid::Principal;
+use pocket_ic::management_canister::CanisterLogRecord;
+use pocket_ic::nonblocking::PocketIc;
+
+/// Returns the log records of the canister, as printed with `ic_cdk::println!` or by a trap.
+/// The logs are fetched on behalf of the first controller of the canister.
+pub async fn canister_logs(env: &PocketIc, canister: Principal) -> Vec<CanisterLogRecord> {
+    let sender = env
+        .get_controllers(canister)
+        .await
+        .first()
+        .copied()
+        .unwrap_or_else(Principal::anonymous);
+
+    env.fetch_canister_logs(canister, sender)
+        .await
+        .unwrap_or_else(|e| panic!("logs of canister [{canister}] should be fetched: {e:?}"))
+}
+
+/// A log record of a canister, with the content decoded as text.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct IcLogRecord {
+    /// The index of the record in the log of the canister.
+    pub idx: u64,
+    /// The time of the record, in nanoseconds since the epoch.
+    pub timestamp_nanos: u64,
+    /// The text of the record, as printed with `ic_cdk::println!`.
+    /// Invalid UTF-8 sequences are replaced.
+    pub message: String,
+}
+
+impl From<CanisterLogRecord> for IcLogRecord {
+    fn from(record: CanisterLogRecord) -> Self {
+        Self {
+            idx: record.idx,
+            timestamp_nanos: record.timestamp_nanos,
+            message: String::from_utf8_lossy(&record.content).into_owned(),
+        }
+    }
+}
+
+/// Returns the log records of the canister, as `canister_logs`, with the content decoded as text.
+pub async fn query_ic_logs(env: &PocketIc, canister: Principal) -> Vec<IcLogRecord> {
+    canister_logs(env, canister)
+        .await
+        .into_iter()
+        .map(IcLogRecord::from)
+        .collect()
+}
+
+/// Asserts that a log record of the canister contains the given substring.
+/// On failure, all the log records of the canister are printed.
+pub async fn assert_log_contains(env: &PocketIc, canister: Principal, substring: &str) {
+    let logs = query_ic_logs(env, canister)
+        .await
+        .into_iter()
+        .map(|record| record.message)
+        .collect::<Vec<_>>();
+
+    assert!(
+        logs.iter().any(|log| log.contains(substring)),
+        "no log record of canister [{canister}] contains [{substring}]. Logs:\n{}",
+        format_logs(&logs)
+    );
+}
+
+fn format_logs(logs: &[String]) -> String {
+    if logs.is_empty() {
+        return "  <no logs>".to_string();
+    }
+    logs.iter()
+        .enumerate()
+        .map(|(idx, log)| format!("  [{idx}] {log}"))
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    #[test]
+    fn should_format_the_logs() {
+        let logs = vec!["first".to_string(), "second".to_string()];
+        assert_eq!(format_logs(&logs), "  [0] first\n  [1] second");
+    }
+
+    #[test]
+    fn should_decode_the_content_of_a_record() {
+        let record = CanisterLogRecord {
+            idx: 3,
+            timestamp_nanos: 42,
+            content: b"increment_counter: 5".to_vec(),
+        };
+
+        assert_eq!(
+            IcLogRecord::from(record),
+            IcLogRecord {
+                idx: 3,
+                timestamp_nanos: 42,
+                message: "increment_counter: 5".to_string(),
+            }
+        );
+    }
+
+    #[test]
+    fn should_format_empty_logs() {
+        assert_eq!(format_logs(&[]), "  <no logs>");
+    }
+}
@@ -0,0 +1,144 @@
+use std::env;
+
+use log::*;
+
+/// The first PocketIC server version published with arm64 binaries.
+const FIRST_ARM64_VERSION: (u32, u32, u32) = (9, 0, 0);
+
+/// The operating system and architecture of a PocketIC server binary,
+/// as they appear in the name of the published artifacts.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Platform {
+    /// The operating system: `linux` or `darwin`.
+    pub os: &'static str,
+    /// The architecture: `x86_64` or `arm64`.
+    pub arch: &'static str,
+}
+
+impl Platform {
+    /// Returns the platform of the server binary to use on this machine for the given server version.
+    ///
+    /// Panics if the operating system is not supported.
+    pub fn current(version: &str) -> Self {
+        Self::resolve(env::consts::OS, env::consts::ARCH, version)
+            .unwrap_or_else(|| panic!("pocket-ic requires linux or macos"))
+    }
+
+    /// Returns the platform of the server binary for the given operating system,
+    /// architecture and server version, or `None` if the operating system is not supported.
+    ///
+    /// On aarch64, the x86_64 binary is used if the version has no arm64 binary.
+    fn resolve(os: &str, arch: &str, version: &str) -> Option<Self> {
+        let os = match os {
+            "linux" => "linux",
+            "macos" => "darwin",
+            _ => return None,
+        };
+
+        let arch = match arch {
+            "aarch64" if has_arm64_binary(version) => "arm64",
+            "aarch64" => {
+                warn!(
+                    "pocket-ic server {version} has no arm64 binary for {os}, the x86_64 binary is used instead"
+                );
+                "x86_64"
+            }
+            _ => "x86_64",
+        };
+
+        Some(Self { os, arch })
+    }
+}
+
+/// Returns true if arm64 binaries are published for the given server version.
+/// Versions that cannot be parsed are assumed to be recent.
+fn has_arm64_binary(version: &str) -> bool {
+    let mut parts = version.trim().split('.').map(|part| part.parse::<u32>());
+    match (parts.next(), parts.next(), parts.next()) {
+        (Some(Ok(major)), Some(Ok(minor)), Some(Ok(patch))) => {
+            (major, minor, patch) >= FIRST_ARM64_VERSION
+        }
+        _ => true,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    #[test]
+    fn should_resolve_linux_x86_64() {
+        assert_eq!(
+            Platform::resolve("linux", "x86_64", "12.0.0"),
+            Some(Platform {
+                os: "linux",
+                arch: "x86_64"
+            })
+        );
+    }
+
+    #[test]
+    fn should_resolve_linux_aarch64() {
+        assert_eq!(
+            Platform::resolve("linux", "aarch64", "12.0.0"),
+            Some(Platform {
+                os: "linux",
+                arch: "arm64"
+            })
+        );
+    }
+
+    #[test]
+    fn should_resolve_macos_x86_64() {
+        assert_eq!(
+            Platform::resolve("macos", "x86_64", "12.0.0"),
+            Some(Platform {
+                os: "darwin",
+                arch: "x86_64"
+            })
+        );
+    }
+
+    #[test]
+    fn should_resolve_macos_aarch64() {
+        assert_eq!(
+            Platform::resolve("macos", "aarch64", "12.0.0"),
+            Some(Platform {
+                os: "darwin",
+                arch: "arm64"
+            })
+        );
+    }
+
+    #[test]
+    fn should_fall_back_to_x86_64_for_old_versions() {
+        assert_eq!(
+            Platform::resolve("macos", "aarch64", "8.0.0"),
+            Some(Platform {
+                os: "darwin",
+                arch: "x86_64"
+            })
+        );
+        assert_eq!(
+            Platform::resolve("linux", "aarch64", "4.0.1"),
+            Some(Platform {
+                os: "linux",
+                arch: "x86_64"
+            })
+        );
+    }
+
+    #[test]
+    fn should_not_resolve_unsupported_os() {
+        assert_eq!(Platform::resolve("windows", "x86_64", "12.0.0"), None);
+        assert_eq!(Platform::resolve("freebsd", "aarch64", "12.0.0"), None);
+    }
+
+    #[test]
+    fn should_assume_unparsable_versions_have_arm64_binaries() {
+        assert!(has_arm64_binary("nightly"));
+        assert!(has_arm64_binary("9.0.0"));
+        assert!(!has_arm64_binary("8.9.9"));
+    }
+}
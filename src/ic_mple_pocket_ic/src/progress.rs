@@ -0,0 +1,176 @@
+use log::*;
+
+/// The percentage of the download between two progress reports.
+const REPORT_PERCENT_STEP: u64 = 10;
+
+/// The bytes downloaded between two progress reports, if the total size is unknown.
+const REPORT_BYTES_STEP: u64 = 5 * 1024 * 1024;
+
+/// Tracks the progress of a download and decides when it should be reported.
+pub(crate) struct DownloadProgress {
+    total: Option<u64>,
+    downloaded: u64,
+    next_report: u64,
+}
+
+impl DownloadProgress {
+    /// Creates a progress tracker for a download of `total` bytes, if known.
+    pub(crate) fn new(total: Option<u64>) -> Self {
+        let total = total.filter(|total| *total > 0);
+        Self {
+            total,
+            downloaded: 0,
+            next_report: if total.is_some() {
+                REPORT_PERCENT_STEP
+            } else {
+                REPORT_BYTES_STEP
+            },
+        }
+    }
+
+    /// Records a downloaded chunk.
+    /// Returns the message to report if the download advanced enough since the last report.
+    pub(crate) fn on_chunk(&mut self, len: usize) -> Option<String> {
+        self.downloaded += len as u64;
+
+        match self.total {
+            Some(total) => {
+                let percent = (self.downloaded * 100 / total).min(100);
+                if percent < self.next_report {
+                    return None;
+                }
+                self.next_report = (percent / REPORT_PERCENT_STEP + 1) * REPORT_PERCENT_STEP;
+                Some(format!(
+                    "downloading pocket-ic server binary: {percent}% ({} of {})",
+                    format_mb(self.downloaded),
+                    format_mb(total)
+                ))
+            }
+            None => {
+                if self.downloaded < self.next_report {
+                    return None;
+                }
+                self.next_report = (self.downloaded / REPORT_BYTES_STEP + 1) * REPORT_BYTES_STEP;
+                Some(format!(
+                    "downloading pocket-ic server binary: {}",
+                    format_mb(self.downloaded)
+                ))
+            }
+        }
+    }
+}
+
+/// Reads all the chunks returned by `next_chunk`, reporting the download progress.
+pub(crate) async fn read_with_progress<C, E, F>(
+    total: Option<u64>,
+    mut next_chunk: F,
+    mut report: impl FnMut(String),
+) -> Result<Vec<u8>, E>
+where
+    C: AsRef<[u8]>,
+    F: AsyncFnMut() -> Result<Option<C>, E>,
+{
+    let mut progress = DownloadProgress::new(total);
+    let mut bytes = Vec::with_capacity(total.unwrap_or_default() as usize);
+
+    while let Some(chunk) = next_chunk().await? {
+        let chunk = chunk.as_ref();
+        bytes.extend_from_slice(chunk);
+        if let Some(message) = progress.on_chunk(chunk.len()) {
+            report(message);
+        }
+    }
+
+    Ok(bytes)
+}
+
+/// Reports a progress message.
+/// The message is logged if a logger is installed, otherwise it is printed to stderr unless `quiet` is set.
+pub(crate) fn report_progress(message: &str, quiet: bool) {
+    if log_enabled!(Level::Info) {
+        info!("{message}");
+    } else if !quiet {
+        eprintln!("{message}");
+    }
+}
+
+fn format_mb(bytes: u64) -> String {
+    format!("{:.1} MB", bytes as f64 / (1024.0 * 1024.0))
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    const MB: usize = 1024 * 1024;
+
+    #[test]
+    fn should_report_every_ten_percent() {
+        let mut progress = DownloadProgress::new(Some(100));
+
+        let reports = (0..100)
+            .filter_map(|_| progress.on_chunk(1))
+            .collect::<Vec<_>>();
+
+        assert_eq!(reports.len(), 10);
+        assert!(reports[0].contains("10%"));
+        assert!(reports[9].contains("100%"));
+    }
+
+    #[test]
+    fn should_report_once_for_a_chunk_spanning_many_steps() {
+        let mut progress = DownloadProgress::new(Some(100));
+
+        assert!(progress.on_chunk(5).is_none());
+        assert!(progress.on_chunk(50).unwrap().contains("55%"));
+        assert!(progress.on_chunk(4).is_none());
+        assert!(progress.on_chunk(1).unwrap().contains("60%"));
+    }
+
+    #[test]
+    fn should_report_bytes_if_the_total_is_unknown() {
+        let mut progress = DownloadProgress::new(None);
+
+        assert!(progress.on_chunk(4 * MB).is_none());
+        assert_eq!(
+            progress.on_chunk(MB).unwrap(),
+            "downloading pocket-ic server binary: 5.0 MB"
+        );
+        assert!(progress.on_chunk(MB).is_none());
+    }
+
+    #[test]
+    fn should_treat_a_zero_total_as_unknown() {
+        let mut progress = DownloadProgress::new(Some(0));
+        assert!(progress.on_chunk(MB).is_none());
+    }
+
+    #[tokio::test]
+    async fn should_read_all_the_chunks_with_progress() {
+        let mut chunks = vec![vec![1u8; 30], vec![2u8; 30], vec![3u8; 40]].into_iter();
+        let mut reports = vec![];
+
+        let bytes = read_with_progress(
+            Some(100),
+            async || Ok::<_, ()>(chunks.next()),
+            |message| reports.push(message),
+        )
+        .await
+        .unwrap();
+
+        assert_eq!(bytes.len(), 100);
+        assert_eq!(&bytes[29..31], &[1, 2]);
+        assert_eq!(reports.len(), 3);
+        assert!(reports[2].contains("100%"));
+    }
+
+    #[tokio::test]
+    async fn should_return_the_chunk_error() {
+        let mut chunks = vec![Ok(Some(vec![1u8; 10])), Err("broken")].into_iter();
+
+        let result = read_with_progress(None, async || chunks.next().unwrap(), |_| {}).await;
+
+        assert_eq!(result, Err("broken"));
+    }
+}
@@ -0,0 +1,256 @@
+use std::net::{SocketAddr, TcpStream};
+use std::path::{Path, PathBuf};
+use std::process::{Child, Command, Stdio};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::Duration;
+use std::{env, fs};
+
+use log::*;
+use reqwest::Url;
+
+use crate::config::PocketIcConfig;
+use crate::pocket_ic_server_binary;
+
+/// The environment variable with the URL of an externally managed PocketIC server.
+/// If set, the PocketIC instances are created on that server instead of spawning a new one.
+pub const POCKET_IC_SERVER_URL_ENV: &str = "POCKET_IC_SERVER_URL";
+
+/// The seconds of inactivity after which the shared server stops.
+const SHARED_SERVER_TTL_SECS: u64 = 300;
+
+/// The max time to wait for the shared server to start.
+const SHARED_SERVER_START_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Returns the URL of a PocketIC server that can be shared by multiple test binaries,
+/// starting it if it is not running.
+///
+/// If the `POCKET_IC_SERVER_URL` environment variable is set, its value is returned.
+/// Otherwise, a server is started, unless one started by a previous call is still running,
+/// and its URL is printed so that it can be exported in the `POCKET_IC_SERVER_URL` variable.
+/// The server stops after some minutes of inactivity.
+///
+/// The PocketIC instances created on a shared server are still independent,
+/// but they share the resources of the same process:
+/// a server crash affects all of them and heavy tests slow down each other.
+pub async fn ensure_shared_server() -> Url {
+    if let Some(url) = shared_server_url() {
+        return url;
+    }
+
+    let binary_path = pocket_ic_server_binary(&PocketIcConfig::default())
+        .await
+        .unwrap_or_else(|e| panic!("{e}"));
+    let port_file = binary_path.with_file_name("pocket-ic-shared.port");
+
+    // The server is not stopped when its process handle is dropped, so that it can be shared
+    let (url, _process) =
+        tokio::task::spawn_blocking(move || start_server(&binary_path, &port_file))
+            .await
+            .expect("pocket-ic shared server should be started");
+
+    eprintln!(
+        "pocket-ic shared server running at {url}. Set {POCKET_IC_SERVER_URL_ENV}={url} to reuse it"
+    );
+    url
+}
+
+/// Returns the URL set in the `POCKET_IC_SERVER_URL` environment variable, if any.
+pub(crate) fn shared_server_url() -> Option<Url> {
+    let url = env::var(POCKET_IC_SERVER_URL_ENV).ok()?;
+    let url = url.trim();
+    if url.is_empty() {
+        return None;
+    }
+    Some(url.parse().unwrap_or_else(|e| {
+        panic!("{POCKET_IC_SERVER_URL_ENV} should be a valid URL, found [{url}]: {e}")
+    }))
+}
+
+/// A PocketIC server process owned by the caller, e.g. a test.
+///
+/// Unlike the server started by `ensure_shared_server`, it is not shared with other processes
+/// and it is stopped when dropped.
+pub struct PocketIcServer {
+    url: Url,
+    port_file: PathBuf,
+    process: Child,
+}
+
+impl PocketIcServer {
+    /// Starts a new PocketIC server, downloading its binary if not present.
+    pub async fn start() -> Self {
+        // The servers started by the same process are distinguished by a counter
+        static STARTED_SERVERS: AtomicU64 = AtomicU64::new(0);
+
+        let binary_path = pocket_ic_server_binary(&PocketIcConfig::default())
+            .await
+            .unwrap_or_else(|e| panic!("{e}"));
+        let port_file = binary_path.with_file_name(format!(
+            "pocket-ic-{}-{}.port",
+            std::process::id(),
+            STARTED_SERVERS.fetch_add(1, Ordering::Relaxed)
+        ));
+
+        let server_port_file = port_file.clone();
+        let (url, process) =
+            tokio::task::spawn_blocking(move || start_server(&binary_path, &server_port_file))
+                .await
+                .expect("pocket-ic server should be started");
+
+        Self {
+            url,
+            port_file,
+            process: process.expect("a new pocket-ic server should be started"),
+        }
+    }
+
+    /// Returns the URL of the server.
+    pub fn url(&self) -> &Url {
+        &self.url
+    }
+}
+
+impl Drop for PocketIcServer {
+    fn drop(&mut self) {
+        stop_server(&mut self.process);
+        let _ = fs::remove_file(&self.port_file);
+    }
+}
+
+/// Starts the server writing its port to the given file and returns its URL,
+/// with the handle of its process.
+/// If the port file points to a running server, that server is reused and no handle is returned.
+fn start_server(binary_path: &Path, port_file: &Path) -> (Url, Option<Child>) {
+    if let Some(port) = read_port(port_file) {
+        if is_listening(port) {
+            info!("reusing pocket-ic shared server on port {port}");
+            return (server_url(port), None);
+        }
+        // The server that wrote the port file is not running anymore
+        let _ = fs::remove_file(port_file);
+    }
+
+    info!("starting pocket-ic server [{binary_path:?}]");
+    let mut process = Command::new(binary_path)
+        .arg("--port-file")
+        .arg(port_file)
+        .arg("--ttl")
+        .arg(SHARED_SERVER_TTL_SECS.to_string())
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .spawn()
+        .unwrap_or_else(|e| panic!("pocket-ic server [{binary_path:?}] should be started: {e}"));
+
+    let started = std::time::Instant::now();
+    while started.elapsed() < SHARED_SERVER_START_TIMEOUT {
+        if let Some(port) = read_port(port_file)
+            && is_listening(port)
+        {
+            return (server_url(port), Some(process));
+        }
+        std::thread::sleep(Duration::from_millis(20));
+    }
+
+    stop_server(&mut process);
+    panic!(
+        "pocket-ic server did not write its port to [{port_file:?}] within {SHARED_SERVER_START_TIMEOUT:?}"
+    );
+}
+
+/// Kills the server process and waits for it to exit.
+fn stop_server(process: &mut Child) {
+    if let Err(e) = process.kill() {
+        warn!("pocket-ic server process cannot be killed: {e}");
+    }
+    let _ = process.wait();
+}
+
+fn read_port(port_file: &Path) -> Option<u16> {
+    fs::read_to_string(port_file).ok()?.trim().parse().ok()
+}
+
+fn is_listening(port: u16) -> bool {
+    let addr = SocketAddr::from(([127, 0, 0, 1], port));
+    TcpStream::connect_timeout(&addr, Duration::from_millis(200)).is_ok()
+}
+
+fn server_url(port: u16) -> Url {
+    format!("http://127.0.0.1:{port}/")
+        .parse()
+        .expect("server URL should be valid")
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+    use crate::Topology;
+
+    #[tokio::test]
+    async fn should_create_independent_instances_on_a_shared_server() {
+        let binary_path = pocket_ic_server_binary(&PocketIcConfig::default())
+            .await
+            .unwrap();
+        let port_file = binary_path.with_file_name("pocket-ic-server-tests.port");
+        let _ = fs::remove_file(&port_file);
+
+        let (url, process) = start_server(&binary_path, &port_file);
+        let mut process = process.expect("a new server should be started");
+
+        let first = Topology::default()
+            .apply(pocket_ic::PocketIcBuilder::new().with_server_url(url.clone()))
+            .build_async()
+            .await;
+        let second = Topology::default()
+            .apply(pocket_ic::PocketIcBuilder::new().with_server_url(url.clone()))
+            .build_async()
+            .await;
+
+        let canister = first.create_canister().await;
+
+        assert!(first.canister_exists(canister).await);
+        assert!(!second.canister_exists(canister).await);
+
+        first.drop().await;
+        second.drop().await;
+
+        // The server is still running and reused
+        let (reused_url, reused_process) = start_server(&binary_path, &port_file);
+        assert_eq!(reused_url, url);
+        assert!(reused_process.is_none());
+
+        stop_server(&mut process);
+        let _ = fs::remove_file(&port_file);
+        assert!(!is_listening(url.port().unwrap()));
+    }
+
+    #[tokio::test]
+    async fn should_stop_an_owned_server_when_dropped() {
+        let server = PocketIcServer::start().await;
+        let port = server.url().port().unwrap();
+        assert!(is_listening(port));
+
+        let pocket_ic = Topology::default()
+            .apply(pocket_ic::PocketIcBuilder::new().with_server_url(server.url().clone()))
+            .build_async()
+            .await;
+        pocket_ic.create_canister().await;
+        pocket_ic.drop().await;
+
+        drop(server);
+        assert!(!is_listening(port));
+    }
+
+    #[test]
+    fn should_not_read_a_missing_port_file() {
+        let path = env::temp_dir()
+            .join("ic_mple_pocket_ic_server_tests")
+            .join("missing.port");
+        assert_eq!(read_port(&path), None);
+    }
+
+    #[test]
+    fn should_build_the_server_url() {
+        assert_eq!(server_url(8080).as_str(), "http://127.0.0.1:8080/");
+    }
+}
@@ -0,0 +1,44 @@
+use candid::Principal;
+use pocket_ic::RejectResponse;
+use pocket_ic::nonblocking::PocketIc;
+
+/// The id of a canister snapshot.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct SnapshotId(pub Vec<u8>);
+
+/// Takes a snapshot of the canister state on behalf of its first controller.
+/// The canister is stopped while the snapshot is taken.
+pub async fn take_snapshot(
+    env: &PocketIc,
+    canister: Principal,
+) -> Result<SnapshotId, RejectResponse> {
+    let sender = first_controller(env, canister).await;
+
+    env.stop_canister(canister, sender).await?;
+    let snapshot = env.take_canister_snapshot(canister, sender, None).await;
+    env.start_canister(canister, sender).await?;
+
+    Ok(SnapshotId(snapshot?.id))
+}
+
+/// Restores the canister state from a snapshot on behalf of its first controller.
+/// The canister is stopped while the snapshot is loaded.
+pub async fn restore_snapshot(
+    env: &PocketIc,
+    canister: Principal,
+    snapshot: &SnapshotId,
+) -> Result<(), RejectResponse> {
+    let sender = first_controller(env, canister).await;
+
+    env.stop_canister(canister, sender).await?;
+    let result = env
+        .load_canister_snapshot(canister, sender, snapshot.0.clone())
+        .await;
+    env.start_canister(canister, sender).await?;
+
+    result
+}
+
+async fn first_controller(env: &PocketIc, canister: Principal) -> Option<Principal> {
+    env.get_controllers(canister).await.first().copied()
+}
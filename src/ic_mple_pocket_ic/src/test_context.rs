@@ -0,0 +1,216 @@
+use std::panic::{self, AssertUnwindSafe};
+use std::pin::Pin;
+use std::sync::Arc;
+use std::task::{Context, Poll};
+
+use candid::utils::ArgumentEncoder;
+use candid::{CandidType, Principal};
+use pocket_ic::RejectResponse;
+use pocket_ic::nonblocking::PocketIc;
+use serde::de::DeserializeOwned;
+
+use crate::{SnapshotId, get_pocket_ic_client, restore_snapshot};
+
+/// The cycles added to the canisters deployed by `TestContext::deploy`.
+const DEPLOY_CYCLES: u128 = 10_u128.pow(12);
+
+/// Error returned by the typed calls of the `TestContext`.
+#[derive(Debug, thiserror::Error)]
+pub enum TestCallError {
+    #[error("call rejected: {0:?}")]
+    Rejected(RejectResponse),
+
+    #[error("candid error: {0}")]
+    Candid(#[from] candid::Error),
+}
+
+/// A well-known principal to use as a test user.
+pub fn alice() -> Principal {
+    Principal::from_text("sgymv-uiaaa-aaaaa-aaaia-cai").unwrap()
+}
+
+/// A well-known principal to use as a test user.
+pub fn bob() -> Principal {
+    Principal::from_slice(&[2; 29])
+}
+
+/// A well-known principal to use as a test user.
+pub fn john() -> Principal {
+    Principal::from_slice(&[3; 29])
+}
+
+/// A PocketIC instance with helpers to deploy and call canisters from tests.
+pub struct TestContext {
+    pub client: Arc<PocketIc>,
+}
+
+impl TestContext {
+    /// Creates a new PocketIC instance with the default topology.
+    pub async fn new() -> Self {
+        let client = get_pocket_ic_client().await.build_async().await;
+        Self::from_client(Arc::new(client))
+    }
+
+    /// Creates a context from an existing PocketIC instance.
+    pub fn from_client(client: Arc<PocketIc>) -> Self {
+        Self { client }
+    }
+
+    /// Creates a canister, adds cycles to it and installs the wasm module with the given init args.
+    pub async fn deploy<T: CandidType>(&self, wasm: Vec<u8>, args: &T) -> Principal {
+        let args = candid::encode_one(args).expect("init args should be candid encoded");
+        let canister = self.client.create_canister().await;
+        self.client.add_cycles(canister, DEPLOY_CYCLES).await;
+        self.client
+            .install_canister(canister, wasm, args, None)
+            .await;
+        canister
+    }
+
+    /// Performs a query call as the anonymous principal.
+    pub async fn query<R>(
+        &self,
+        canister: Principal,
+        method: &str,
+        args: impl ArgumentEncoder,
+    ) -> Result<R, TestCallError>
+    where
+        R: CandidType + DeserializeOwned,
+    {
+        self.query_as(canister, Principal::anonymous(), method, args)
+            .await
+    }
+
+    /// Performs a query call as the given sender.
+    pub async fn query_as<R>(
+        &self,
+        canister: Principal,
+        sender: Principal,
+        method: &str,
+        args: impl ArgumentEncoder,
+    ) -> Result<R, TestCallError>
+    where
+        R: CandidType + DeserializeOwned,
+    {
+        let args = candid::encode_args(args)?;
+        let response = self
+            .client
+            .query_call(canister, sender, method, args)
+            .await
+            .map_err(TestCallError::Rejected)?;
+        Ok(candid::decode_one(&response)?)
+    }
+
+    /// Performs an update call as the anonymous principal.
+    pub async fn update<R>(
+        &self,
+        canister: Principal,
+        method: &str,
+        args: impl ArgumentEncoder,
+    ) -> Result<R, TestCallError>
+    where
+        R: CandidType + DeserializeOwned,
+    {
+        self.update_as(canister, Principal::anonymous(), method, args)
+            .await
+    }
+
+    /// Performs an update call as the given sender.
+    pub async fn update_as<R>(
+        &self,
+        canister: Principal,
+        sender: Principal,
+        method: &str,
+        args: impl ArgumentEncoder,
+    ) -> Result<R, TestCallError>
+    where
+        R: CandidType + DeserializeOwned,
+    {
+        let args = candid::encode_args(args)?;
+        let response = self
+            .client
+            .update_call(canister, sender, method, args)
+            .await
+            .map_err(TestCallError::Rejected)?;
+        Ok(candid::decode_one(&response)?)
+    }
+
+    /// Restores the canister from the snapshot and then runs the function.
+    /// It allows each test case to start from the same state of a canister
+    /// without deploying and initializing it again.
+    pub async fn with_snapshot<F, R>(&self, canister: Principal, snapshot: &SnapshotId, f: F) -> R
+    where
+        F: AsyncFnOnce(&TestContext) -> R,
+    {
+        restore_snapshot(&self.client, canister, snapshot)
+            .await
+            .unwrap_or_else(|e| {
+                panic!("snapshot of canister [{canister}] should be restored: {e:?}")
+            });
+        f(self).await
+    }
+
+    /// Tears down the PocketIC instance, if it is not shared with other owners.
+    pub async fn teardown(self) {
+        if let Ok(client) = Arc::try_unwrap(self.client) {
+            client.drop().await
+        }
+    }
+}
+
+/// Runs the function with a new `TestContext`.
+/// The PocketIC instance is torn down when the function completes, even if it panics.
+pub async fn with_context<F, R>(f: F) -> R
+where
+    F: AsyncFnOnce(&TestContext) -> R,
+{
+    let ctx = TestContext::new().await;
+    let result = CatchUnwind(Box::pin(f(&ctx))).await;
+    ctx.teardown().await;
+
+    match result {
+        Ok(result) => result,
+        Err(panic) => panic::resume_unwind(panic),
+    }
+}
+
+/// A future that catches the panics of the inner future.
+struct CatchUnwind<F>(Pin<Box<F>>);
+
+impl<F: Future> Future for CatchUnwind<F> {
+    type Output = std::thread::Result<F::Output>;
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let inner = self.0.as_mut();
+        match panic::catch_unwind(AssertUnwindSafe(|| inner.poll(cx))) {
+            Ok(Poll::Pending) => Poll::Pending,
+            Ok(Poll::Ready(output)) => Poll::Ready(Ok(output)),
+            Err(panic) => Poll::Ready(Err(panic)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    #[tokio::test]
+    async fn should_catch_the_panic_of_the_inner_future() {
+        let result = CatchUnwind(Box::pin(async { panic!("boom") })).await;
+        assert!(result.is_err());
+    }
+
+    #[tokio::test]
+    async fn should_return_the_output_of_the_inner_future() {
+        let result = CatchUnwind(Box::pin(async { 42 })).await;
+        assert_eq!(result.unwrap(), 42);
+    }
+
+    #[test]
+    fn should_return_distinct_test_principals() {
+        assert_ne!(alice(), bob());
+        assert_ne!(alice(), john());
+        assert_ne!(bob(), john());
+    }
+}
@@ -0,0 +1,84 @@
+use std::time::Duration;
+
+use pocket_ic::nonblocking::PocketIc;
+
+/// Error returned by `advance_until` when the predicate is not satisfied in the given iterations.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, thiserror::Error)]
+#[error("predicate not satisfied after {iterations} iterations and {elapsed:?} of simulated time")]
+pub struct AdvanceTimeout {
+    /// The number of iterations performed.
+    pub iterations: usize,
+    /// The simulated time elapsed before giving up.
+    pub elapsed: Duration,
+}
+
+/// Advances the time of the PocketIC instance by `step` and ticks,
+/// until the predicate returns true or `max_iterations` are performed.
+pub async fn advance_until<F>(
+    env: &PocketIc,
+    step: Duration,
+    max_iterations: usize,
+    mut predicate: F,
+) -> Result<(), AdvanceTimeout>
+where
+    F: AsyncFnMut(&PocketIc) -> bool,
+{
+    for _ in 0..max_iterations {
+        env.advance_time(step).await;
+        env.tick().await;
+
+        if predicate(env).await {
+            return Ok(());
+        }
+    }
+
+    Err(AdvanceTimeout {
+        iterations: max_iterations,
+        elapsed: step * max_iterations as u32,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+    use crate::get_pocket_ic_client;
+
+    #[tokio::test]
+    async fn should_advance_until_the_predicate_is_satisfied() {
+        let env = get_pocket_ic_client().await.build_async().await;
+        let start = env.get_time().await;
+
+        let mut iterations = 0;
+        advance_until(&env, Duration::from_secs(1), 10, async |_| {
+            iterations += 1;
+            iterations == 3
+        })
+        .await
+        .unwrap();
+
+        let elapsed =
+            env.get_time().await.as_nanos_since_unix_epoch() - start.as_nanos_since_unix_epoch();
+        assert_eq!(iterations, 3);
+        assert!(elapsed >= Duration::from_secs(3).as_nanos() as u64);
+
+        env.drop().await;
+    }
+
+    #[tokio::test]
+    async fn should_report_the_elapsed_time_on_timeout() {
+        let env = get_pocket_ic_client().await.build_async().await;
+
+        let result = advance_until(&env, Duration::from_secs(2), 5, async |_| false).await;
+
+        assert_eq!(
+            result,
+            Err(AdvanceTimeout {
+                iterations: 5,
+                elapsed: Duration::from_secs(10)
+            })
+        );
+
+        env.drop().await;
+    }
+}
@@ -0,0 +1,57 @@
+use pocket_ic::PocketIcBuilder;
+
+/// The subnets of a PocketIC instance.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Topology {
+    /// Whether to create the NNS subnet.
+    pub nns: bool,
+    /// Whether to create the II subnet. It is the subnet with the ECDSA keys.
+    pub ii: bool,
+    /// The number of application subnets.
+    pub app_subnets: u8,
+    /// Whether to create the Bitcoin subnet.
+    pub bitcoin: bool,
+    /// Whether to create the fiduciary subnet.
+    pub fiduciary: bool,
+    /// Whether to create the SNS subnet.
+    pub sns: bool,
+}
+
+impl Default for Topology {
+    /// Returns the default topology, consisting of the NNS, the II and one application subnet.
+    fn default() -> Self {
+        Self {
+            nns: true,
+            ii: true,
+            app_subnets: 1,
+            bitcoin: false,
+            fiduciary: false,
+            sns: false,
+        }
+    }
+}
+
+impl Topology {
+    /// Adds the subnets of this topology to the given builder.
+    pub fn apply(&self, mut builder: PocketIcBuilder) -> PocketIcBuilder {
+        if self.nns {
+            builder = builder.with_nns_subnet();
+        }
+        if self.ii {
+            builder = builder.with_ii_subnet();
+        }
+        if self.bitcoin {
+            builder = builder.with_bitcoin_subnet();
+        }
+        if self.fiduciary {
+            builder = builder.with_fiduciary_subnet();
+        }
+        if self.sns {
+            builder = builder.with_sns_subnet();
+        }
+        for _ in 0..self.app_subnets {
+            builder = builder.with_application_subnet();
+        }
+        builder
+    }
+}
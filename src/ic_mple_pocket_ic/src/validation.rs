@@ -0,0 +1,224 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+use log::*;
+
+use crate::checksum::{ChecksumMismatch, verify_sha256};
+
+/// The reason why a pocket-ic server binary cannot be used.
+#[derive(Debug, thiserror::Error)]
+pub(crate) enum InvalidBinary {
+    #[error("binary [{path:?}] cannot be read: {source}")]
+    Unreadable {
+        path: PathBuf,
+        source: std::io::Error,
+    },
+
+    #[error("binary [{path:?}] is empty")]
+    Empty { path: PathBuf },
+
+    #[error("binary [{path:?}] cannot be made executable: {source}")]
+    NotExecutable {
+        path: PathBuf,
+        source: std::io::Error,
+    },
+
+    #[error("binary [{path:?}] has an invalid checksum: {source}")]
+    Checksum {
+        path: PathBuf,
+        source: ChecksumMismatch,
+    },
+
+    #[error("binary [{path:?}] cannot be executed to check its version: {source}")]
+    VersionCheck {
+        path: PathBuf,
+        source: std::io::Error,
+    },
+
+    #[error("binary [{path:?}] has version [{actual}], expected [{expected}]")]
+    VersionMismatch {
+        path: PathBuf,
+        expected: String,
+        actual: String,
+    },
+}
+
+/// Validates an existing pocket-ic server binary.
+/// The binary must not be empty, it is made executable if it is not,
+/// and it must match the expected checksum and version, if any.
+pub(crate) fn validate_binary(
+    path: &Path,
+    sha256: Option<&str>,
+    expected_version: Option<&str>,
+) -> Result<(), InvalidBinary> {
+    let metadata = fs::metadata(path).map_err(|source| InvalidBinary::Unreadable {
+        path: path.to_path_buf(),
+        source,
+    })?;
+
+    if metadata.len() == 0 {
+        return Err(InvalidBinary::Empty {
+            path: path.to_path_buf(),
+        });
+    }
+
+    ensure_executable(path, metadata).map_err(|source| InvalidBinary::NotExecutable {
+        path: path.to_path_buf(),
+        source,
+    })?;
+
+    if let Some(sha256) = sha256 {
+        let binary = fs::read(path).map_err(|source| InvalidBinary::Unreadable {
+            path: path.to_path_buf(),
+            source,
+        })?;
+        verify_sha256(&binary, sha256).map_err(|source| InvalidBinary::Checksum {
+            path: path.to_path_buf(),
+            source,
+        })?;
+    }
+
+    if let Some(expected_version) = expected_version {
+        let output = Command::new(path)
+            .arg("--version")
+            .output()
+            .map_err(|source| InvalidBinary::VersionCheck {
+                path: path.to_path_buf(),
+                source,
+            })?;
+        let actual = String::from_utf8_lossy(&output.stdout).trim().to_string();
+        if !actual
+            .split_whitespace()
+            .any(|word| word == expected_version)
+        {
+            return Err(InvalidBinary::VersionMismatch {
+                path: path.to_path_buf(),
+                expected: expected_version.to_string(),
+                actual,
+            });
+        }
+    }
+
+    Ok(())
+}
+
+/// Sets the executable permissions of the binary if they are missing.
+#[cfg(target_family = "unix")]
+fn ensure_executable(path: &Path, metadata: fs::Metadata) -> std::io::Result<()> {
+    use std::os::unix::prelude::PermissionsExt;
+
+    let mut perms = metadata.permissions();
+    if perms.mode() & 0o111 == 0 {
+        info!("setting the executable permissions of pocket-ic server binary [{path:?}]");
+        perms.set_mode(0o770);
+        fs::set_permissions(path, perms)?;
+    }
+    Ok(())
+}
+
+#[cfg(not(target_family = "unix"))]
+fn ensure_executable(_path: &Path, _metadata: fs::Metadata) -> std::io::Result<()> {
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+
+    use std::env;
+
+    use super::*;
+    use crate::checksum::sha256_hex;
+
+    #[test]
+    fn should_reject_a_missing_binary() {
+        let dir = test_dir("missing");
+        let error = validate_binary(&dir.join("pocket-ic"), None, None).unwrap_err();
+        assert!(matches!(error, InvalidBinary::Unreadable { .. }));
+        assert!(error.to_string().contains("missing/pocket-ic"));
+    }
+
+    #[test]
+    fn should_reject_an_empty_binary() {
+        let dir = test_dir("empty");
+        let path = dir.join("pocket-ic");
+        fs::write(&path, b"").unwrap();
+
+        let error = validate_binary(&path, None, None).unwrap_err();
+
+        assert!(matches!(error, InvalidBinary::Empty { .. }));
+        assert!(error.to_string().contains("empty/pocket-ic"));
+    }
+
+    #[test]
+    fn should_reject_a_binary_with_an_invalid_checksum() {
+        let dir = test_dir("invalid_checksum");
+        let path = dir.join("pocket-ic");
+        fs::write(&path, b"bin").unwrap();
+
+        let error = validate_binary(&path, Some(&sha256_hex(b"binary")), None).unwrap_err();
+
+        assert!(matches!(error, InvalidBinary::Checksum { .. }));
+    }
+
+    #[test]
+    fn should_accept_a_binary_with_a_valid_checksum() {
+        let dir = test_dir("valid_checksum");
+        let path = dir.join("pocket-ic");
+        fs::write(&path, b"binary").unwrap();
+
+        assert!(validate_binary(&path, Some(&sha256_hex(b"binary")), None).is_ok());
+    }
+
+    #[cfg(target_family = "unix")]
+    #[test]
+    fn should_make_the_binary_executable() {
+        use std::os::unix::prelude::PermissionsExt;
+
+        let dir = test_dir("not_executable");
+        let path = dir.join("pocket-ic");
+        fs::write(&path, b"binary").unwrap();
+        fs::set_permissions(&path, fs::Permissions::from_mode(0o600)).unwrap();
+
+        validate_binary(&path, None, None).unwrap();
+
+        let mode = fs::metadata(&path).unwrap().permissions().mode();
+        assert_eq!(mode & 0o777, 0o770);
+    }
+
+    #[cfg(target_family = "unix")]
+    #[test]
+    fn should_check_the_binary_version() {
+        let dir = test_dir("version");
+        let path = dir.join("pocket-ic");
+        fs::write(&path, "#!/bin/sh\necho pocket-ic-server 12.0.0\n").unwrap();
+
+        assert!(validate_binary(&path, None, Some("12.0.0")).is_ok());
+
+        let error = validate_binary(&path, None, Some("13.0.0")).unwrap_err();
+        assert!(matches!(
+            error,
+            InvalidBinary::VersionMismatch { ref actual, .. } if actual == "pocket-ic-server 12.0.0"
+        ));
+    }
+
+    #[test]
+    fn should_reject_a_binary_that_cannot_be_executed() {
+        let dir = test_dir("garbage");
+        let path = dir.join("pocket-ic");
+        fs::write(&path, b"garbage").unwrap();
+
+        let error = validate_binary(&path, None, Some("12.0.0")).unwrap_err();
+
+        assert!(matches!(error, InvalidBinary::VersionCheck { .. }));
+    }
+
+    fn test_dir(name: &str) -> PathBuf {
+        let dir = env::temp_dir()
+            .join("ic_mple_pocket_ic_validation_tests")
+            .join(name);
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+}
@@ -0,0 +1,124 @@
+use std::fs;
+use std::io::Read;
+use std::path::{Path, PathBuf};
+
+use flate2::read::GzDecoder;
+
+/// The header of a wasm module.
+const WASM_MAGIC_BYTES: &[u8] = b"\0asm";
+
+/// The header of a gzip file.
+const GZIP_MAGIC_BYTES: &[u8] = &[0x1f, 0x8b];
+
+/// Error returned when a wasm module cannot be loaded.
+#[derive(Debug, thiserror::Error)]
+pub enum WasmLoadError {
+    #[error("cannot read wasm file [{path:?}]: {source}")]
+    Io {
+        path: PathBuf,
+        source: std::io::Error,
+    },
+
+    #[error("cannot decompress gzipped wasm file [{path:?}]: {source}")]
+    Decompress {
+        path: PathBuf,
+        source: std::io::Error,
+    },
+
+    #[error("file [{path:?}] is not a wasm module")]
+    InvalidModule { path: PathBuf },
+}
+
+/// Loads a wasm module from a file.
+/// Gzipped files, like the `.wasm.gz` artifacts produced by dfx, are decompressed.
+/// Returns an error if the file content is not a wasm module.
+pub fn load_wasm(path: impl AsRef<Path>) -> Result<Vec<u8>, WasmLoadError> {
+    let path = path.as_ref();
+    let bytes = fs::read(path).map_err(|source| WasmLoadError::Io {
+        path: path.to_path_buf(),
+        source,
+    })?;
+
+    let wasm = if bytes.starts_with(GZIP_MAGIC_BYTES) {
+        let mut wasm = vec![];
+        GzDecoder::new(bytes.as_slice())
+            .read_to_end(&mut wasm)
+            .map_err(|source| WasmLoadError::Decompress {
+                path: path.to_path_buf(),
+                source,
+            })?;
+        wasm
+    } else {
+        bytes
+    };
+
+    if !wasm.starts_with(WASM_MAGIC_BYTES) {
+        return Err(WasmLoadError::InvalidModule {
+            path: path.to_path_buf(),
+        });
+    }
+
+    Ok(wasm)
+}
+
+#[cfg(test)]
+mod tests {
+
+    use std::io::Write;
+
+    use flate2::Compression;
+    use flate2::write::GzEncoder;
+
+    use super::*;
+
+    const WASM: &[u8] = b"\0asm\x01\0\0\0";
+
+    #[test]
+    fn should_load_a_plain_wasm_file() {
+        let path = test_file("plain.wasm", WASM);
+        assert_eq!(load_wasm(&path).unwrap(), WASM);
+    }
+
+    #[test]
+    fn should_load_a_gzipped_wasm_file() {
+        let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
+        encoder.write_all(WASM).unwrap();
+        let path = test_file("gzipped.wasm.gz", &encoder.finish().unwrap());
+
+        assert_eq!(load_wasm(&path).unwrap(), WASM);
+    }
+
+    #[test]
+    fn should_return_the_path_of_a_missing_file() {
+        let path = std::env::temp_dir()
+            .join("ic_mple_pocket_ic_wasm_tests")
+            .join("missing.wasm");
+
+        let error = load_wasm(&path).unwrap_err();
+
+        assert!(matches!(error, WasmLoadError::Io { .. }));
+        assert!(error.to_string().contains("missing.wasm"));
+    }
+
+    #[test]
+    fn should_reject_a_garbage_file() {
+        let path = test_file("garbage.wasm", b"garbage");
+        let error = load_wasm(&path).unwrap_err();
+        assert!(matches!(error, WasmLoadError::InvalidModule { .. }));
+    }
+
+    #[test]
+    fn should_reject_a_corrupted_gzipped_file() {
+        let path = test_file("corrupted.wasm.gz", &[0x1f, 0x8b, 0, 1, 2, 3]);
+        let error = load_wasm(&path).unwrap_err();
+        assert!(matches!(error, WasmLoadError::Decompress { .. }));
+    }
+
+    fn test_file(name: &str, content: &[u8]) -> PathBuf {
+        let dir = std::env::temp_dir().join("ic_mple_pocket_ic_wasm_tests");
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join(name);
+        fs::write(&path, content).unwrap();
+        path
+    }
+}
@@ -4,7 +4,7 @@ use std::sync::atomic::{AtomicU64, Ordering};
 
 use candid::CandidType;
 use ic_mple_structures::{BTreeMapIteratorStructure, BTreeMapStructure, CellStructure};
-use ic_mple_utils::ic_api::{IcApi, IcTrait};
+use ic_mple_utils::ic_api::{IcApi, IcTrait, ic};
 use log::{debug, warn};
 use serde::de::DeserializeOwned;
 
@@ -46,7 +46,7 @@ where
     /// The sequence is used to generate the next task id. The caller is responsible for ensuring
     /// that the sequence starts from an initial value that is not used by any existing pending task.
     pub fn new(pending_tasks: P, task_id_sequence: S) -> Self {
-        Self::new_with_ic(pending_tasks, task_id_sequence, IcApi::default())
+        Self::new_with_ic(pending_tasks, task_id_sequence, ic())
     }
 }
 
@@ -560,6 +560,7 @@ mod test {
         use std::time::Duration;
 
         use ic_mple_structures::{StableBTreeMap, StableCell, VectorMemory};
+        use ic_mple_utils::ic_api::mock::IcMock;
         use rand::random;
         use serde::Deserialize;
 
@@ -642,6 +643,44 @@ mod test {
                 })
                 .await;
         }
+
+        #[tokio::test]
+        async fn test_execute_after_timestamp_with_mock_time() {
+            let local = tokio::task::LocalSet::new();
+            local
+                .run_until(async move {
+                    let mut ic = IcMock::default();
+                    ic.set_time(1_000 * 1_000_000_000);
+                    let map = StableBTreeMap::new(VectorMemory::default());
+                    let sequence = StableCell::new(VectorMemory::default(), 0);
+                    let scheduler = Scheduler::new_with_ic(map, sequence, ic.clone());
+                    let id = random();
+
+                    scheduler.append_task(
+                        (
+                            SimpleTask::StepOne { id },
+                            TaskOptions::new().with_execute_after_timestamp_in_secs(1_010),
+                        )
+                            .into(),
+                    );
+
+                    ic.advance_time(Duration::from_secs(9));
+                    scheduler.run(()).unwrap();
+                    tokio::time::sleep(Duration::from_millis(25)).await;
+                    assert_eq!(1, scheduler.pending_tasks.borrow().len());
+
+                    ic.advance_time(Duration::from_secs(1));
+                    scheduler.run(()).unwrap();
+                    tokio::time::sleep(Duration::from_millis(25)).await;
+                    STATE.with(|state| {
+                        let state = state.borrow();
+                        let messages = state.get(&id).cloned().unwrap_or_default();
+                        assert_eq!(messages, vec![format!("{} - StepOne", id)]);
+                    });
+                    assert!(scheduler.pending_tasks.borrow().is_empty());
+                })
+                .await;
+        }
     }
 
     mod test_failure_and_retry {
@@ -1,29 +1,62 @@
+use std::borrow::Cow;
 use std::cell::RefCell;
 use std::future::Future;
 use std::pin::Pin;
 use std::time::Duration;
 
-use candid::CandidType;
-use ic_cdk::{init, post_upgrade, query, update};
+use candid::{CandidType, Decode, Encode};
+use ic_cdk::{init, inspect_message, query, update};
+use ic_mple_auth::inspect::InspectPolicy;
+use ic_mple_auth::{AuthService, AuthServiceStorage};
 use ic_mple_scheduler::SchedulerError;
 use ic_mple_scheduler::scheduler::{Scheduler, TaskScheduler};
 use ic_mple_scheduler::task::{InnerScheduledTask, ScheduledTask, Task, TaskStatus};
 use ic_mple_structures::DefaultMemoryImpl;
-use ic_mple_structures::{MemoryId, MemoryManager, StableBTreeMap, StableCell, VirtualMemory};
+use ic_mple_structures::{
+    Bound, MemoryPages, MemoryRegistry, StableBTreeMap, StableCell, Storable, VirtualMemory,
+    declare_memories,
+};
+use ic_mple_utils::ic_api::{IcTrait, MemoryPressure, MemoryPressureThresholds, ic};
+use ic_mple_utils::register_stable_state;
+use ic_mple_utils::stable_state::StableState;
 use serde::{Deserialize, Serialize};
 
 type Storage = StableBTreeMap<u64, InnerScheduledTask<DummyTask>, VirtualMemory<DefaultMemoryImpl>>;
 type Sequence = StableCell<u64, VirtualMemory<DefaultMemoryImpl>>;
 type PanickingScheduler = Scheduler<DummyTask, Storage, Sequence>;
-
-const SCHEDULER_STORAGE_MEMORY_ID: MemoryId = MemoryId::new(1);
+type Auth = AuthService<RefCell<AuthServiceStorage<Permission>>, Permission>;
+type TaskResultsState =
+    StableState<TaskResults, StableCell<TaskResults, VirtualMemory<DefaultMemoryImpl>>>;
+
+declare_memories! {
+    #[derive(Clone)]
+    struct Memories<DefaultMemoryImpl> {
+        scheduler_tasks,
+        scheduler_sequence,
+        permissions,
+        task_results,
+    }
+}
 
 thread_local! {
-    pub static MEMORY_MANAGER: MemoryManager<DefaultMemoryImpl> = MemoryManager::init(DefaultMemoryImpl::default());
+    pub static MEMORY_REGISTRY: MemoryRegistry<DefaultMemoryImpl> = MemoryRegistry::init(DefaultMemoryImpl::default());
+
+    static MEMORIES: Memories = MEMORY_REGISTRY.with(Memories::allocate).expect("failed to allocate the memories");
+
+    static AUTH: RefCell<Auth> = RefCell::new(Auth::new(RefCell::new(StableBTreeMap::init(
+        MEMORIES.with(|memories| memories.permissions.clone()),
+    ))));
+
+    static INSPECT_POLICY: InspectPolicy<Permission> = InspectPolicy::new()
+        .deny_anonymous()
+        .allow_method("run_scheduler")
+        .allow_method("clear_executed_tasks")
+        .require_permission("schedule_tasks", Permission::ScheduleTasks);
 
     static SCHEDULER: RefCell<PanickingScheduler> = {
-        let map: Storage = Storage::new(MEMORY_MANAGER.with(|mm| mm.get(SCHEDULER_STORAGE_MEMORY_ID)));
-        let sequence: Sequence = Sequence::new(MEMORY_MANAGER.with(|mm| mm.get(SCHEDULER_STORAGE_MEMORY_ID)), 0);
+        let memories = MEMORIES.with(|memories| memories.clone());
+        let map: Storage = Storage::new(memories.scheduler_tasks);
+        let sequence: Sequence = Sequence::new(memories.scheduler_sequence, 0);
 
         let mut scheduler = PanickingScheduler::new(
             map,
@@ -36,10 +69,42 @@ thread_local! {
         RefCell::new(scheduler)
     };
 
-    static COMPLETED_TASKS: RefCell<Vec<u64>> = const { RefCell::new(vec![]) };
-    static FAILED_TASKS: RefCell<Vec<u64>> = const { RefCell::new(vec![]) };
-    static PANICKED_TASKS : RefCell<Vec<u64>> = const { RefCell::new(vec![]) };
+    static TASK_RESULTS: RefCell<TaskResults> = RefCell::new(TaskResults::default());
+
+    static TASK_RESULTS_STATE: RefCell<TaskResultsState> = RefCell::new(TaskResultsState::init(
+        MEMORIES.with(|memories| memories.task_results.clone()),
+    ));
+
+}
 
+register_stable_state! {
+    heap: TASK_RESULTS,
+    stable: TASK_RESULTS_STATE,
+    post_upgrade: set_timers,
+}
+
+/// The ids of the executed tasks, kept in the heap and saved to the stable memory on upgrade.
+#[derive(CandidType, Serialize, Deserialize, Debug, Clone, Default)]
+pub struct TaskResults {
+    completed: Vec<u64>,
+    failed: Vec<u64>,
+    panicked: Vec<u64>,
+}
+
+impl Storable for TaskResults {
+    const BOUND: Bound = Bound::Unbounded;
+
+    fn to_bytes(&self) -> Cow<'_, [u8]> {
+        Encode!(&self).unwrap().into()
+    }
+
+    fn into_bytes(self) -> Vec<u8> {
+        Encode!(&self).unwrap()
+    }
+
+    fn from_bytes(bytes: Cow<[u8]>) -> Self {
+        Decode!(&bytes, Self).unwrap()
+    }
 }
 
 #[derive(CandidType, Serialize, Deserialize, Debug, Clone)]
@@ -71,14 +136,22 @@ impl Task for DummyTask {
     }
 }
 
+#[derive(CandidType, Serialize, Deserialize, Debug, Clone, Hash, PartialEq, Eq)]
+pub enum Permission {
+    ScheduleTasks,
+}
+
 #[init]
 pub fn init() {
+    let installer = ic().msg_caller();
+    AUTH.with_borrow_mut(|auth| auth.add_permissions(installer, vec![Permission::ScheduleTasks]))
+        .expect("failed to grant the permissions to the installer");
     set_timers();
 }
 
-#[post_upgrade]
-pub fn post_upgrade() {
-    set_timers();
+#[inspect_message]
+fn inspect_message() {
+    INSPECT_POLICY.with(|policy| AUTH.with_borrow(|auth| policy.inspect(auth)));
 }
 
 fn set_timers() {
@@ -87,17 +160,17 @@ fn set_timers() {
 
 #[query]
 pub fn panicked_tasks() -> Vec<u64> {
-    PANICKED_TASKS.with_borrow(|tasks| tasks.clone())
+    TASK_RESULTS.with_borrow(|results| results.panicked.clone())
 }
 
 #[query]
 pub fn completed_tasks() -> Vec<u64> {
-    COMPLETED_TASKS.with_borrow(|tasks| tasks.clone())
+    TASK_RESULTS.with_borrow(|results| results.completed.clone())
 }
 
 #[query]
 pub fn failed_tasks() -> Vec<u64> {
-    FAILED_TASKS.with_borrow(|tasks| tasks.clone())
+    TASK_RESULTS.with_borrow(|results| results.failed.clone())
 }
 
 #[query]
@@ -106,6 +179,28 @@ pub fn get_task(task_id: u64) -> Option<InnerScheduledTask<DummyTask>> {
     scheduler.get_task(task_id)
 }
 
+#[query]
+pub fn memory_report() -> Vec<MemoryPages> {
+    MEMORY_REGISTRY.with(|registry| registry.memory_report())
+}
+
+#[query]
+pub fn memory_pressure() -> MemoryPressure {
+    MemoryPressureThresholds::default().check(&ic())
+}
+
+/// Clears the ids of the executed tasks.
+/// Only the controllers of the canister can call it.
+#[update]
+pub fn clear_executed_tasks() {
+    let ic = ic();
+    if !ic.is_controller(&ic.msg_caller()) {
+        ic_cdk::trap("the caller is not a controller");
+    }
+
+    TASK_RESULTS.with_borrow_mut(|results| *results = TaskResults::default());
+}
+
 #[update]
 pub fn schedule_tasks(tasks: Vec<DummyTask>) -> Vec<u64> {
     let scheduler = SCHEDULER.with_borrow(|scheduler| scheduler.clone());
@@ -128,19 +223,19 @@ fn save_state_cb(task: InnerScheduledTask<DummyTask>) {
     match task.status() {
         TaskStatus::Waiting { .. } => {}
         TaskStatus::Completed { .. } => {
-            COMPLETED_TASKS.with_borrow_mut(|tasks| {
-                tasks.push(task.id());
+            TASK_RESULTS.with_borrow_mut(|results| {
+                results.completed.push(task.id());
             });
         }
         TaskStatus::Running { .. } => {}
         TaskStatus::Failed { .. } => {
-            FAILED_TASKS.with_borrow_mut(|tasks| {
-                tasks.push(task.id());
+            TASK_RESULTS.with_borrow_mut(|results| {
+                results.failed.push(task.id());
             });
         }
         TaskStatus::TimeoutOrPanic { .. } => {
-            PANICKED_TASKS.with_borrow_mut(|tasks| {
-                tasks.push(task.id());
+            TASK_RESULTS.with_borrow_mut(|results| {
+                results.panicked.push(task.id());
             });
         }
         TaskStatus::Scheduled { .. } => {}
@@ -0,0 +1,27 @@
+use std::time::Duration;
+
+use ic_mple_pocket_ic::advance_until;
+
+use crate::pocket_ic_tests::{DummyTask, alice, bob, deploy_dummy_scheduler_canister};
+
+#[tokio::test]
+async fn test_only_the_controller_should_clear_the_executed_tasks() {
+    // Arrange
+    let test_ctx = deploy_dummy_scheduler_canister().await;
+    test_ctx.schedule_tasks(vec![DummyTask::GoodTask]).await;
+    advance_until(
+        test_ctx.client(),
+        Duration::from_millis(100),
+        20,
+        async |_| !test_ctx.completed_tasks().await.is_empty(),
+    )
+    .await
+    .unwrap();
+
+    // Act & Assert
+    assert!(test_ctx.clear_executed_tasks(bob()).await.is_err());
+    assert_eq!(test_ctx.completed_tasks().await.len(), 1);
+
+    test_ctx.clear_executed_tasks(alice()).await.unwrap();
+    assert!(test_ctx.completed_tasks().await.is_empty());
+}
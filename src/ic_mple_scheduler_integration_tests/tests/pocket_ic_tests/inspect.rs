@@ -0,0 +1,28 @@
+use candid::Principal;
+
+use crate::pocket_ic_tests::{DummyTask, alice, bob, deploy_dummy_scheduler_canister};
+
+#[tokio::test]
+async fn test_inspect_message_should_reject_the_unauthorized_calls() {
+    // Arrange
+    let test_ctx = deploy_dummy_scheduler_canister().await;
+
+    // Act
+    let stranger_result = test_ctx
+        .schedule_tasks_as(bob(), vec![DummyTask::GoodTask])
+        .await;
+    let anonymous_result = test_ctx
+        .schedule_tasks_as(Principal::anonymous(), vec![DummyTask::GoodTask])
+        .await;
+
+    // Assert
+    assert!(stranger_result.is_err());
+    assert!(anonymous_result.is_err());
+    // the rejected calls never reached the handler, so no task id was generated
+    assert_eq!(test_ctx.get_task(0).await, None);
+    let ids = test_ctx
+        .schedule_tasks_as(alice(), vec![DummyTask::GoodTask])
+        .await
+        .unwrap();
+    assert_eq!(ids, vec![0]);
+}
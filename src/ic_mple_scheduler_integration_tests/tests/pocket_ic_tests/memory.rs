@@ -0,0 +1,42 @@
+use ic_mple_structures::MemoryPages;
+use ic_mple_utils::ic_api::MemoryPressure;
+
+use crate::pocket_ic_tests::{DummyTask, deploy_dummy_scheduler_canister};
+
+fn total_pages(report: &[MemoryPages]) -> u64 {
+    report.iter().map(|memory| memory.pages).sum()
+}
+
+#[tokio::test]
+async fn test_memory_report_should_grow_after_inserting_tasks() {
+    // Arrange
+    let test_ctx = deploy_dummy_scheduler_canister().await;
+    let report_before = test_ctx.memory_report().await;
+
+    // Act
+    test_ctx
+        .schedule_tasks(vec![DummyTask::GoodTask; 5000])
+        .await;
+
+    // Assert
+    let report_after = test_ctx.memory_report().await;
+    assert!(total_pages(&report_after) > total_pages(&report_before));
+    // The memory ids are reported in ascending order and are never repeated
+    assert!(
+        report_after
+            .windows(2)
+            .all(|memories| memories[0].memory_id < memories[1].memory_id)
+    );
+}
+
+#[tokio::test]
+async fn test_memory_pressure_should_be_ok_for_a_new_canister() {
+    // Arrange
+    let test_ctx = deploy_dummy_scheduler_canister().await;
+
+    // Act
+    let pressure = test_ctx.memory_pressure().await;
+
+    // Assert
+    assert_eq!(pressure, MemoryPressure::Ok);
+}
@@ -1,17 +1,22 @@
+mod controller;
+mod inspect;
+mod memory;
 mod scheduler;
+mod upgrade;
 mod wasm_utils;
 
 use std::future::Future;
 use std::pin::Pin;
-use std::time::Duration;
 
 use candid::{CandidType, Encode, Principal};
-use ic_mple_client::{CanisterClient, PocketIcClient};
-use ic_mple_pocket_ic::get_pocket_ic_client;
+use ic_mple_client::{CanisterClient, CanisterClientResult, PocketIcClient};
 use ic_mple_pocket_ic::pocket_ic::nonblocking::PocketIc;
+use ic_mple_pocket_ic::{CanisterSettingsArgs, create_canister_with, get_pocket_ic_client};
 use ic_mple_scheduler::SchedulerError;
 use ic_mple_scheduler::scheduler::TaskScheduler;
 use ic_mple_scheduler::task::{InnerScheduledTask, Task};
+use ic_mple_structures::MemoryPages;
+use ic_mple_utils::ic_api::MemoryPressure;
 use serde::{Deserialize, Serialize};
 use wasm_utils::get_dummy_scheduler_canister_bytecode;
 
@@ -19,6 +24,10 @@ pub fn alice() -> Principal {
     Principal::from_text("sgymv-uiaaa-aaaaa-aaaia-cai").unwrap()
 }
 
+pub fn bob() -> Principal {
+    Principal::from_slice(&[7; 29])
+}
+
 pub struct PocketIcTestContext {
     canister_client: PocketIcClient,
     pub dummy_scheduler_canister: Principal,
@@ -58,18 +67,43 @@ impl PocketIcTestContext {
             .unwrap()
     }
 
-    pub async fn schedule_tasks(&self, tasks: Vec<DummyTask>) -> Vec<u64> {
+    pub async fn memory_report(&self) -> Vec<MemoryPages> {
         self.canister_client
-            .update("schedule_tasks", (tasks,))
+            .query("memory_report", ())
             .await
             .unwrap()
     }
 
-    pub async fn run_scheduler(&self) {
-        self.client()
-            .advance_time(Duration::from_millis(5000))
-            .await;
-        self.client().tick().await;
+    pub async fn memory_pressure(&self) -> MemoryPressure {
+        self.canister_client
+            .query("memory_pressure", ())
+            .await
+            .unwrap()
+    }
+
+    /// Calls `clear_executed_tasks` as the given caller.
+    pub async fn clear_executed_tasks(&self, caller: Principal) -> CanisterClientResult<()> {
+        let mut canister_client = self.canister_client.clone();
+        canister_client.caller = caller;
+        canister_client.update("clear_executed_tasks", ()).await
+    }
+
+    /// Calls `schedule_tasks` as the given caller.
+    pub async fn schedule_tasks_as(
+        &self,
+        caller: Principal,
+        tasks: Vec<DummyTask>,
+    ) -> CanisterClientResult<Vec<u64>> {
+        let mut canister_client = self.canister_client.clone();
+        canister_client.caller = caller;
+        canister_client.update("schedule_tasks", (tasks,)).await
+    }
+
+    pub async fn schedule_tasks(&self, tasks: Vec<DummyTask>) -> Vec<u64> {
+        self.canister_client
+            .update("schedule_tasks", (tasks,))
+            .await
+            .unwrap()
     }
 }
 
@@ -77,10 +111,15 @@ async fn deploy_dummy_scheduler_canister() -> PocketIcTestContext {
     let client = get_pocket_ic_client().await.build_async().await;
     println!("Creating dummy canister");
 
-    let sender = alice();
-    let canister = client
-        .create_canister_with_settings(Some(sender), None)
-        .await;
+    let canister = create_canister_with(
+        &client,
+        CanisterSettingsArgs {
+            controllers: Some(vec![alice()]),
+            ..Default::default()
+        },
+        10_u128.pow(14),
+    )
+    .await;
     println!("Canister created with principal {}", canister);
 
     let canister_client = PocketIcClient::from_client(client, canister, alice());
@@ -90,13 +129,10 @@ async fn deploy_dummy_scheduler_canister() -> PocketIcTestContext {
         dummy_scheduler_canister: canister,
     };
 
-    env.client().add_cycles(canister, 10_u128.pow(14)).await;
-    println!("cycles added");
-
     let dummy_wasm = get_dummy_scheduler_canister_bytecode();
     let args = Encode!(&()).unwrap();
     env.client()
-        .install_canister(canister, dummy_wasm.to_vec(), args, Some(sender))
+        .install_canister(canister, dummy_wasm.to_vec(), args, Some(alice()))
         .await;
 
     println!("Installed dummy canister");
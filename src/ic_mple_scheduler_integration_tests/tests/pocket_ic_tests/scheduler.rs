@@ -1,6 +1,8 @@
 use std::collections::BTreeMap;
+use std::time::Duration;
 
 use candid::Principal;
+use ic_mple_pocket_ic::advance_until;
 use rand::Rng;
 
 use crate::pocket_ic_tests::{DummyTask, deploy_dummy_scheduler_canister};
@@ -54,12 +56,22 @@ async fn test_should_remove_panicking_task() {
     assert_eq!(tasks.len(), tasks_map.len());
 
     // Act
-    for _ in 0..10 {
-        test_ctx.run_scheduler().await;
-        println!("Get task 0: {:?}", test_ctx.get_task(0).await);
-        println!("Get task 1: {:?}", test_ctx.get_task(1).await);
-        println!("Get task 2: {:?}", test_ctx.get_task(2).await);
-    }
+    // The panicking tasks are the last to be reported, as they are detected only after the running task timeout
+    let expected_panicked_tasks = tasks
+        .iter()
+        .filter(|task| **task == DummyTask::Panicking)
+        .count();
+    advance_until(
+        test_ctx.client(),
+        Duration::from_millis(5000),
+        20,
+        async |_| test_ctx.panicked_tasks().await.len() == expected_panicked_tasks,
+    )
+    .await
+    .unwrap();
+    println!("Get task 0: {:?}", test_ctx.get_task(0).await);
+    println!("Get task 1: {:?}", test_ctx.get_task(1).await);
+    println!("Get task 2: {:?}", test_ctx.get_task(2).await);
 
     // Assert
     let panicked_tasks = test_ctx.panicked_tasks().await;
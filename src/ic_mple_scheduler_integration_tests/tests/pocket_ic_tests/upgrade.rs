@@ -0,0 +1,56 @@
+use std::time::Duration;
+
+use candid::Encode;
+use ic_mple_pocket_ic::advance_until;
+
+use crate::pocket_ic_tests::wasm_utils::get_dummy_scheduler_canister_bytecode;
+use crate::pocket_ic_tests::{DummyTask, alice, deploy_dummy_scheduler_canister};
+
+#[tokio::test]
+async fn test_executed_tasks_should_survive_an_upgrade() {
+    // Arrange
+    let test_ctx = deploy_dummy_scheduler_canister().await;
+    test_ctx
+        .schedule_tasks(vec![DummyTask::GoodTask, DummyTask::FailTask])
+        .await;
+    advance_until(
+        test_ctx.client(),
+        Duration::from_millis(100),
+        20,
+        async |_| {
+            !test_ctx.completed_tasks().await.is_empty()
+                && !test_ctx.failed_tasks().await.is_empty()
+        },
+    )
+    .await
+    .unwrap();
+    let completed_tasks = test_ctx.completed_tasks().await;
+    let failed_tasks = test_ctx.failed_tasks().await;
+
+    // Act
+    test_ctx
+        .client()
+        .upgrade_canister(
+            test_ctx.dummy_scheduler_canister,
+            get_dummy_scheduler_canister_bytecode(),
+            Encode!(&()).unwrap(),
+            Some(alice()),
+        )
+        .await
+        .unwrap();
+
+    // Assert
+    assert_eq!(test_ctx.completed_tasks().await, completed_tasks);
+    assert_eq!(test_ctx.failed_tasks().await, failed_tasks);
+
+    // the timers are set again after the upgrade
+    test_ctx.schedule_tasks(vec![DummyTask::GoodTask]).await;
+    advance_until(
+        test_ctx.client(),
+        Duration::from_millis(100),
+        20,
+        async |_| test_ctx.completed_tasks().await.len() > completed_tasks.len(),
+    )
+    .await
+    .unwrap();
+}
@@ -1,13 +1,40 @@
-use std::{hash::Hash, ops::RangeBounds};
+use std::{cell::Cell, hash::Hash, ops::RangeBounds};
 
 use ic_stable_structures::{BTreeMap, Memory, Storable};
 
 use crate::{
     BTreeMapIter,
-    btreemap::{BTreeMapIteratorStructure, BTreeMapStructure},
-    common::LruCache,
+    btreemap::{
+        BTreeMapIteratorStructure, BTreeMapStructure, drain_range_with, remove_range_in_batches,
+        retain_in_batches,
+    },
+    common::{CacheCapacity, CacheStats, CacheWeight, LruCache, MemoryUsage},
 };
 
+/// The caching policy of a `CachedBTreeMap`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CachePolicy {
+    /// Whether the inserted values are put into the cache.
+    /// If false, only the values read by `get` are cached, so that bulk writes
+    /// do not push the hot entries out of the cache.
+    pub cache_on_insert: bool,
+    /// Whether to remember the keys that are not in the map, so that `get` and `contains_key`
+    /// on a missing key do not read the stable memory again until the key is inserted.
+    pub negative_caching: bool,
+    /// The number of lookups for which a missing key is remembered, `0` means until the key is inserted.
+    pub negative_ttl_ops: u32,
+}
+
+impl Default for CachePolicy {
+    fn default() -> Self {
+        Self {
+            cache_on_insert: true,
+            negative_caching: false,
+            negative_ttl_ops: 0,
+        }
+    }
+}
+
 /// A LRU Cache for BTreeMap
 pub struct CachedBTreeMap<K, V, M>
 where
@@ -17,6 +44,10 @@ where
 {
     inner: BTreeMap<K, V, M>,
     cache: LruCache<K, V>,
+    policy: CachePolicy,
+    /// The keys known to be missing, with the lookup count at which they were found missing
+    missing_keys: LruCache<K, u64>,
+    lookups: Cell<u64>,
 }
 
 impl<K, V, M> CachedBTreeMap<K, V, M>
@@ -42,16 +73,183 @@ where
 
     /// Create new instance of the CachedUnboundedMap with a fixed number of max cached elements.
     pub fn with_map(inner: BTreeMap<K, V, M>, max_cache_items: u32) -> Self {
+        Self::with_caches(
+            inner,
+            LruCache::new(max_cache_items),
+            LruCache::new(max_cache_items),
+        )
+    }
+
+    /// Create new instance of the CachedUnboundedMap with a cache bounded either by
+    /// the number of elements or by the total `CacheWeight` of the cached values.
+    ///
+    /// With a byte budget, every key remembered as missing by the negative caching weighs 8 bytes.
+    pub fn with_capacity(inner: BTreeMap<K, V, M>, capacity: CacheCapacity) -> Self
+    where
+        V: CacheWeight,
+    {
+        Self::with_caches(
+            inner,
+            LruCache::with_capacity(capacity),
+            LruCache::with_capacity(capacity),
+        )
+    }
+
+    fn with_caches(
+        inner: BTreeMap<K, V, M>,
+        cache: LruCache<K, V>,
+        missing_keys: LruCache<K, u64>,
+    ) -> Self {
         Self {
             inner,
-            cache: LruCache::new(max_cache_items),
+            cache,
+            policy: CachePolicy::default(),
+            missing_keys,
+            lookups: Cell::new(0),
         }
     }
 
+    /// Sets the caching policy.
+    /// It should be set right after the creation of the map, before it is used.
+    pub fn with_policy(mut self, policy: CachePolicy) -> Self {
+        self.policy = policy;
+        self
+    }
+
     /// Returns the inner collection so that the caller can have a readonly access to it that bypasses the cache.
     pub fn inner(&self) -> &BTreeMap<K, V, M> {
         &self.inner
     }
+
+    /// Returns the inner collection so that the caller can modify it bypassing the cache.
+    ///
+    /// WARN: the cache is not updated by the changes made through the returned map,
+    /// so the affected keys must be invalidated with `invalidate`, `invalidate_many` or `invalidate_all`.
+    pub fn inner_mut(&mut self) -> &mut BTreeMap<K, V, M> {
+        &mut self.inner
+    }
+
+    /// Drops the cached value of the key, so that the next read loads it from the inner map.
+    ///
+    /// Only the cache is touched: unlike `remove`, the entry is not removed from the inner map.
+    pub fn invalidate(&mut self, key: &K) {
+        self.cache.remove(key);
+        self.missing_keys.remove(key);
+    }
+
+    /// Drops the cached values of the keys, so that the next reads load them from the inner map.
+    ///
+    /// Only the cache is touched: unlike `remove`, the entries are not removed from the inner map.
+    pub fn invalidate_many(&mut self, keys: &[K]) {
+        for key in keys {
+            self.invalidate(key);
+        }
+    }
+
+    /// Drops all the cached values, so that the next reads load them from the inner map.
+    ///
+    /// Only the cache is touched: unlike `clear`, which erases the stable memory,
+    /// the inner map is left untouched.
+    pub fn invalidate_all(&mut self) {
+        self.cache.clear();
+        self.missing_keys.clear();
+    }
+
+    /// Returns the hit, miss, insertion and eviction counters of the cache.
+    /// The counters are not persisted, so they restart from zero after an upgrade.
+    pub fn cache_stats(&self) -> CacheStats {
+        self.cache.stats()
+    }
+
+    /// Resets the counters of the cache.
+    pub fn reset_cache_stats(&self) {
+        self.cache.reset_stats();
+    }
+
+    /// Returns a writer that bypasses the cache, e.g. for a bulk import.
+    ///
+    /// The values written through the writer are not cached, and the cached values of the
+    /// written keys are invalidated, so that the hot entries already in the cache are preserved.
+    pub fn bulk_writer(&mut self) -> BulkWriter<'_, K, V, M> {
+        BulkWriter { map: self }
+    }
+
+    /// Returns true if the key is remembered as missing by the negative caching.
+    fn is_known_missing(&self, key: &K) -> bool {
+        if !self.policy.negative_caching {
+            return false;
+        }
+
+        let lookups = self.lookups.get() + 1;
+        self.lookups.set(lookups);
+
+        match self.missing_keys.get(key) {
+            Some(found_at)
+                if self.policy.negative_ttl_ops == 0
+                    || lookups - found_at <= self.policy.negative_ttl_ops as u64 =>
+            {
+                true
+            }
+            Some(_) => {
+                self.missing_keys.remove(key);
+                false
+            }
+            None => false,
+        }
+    }
+
+    /// Remembers the key as missing, if the negative caching is enabled.
+    fn set_missing(&self, key: &K) {
+        if self.policy.negative_caching {
+            self.missing_keys.insert(key.clone(), self.lookups.get());
+        }
+    }
+
+    /// Forgets that the key is missing.
+    fn unset_missing(&self, key: &K) {
+        if self.policy.negative_caching {
+            self.missing_keys.remove(key);
+        }
+    }
+}
+
+/// A writer of a `CachedBTreeMap` that bypasses the cache.
+/// See `CachedBTreeMap::bulk_writer`.
+pub struct BulkWriter<'a, K, V, M>
+where
+    K: Storable + Clone + Send + Sync + 'static + Hash + Eq + PartialEq + Ord,
+    V: Storable + Clone + Send + Sync + 'static,
+    M: Memory,
+{
+    map: &'a mut CachedBTreeMap<K, V, M>,
+}
+
+impl<K, V, M> BulkWriter<'_, K, V, M>
+where
+    K: Storable + Clone + Send + Sync + 'static + Hash + Eq + PartialEq + Ord,
+    V: Storable + Clone + Send + Sync + 'static,
+    M: Memory,
+{
+    /// Add or replace value associated with `key`, without caching it.
+    pub fn insert(&mut self, key: K, value: V) -> Option<V> {
+        self.map.invalidate(&key);
+        self.map.inner.insert(key, value)
+    }
+
+    /// Add or replace the values associated with the keys of `entries`, in order, without caching them.
+    /// Returns the previous values, in the same order of the entries.
+    pub fn insert_many(&mut self, entries: Vec<(K, V)>) -> Vec<Option<V>> {
+        entries
+            .into_iter()
+            .map(|(key, value)| self.insert(key, value))
+            .collect()
+    }
+
+    /// Remove value associated with `key`.
+    pub fn remove(&mut self, key: &K) -> Option<V> {
+        self.map.invalidate(key);
+        self.map.inner.remove(key)
+    }
 }
 
 impl<K, V, M> BTreeMapStructure<K, V> for CachedBTreeMap<K, V, M>
@@ -61,18 +259,85 @@ where
     M: Memory,
 {
     fn get(&self, key: &K) -> Option<V> {
-        self.cache
-            .get_or_insert_with(key, |key| self.inner.get(key))
+        if self.is_known_missing(key) {
+            return None;
+        }
+
+        let value = self
+            .cache
+            .get_or_insert_with(key, |key| self.inner.get(key));
+        if value.is_none() {
+            self.set_missing(key);
+        }
+        value
     }
 
-    /// When a new value is inserted, it is also inserted into the cache; this is
-    /// required because caching on the `get` is useless in IC if the method is used in a `query` call
+    /// When a new value is inserted, it is also inserted into the cache, unless disabled by the `CachePolicy`;
+    /// this is required because caching on the `get` is useless in IC if the method is used in a `query` call
     fn insert(&mut self, key: K, value: V) -> Option<V> {
-        self.cache.insert(key.clone(), value.clone());
+        self.unset_missing(&key);
+        if self.policy.cache_on_insert {
+            self.cache.insert(key.clone(), value.clone());
+        } else {
+            self.cache.remove(&key);
+        }
         self.inner.insert(key, value)
     }
 
+    /// The cache is borrowed only once for all the keys.
+    /// As for `get`, the keys known to be missing are not looked up.
+    fn get_many(&self, keys: &[K]) -> Vec<Option<V>> {
+        let known_missing: Vec<bool> = keys.iter().map(|key| self.is_known_missing(key)).collect();
+        let lookup_keys: Vec<K> = keys
+            .iter()
+            .zip(&known_missing)
+            .filter(|(_, known_missing)| !**known_missing)
+            .map(|(key, _)| key.clone())
+            .collect();
+
+        let mut values = self
+            .cache
+            .get_or_insert_many_with(&lookup_keys, |key| {
+                let value = self.inner.get(key);
+                if value.is_none() {
+                    self.set_missing(key);
+                }
+                value
+            })
+            .into_iter();
+
+        known_missing
+            .into_iter()
+            .map(|known_missing| {
+                if known_missing {
+                    None
+                } else {
+                    values.next().flatten()
+                }
+            })
+            .collect()
+    }
+
+    /// As for `insert`, the inserted values are also inserted into the cache
+    fn insert_many(&mut self, entries: Vec<(K, V)>) -> Vec<Option<V>> {
+        for (key, _) in &entries {
+            self.unset_missing(key);
+        }
+        if self.policy.cache_on_insert {
+            self.cache.insert_many(entries.iter().cloned());
+        } else {
+            for (key, _) in &entries {
+                self.cache.remove(key);
+            }
+        }
+        entries
+            .into_iter()
+            .map(|(key, value)| self.inner.insert(key, value))
+            .collect()
+    }
+
     fn remove(&mut self, key: &K) -> Option<V> {
+        self.unset_missing(key);
         self.cache.remove(key);
         self.inner.remove(key)
     }
@@ -96,7 +361,15 @@ where
     }
 
     fn contains_key(&self, key: &K) -> bool {
-        self.cache.contains_key(key) || self.inner.contains_key(key)
+        if self.is_known_missing(key) {
+            return false;
+        }
+
+        let contains_key = self.cache.contains_key(key) || self.inner.contains_key(key);
+        if !contains_key {
+            self.set_missing(key);
+        }
+        contains_key
     }
 
     fn is_empty(&self) -> bool {
@@ -105,6 +378,7 @@ where
 
     fn clear(&mut self) {
         self.cache.clear();
+        self.missing_keys.clear();
         self.inner.clear_new()
     }
 
@@ -117,6 +391,44 @@ where
     fn last_key_value(&self) -> Option<(K, V)> {
         self.inner.last_key_value()
     }
+
+    /// The removed entries are also evicted from the cache
+    fn remove_range(&mut self, key_range: impl RangeBounds<K>, limit: Option<u64>) -> u64 {
+        let cache = &self.cache;
+        remove_range_in_batches(&mut self.inner, key_range, limit, |key| {
+            cache.remove(key);
+        })
+    }
+
+    /// The removed entries are also evicted from the cache
+    fn drain_range(&mut self, key_range: impl RangeBounds<K>, limit: u64) -> Vec<(K, V)> {
+        let cache = &self.cache;
+        drain_range_with(
+            &mut self.inner,
+            key_range,
+            limit,
+            |value| value,
+            |key| {
+                cache.remove(key);
+            },
+        )
+    }
+
+    /// The removed entries are also evicted from the cache
+    fn retain(&mut self, mut f: impl FnMut(&K, &V) -> bool) {
+        let cache = &self.cache;
+        retain_in_batches(
+            &mut self.inner,
+            |value| value,
+            |key, value| {
+                let keep = f(key, value);
+                if !keep {
+                    cache.remove(key);
+                }
+                keep
+            },
+        )
+    }
 }
 
 impl<K, V, M> BTreeMapIteratorStructure<K, V> for CachedBTreeMap<K, V, M>
@@ -141,6 +453,42 @@ where
     fn iter_from_prev_key(&self, bound: &K) -> Self::Iterator<'_> {
         BTreeMapIteratorStructure::iter_from_prev_key(&self.inner, bound)
     }
+
+    fn keys(&self) -> impl Iterator<Item = K> {
+        BTreeMapIteratorStructure::keys(&self.inner)
+    }
+
+    fn keys_range(&self, key_range: impl RangeBounds<K>) -> impl Iterator<Item = K> {
+        BTreeMapIteratorStructure::keys_range(&self.inner, key_range)
+    }
+
+    fn values(&self) -> impl Iterator<Item = V> {
+        BTreeMapIteratorStructure::values(&self.inner)
+    }
+
+    fn values_range(&self, key_range: impl RangeBounds<K>) -> impl Iterator<Item = V> {
+        BTreeMapIteratorStructure::values_range(&self.inner, key_range)
+    }
+
+    fn range_rev(&self, key_range: impl RangeBounds<K>) -> impl Iterator<Item = (K, V)> {
+        BTreeMapIteratorStructure::range_rev(&self.inner, key_range)
+    }
+}
+
+/// The cache lives in the heap, so it is not included.
+impl<K, V, M> MemoryUsage for CachedBTreeMap<K, V, M>
+where
+    K: Storable + Clone + Send + Sync + 'static + Hash + Eq + PartialEq + Ord,
+    V: Storable + Clone + Send + Sync + 'static,
+    M: Memory,
+{
+    fn allocated_bytes(&self) -> u64 {
+        self.inner.allocated_bytes()
+    }
+
+    fn used_bytes(&self) -> Option<u64> {
+        self.inner.used_bytes()
+    }
 }
 
 #[cfg(test)]
@@ -327,6 +675,29 @@ mod tests {
         assert_eq!(iter.next(), None);
     }
 
+    #[test]
+    fn should_iterate_in_reverse_order() {
+        let mut map = CachedBTreeMap::<u32, u64, _>::new(VectorMemory::default(), 10);
+        for i in 0..300 {
+            map.insert(i * 2, i as u64);
+        }
+
+        let mut expected = map.iter().collect::<Vec<_>>();
+        expected.reverse();
+        assert_eq!(map.iter_rev().collect::<Vec<_>>(), expected);
+
+        let mut expected = map.range(101..=401).collect::<Vec<_>>();
+        expected.reverse();
+        assert_eq!(map.range_rev(101..=401).collect::<Vec<_>>(), expected);
+        assert_eq!(map.range_rev(101..=401).next(), Some((400, 200)));
+
+        assert_eq!(
+            map.range_rev(..10).map(|(key, _)| key).collect::<Vec<_>>(),
+            vec![8, 6, 4, 2, 0]
+        );
+        assert_eq!(map.range_rev(1000..).next(), None);
+    }
+
     #[test]
     fn should_iterate_upper_bound() {
         let cache_items = 2;
@@ -343,6 +714,401 @@ mod tests {
         assert_eq!(iter.next(), None);
     }
 
+    #[test]
+    fn should_iterate_over_keys_and_values() {
+        let cache_items = 2;
+        let mut map = CachedBTreeMap::<u32, Array<2>, _>::new(VectorMemory::default(), cache_items);
+
+        assert_eq!(None, map.insert(1, Array([1u8, 1])));
+        assert_eq!(None, map.insert(2, Array([2u8, 1])));
+        assert_eq!(None, map.insert(3, Array([3u8, 1])));
+
+        assert_eq!(map.keys().collect::<Vec<_>>(), vec![1, 2, 3]);
+        assert_eq!(map.keys_range(2..).collect::<Vec<_>>(), vec![2, 3]);
+        assert_eq!(
+            map.values().collect::<Vec<_>>(),
+            vec![Array([1u8, 1]), Array([2u8, 1]), Array([3u8, 1])]
+        );
+        assert_eq!(
+            map.values_range(..2).collect::<Vec<_>>(),
+            vec![Array([1u8, 1])]
+        );
+    }
+
+    #[test]
+    fn should_retain_entries_and_evict_them_from_the_cache() {
+        let cache_items = 10;
+        let mut map = CachedBTreeMap::<u32, Array<2>, _>::new(VectorMemory::default(), cache_items);
+        for i in 0..5u8 {
+            map.insert(i as u32, Array([i, 1]));
+        }
+
+        map.retain(|key, _| key % 2 == 0);
+
+        assert_eq!(map.len(), 3);
+        assert_eq!(map.keys().collect::<Vec<_>>(), vec![0, 2, 4]);
+        for key in [1, 3] {
+            assert!(!map.cache.contains_key(&key));
+            assert!(!map.contains_key(&key));
+            assert_eq!(map.get(&key), None);
+        }
+        for key in [0, 2, 4] {
+            assert!(map.cache.contains_key(&key));
+            assert_eq!(map.get(&key), Some(Array([key as u8, 1])));
+        }
+    }
+
+    #[test]
+    fn should_retain_all_or_no_entries() {
+        let cache_items = 10;
+        let mut map = CachedBTreeMap::<u32, Array<2>, _>::new(VectorMemory::default(), cache_items);
+        for i in 0..5u8 {
+            map.insert(i as u32, Array([i, 1]));
+        }
+
+        map.retain(|_, _| true);
+        assert_eq!(map.len(), 5);
+
+        map.retain(|_, _| false);
+        assert!(map.is_empty());
+    }
+
+    #[test]
+    fn should_get_and_insert_many() {
+        let cache_items = 10;
+        let mut map = CachedBTreeMap::<u32, Array<2>, _>::new(VectorMemory::default(), cache_items);
+
+        assert_eq!(
+            map.insert_many(vec![(3, Array([3u8, 1])), (1, Array([1u8, 1]))]),
+            vec![None, None]
+        );
+        assert!(map.cache.contains_key(&1));
+        assert!(map.cache.contains_key(&3));
+
+        assert_eq!(
+            map.insert_many(vec![(1, Array([1u8, 2])), (2, Array([2u8, 1]))]),
+            vec![Some(Array([1u8, 1])), None]
+        );
+        assert_eq!(map.cache.get(&1), Some(Array([1u8, 2])));
+
+        assert_eq!(
+            map.get_many(&[4, 3, 1, 2]),
+            vec![
+                None,
+                Some(Array([3u8, 1])),
+                Some(Array([1u8, 2])),
+                Some(Array([2u8, 1]))
+            ]
+        );
+    }
+
+    #[test]
+    fn should_cache_the_values_read_with_get_many() {
+        let cache_items = 10;
+        let mut inner = BTreeMap::new(VectorMemory::default());
+        inner.insert(1u32, Array([1u8, 1]));
+        inner.insert(2u32, Array([2u8, 1]));
+        let map = CachedBTreeMap::with_map(inner, cache_items);
+
+        assert!(map.cache.is_empty());
+        assert_eq!(
+            map.get_many(&[2, 3, 1]),
+            vec![Some(Array([2u8, 1])), None, Some(Array([1u8, 1]))]
+        );
+        assert!(map.cache.contains_key(&1));
+        assert!(map.cache.contains_key(&2));
+        assert!(!map.cache.contains_key(&3));
+    }
+
+    #[test]
+    fn should_remove_a_range_and_evict_it_from_the_cache() {
+        let cache_items = 10;
+        let mut map = CachedBTreeMap::<u32, Array<2>, _>::new(VectorMemory::default(), cache_items);
+        for i in 0..5u8 {
+            map.insert(i as u32, Array([i, 1]));
+        }
+
+        assert_eq!(map.remove_range(1..=3, Some(2)), 2);
+        assert_eq!(map.keys().collect::<Vec<_>>(), vec![0, 3, 4]);
+        assert!(!map.cache.contains_key(&1));
+        assert!(!map.cache.contains_key(&2));
+        assert!(map.cache.contains_key(&3));
+
+        assert_eq!(map.remove_range(1..=3, None), 1);
+        assert!(!map.cache.contains_key(&3));
+        assert_eq!(map.get(&3), None);
+        assert_eq!(map.keys().collect::<Vec<_>>(), vec![0, 4]);
+    }
+
+    #[test]
+    fn should_drain_a_range_and_evict_it_from_the_cache() {
+        let mut map = CachedBTreeMap::<u32, Array<2>, _>::new(VectorMemory::default(), 10);
+        for i in 0..5u8 {
+            map.insert(i as u32, Array([i, 1]));
+        }
+
+        assert_eq!(
+            map.drain_range(1..=3, 2),
+            vec![(1, Array([1, 1])), (2, Array([2, 1]))]
+        );
+        assert!(!map.cache.contains_key(&1));
+        assert!(!map.cache.contains_key(&2));
+        assert!(map.cache.contains_key(&3));
+
+        assert_eq!(
+            map.pop_first_n(2),
+            vec![(0, Array([0, 1])), (3, Array([3, 1]))]
+        );
+        assert!(!map.cache.contains_key(&3));
+        assert_eq!(map.get(&3), None);
+        assert_eq!(map.keys().collect::<Vec<_>>(), vec![4]);
+    }
+
+    #[test]
+    fn should_bound_the_cache_by_weight() {
+        let mut map = CachedBTreeMap::<u32, Vec<u8>, _>::with_capacity(
+            BTreeMap::new(VectorMemory::default()),
+            CacheCapacity::Bytes(100),
+        );
+
+        map.insert(1, vec![1; 60]);
+        map.insert(2, vec![2; 30]);
+        assert!(map.cache.contains_key(&1));
+
+        // the least recently used value is evicted to make room for the new one
+        map.insert(3, vec![3; 20]);
+        assert!(!map.cache.contains_key(&1));
+        assert!(map.cache.contains_key(&2));
+        assert!(map.cache.contains_key(&3));
+
+        // the evicted value is still in the stable map
+        assert_eq!(map.get(&1), Some(vec![1; 60]));
+        assert!(map.cache.contains_key(&1));
+        assert!(!map.cache.contains_key(&2));
+    }
+
+    #[test]
+    fn should_preserve_the_hot_entries_during_a_bulk_import() {
+        let cache_items = 3;
+        let mut map = CachedBTreeMap::<u32, Array<2>, _>::new(VectorMemory::default(), cache_items);
+        map.insert(1, Array([1u8, 1]));
+        map.insert(2, Array([2u8, 1]));
+
+        {
+            let mut writer = map.bulk_writer();
+            for i in 10..1000u32 {
+                writer.insert(i, Array([(i % 256) as u8, 2]));
+            }
+            assert_eq!(writer.insert(2, Array([2u8, 2])), Some(Array([2u8, 1])));
+            assert_eq!(writer.remove(&11), Some(Array([11u8, 2])));
+        }
+
+        // the hot entry survives, the overwritten one is invalidated
+        assert!(map.cache.contains_key(&1));
+        assert!(!map.cache.contains_key(&2));
+        assert!(!map.cache.contains_key(&10));
+        assert_eq!(map.cache_stats().insertions, 2);
+
+        assert_eq!(map.get(&1), Some(Array([1u8, 1])));
+        assert_eq!(map.get(&2), Some(Array([2u8, 2])));
+        assert_eq!(map.get(&999), Some(Array([(999 % 256) as u8, 2])));
+        assert_eq!(map.get(&11), None);
+        assert_eq!(map.len(), 991);
+    }
+
+    #[test]
+    fn should_count_cache_hits_and_misses() {
+        let cache_items = 2;
+        let mut inner = BTreeMap::new(VectorMemory::default());
+        inner.insert(1u32, Array([1u8, 1]));
+        inner.insert(2u32, Array([2u8, 1]));
+        let mut map = CachedBTreeMap::with_map(inner, cache_items);
+
+        // misses that load the values from the stable map
+        assert_eq!(map.get(&1), Some(Array([1u8, 1])));
+        assert_eq!(map.get(&2), Some(Array([2u8, 1])));
+        // a miss of a missing key
+        assert_eq!(map.get(&3), None);
+        // hits
+        assert_eq!(map.get(&1), Some(Array([1u8, 1])));
+        assert_eq!(map.get(&2), Some(Array([2u8, 1])));
+        // an insertion that evicts the least recently used key
+        map.insert(3, Array([3u8, 1]));
+        // a miss of the evicted key
+        assert_eq!(map.get(&1), Some(Array([1u8, 1])));
+
+        assert_eq!(
+            map.cache_stats(),
+            CacheStats {
+                hits: 2,
+                misses: 4,
+                insertions: 4,
+                evictions: 2,
+            }
+        );
+
+        map.reset_cache_stats();
+        assert_eq!(map.cache_stats(), CacheStats::default());
+    }
+
+    #[test]
+    fn should_not_cache_on_insert_if_disabled() {
+        let cache_items = 10;
+        let mut map = CachedBTreeMap::<u32, Array<2>, _>::new(VectorMemory::default(), cache_items)
+            .with_policy(CachePolicy {
+                cache_on_insert: false,
+                ..Default::default()
+            });
+
+        map.insert(1, Array([1u8, 1]));
+        assert!(!map.cache.contains_key(&1));
+
+        assert_eq!(map.get(&1), Some(Array([1u8, 1])));
+        assert!(map.cache.contains_key(&1));
+
+        // the cached value is replaced by the next read
+        map.insert(1, Array([1u8, 2]));
+        assert!(!map.cache.contains_key(&1));
+        assert_eq!(map.get(&1), Some(Array([1u8, 2])));
+
+        map.insert_many(vec![(2, Array([2u8, 1]))]);
+        assert!(!map.cache.contains_key(&2));
+        assert_eq!(map.get(&2), Some(Array([2u8, 1])));
+    }
+
+    #[test]
+    fn should_remember_missing_keys() {
+        let cache_items = 10;
+        let map = CachedBTreeMap::<u32, Array<2>, _>::new(VectorMemory::default(), cache_items)
+            .with_policy(CachePolicy {
+                negative_caching: true,
+                ..Default::default()
+            });
+
+        assert_eq!(map.get(&1), None);
+        assert_eq!(map.get(&1), None);
+        assert!(!map.contains_key(&1));
+
+        // only the first lookup reached the cache
+        assert_eq!(map.cache_stats().misses, 1);
+    }
+
+    #[test]
+    fn should_remember_missing_keys_on_get_many() {
+        let cache_items = 10;
+        let mut map = CachedBTreeMap::<u32, Array<2>, _>::new(VectorMemory::default(), cache_items)
+            .with_policy(CachePolicy {
+                negative_caching: true,
+                ..Default::default()
+            });
+        map.insert(1, Array([1u8, 1]));
+
+        assert_eq!(map.get_many(&[1, 2]), vec![Some(Array([1u8, 1])), None]);
+        assert_eq!(map.cache_stats().misses, 1);
+
+        // the miss is remembered by both get_many and get
+        assert_eq!(map.get_many(&[2, 1]), vec![None, Some(Array([1u8, 1]))]);
+        assert_eq!(map.get(&2), None);
+        assert_eq!(map.cache_stats().misses, 1);
+
+        map.insert(2, Array([2u8, 1]));
+        assert_eq!(
+            map.get_many(&[1, 2]),
+            vec![Some(Array([1u8, 1])), Some(Array([2u8, 1]))]
+        );
+    }
+
+    #[test]
+    fn should_see_a_key_inserted_after_being_remembered_as_missing() {
+        let cache_items = 10;
+        let mut map = CachedBTreeMap::<u32, Array<2>, _>::new(VectorMemory::default(), cache_items)
+            .with_policy(CachePolicy {
+                cache_on_insert: false,
+                negative_caching: true,
+                negative_ttl_ops: 0,
+            });
+
+        assert_eq!(map.get(&1), None);
+        map.insert(1, Array([1u8, 1]));
+        assert_eq!(map.get(&1), Some(Array([1u8, 1])));
+
+        assert!(!map.contains_key(&2));
+        map.insert_many(vec![(2, Array([2u8, 1]))]);
+        assert!(map.contains_key(&2));
+
+        map.remove(&2);
+        assert!(!map.contains_key(&2));
+        map.insert(2, Array([2u8, 2]));
+        assert_eq!(map.get(&2), Some(Array([2u8, 2])));
+    }
+
+    #[test]
+    fn should_forget_missing_keys_after_the_ttl() {
+        let cache_items = 10;
+        let map = CachedBTreeMap::<u32, Array<2>, _>::new(VectorMemory::default(), cache_items)
+            .with_policy(CachePolicy {
+                cache_on_insert: true,
+                negative_caching: true,
+                negative_ttl_ops: 2,
+            });
+
+        assert_eq!(map.get(&1), None);
+        assert_eq!(map.get(&1), None);
+        assert_eq!(map.get(&1), None);
+        assert_eq!(map.cache_stats().misses, 1);
+
+        // the ttl is expired, so the stable map is read again
+        assert_eq!(map.get(&1), None);
+        assert_eq!(map.cache_stats().misses, 2);
+    }
+
+    #[test]
+    fn should_invalidate_the_cache_without_touching_the_inner_map() {
+        let cache_items = 10;
+        let mut map = CachedBTreeMap::<u32, Array<2>, _>::new(VectorMemory::default(), cache_items);
+        map.insert(1, Array([1u8, 1]));
+        map.insert(2, Array([2u8, 1]));
+        map.insert(3, Array([3u8, 1]));
+
+        map.inner_mut().insert(1, Array([1u8, 2]));
+        map.inner_mut().insert(2, Array([2u8, 2]));
+        map.inner_mut().insert(3, Array([3u8, 2]));
+
+        // stale reads
+        assert_eq!(map.get(&1), Some(Array([1u8, 1])));
+        assert_eq!(map.get(&2), Some(Array([2u8, 1])));
+        assert_eq!(map.get(&3), Some(Array([3u8, 1])));
+
+        map.invalidate(&1);
+        assert_eq!(map.get(&1), Some(Array([1u8, 2])));
+        assert_eq!(map.get(&2), Some(Array([2u8, 1])));
+
+        map.invalidate_many(&[2]);
+        assert_eq!(map.get(&2), Some(Array([2u8, 2])));
+        assert_eq!(map.get(&3), Some(Array([3u8, 1])));
+
+        map.invalidate_all();
+        assert_eq!(map.get(&3), Some(Array([3u8, 2])));
+        assert_eq!(map.len(), 3);
+    }
+
+    #[test]
+    fn should_invalidate_the_missing_keys() {
+        let cache_items = 10;
+        let mut map = CachedBTreeMap::<u32, Array<2>, _>::new(VectorMemory::default(), cache_items)
+            .with_policy(CachePolicy {
+                negative_caching: true,
+                ..Default::default()
+            });
+
+        assert_eq!(map.get(&1), None);
+        map.inner_mut().insert(1, Array([1u8, 1]));
+        assert_eq!(map.get(&1), None);
+
+        map.invalidate(&1);
+        assert_eq!(map.get(&1), Some(Array([1u8, 1])));
+    }
+
     #[test]
     fn test_last_key_value() {
         let cache_items = 2;
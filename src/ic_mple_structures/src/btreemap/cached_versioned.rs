@@ -0,0 +1,381 @@
+use std::{hash::Hash, ops::RangeBounds};
+
+use ic_stable_structures::{BTreeMap, Memory, Storable};
+
+use crate::{
+    btreemap::{
+        BTreeMapIteratorStructure, BTreeMapStructure, drain_range_with, remove_range_in_batches,
+        versioned::{VersionedBTreeMap, VersionedBTreeMapIter},
+    },
+    common::{CacheStats, Codec, LruCache, MemoryUsage},
+};
+
+/// A LRU Cache for VersionedBTreeMap.
+///
+/// The cache holds the decoded values, so the codec is used only when a value
+/// is read from the stable memory.
+pub struct CachedVersionedBTreeMap<K, V, C: Codec<V>, M>
+where
+    K: Storable + Clone + Send + Sync + 'static + Hash + Eq + PartialEq + Ord,
+    V: Clone + Send + Sync + 'static,
+    M: Memory,
+{
+    inner: VersionedBTreeMap<K, V, C, M>,
+    cache: LruCache<K, V>,
+}
+
+impl<K, V, C: Codec<V>, M> CachedVersionedBTreeMap<K, V, C, M>
+where
+    K: Storable + Clone + Send + Sync + 'static + Hash + Eq + PartialEq + Ord,
+    V: Clone + Send + Sync + 'static,
+    M: Memory,
+{
+    /// Create new instance of the CachedVersionedBTreeMap with a fixed number of max cached elements,
+    /// overwriting any data structures the memory might have
+    /// contained previously.
+    pub fn new(memory: M, max_cache_items: u32) -> Self {
+        Self::with_map(BTreeMap::new(memory), max_cache_items)
+    }
+
+    /// Create new instance of the CachedVersionedBTreeMap with a fixed number of max cached elements.
+    ///
+    /// PRECONDITION: the memory is either empty or contains a valid
+    /// stable BTreeMap.
+    pub fn init(memory: M, max_cache_items: u32) -> Self {
+        Self::with_map(BTreeMap::init(memory), max_cache_items)
+    }
+
+    /// Create new instance of the CachedVersionedBTreeMap with a fixed number of max cached elements.
+    pub fn with_map(inner: BTreeMap<K, C, M>, max_cache_items: u32) -> Self {
+        Self {
+            inner: VersionedBTreeMap::with_map(inner),
+            cache: LruCache::new(max_cache_items),
+        }
+    }
+
+    /// Returns the inner collection so that the caller can have a readonly access to it that bypasses the cache.
+    pub fn inner(&self) -> &VersionedBTreeMap<K, V, C, M> {
+        &self.inner
+    }
+
+    /// Returns the hit, miss, insertion and eviction counters of the cache.
+    /// The counters are not persisted, so they restart from zero after an upgrade.
+    pub fn cache_stats(&self) -> CacheStats {
+        self.cache.stats()
+    }
+
+    /// Resets the counters of the cache.
+    pub fn reset_cache_stats(&self) {
+        self.cache.reset_stats();
+    }
+}
+
+impl<K, V, C: Codec<V>, M> BTreeMapStructure<K, V> for CachedVersionedBTreeMap<K, V, C, M>
+where
+    K: Storable + Clone + Send + Sync + 'static + Hash + Eq + PartialEq + Ord,
+    V: Clone + Send + Sync + 'static,
+    M: Memory,
+{
+    fn get(&self, key: &K) -> Option<V> {
+        self.cache
+            .get_or_insert_with(key, |key| self.inner.get(key))
+    }
+
+    /// When a new value is inserted, it is also inserted into the cache; this is
+    /// required because caching on the `get` is useless in IC if the method is used in a `query` call
+    fn insert(&mut self, key: K, value: V) -> Option<V> {
+        self.cache.insert(key.clone(), value.clone());
+        self.inner.insert(key, value)
+    }
+
+    /// The cache is borrowed only once for all the keys
+    fn get_many(&self, keys: &[K]) -> Vec<Option<V>> {
+        self.cache
+            .get_or_insert_many_with(keys, |key| self.inner.get(key))
+    }
+
+    /// As for `insert`, the inserted values are also inserted into the cache
+    fn insert_many(&mut self, entries: Vec<(K, V)>) -> Vec<Option<V>> {
+        self.cache.insert_many(entries.iter().cloned());
+        self.inner.insert_many(entries)
+    }
+
+    fn remove(&mut self, key: &K) -> Option<V> {
+        self.cache.remove(key);
+        self.inner.remove(key)
+    }
+
+    fn pop_first(&mut self) -> Option<(K, V)> {
+        let (k, v) = self.inner.pop_first()?;
+        self.cache.remove(&k);
+
+        Some((k, v))
+    }
+
+    fn pop_last(&mut self) -> Option<(K, V)> {
+        let (k, v) = self.inner.pop_last()?;
+        self.cache.remove(&k);
+
+        Some((k, v))
+    }
+
+    fn contains_key(&self, key: &K) -> bool {
+        self.cache.contains_key(key) || self.inner.contains_key(key)
+    }
+
+    /// WARN: this bypasses the cache
+    fn first_key_value(&self) -> Option<(K, V)> {
+        self.inner.first_key_value()
+    }
+
+    /// WARN: this bypasses the cache
+    fn last_key_value(&self) -> Option<(K, V)> {
+        self.inner.last_key_value()
+    }
+
+    fn len(&self) -> u64 {
+        self.inner.len()
+    }
+
+    fn is_empty(&self) -> bool {
+        self.inner.is_empty()
+    }
+
+    fn clear(&mut self) {
+        self.cache.clear();
+        self.inner.clear()
+    }
+
+    /// The removed entries are also evicted from the cache
+    fn retain(&mut self, mut f: impl FnMut(&K, &V) -> bool) {
+        let cache = &self.cache;
+        self.inner.retain(|key, value| {
+            let keep = f(key, value);
+            if !keep {
+                cache.remove(key);
+            }
+            keep
+        })
+    }
+
+    /// The removed entries are also evicted from the cache
+    fn remove_range(&mut self, key_range: impl RangeBounds<K>, limit: Option<u64>) -> u64 {
+        let cache = &self.cache;
+        remove_range_in_batches(self.inner.inner_mut(), key_range, limit, |key| {
+            cache.remove(key);
+        })
+    }
+
+    /// The removed entries are also evicted from the cache
+    fn drain_range(&mut self, key_range: impl RangeBounds<K>, limit: u64) -> Vec<(K, V)> {
+        let cache = &self.cache;
+        drain_range_with(self.inner.inner_mut(), key_range, limit, C::decode, |key| {
+            cache.remove(key);
+        })
+    }
+}
+
+/// WARN: the iterators bypass the cache
+impl<K, V, C: Codec<V>, M> BTreeMapIteratorStructure<K, V> for CachedVersionedBTreeMap<K, V, C, M>
+where
+    K: Storable + Clone + Send + Sync + 'static + Hash + Eq + PartialEq + Ord,
+    V: Clone + Send + Sync + 'static,
+    M: Memory,
+{
+    type Iterator<'a>
+        = VersionedBTreeMapIter<'a, K, V, C, M>
+    where
+        Self: 'a;
+
+    fn iter(&self) -> Self::Iterator<'_> {
+        self.inner.iter()
+    }
+
+    fn range(&self, key_range: impl RangeBounds<K>) -> Self::Iterator<'_> {
+        self.inner.range(key_range)
+    }
+
+    fn iter_from_prev_key(&self, bound: &K) -> Self::Iterator<'_> {
+        self.inner.iter_from_prev_key(bound)
+    }
+
+    fn keys(&self) -> impl Iterator<Item = K> {
+        self.inner.keys()
+    }
+
+    fn keys_range(&self, key_range: impl RangeBounds<K>) -> impl Iterator<Item = K> {
+        self.inner.keys_range(key_range)
+    }
+
+    fn values(&self) -> impl Iterator<Item = V> {
+        self.inner.values()
+    }
+
+    fn values_range(&self, key_range: impl RangeBounds<K>) -> impl Iterator<Item = V> {
+        self.inner.values_range(key_range)
+    }
+
+    fn range_rev(&self, key_range: impl RangeBounds<K>) -> impl Iterator<Item = (K, V)> {
+        self.inner.range_rev(key_range)
+    }
+}
+
+/// The cache lives in the heap, so it is not included.
+impl<K, V, C: Codec<V>, M> MemoryUsage for CachedVersionedBTreeMap<K, V, C, M>
+where
+    K: Storable + Clone + Send + Sync + 'static + Hash + Eq + PartialEq + Ord,
+    V: Clone + Send + Sync + 'static,
+    M: Memory,
+{
+    fn allocated_bytes(&self) -> u64 {
+        self.inner.allocated_bytes()
+    }
+
+    fn used_bytes(&self) -> Option<u64> {
+        self.inner.used_bytes()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use ic_stable_structures::VectorMemory;
+
+    use super::*;
+    use crate::test_utils::{UserCodec, UserV1, UserV2};
+
+    fn user(name: &str, age: Option<u8>) -> UserV2 {
+        UserV2 {
+            name: name.to_string(),
+            age,
+        }
+    }
+
+    fn users_map() -> BTreeMap<u32, UserCodec, VectorMemory> {
+        let mut btree_map = BTreeMap::new(VectorMemory::default());
+        btree_map.insert(1u32, UserCodec::V1(UserV1("roger".to_string())));
+        btree_map.insert(2, UserCodec::V2(user("brian", Some(42))));
+        btree_map.insert(3, UserCodec::V1(UserV1("freddie".to_string())));
+        btree_map
+    }
+
+    #[test]
+    fn should_cache_the_decoded_values() {
+        let map = CachedVersionedBTreeMap::<u32, UserV2, UserCodec, _>::with_map(users_map(), 10);
+
+        assert_eq!(map.get(&1), Some(user("roger", None)));
+        assert_eq!(
+            map.cache_stats(),
+            CacheStats {
+                hits: 0,
+                misses: 1,
+                insertions: 1,
+                evictions: 0,
+            }
+        );
+
+        assert_eq!(map.get(&1), Some(user("roger", None)));
+        assert_eq!(map.get(&2), Some(user("brian", Some(42))));
+        assert_eq!(map.get(&4), None);
+        assert_eq!(
+            map.cache_stats(),
+            CacheStats {
+                hits: 1,
+                misses: 3,
+                insertions: 2,
+                evictions: 0,
+            }
+        );
+    }
+
+    #[test]
+    fn should_get_and_insert() {
+        let mut map =
+            CachedVersionedBTreeMap::<u32, UserV2, UserCodec, _>::new(VectorMemory::default(), 2);
+
+        assert!(map.is_empty());
+
+        assert_eq!(map.insert(1, user("john", None)), None);
+        assert_eq!(map.insert(2, user("paul", None)), None);
+        assert_eq!(map.insert(3, user("george", None)), None);
+        assert_eq!(
+            map.insert(1, user("ringo", Some(30))),
+            Some(user("john", None))
+        );
+
+        assert_eq!(map.len(), 3);
+        assert!(map.contains_key(&2));
+        assert_eq!(
+            map.get_many(&[3, 4, 1]),
+            vec![
+                Some(user("george", None)),
+                None,
+                Some(user("ringo", Some(30)))
+            ]
+        );
+
+        assert_eq!(map.remove(&1), Some(user("ringo", Some(30))));
+        assert_eq!(map.get(&1), None);
+        assert!(!map.contains_key(&1));
+    }
+
+    #[test]
+    fn should_evict_the_removed_entries() {
+        let mut map =
+            CachedVersionedBTreeMap::<u32, UserV2, UserCodec, _>::with_map(users_map(), 10);
+        map.insert(4, user("john", None));
+        map.insert(5, user("paul", None));
+        assert_eq!(map.get_many(&[1, 2, 3]).len(), 3);
+
+        assert_eq!(map.pop_first(), Some((1, user("roger", None))));
+        assert_eq!(map.pop_last(), Some((5, user("paul", None))));
+        assert!(!map.cache.contains_key(&1));
+        assert!(!map.cache.contains_key(&5));
+
+        map.retain(|key, _| *key != 2);
+        assert!(!map.cache.contains_key(&2));
+
+        assert_eq!(map.remove_range(..4, None), 1);
+        assert!(!map.cache.contains_key(&3));
+        assert_eq!(map.keys().collect::<Vec<_>>(), vec![4]);
+
+        assert_eq!(map.get(&4), Some(user("john", None)));
+        assert_eq!(map.pop_first_n(5), vec![(4, user("john", None))]);
+        assert!(!map.cache.contains_key(&4));
+
+        map.clear();
+        assert!(map.is_empty());
+        assert_eq!(map.get(&4), None);
+    }
+
+    #[test]
+    fn should_iterate() {
+        let map = CachedVersionedBTreeMap::<u32, UserV2, UserCodec, _>::with_map(users_map(), 10);
+
+        assert_eq!(
+            map.iter().collect::<Vec<_>>(),
+            vec![
+                (1, user("roger", None)),
+                (2, user("brian", Some(42))),
+                (3, user("freddie", None))
+            ]
+        );
+        assert_eq!(
+            map.range(2..).map(|(key, _)| key).collect::<Vec<_>>(),
+            vec![2, 3]
+        );
+        assert_eq!(
+            map.iter_from_prev_key(&3)
+                .map(|(key, _)| key)
+                .collect::<Vec<_>>(),
+            vec![2, 3]
+        );
+        assert_eq!(map.keys_range(..2).collect::<Vec<_>>(), vec![1]);
+        assert_eq!(
+            map.values_range(3..).collect::<Vec<_>>(),
+            vec![user("freddie", None)]
+        );
+        assert_eq!(map.values().count(), 3);
+        assert_eq!(map.first_key_value(), Some((1, user("roger", None))));
+        assert_eq!(map.last_key_value(), Some((3, user("freddie", None))));
+    }
+}
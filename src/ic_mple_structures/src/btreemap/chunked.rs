@@ -0,0 +1,314 @@
+use ic_stable_structures::{BTreeMap, Memory, Storable};
+
+/// The chunk index of the manifest record of a value.
+/// It is the last index, so that the manifest follows all the chunks of the value.
+const MANIFEST_INDEX: u32 = u32::MAX;
+
+/// A map storing large values as a sequence of fixed-size chunks.
+///
+/// Every value is stored in an inner map keyed by `(key, chunk_index)`, followed by
+/// a manifest record with the total length of the value. The manifest is written last,
+/// so a value is visible only after all its chunks are written: if a write is interrupted,
+/// e.g. because a streaming upload spans several messages, `get` keeps returning `None`
+/// until the value is committed.
+///
+/// The chunk size must be the same every time the map is initialized from the same memory.
+pub struct ChunkedMap<K, M>
+where
+    K: Storable + Ord + Clone,
+    M: Memory,
+{
+    chunks: BTreeMap<(K, u32), Vec<u8>, M>,
+    chunk_size: usize,
+}
+
+impl<K, M> ChunkedMap<K, M>
+where
+    K: Storable + Ord + Clone,
+    M: Memory,
+{
+    /// Create new instance of the ChunkedMap,
+    /// overwriting any data structures the memory might have
+    /// contained previously.
+    ///
+    /// Panics if `chunk_size` is zero.
+    pub fn new(memory: M, chunk_size: u32) -> Self {
+        Self::with_map(BTreeMap::new(memory), chunk_size)
+    }
+
+    /// Create new instance of the ChunkedMap.
+    ///
+    /// PRECONDITION: the memory is either empty or contains a valid
+    /// ChunkedMap with the same chunk size.
+    ///
+    /// Panics if `chunk_size` is zero.
+    pub fn init(memory: M, chunk_size: u32) -> Self {
+        Self::with_map(BTreeMap::init(memory), chunk_size)
+    }
+
+    fn with_map(chunks: BTreeMap<(K, u32), Vec<u8>, M>, chunk_size: u32) -> Self {
+        assert!(chunk_size > 0, "the chunk size must be greater than zero");
+        Self {
+            chunks,
+            chunk_size: chunk_size as usize,
+        }
+    }
+
+    /// Returns the size of the chunks.
+    pub fn chunk_size(&self) -> u32 {
+        self.chunk_size as u32
+    }
+
+    /// Stores `bytes` as the value of `key`, replacing the previous value
+    /// and any pending chunks.
+    pub fn insert(&mut self, key: K, bytes: Vec<u8>) {
+        self.remove(&key);
+        for (index, chunk) in bytes.chunks(self.chunk_size).enumerate() {
+            self.chunks
+                .insert((key.clone(), index as u32), chunk.to_vec());
+        }
+        self.write_manifest(key, bytes.len() as u64);
+    }
+
+    /// Returns the value of `key`, or `None` if there is no committed value.
+    pub fn get(&self, key: &K) -> Option<Vec<u8>> {
+        let len = self.len_bytes(key)?;
+        let mut bytes = Vec::with_capacity(len as usize);
+        for entry in self
+            .chunks
+            .range((key.clone(), 0)..(key.clone(), MANIFEST_INDEX))
+        {
+            let (_, chunk) = entry.into_pair();
+            bytes.extend_from_slice(&chunk);
+        }
+        debug_assert_eq!(bytes.len() as u64, len);
+        Some(bytes)
+    }
+
+    /// Appends `bytes` to the pending value of `key`, and returns the length of the pending value.
+    ///
+    /// The value is not visible until `commit` is called, so a value can be uploaded
+    /// across several messages. Appending to a committed value makes it pending again.
+    pub fn append_chunk(&mut self, key: K, bytes: &[u8]) -> u64 {
+        self.chunks.remove(&(key.clone(), MANIFEST_INDEX));
+
+        let (mut index, mut last_chunk) = match self.last_chunk(&key) {
+            Some((index, chunk)) => (index, chunk),
+            None => (0, Vec::new()),
+        };
+
+        let mut remaining = bytes;
+        loop {
+            let free = self.chunk_size - last_chunk.len();
+            let (head, tail) = remaining.split_at(free.min(remaining.len()));
+            last_chunk.extend_from_slice(head);
+            remaining = tail;
+
+            if remaining.is_empty() {
+                break;
+            }
+            self.chunks.insert((key.clone(), index), last_chunk);
+            index += 1;
+            last_chunk = Vec::with_capacity(self.chunk_size);
+        }
+
+        let pending_len = index as u64 * self.chunk_size as u64 + last_chunk.len() as u64;
+        if !last_chunk.is_empty() {
+            self.chunks.insert((key, index), last_chunk);
+        }
+        pending_len
+    }
+
+    /// Commits the chunks appended to `key`, making the value visible, and returns its length.
+    pub fn commit(&mut self, key: K) -> u64 {
+        let len = self.pending_len(&key);
+        self.write_manifest(key, len);
+        len
+    }
+
+    /// Removes the value of `key` and any pending chunks.
+    ///
+    /// Returns true if a committed value was removed.
+    pub fn remove(&mut self, key: &K) -> bool {
+        let existed = self.chunks.contains_key(&(key.clone(), MANIFEST_INDEX));
+        let keys: Vec<_> = self
+            .chunks
+            .keys_range((key.clone(), 0)..=(key.clone(), MANIFEST_INDEX))
+            .collect();
+        for chunk_key in keys {
+            self.chunks.remove(&chunk_key);
+        }
+        existed
+    }
+
+    /// Returns the length in bytes of the committed value of `key`.
+    pub fn len_bytes(&self, key: &K) -> Option<u64> {
+        self.chunks
+            .get(&(key.clone(), MANIFEST_INDEX))
+            .map(|manifest| {
+                u64::from_le_bytes(
+                    manifest
+                        .as_slice()
+                        .try_into()
+                        .expect("invalid chunked value manifest"),
+                )
+            })
+    }
+
+    /// True if `key` has a committed value.
+    pub fn contains_key(&self, key: &K) -> bool {
+        self.chunks.contains_key(&(key.clone(), MANIFEST_INDEX))
+    }
+
+    /// Remove all the values and the pending chunks.
+    pub fn clear(&mut self) {
+        self.chunks.clear_new();
+    }
+
+    fn write_manifest(&mut self, key: K, len: u64) {
+        self.chunks
+            .insert((key, MANIFEST_INDEX), len.to_le_bytes().to_vec());
+    }
+
+    /// Returns the last chunk stored for `key`, excluding the manifest.
+    fn last_chunk(&self, key: &K) -> Option<(u32, Vec<u8>)> {
+        let index = self
+            .chunks
+            .keys_range((key.clone(), 0)..(key.clone(), MANIFEST_INDEX))
+            .next_back()?
+            .1;
+        let chunk = self.chunks.get(&(key.clone(), index))?;
+        Some((index, chunk))
+    }
+
+    /// Returns the length of the chunks stored for `key`.
+    /// All the chunks but the last one are full.
+    fn pending_len(&self, key: &K) -> u64 {
+        match self.last_chunk(key) {
+            Some((index, chunk)) => index as u64 * self.chunk_size as u64 + chunk.len() as u64,
+            None => 0,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use ic_stable_structures::VectorMemory;
+
+    use super::*;
+
+    fn bytes(len: usize) -> Vec<u8> {
+        (0..len).map(|i| (i % 251) as u8).collect()
+    }
+
+    #[test]
+    fn should_store_values_smaller_than_a_chunk() {
+        let mut map = ChunkedMap::<u32, _>::new(VectorMemory::default(), 16);
+
+        map.insert(1, bytes(5));
+        map.insert(2, Vec::new());
+
+        assert_eq!(map.get(&1), Some(bytes(5)));
+        assert_eq!(map.len_bytes(&1), Some(5));
+        assert_eq!(map.get(&2), Some(Vec::new()));
+        assert_eq!(map.len_bytes(&2), Some(0));
+        assert_eq!(map.get(&3), None);
+        assert_eq!(map.len_bytes(&3), None);
+    }
+
+    #[test]
+    fn should_store_exact_multiples_of_the_chunk_size() {
+        let mut map = ChunkedMap::<u32, _>::new(VectorMemory::default(), 16);
+
+        map.insert(1, bytes(16));
+        map.insert(2, bytes(64));
+        map.insert(3, bytes(65));
+
+        assert_eq!(map.get(&1), Some(bytes(16)));
+        assert_eq!(map.get(&2), Some(bytes(64)));
+        assert_eq!(map.get(&3), Some(bytes(65)));
+        assert_eq!(map.len_bytes(&2), Some(64));
+    }
+
+    #[test]
+    fn should_replace_a_value() {
+        let mut map = ChunkedMap::<u32, _>::new(VectorMemory::default(), 16);
+
+        map.insert(1, bytes(100));
+        map.insert(1, vec![7; 3]);
+
+        assert_eq!(map.get(&1), Some(vec![7; 3]));
+        assert_eq!(map.len_bytes(&1), Some(3));
+    }
+
+    #[test]
+    fn should_stream_chunks() {
+        let mut map = ChunkedMap::<u32, _>::new(VectorMemory::default(), 16);
+        let value = bytes(100);
+
+        assert_eq!(map.append_chunk(1, &value[..10]), 10);
+        assert_eq!(map.append_chunk(1, &value[10..16]), 16);
+        assert_eq!(map.append_chunk(1, &value[16..55]), 55);
+        assert_eq!(map.append_chunk(1, &value[55..]), 100);
+        assert_eq!(map.commit(1), 100);
+
+        assert_eq!(map.get(&1), Some(value));
+    }
+
+    #[test]
+    fn should_recover_from_an_interrupted_write() {
+        let memory = VectorMemory::default();
+        let value = bytes(50);
+        {
+            let mut map = ChunkedMap::<u32, _>::new(memory.clone(), 16);
+            map.insert(1, bytes(10));
+            map.append_chunk(2, &value[..20]);
+            // the upgrade happens before the commit
+        }
+
+        let mut map = ChunkedMap::<u32, _>::init(memory, 16);
+        assert_eq!(map.get(&1), Some(bytes(10)));
+        assert_eq!(map.get(&2), None);
+        assert_eq!(map.len_bytes(&2), None);
+        assert!(!map.contains_key(&2));
+
+        // the upload can be resumed
+        assert_eq!(map.append_chunk(2, &value[20..]), 50);
+        map.commit(2);
+        assert_eq!(map.get(&2), Some(value));
+    }
+
+    #[test]
+    fn should_hide_a_committed_value_while_appending() {
+        let mut map = ChunkedMap::<u32, _>::new(VectorMemory::default(), 16);
+
+        map.insert(1, bytes(20));
+        map.append_chunk(1, &[1, 2, 3]);
+        assert_eq!(map.get(&1), None);
+
+        map.commit(1);
+        let mut expected = bytes(20);
+        expected.extend_from_slice(&[1, 2, 3]);
+        assert_eq!(map.get(&1), Some(expected));
+    }
+
+    #[test]
+    fn should_remove_values_and_pending_chunks() {
+        let mut map = ChunkedMap::<u32, _>::new(VectorMemory::default(), 16);
+
+        map.insert(1, bytes(40));
+        map.insert(2, bytes(40));
+        map.append_chunk(3, &bytes(40));
+
+        assert!(map.remove(&1));
+        assert!(!map.remove(&1));
+        assert!(!map.remove(&3));
+
+        assert_eq!(map.get(&1), None);
+        assert_eq!(map.get(&2), Some(bytes(40)));
+        assert_eq!(map.commit(3), 0);
+
+        map.clear();
+        assert_eq!(map.get(&2), None);
+    }
+}
@@ -1,13 +1,22 @@
-use std::ops::RangeBounds;
+use std::ops::{Bound, RangeBounds};
 
+use ic_stable_structures::storable::Bound as StorableBound;
 use ic_stable_structures::{BTreeMap, Memory, Storable, btreemap};
 
+use crate::common::{MemoryUsage, Pagination, fixed_size};
+
 #[cfg(feature = "cached")]
 mod cached;
+#[cfg(feature = "cached")]
+mod cached_versioned;
+mod chunked;
 mod versioned;
 
 #[cfg(feature = "cached")]
-pub use cached::CachedBTreeMap;
+pub use cached::{BulkWriter, CachePolicy, CachedBTreeMap};
+#[cfg(feature = "cached")]
+pub use cached_versioned::CachedVersionedBTreeMap;
+pub use chunked::ChunkedMap;
 pub use versioned::VersionedBTreeMap;
 
 pub trait BTreeMapStructure<K, V> {
@@ -50,6 +59,200 @@ pub trait BTreeMapStructure<K, V> {
 
     /// Remove all entries from the map.
     fn clear(&mut self);
+
+    /// Returns the values associated with `keys`, in the same order of the keys.
+    fn get_many(&self, keys: &[K]) -> Vec<Option<V>> {
+        keys.iter().map(|key| self.get(key)).collect()
+    }
+
+    /// Add or replace the values associated with the keys of `entries`, in order.
+    /// Returns the previous values, in the same order of the entries.
+    ///
+    /// # Preconditions:
+    ///   - `key.to_bytes().len() <= K::MAX_SIZE`
+    ///   - `value.to_bytes().len() <= V::MAX_SIZE`
+    fn insert_many(&mut self, entries: Vec<(K, V)>) -> Vec<Option<V>> {
+        entries
+            .into_iter()
+            .map(|(key, value)| self.insert(key, value))
+            .collect()
+    }
+
+    /// Retains only the entries for which `f` returns `true`, removing all the others.
+    ///
+    /// The stable map cannot be mutated while it is iterated, so the entries are visited
+    /// in passes of at most `RETAIN_BATCH_SIZE` entries, and the keys to remove are
+    /// collected on the heap and removed at the end of each pass.
+    /// Every entry of the map is read and decoded once, and every removed entry costs
+    /// an additional `remove`, so the cost is linear in the size of the map.
+    fn retain(&mut self, f: impl FnMut(&K, &V) -> bool);
+
+    /// Removes the entries where keys belong to the specified range, in ascending order
+    /// of the keys, and returns the number of removed entries.
+    ///
+    /// If `limit` is set, at most `limit` entries are removed, so that pruning a big range
+    /// can be split across messages by calling it until it returns less than `limit`.
+    /// The values are never read.
+    fn remove_range(&mut self, key_range: impl RangeBounds<K>, limit: Option<u64>) -> u64;
+
+    /// Removes and returns the entries where keys belong to the specified range,
+    /// in ascending order of the keys, up to `limit` entries.
+    fn drain_range(&mut self, key_range: impl RangeBounds<K>, limit: u64) -> Vec<(K, V)>;
+
+    /// Removes and returns the first `n` entries of the map, in ascending order of the keys.
+    fn pop_first_n(&mut self, n: u64) -> Vec<(K, V)> {
+        self.drain_range(.., n)
+    }
+}
+
+/// The max number of entries visited by `retain` and `remove_range` before removing them.
+pub const RETAIN_BATCH_SIZE: usize = 1024;
+
+/// Retains only the entries of the map for which `f` returns `true`.
+/// The stored values are converted with `decode` before being passed to `f`.
+/// See `BTreeMapStructure::retain`.
+pub(crate) fn retain_in_batches<K, S, V, M>(
+    map: &mut BTreeMap<K, S, M>,
+    decode: impl Fn(S) -> V,
+    mut f: impl FnMut(&K, &V) -> bool,
+) where
+    K: Storable + Ord + Clone,
+    S: Storable,
+    M: Memory,
+{
+    let mut start = Bound::Unbounded;
+    loop {
+        let mut to_remove = Vec::new();
+        let mut last_key = None;
+
+        for entry in map.range((start, Bound::Unbounded)).take(RETAIN_BATCH_SIZE) {
+            let (key, value) = entry.into_pair();
+            if !f(&key, &decode(value)) {
+                to_remove.push(key.clone());
+            }
+            last_key = Some(key);
+        }
+
+        let Some(last_key) = last_key else {
+            return;
+        };
+
+        for key in &to_remove {
+            map.remove(key);
+        }
+
+        start = Bound::Excluded(last_key);
+    }
+}
+
+/// Removes the entries of the map where keys belong to `key_range`, up to `limit` entries,
+/// and calls `on_remove` for every removed key.
+/// See `BTreeMapStructure::remove_range`.
+pub(crate) fn remove_range_in_batches<K, S, M>(
+    map: &mut BTreeMap<K, S, M>,
+    key_range: impl RangeBounds<K>,
+    limit: Option<u64>,
+    mut on_remove: impl FnMut(&K),
+) -> u64
+where
+    K: Storable + Ord + Clone,
+    S: Storable,
+    M: Memory,
+{
+    let mut start = key_range.start_bound().cloned();
+    let end = key_range.end_bound().cloned();
+    let mut removed = 0;
+    loop {
+        let batch_size = match limit {
+            Some(limit) => (limit - removed).min(RETAIN_BATCH_SIZE as u64) as usize,
+            None => RETAIN_BATCH_SIZE,
+        };
+        let keys: Vec<K> = map
+            .keys_range((start, end.clone()))
+            .take(batch_size)
+            .collect();
+
+        for key in &keys {
+            map.remove(key);
+            on_remove(key);
+        }
+        removed += keys.len() as u64;
+
+        match keys.into_iter().last() {
+            Some(last_key) if removed < limit.unwrap_or(u64::MAX) => {
+                start = Bound::Excluded(last_key)
+            }
+            _ => return removed,
+        }
+    }
+}
+
+/// Removes the entries of the map where keys belong to `key_range`, up to `limit` entries,
+/// calls `on_remove` for every removed key and returns the removed entries.
+/// The stored values are converted with `decode`.
+/// See `BTreeMapStructure::drain_range`.
+pub(crate) fn drain_range_with<K, S, V, M>(
+    map: &mut BTreeMap<K, S, M>,
+    key_range: impl RangeBounds<K>,
+    limit: u64,
+    decode: impl Fn(S) -> V,
+    mut on_remove: impl FnMut(&K),
+) -> Vec<(K, V)>
+where
+    K: Storable + Ord + Clone,
+    S: Storable,
+    M: Memory,
+{
+    let keys: Vec<K> = map
+        .keys_range(key_range)
+        .take(usize::try_from(limit).unwrap_or(usize::MAX))
+        .collect();
+
+    keys.into_iter()
+        .filter_map(|key| {
+            let value = map.remove(&key)?;
+            on_remove(&key);
+            Some((key, decode(value)))
+        })
+        .collect()
+}
+
+/// Returns the entries of the page, in ascending order of the keys.
+/// See `page_range`.
+pub fn page<K, V>(map: &impl BTreeMapIteratorStructure<K, V>, pagination: Pagination) -> Vec<(K, V)>
+where
+    K: Clone,
+{
+    page_range(map, .., pagination)
+}
+
+/// Returns the entries of the page, among the ones where keys belong to the specified range,
+/// in ascending order of the keys.
+///
+/// The skipped entries are found by reading the keys only, so their values are never read.
+pub fn page_range<K, V>(
+    map: &impl BTreeMapIteratorStructure<K, V>,
+    key_range: impl RangeBounds<K>,
+    pagination: Pagination,
+) -> Vec<(K, V)>
+where
+    K: Clone,
+{
+    if pagination.count == 0 {
+        return Vec::new();
+    }
+
+    let end = key_range.end_bound().cloned();
+    let Some(first_key) = map
+        .keys_range((key_range.start_bound().cloned(), end.clone()))
+        .nth(pagination.offset_usize())
+    else {
+        return Vec::new();
+    };
+
+    map.range((Bound::Included(first_key), end))
+        .take(pagination.count_usize())
+        .collect()
 }
 
 /// Map that supports ordered iterator
@@ -73,6 +276,31 @@ pub trait BTreeMapIteratorStructure<K, V> {
     ///
     /// Returns an empty iterator if no smaller key exists.
     fn iter_from_prev_key(&self, bound: &K) -> Self::Iterator<'_>;
+
+    /// Returns an iterator over the keys of the whole collection.
+    ///
+    /// The values are never read, so it should be preferred to `iter`
+    /// when only the keys are needed.
+    fn keys(&self) -> impl Iterator<Item = K>;
+
+    /// Returns an iterator over the keys that belong to the specified range.
+    fn keys_range(&self, key_range: impl RangeBounds<K>) -> impl Iterator<Item = K>;
+
+    /// Returns an iterator over the values of the whole collection.
+    fn values(&self) -> impl Iterator<Item = V>;
+
+    /// Returns an iterator over the values of the entries where keys
+    /// belong to the specified range.
+    fn values_range(&self, key_range: impl RangeBounds<K>) -> impl Iterator<Item = V>;
+
+    /// Returns an iterator over the whole collection in descending order of the keys.
+    fn iter_rev(&self) -> impl Iterator<Item = (K, V)> {
+        self.range_rev(..)
+    }
+
+    /// Returns an iterator over the entries where keys belong to the specified range,
+    /// in descending order of the keys.
+    fn range_rev(&self, key_range: impl RangeBounds<K>) -> impl Iterator<Item = (K, V)>;
 }
 
 impl<K, V, M> BTreeMapStructure<K, V> for BTreeMap<K, V, M>
@@ -124,6 +352,18 @@ where
     fn last_key_value(&self) -> Option<(K, V)> {
         self.last_key_value()
     }
+
+    fn retain(&mut self, f: impl FnMut(&K, &V) -> bool) {
+        retain_in_batches(self, |value| value, f)
+    }
+
+    fn remove_range(&mut self, key_range: impl RangeBounds<K>, limit: Option<u64>) -> u64 {
+        remove_range_in_batches(self, key_range, limit, |_| ())
+    }
+
+    fn drain_range(&mut self, key_range: impl RangeBounds<K>, limit: u64) -> Vec<(K, V)> {
+        drain_range_with(self, key_range, limit, |value| value, |_| ())
+    }
 }
 
 impl<K, V, M> BTreeMapIteratorStructure<K, V> for BTreeMap<K, V, M>
@@ -148,6 +388,80 @@ where
     fn iter_from_prev_key(&self, bound: &K) -> Self::Iterator<'_> {
         BTreeMapIter(self.iter_from_prev_key(bound))
     }
+
+    fn keys(&self) -> impl Iterator<Item = K> {
+        self.keys()
+    }
+
+    fn keys_range(&self, key_range: impl RangeBounds<K>) -> impl Iterator<Item = K> {
+        self.keys_range(key_range)
+    }
+
+    fn values(&self) -> impl Iterator<Item = V> {
+        self.values()
+    }
+
+    fn values_range(&self, key_range: impl RangeBounds<K>) -> impl Iterator<Item = V> {
+        self.values_range(key_range)
+    }
+
+    fn range_rev(&self, key_range: impl RangeBounds<K>) -> impl Iterator<Item = (K, V)> {
+        self.range(key_range).rev().map(|entry| entry.into_pair())
+    }
+}
+
+/// The size of the header of a BTreeMap and of its allocator.
+const BTREEMAP_HEADER_SIZE: u64 = 52 + 48;
+/// The size of the header of a chunk allocated to a node.
+const NODE_CHUNK_HEADER_SIZE: u64 = 16;
+/// The size of the header of a node: magic (3 bytes), version (1 byte), type (1 byte), entries (2 bytes).
+const NODE_HEADER_SIZE: u64 = 7;
+/// The max number of entries of a node.
+const NODE_CAPACITY: u64 = 11;
+/// The size of a node when the size of the keys or of the values is unbounded.
+const UNBOUNDED_NODE_SIZE: u64 = 1024;
+
+/// Returns the size in bytes of a node of a BTreeMap with the given bounds.
+fn node_size(key_bound: StorableBound, value_bound: StorableBound) -> u64 {
+    match (key_bound, value_bound) {
+        (
+            StorableBound::Bounded {
+                max_size: max_key_size,
+                ..
+            },
+            StorableBound::Bounded {
+                max_size: max_value_size,
+                ..
+            },
+        ) => {
+            // every entry stores the size of the key and of the value,
+            // and every node stores the addresses of up to `NODE_CAPACITY + 1` children
+            NODE_HEADER_SIZE
+                + NODE_CAPACITY * (4 + max_key_size as u64 + 4 + max_value_size as u64)
+                + (NODE_CAPACITY + 1) * 8
+        }
+        _ => UNBOUNDED_NODE_SIZE,
+    }
+}
+
+/// The nodes are split only when full, so their actual number can be larger:
+/// the allocated bytes are a lower bound, assuming that every node is full.
+impl<K, V, M> MemoryUsage for BTreeMap<K, V, M>
+where
+    K: Storable + Ord + Clone,
+    V: Storable,
+    M: Memory,
+{
+    fn allocated_bytes(&self) -> u64 {
+        let nodes = self.len().div_ceil(NODE_CAPACITY);
+        BTREEMAP_HEADER_SIZE + nodes * (NODE_CHUNK_HEADER_SIZE + node_size(K::BOUND, V::BOUND))
+    }
+
+    /// The size of the entries can be derived only if the keys and the values have a fixed size.
+    fn used_bytes(&self) -> Option<u64> {
+        let entry_size = fixed_size(K::BOUND)? + fixed_size(V::BOUND)?;
+        Some(self.len() * entry_size)
+    }
 }
 
 pub struct BTreeMapIter<'a, K, V, M>(btreemap::Iter<'a, K, V, M>)
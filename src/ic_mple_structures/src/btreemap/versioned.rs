@@ -1,8 +1,12 @@
-use std::{hash::Hash, ops::RangeBounds};
+use std::ops::{Bound, RangeBounds};
 
 use ic_stable_structures::{BTreeMap, Memory, Storable, btreemap};
 
-use crate::{BTreeMapIteratorStructure, btreemap::BTreeMapStructure, common::Codec};
+use crate::{
+    BTreeMapIteratorStructure,
+    btreemap::{BTreeMapStructure, drain_range_with, remove_range_in_batches, retain_in_batches},
+    common::{Codec, CodecError, MemoryUsage, TryCodec},
+};
 
 /// A versioned BTreeMap.
 pub struct VersionedBTreeMap<K, V, C: Codec<V>, M>
@@ -41,6 +45,85 @@ where
             phantom_v: std::marker::PhantomData,
         }
     }
+
+    /// Returns the value associated with `key`, or an error if it cannot be decoded.
+    pub fn try_get(&self, key: &K) -> Result<Option<V>, CodecError>
+    where
+        C: TryCodec<V>,
+    {
+        self.inner
+            .get(key)
+            .map(<C as TryCodec<V>>::try_decode)
+            .transpose()
+    }
+
+    /// Returns an iterator over the whole collection, where the values that cannot be decoded
+    /// are returned as errors, so that they can be skipped or reported.
+    pub fn try_iter(&self) -> impl Iterator<Item = (K, Result<V, CodecError>)>
+    where
+        C: TryCodec<V>,
+    {
+        self.try_range(..)
+    }
+
+    /// Returns an iterator over the entries where keys belong to the specified range,
+    /// where the values that cannot be decoded are returned as errors.
+    pub fn try_range(
+        &self,
+        key_range: impl RangeBounds<K>,
+    ) -> impl Iterator<Item = (K, Result<V, CodecError>)>
+    where
+        C: TryCodec<V>,
+    {
+        self.inner.range(key_range).map(|entry| {
+            let (key, value) = entry.into_pair();
+            (key, <C as TryCodec<V>>::try_decode(value))
+        })
+    }
+
+    /// Re-encodes in the latest version the stored values that are not already in it,
+    /// according to `Codec::is_latest`, so that the old versions can eventually be dropped from the codec.
+    ///
+    /// The entries are visited in ascending order of the keys, starting after `cursor`,
+    /// until `limit` values are re-encoded.
+    /// Returns the number of re-encoded values and the cursor to pass to the next call,
+    /// or `None` if all the entries have been visited. A zero `limit` returns the given cursor.
+    pub fn migrate_all(&mut self, cursor: Option<K>, limit: u64) -> (u64, Option<K>) {
+        if limit == 0 {
+            return (0, cursor);
+        }
+
+        let start = cursor.map_or(Bound::Unbounded, Bound::Excluded);
+        let mut migrated = Vec::new();
+        let mut last_key = None;
+        let mut completed = true;
+
+        for entry in self.inner.range((start, Bound::Unbounded)) {
+            if migrated.len() as u64 >= limit {
+                completed = false;
+                break;
+            }
+
+            let (key, value) = entry.into_pair();
+            if !C::is_latest(&value) {
+                migrated.push((key.clone(), C::encode(C::decode(value))));
+            }
+            last_key = Some(key);
+        }
+
+        let count = migrated.len() as u64;
+        for (key, value) in migrated {
+            self.inner.insert(key, value);
+        }
+
+        (count, if completed { None } else { last_key })
+    }
+
+    /// Returns the inner map of the encoded values.
+    #[cfg(feature = "cached")]
+    pub(crate) fn inner_mut(&mut self) -> &mut BTreeMap<K, C, M> {
+        &mut self.inner
+    }
 }
 
 impl<K, V, C: Codec<V>, M> BTreeMapStructure<K, V> for VersionedBTreeMap<K, V, C, M>
@@ -97,6 +180,18 @@ where
     fn clear(&mut self) {
         self.inner.clear_new()
     }
+
+    fn retain(&mut self, f: impl FnMut(&K, &V) -> bool) {
+        retain_in_batches(&mut self.inner, C::decode, f)
+    }
+
+    fn remove_range(&mut self, key_range: impl RangeBounds<K>, limit: Option<u64>) -> u64 {
+        remove_range_in_batches(&mut self.inner, key_range, limit, |_| ())
+    }
+
+    fn drain_range(&mut self, key_range: impl RangeBounds<K>, limit: u64) -> Vec<(K, V)> {
+        drain_range_with(&mut self.inner, key_range, limit, C::decode, |_| ())
+    }
 }
 
 pub struct VersionedBTreeMapIter<'a, K, V, C: Codec<V>, M>(
@@ -105,13 +200,11 @@ pub struct VersionedBTreeMapIter<'a, K, V, C: Codec<V>, M>(
 )
 where
     K: Storable + Ord + Clone,
-    V: Storable,
     M: Memory;
 
 impl<K, V, C: Codec<V>, M> Iterator for VersionedBTreeMapIter<'_, K, V, C, M>
 where
     K: Storable + Ord + Clone,
-    V: Storable,
     M: Memory,
 {
     type Item = (K, V);
@@ -126,8 +219,7 @@ where
 
 impl<K, V, C: Codec<V>, M> BTreeMapIteratorStructure<K, V> for VersionedBTreeMap<K, V, C, M>
 where
-    K: Storable + Clone + Send + Sync + Hash + Eq + PartialEq + Ord,
-    V: Storable + Clone + Send + Sync,
+    K: Storable + Ord + Clone,
     M: Memory,
 {
     type Iterator<'a>
@@ -149,17 +241,90 @@ where
             std::marker::PhantomData,
         )
     }
+
+    fn keys(&self) -> impl Iterator<Item = K> {
+        self.inner.keys()
+    }
+
+    fn keys_range(&self, key_range: impl RangeBounds<K>) -> impl Iterator<Item = K> {
+        self.inner.keys_range(key_range)
+    }
+
+    fn values(&self) -> impl Iterator<Item = V> {
+        self.inner.values().map(C::decode)
+    }
+
+    fn values_range(&self, key_range: impl RangeBounds<K>) -> impl Iterator<Item = V> {
+        self.inner.values_range(key_range).map(C::decode)
+    }
+
+    fn range_rev(&self, key_range: impl RangeBounds<K>) -> impl Iterator<Item = (K, V)> {
+        self.inner.range(key_range).rev().map(|entry| {
+            let (key, value) = entry.into_pair();
+            (key, C::decode(value))
+        })
+    }
+}
+
+impl<K, V, C: Codec<V>, M> MemoryUsage for VersionedBTreeMap<K, V, C, M>
+where
+    K: Storable + Ord + Clone,
+    M: Memory,
+{
+    fn allocated_bytes(&self) -> u64 {
+        self.inner.allocated_bytes()
+    }
+
+    fn used_bytes(&self) -> Option<u64> {
+        self.inner.used_bytes()
+    }
 }
 
 #[cfg(test)]
 mod tests {
 
+    use std::borrow::Cow;
+
     use ic_stable_structures::VectorMemory;
+    use ic_stable_structures::storable::Bound as StorableBound;
 
-    use crate::test_utils::{Array, UserCodec, UserV1, UserV2};
+    use crate::RETAIN_BATCH_SIZE;
+    use crate::btreemap::{page, page_range};
+    use crate::common::Pagination;
+    use crate::test_utils::{Array, StringCodec, UserCodec, UserV1, UserV2};
 
     use super::*;
 
+    /// A codec that panics if a value is decoded.
+    #[derive(Clone)]
+    struct PanicOnDecode(u32);
+
+    impl Storable for PanicOnDecode {
+        const BOUND: StorableBound = u32::BOUND;
+
+        fn to_bytes(&self) -> Cow<'_, [u8]> {
+            Cow::Owned(self.0.to_be_bytes().to_vec())
+        }
+
+        fn into_bytes(self) -> Vec<u8> {
+            self.0.to_be_bytes().to_vec()
+        }
+
+        fn from_bytes(bytes: Cow<[u8]>) -> Self {
+            Self(u32::from_bytes(bytes))
+        }
+    }
+
+    impl Codec<u32> for PanicOnDecode {
+        fn decode(_source: PanicOnDecode) -> u32 {
+            panic!("the value should not be decoded")
+        }
+
+        fn encode(dest: u32) -> PanicOnDecode {
+            PanicOnDecode(dest)
+        }
+    }
+
     #[test]
     fn should_use_user_codec() {
         let memory = VectorMemory::default();
@@ -209,6 +374,113 @@ mod tests {
         );
     }
 
+    #[test]
+    fn should_migrate_the_old_versions() {
+        let mut btree_map = BTreeMap::new(VectorMemory::default());
+        btree_map.insert(1u32, UserCodec::V1(UserV1("roger".to_string())));
+        btree_map.insert(
+            2,
+            UserCodec::V2(UserV2 {
+                name: "brian".to_string(),
+                age: Some(42),
+            }),
+        );
+        btree_map.insert(3, UserCodec::V1(UserV1("freddie".to_string())));
+        let mut version_map = VersionedBTreeMap::<u32, UserV2, UserCodec, _>::with_map(btree_map);
+
+        assert_eq!(version_map.migrate_all(None, 1), (1, Some(1)));
+        assert_eq!(version_map.migrate_all(Some(1), 1), (1, None));
+
+        assert!(
+            version_map
+                .inner
+                .values()
+                .all(|user| matches!(user, UserCodec::V2(_)))
+        );
+        assert_eq!(
+            version_map.inner.get(&3),
+            Some(UserCodec::V2(UserV2 {
+                name: "freddie".to_string(),
+                age: None
+            }))
+        );
+        assert_eq!(
+            version_map.get(&1),
+            Some(UserV2 {
+                name: "roger".to_string(),
+                age: None
+            })
+        );
+
+        // nothing left to migrate
+        assert_eq!(version_map.migrate_all(None, 10), (0, None));
+    }
+
+    #[test]
+    fn should_not_migrate_with_a_zero_limit() {
+        let mut btree_map = BTreeMap::new(VectorMemory::default());
+        btree_map.insert(1u32, UserCodec::V1(UserV1("roger".to_string())));
+        let mut version_map = VersionedBTreeMap::<u32, UserV2, UserCodec, _>::with_map(btree_map);
+
+        assert_eq!(version_map.migrate_all(Some(0), 0), (0, Some(0)));
+        assert_eq!(
+            version_map.inner.get(&1),
+            Some(UserCodec::V1(UserV1("roger".to_string())))
+        );
+    }
+
+    #[test]
+    fn should_report_the_values_that_cannot_be_decoded() {
+        let mut btree_map = BTreeMap::new(VectorMemory::default());
+        btree_map.insert(
+            1u32,
+            StringCodec {
+                version: 1,
+                bytes: b"one".to_vec(),
+            },
+        );
+        btree_map.insert(
+            2,
+            StringCodec {
+                version: 2,
+                bytes: b"two".to_vec(),
+            },
+        );
+        btree_map.insert(
+            3,
+            StringCodec {
+                version: 1,
+                bytes: vec![0xff],
+            },
+        );
+        let mut version_map = VersionedBTreeMap::<u32, String, StringCodec, _>::with_map(btree_map);
+        version_map.insert(4, "four".to_string());
+
+        assert_eq!(version_map.try_get(&1), Ok(Some("one".to_string())));
+        assert_eq!(
+            version_map.try_get(&2),
+            Err(CodecError::UnknownVersion("2".to_string()))
+        );
+        assert!(matches!(
+            version_map.try_get(&3),
+            Err(CodecError::InvalidValue(_))
+        ));
+        assert_eq!(version_map.try_get(&5), Ok(None));
+
+        let values = version_map
+            .try_iter()
+            .filter_map(|(_, value)| value.ok())
+            .collect::<Vec<_>>();
+        assert_eq!(values, vec!["one".to_string(), "four".to_string()]);
+
+        let errors = version_map
+            .try_range(2..)
+            .filter(|(_, value)| value.is_err())
+            .map(|(key, _)| key)
+            .collect::<Vec<_>>();
+        assert_eq!(errors, vec![2, 3]);
+    }
+
     #[test]
     fn should_get_and_insert() {
         let mut map = VersionedBTreeMap::<u32, Array<2>, Array<2>, _>::new(VectorMemory::default());
@@ -380,6 +652,29 @@ mod tests {
         assert_eq!(iter.next(), None);
     }
 
+    #[test]
+    fn should_iterate_in_reverse_order() {
+        let mut map = VersionedBTreeMap::<u32, u64, u64, _>::new(VectorMemory::default());
+        for i in 0..300 {
+            map.insert(i * 2, i as u64);
+        }
+
+        let mut expected = map.iter().collect::<Vec<_>>();
+        expected.reverse();
+        assert_eq!(map.iter_rev().collect::<Vec<_>>(), expected);
+
+        let mut expected = map.range(101..=401).collect::<Vec<_>>();
+        expected.reverse();
+        assert_eq!(map.range_rev(101..=401).collect::<Vec<_>>(), expected);
+        assert_eq!(map.range_rev(101..=401).next(), Some((400, 200)));
+
+        assert_eq!(
+            map.range_rev(..10).map(|(key, _)| key).collect::<Vec<_>>(),
+            vec![8, 6, 4, 2, 0]
+        );
+        assert_eq!(map.range_rev(1000..).next(), None);
+    }
+
     #[test]
     fn should_iterate_upper_bound() {
         let mut map = VersionedBTreeMap::<u32, Array<2>, Array<2>, _>::new(VectorMemory::default());
@@ -394,6 +689,254 @@ mod tests {
         assert_eq!(iter.next(), None);
     }
 
+    #[test]
+    fn should_iterate_over_keys_and_values() {
+        let mut map = VersionedBTreeMap::<u32, Array<2>, Array<2>, _>::new(VectorMemory::default());
+
+        assert_eq!(None, map.insert(1, Array([1u8, 1])));
+        assert_eq!(None, map.insert(2, Array([2u8, 1])));
+        assert_eq!(None, map.insert(3, Array([3u8, 1])));
+
+        assert_eq!(map.keys().collect::<Vec<_>>(), vec![1, 2, 3]);
+        assert_eq!(map.keys_range(2..).collect::<Vec<_>>(), vec![2, 3]);
+        assert_eq!(
+            map.values().collect::<Vec<_>>(),
+            vec![Array([1u8, 1]), Array([2u8, 1]), Array([3u8, 1])]
+        );
+        assert_eq!(
+            map.values_range(..2).collect::<Vec<_>>(),
+            vec![Array([1u8, 1])]
+        );
+    }
+
+    #[test]
+    fn should_iterate_over_keys_without_decoding_the_values() {
+        let mut map = VersionedBTreeMap::<u32, u32, PanicOnDecode, _>::new(VectorMemory::default());
+
+        assert_eq!(None, map.insert(1, 10));
+        assert_eq!(None, map.insert(2, 20));
+        assert_eq!(None, map.insert(3, 30));
+
+        assert_eq!(map.keys().collect::<Vec<_>>(), vec![1, 2, 3]);
+        assert_eq!(map.keys_range(..3).collect::<Vec<_>>(), vec![1, 2]);
+    }
+
+    #[test]
+    fn should_retain_all_entries() {
+        let mut map = VersionedBTreeMap::<u32, u32, u32, _>::new(VectorMemory::default());
+        for i in 0..10 {
+            map.insert(i, i * 10);
+        }
+
+        map.retain(|_, _| true);
+
+        assert_eq!(map.len(), 10);
+        assert_eq!(map.keys().collect::<Vec<_>>(), (0..10).collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn should_retain_no_entries() {
+        let mut map = VersionedBTreeMap::<u32, u32, u32, _>::new(VectorMemory::default());
+        for i in 0..10 {
+            map.insert(i, i * 10);
+        }
+
+        map.retain(|_, _| false);
+
+        assert!(map.is_empty());
+    }
+
+    #[test]
+    fn should_retain_a_subset_of_the_entries() {
+        let mut map = VersionedBTreeMap::<u32, u32, u32, _>::new(VectorMemory::default());
+        for i in 0..10 {
+            map.insert(i, i * 10);
+        }
+
+        map.retain(|key, value| *key >= 3 && *value < 70);
+
+        assert_eq!(map.keys().collect::<Vec<_>>(), vec![3, 4, 5, 6]);
+        assert_eq!(map.get(&2), None);
+        assert_eq!(map.get(&3), Some(30));
+    }
+
+    #[test]
+    fn should_retain_entries_in_more_than_one_batch() {
+        let mut map = VersionedBTreeMap::<u32, u32, u32, _>::new(VectorMemory::default());
+        let entries = (RETAIN_BATCH_SIZE * 2 + 10) as u32;
+        for i in 0..entries {
+            map.insert(i, i);
+        }
+
+        let mut visited = 0;
+        map.retain(|key, _| {
+            visited += 1;
+            key % 2 == 0
+        });
+
+        assert_eq!(visited, entries);
+        assert_eq!(map.len(), entries as u64 / 2);
+        assert!(map.keys().all(|key| key % 2 == 0));
+    }
+
+    #[test]
+    fn should_get_and_insert_many() {
+        let mut map = VersionedBTreeMap::<u32, UserV2, UserCodec, _>::new(VectorMemory::default());
+        let user = |name: &str| UserV2 {
+            name: name.to_string(),
+            age: None,
+        };
+
+        assert_eq!(
+            map.insert_many(vec![(3, user("three")), (1, user("one"))]),
+            vec![None, None]
+        );
+        assert_eq!(
+            map.insert_many(vec![(1, user("uno")), (2, user("two"))]),
+            vec![Some(user("one")), None]
+        );
+
+        assert_eq!(
+            map.get_many(&[4, 3, 1, 2]),
+            vec![
+                None,
+                Some(user("three")),
+                Some(user("uno")),
+                Some(user("two"))
+            ]
+        );
+    }
+
+    #[test]
+    fn should_remove_a_range_in_limited_calls() {
+        let mut map = VersionedBTreeMap::<u32, u32, u32, _>::new(VectorMemory::default());
+        for i in 0..1000 {
+            map.insert(i, i);
+        }
+
+        assert_eq!(map.remove_range(..800, Some(500)), 500);
+        assert_eq!(map.first_key_value(), Some((500, 500)));
+
+        assert_eq!(map.remove_range(..800, Some(500)), 300);
+        assert_eq!(map.first_key_value(), Some((800, 800)));
+        assert_eq!(map.len(), 200);
+
+        assert_eq!(map.remove_range(..800, Some(500)), 0);
+    }
+
+    #[test]
+    fn should_remove_a_range_respecting_the_bounds() {
+        let mut map = VersionedBTreeMap::<u32, u32, u32, _>::new(VectorMemory::default());
+        for i in 0..10 {
+            map.insert(i, i);
+        }
+
+        assert_eq!(
+            map.remove_range((Bound::Excluded(2), Bound::Excluded(5)), None),
+            2
+        );
+        assert_eq!(map.keys().collect::<Vec<_>>(), vec![0, 1, 2, 5, 6, 7, 8, 9]);
+
+        assert_eq!(
+            map.remove_range((Bound::Included(5), Bound::Included(7)), None),
+            3
+        );
+        assert_eq!(map.keys().collect::<Vec<_>>(), vec![0, 1, 2, 8, 9]);
+
+        assert_eq!(map.remove_range(.., None), 5);
+        assert!(map.is_empty());
+    }
+
+    #[test]
+    fn should_drain_a_range_respecting_the_limit() {
+        let mut map = VersionedBTreeMap::<u32, u32, u32, _>::new(VectorMemory::default());
+        for i in 0..3000 {
+            map.insert(i, i * 10);
+        }
+
+        let drained = map.drain_range(100..2900, 2500);
+        assert_eq!(drained.len(), 2500);
+        assert_eq!(drained.first(), Some(&(100, 1000)));
+        assert_eq!(drained.last(), Some(&(2599, 25990)));
+        assert!(drained.windows(2).all(|pair| pair[0].0 < pair[1].0));
+        assert_eq!(map.len(), 500);
+
+        let drained = map.drain_range(100..2900, 2500);
+        assert_eq!(drained.len(), 300);
+        assert_eq!(drained.first(), Some(&(2600, 26000)));
+        assert!(map.drain_range(100..2900, 2500).is_empty());
+        assert_eq!(map.keys().filter(|key| *key >= 100).count(), 100);
+    }
+
+    #[test]
+    fn should_pop_the_first_n_entries() {
+        let mut map = VersionedBTreeMap::<u32, UserV2, UserCodec, _>::new(VectorMemory::default());
+        for (key, name) in [(3, "freddie"), (1, "roger"), (2, "brian")] {
+            map.insert(
+                key,
+                UserV2 {
+                    name: name.to_string(),
+                    age: None,
+                },
+            );
+        }
+
+        let names = |entries: Vec<(u32, UserV2)>| {
+            entries
+                .into_iter()
+                .map(|(key, user)| (key, user.name))
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(
+            names(map.pop_first_n(2)),
+            vec![(1, "roger".to_string()), (2, "brian".to_string())]
+        );
+        assert_eq!(names(map.pop_first_n(2)), vec![(3, "freddie".to_string())]);
+        assert!(map.pop_first_n(2).is_empty());
+        assert!(map.is_empty());
+    }
+
+    #[test]
+    fn should_read_pages() {
+        let mut map = VersionedBTreeMap::<u32, u32, PanicOnDecode, _>::new(VectorMemory::default());
+        for i in 0..10 {
+            map.inner.insert(i, PanicOnDecode(i));
+        }
+        let keys =
+            |entries: Vec<(u32, u32)>| entries.into_iter().map(|(key, _)| key).collect::<Vec<_>>();
+
+        // The skipped values are never decoded, the taken ones would panic
+        assert!(page(&map, Pagination::new(3, 0)).is_empty());
+        assert!(page(&map, Pagination::new(10, 5)).is_empty());
+        assert!(page_range(&map, 5.., Pagination::new(5, 1)).is_empty());
+
+        let mut map = VersionedBTreeMap::<u32, u32, u32, _>::new(VectorMemory::default());
+        for i in 0..10 {
+            map.insert(i, i * 10);
+        }
+        assert_eq!(
+            page(&map, Pagination::new(2, 3)),
+            vec![(2, 20), (3, 30), (4, 40)]
+        );
+        assert_eq!(keys(page(&map, Pagination::new(8, 5))), vec![8, 9]);
+        assert_eq!(
+            keys(page(&map, Pagination::new(0, 100).validate(2))),
+            vec![0, 1]
+        );
+        assert_eq!(
+            keys(page_range(&map, 3..7, Pagination::new(1, 10))),
+            vec![4, 5, 6]
+        );
+        assert_eq!(
+            keys(page_range(
+                &map,
+                (Bound::Excluded(3), Bound::Included(7)),
+                Pagination::new(0, 2)
+            )),
+            vec![4, 5]
+        );
+    }
+
     #[test]
     fn test_last_key_value() {
         let mut map = VersionedBTreeMap::<u32, u32, u32, _>::new(VectorMemory::default());
@@ -475,4 +1018,69 @@ mod tests {
             assert_eq!(None, map.get(&1));
         }
     }
+
+    /// A decoded type that is neither `Storable` nor `Send`.
+    #[derive(Debug, Clone, PartialEq)]
+    struct Price {
+        cents: u64,
+        _not_send: std::marker::PhantomData<std::rc::Rc<()>>,
+    }
+
+    impl Price {
+        fn new(cents: u64) -> Self {
+            Self {
+                cents,
+                _not_send: std::marker::PhantomData,
+            }
+        }
+    }
+
+    #[derive(Clone)]
+    struct PriceCodec(u64);
+
+    impl Storable for PriceCodec {
+        const BOUND: StorableBound = u64::BOUND;
+
+        fn to_bytes(&self) -> Cow<'_, [u8]> {
+            Cow::Owned(self.0.to_be_bytes().to_vec())
+        }
+
+        fn into_bytes(self) -> Vec<u8> {
+            self.0.to_be_bytes().to_vec()
+        }
+
+        fn from_bytes(bytes: Cow<[u8]>) -> Self {
+            Self(u64::from_bytes(bytes))
+        }
+    }
+
+    impl Codec<Price> for PriceCodec {
+        fn decode(source: PriceCodec) -> Price {
+            Price::new(source.0)
+        }
+
+        fn encode(dest: Price) -> PriceCodec {
+            PriceCodec(dest.cents)
+        }
+    }
+
+    #[test]
+    fn should_iterate_values_that_are_not_storable() {
+        let mut map = VersionedBTreeMap::<u32, Price, PriceCodec, _>::new(VectorMemory::default());
+        map.insert(1, Price::new(100));
+        map.insert(2, Price::new(250));
+
+        assert_eq!(
+            map.iter().collect::<Vec<_>>(),
+            vec![(1, Price::new(100)), (2, Price::new(250))]
+        );
+        assert_eq!(
+            map.range(2..).collect::<Vec<_>>(),
+            vec![(2, Price::new(250))]
+        );
+        assert_eq!(
+            map.values().map(|price| price.cents).collect::<Vec<_>>(),
+            vec![100, 250]
+        );
+    }
 }
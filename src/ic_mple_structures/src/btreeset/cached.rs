@@ -0,0 +1,187 @@
+use std::hash::Hash;
+use std::ops::RangeBounds;
+
+use ic_stable_structures::{BTreeSet, Memory, Storable};
+
+use crate::{
+    btreeset::{SetIteratorStructure, SetStructure},
+    common::{CacheStats, LruCache},
+};
+
+/// A LRU Cache for the membership checks of a BTreeSet.
+/// Both the keys found in the set and the keys not found are cached.
+pub struct CachedBTreeSet<K, M>
+where
+    K: Storable + Clone + Send + Sync + 'static + Hash + Eq + PartialEq + Ord,
+    M: Memory,
+{
+    inner: BTreeSet<K, M>,
+    /// Whether the cached keys are in the set
+    cache: LruCache<K, bool>,
+}
+
+impl<K, M> CachedBTreeSet<K, M>
+where
+    K: Storable + Clone + Send + Sync + 'static + Hash + Eq + PartialEq + Ord,
+    M: Memory,
+{
+    /// Create new instance of the CachedBTreeSet with a fixed number of max cached elements,
+    /// overwriting any data structures the memory might have
+    /// contained previously.
+    pub fn new(memory: M, max_cache_items: u32) -> Self {
+        Self::with_set(BTreeSet::new(memory), max_cache_items)
+    }
+
+    /// Create new instance of the CachedBTreeSet with a fixed number of max cached elements.
+    ///
+    /// PRECONDITION: the memory is either empty or contains a valid
+    /// stable BTreeSet.
+    pub fn init(memory: M, max_cache_items: u32) -> Self {
+        Self::with_set(BTreeSet::init(memory), max_cache_items)
+    }
+
+    /// Create new instance of the CachedBTreeSet with a fixed number of max cached elements.
+    pub fn with_set(inner: BTreeSet<K, M>, max_cache_items: u32) -> Self {
+        Self {
+            inner,
+            cache: LruCache::new(max_cache_items),
+        }
+    }
+
+    /// Returns the inner collection so that the caller can have a readonly access to it that bypasses the cache.
+    pub fn inner(&self) -> &BTreeSet<K, M> {
+        &self.inner
+    }
+
+    /// Returns the hit, miss, insertion and eviction counters of the cache.
+    /// The counters are not persisted, so they restart from zero after an upgrade.
+    pub fn cache_stats(&self) -> CacheStats {
+        self.cache.stats()
+    }
+
+    /// Resets the counters of the cache.
+    pub fn reset_cache_stats(&self) {
+        self.cache.reset_stats();
+    }
+}
+
+impl<K, M> SetStructure<K> for CachedBTreeSet<K, M>
+where
+    K: Storable + Clone + Send + Sync + 'static + Hash + Eq + PartialEq + Ord,
+    M: Memory,
+{
+    fn insert(&mut self, key: K) -> bool {
+        self.cache.insert(key.clone(), true);
+        self.inner.insert(key)
+    }
+
+    fn remove(&mut self, key: &K) -> bool {
+        self.cache.remove(key);
+        self.inner.remove(key)
+    }
+
+    fn contains(&self, key: &K) -> bool {
+        self.cache
+            .get_or_insert_with(key, |key| Some(self.inner.contains(key)))
+            .unwrap_or_default()
+    }
+
+    fn len(&self) -> u64 {
+        self.inner.len()
+    }
+
+    fn is_empty(&self) -> bool {
+        self.inner.is_empty()
+    }
+
+    fn clear(&mut self) {
+        self.cache.clear();
+        self.inner.clear();
+    }
+}
+
+impl<K, M> SetIteratorStructure<K> for CachedBTreeSet<K, M>
+where
+    K: Storable + Clone + Send + Sync + 'static + Hash + Eq + PartialEq + Ord,
+    M: Memory,
+{
+    type Iterator<'a>
+        = <BTreeSet<K, M> as SetIteratorStructure<K>>::Iterator<'a>
+    where
+        Self: 'a;
+
+    fn iter(&self) -> Self::Iterator<'_> {
+        self.inner.iter()
+    }
+
+    fn range(&self, key_range: impl RangeBounds<K>) -> Self::Iterator<'_> {
+        self.inner.range(key_range)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use ic_stable_structures::VectorMemory;
+
+    use super::*;
+
+    #[test]
+    fn should_cache_the_membership() {
+        let memory = VectorMemory::default();
+        let mut set = CachedBTreeSet::<u64, _>::new(memory.clone(), 10);
+
+        assert!(set.insert(1));
+        assert!(set.contains(&1));
+        assert!(!set.contains(&2));
+        assert!(!set.contains(&2));
+
+        let stats = set.cache_stats();
+        assert_eq!(stats.hits, 2);
+        assert_eq!(stats.misses, 1);
+
+        // the cache is not touched by the changes made through another handle of the same memory
+        let mut other = BTreeSet::<u64, _>::init(memory);
+        other.insert(2);
+        other.remove(&1);
+        assert!(set.contains(&1));
+        assert!(!set.contains(&2));
+    }
+
+    #[test]
+    fn should_invalidate_the_cache_on_removal() {
+        let mut set = CachedBTreeSet::<u64, _>::new(VectorMemory::default(), 10);
+
+        set.insert(1);
+        assert!(set.contains(&1));
+
+        assert!(set.remove(&1));
+        assert!(!set.contains(&1));
+        assert!(!set.remove(&1));
+
+        assert!(set.insert(1));
+        assert!(set.contains(&1));
+
+        set.clear();
+        assert!(!set.contains(&1));
+        assert!(set.is_empty());
+    }
+
+    #[test]
+    fn should_get_the_membership_from_existing_set() {
+        let memory = VectorMemory::default();
+        {
+            let mut set = BTreeSet::new(memory.clone());
+            set.insert(1u64);
+            set.insert(2);
+        }
+
+        let set = CachedBTreeSet::<u64, _>::init(memory, 1);
+        assert!(set.contains(&1));
+        assert!(set.contains(&2));
+        assert!(!set.contains(&3));
+        assert!(set.contains(&1));
+        assert_eq!(set.iter().collect::<Vec<_>>(), vec![1, 2]);
+        assert_eq!(set.inner().len(), 2);
+    }
+}
@@ -0,0 +1,157 @@
+use std::ops::RangeBounds;
+
+use ic_stable_structures::{BTreeSet, Memory, Storable, btreeset};
+
+#[cfg(feature = "cached")]
+mod cached;
+
+#[cfg(feature = "cached")]
+pub use cached::CachedBTreeSet;
+
+pub trait SetStructure<K> {
+    /// Add `key` to the set.
+    /// Returns true if the key was not already in the set.
+    ///
+    /// # Preconditions:
+    ///   - `key.to_bytes().len() <= K::MAX_SIZE`
+    fn insert(&mut self, key: K) -> bool;
+
+    /// Remove `key` from the set.
+    /// Returns true if the key was in the set.
+    fn remove(&mut self, key: &K) -> bool;
+
+    /// True if contains the key.
+    fn contains(&self, key: &K) -> bool;
+
+    /// Count of keys in the set.
+    fn len(&self) -> u64;
+
+    /// Is the set empty.
+    fn is_empty(&self) -> bool;
+
+    /// Remove all keys from the set.
+    fn clear(&mut self);
+}
+
+/// Set that supports ordered iterator
+pub trait SetIteratorStructure<K> {
+    /// Set iterator type
+    type Iterator<'a>: Iterator<Item = K>
+    where
+        Self: 'a;
+
+    /// Returns iterator over the whole collection, in ascending order
+    fn iter(&self) -> Self::Iterator<'_>;
+
+    /// Returns an iterator over the keys that belong to the specified range, in ascending order.
+    fn range(&self, key_range: impl RangeBounds<K>) -> Self::Iterator<'_>;
+}
+
+impl<K, M> SetStructure<K> for BTreeSet<K, M>
+where
+    K: Storable + Ord + Clone,
+    M: Memory,
+{
+    fn insert(&mut self, key: K) -> bool {
+        self.insert(key)
+    }
+
+    fn remove(&mut self, key: &K) -> bool {
+        self.remove(key)
+    }
+
+    fn contains(&self, key: &K) -> bool {
+        self.contains(key)
+    }
+
+    fn len(&self) -> u64 {
+        self.len()
+    }
+
+    fn is_empty(&self) -> bool {
+        self.is_empty()
+    }
+
+    fn clear(&mut self) {
+        self.clear();
+    }
+}
+
+impl<K, M> SetIteratorStructure<K> for BTreeSet<K, M>
+where
+    K: Storable + Ord + Clone,
+    M: Memory,
+{
+    type Iterator<'a>
+        = btreeset::Iter<'a, K, M>
+    where
+        Self: 'a;
+
+    fn iter(&self) -> Self::Iterator<'_> {
+        self.iter()
+    }
+
+    fn range(&self, key_range: impl RangeBounds<K>) -> Self::Iterator<'_> {
+        self.range(key_range)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use ic_stable_structures::VectorMemory;
+
+    use super::*;
+
+    fn check_set<S: SetStructure<u64> + SetIteratorStructure<u64>>(set: &mut S) {
+        assert!(set.is_empty());
+        assert!(!set.contains(&1));
+
+        assert!(set.insert(3));
+        assert!(set.insert(1));
+        assert!(set.insert(2));
+        assert!(!set.insert(2));
+
+        assert_eq!(set.len(), 3);
+        assert!(set.contains(&1));
+        assert!(!set.contains(&4));
+        assert_eq!(set.iter().collect::<Vec<_>>(), vec![1, 2, 3]);
+        assert_eq!(set.range(2..).collect::<Vec<_>>(), vec![2, 3]);
+        assert_eq!(set.range(..2).collect::<Vec<_>>(), vec![1]);
+        assert_eq!(set.range(4..).next(), None);
+
+        assert!(set.remove(&2));
+        assert!(!set.remove(&2));
+        assert!(!set.contains(&2));
+        assert_eq!(set.iter().collect::<Vec<_>>(), vec![1, 3]);
+
+        set.clear();
+        assert!(set.is_empty());
+        assert!(!set.contains(&1));
+        assert_eq!(set.iter().next(), None);
+    }
+
+    #[test]
+    fn should_insert_remove_and_iterate() {
+        check_set(&mut BTreeSet::new(VectorMemory::default()));
+    }
+
+    #[cfg(feature = "cached")]
+    #[test]
+    fn should_insert_remove_and_iterate_with_cache() {
+        check_set(&mut CachedBTreeSet::new(VectorMemory::default(), 2));
+    }
+
+    #[test]
+    fn should_reuse_existing_data_on_init() {
+        let memory = VectorMemory::default();
+        {
+            let mut set = BTreeSet::init(memory.clone());
+            SetStructure::insert(&mut set, 1u64);
+        }
+
+        let set = BTreeSet::<u64, _>::init(memory);
+        assert!(SetStructure::contains(&set, &1));
+        assert_eq!(SetStructure::len(&set), 1);
+    }
+}
@@ -0,0 +1,204 @@
+use std::borrow::Cow;
+
+use ic_stable_structures::Memory;
+
+use crate::{
+    CellStructure, VersionedStableCell,
+    common::{RefCodec, StructureError},
+};
+
+/// A stable cell that keeps the value in the heap.
+///
+/// The value is decoded only once, when the cell is created, so that the following reads
+/// are served from the heap; this is possible because the cell is the only writer of its memory.
+pub struct CachedVersionedCell<T: Clone, C: RefCodec<T>, M: Memory> {
+    inner: VersionedStableCell<T, C, M>,
+    cache: T,
+}
+
+/// A stable cell that keeps the value in the heap.
+pub type CachedCell<T, M> = CachedVersionedCell<T, T, M>;
+
+impl<T: Clone, C: RefCodec<T>, M: Memory> CachedVersionedCell<T, C, M> {
+    /// Initializes a CachedVersionedCell in the specified memory,
+    /// reading the current value into the cache.
+    ///
+    /// PRECONDITION: the memory is either empty or contains a valid
+    /// VersionedStableCell.
+    pub fn init(memory: M, default_value: T) -> Self {
+        Self::with_cell(VersionedStableCell::init(memory, default_value))
+    }
+
+    /// Creates a new CachedVersionedCell in the specified memory,
+    /// overwriting any data structures the memory might have
+    /// contained previously.
+    pub fn new(memory: M, default_value: T) -> Self {
+        Self::with_cell(VersionedStableCell::new(memory, default_value))
+    }
+
+    /// Creates a new CachedVersionedCell from a VersionedStableCell,
+    /// reading its current value into the cache.
+    pub fn with_cell(cell: VersionedStableCell<T, C, M>) -> Self {
+        let cache = cell.get().into_owned();
+        Self { inner: cell, cache }
+    }
+
+    /// Updates the current value in the cell, or returns an error if the stable memory cannot grow.
+    /// The cached value is not changed if the write fails.
+    pub fn try_set(&mut self, value: T) -> Result<(), StructureError> {
+        self.inner.try_set(value.clone())?;
+        self.cache = value;
+        Ok(())
+    }
+}
+
+impl<T: Clone, C: RefCodec<T>, M: Memory> CellStructure<T> for CachedVersionedCell<T, C, M> {
+    fn get(&self) -> Cow<'_, T> {
+        Cow::Borrowed(&self.cache)
+    }
+
+    fn set(&mut self, value: T) {
+        self.inner.set(value.clone());
+        self.cache = value;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::cell::Cell;
+
+    use ic_stable_structures::VectorMemory;
+    use ic_stable_structures::{Storable, storable::Bound};
+
+    use super::*;
+
+    thread_local! {
+        static FROM_BYTES_CALLS: Cell<u64> = const { Cell::new(0) };
+        static DECODE_CALLS: Cell<u64> = const { Cell::new(0) };
+    }
+
+    /// A value that counts how many times it is read from the stable memory.
+    #[derive(Clone, Debug, PartialEq, Eq)]
+    struct Config(u64);
+
+    impl Storable for Config {
+        const BOUND: Bound = u64::BOUND;
+
+        fn to_bytes(&self) -> Cow<'_, [u8]> {
+            Cow::Owned(self.0.to_be_bytes().to_vec())
+        }
+
+        fn into_bytes(self) -> Vec<u8> {
+            self.0.to_be_bytes().to_vec()
+        }
+
+        fn from_bytes(bytes: Cow<[u8]>) -> Self {
+            FROM_BYTES_CALLS.with(|calls| calls.set(calls.get() + 1));
+            Self(u64::from_bytes(bytes))
+        }
+    }
+
+    /// A codec that counts how many times it decodes a value.
+    #[derive(Clone, Debug, PartialEq, Eq)]
+    struct ConfigCodec(u64);
+
+    impl Storable for ConfigCodec {
+        const BOUND: Bound = u64::BOUND;
+
+        fn to_bytes(&self) -> Cow<'_, [u8]> {
+            Cow::Owned(self.0.to_be_bytes().to_vec())
+        }
+
+        fn into_bytes(self) -> Vec<u8> {
+            self.0.to_be_bytes().to_vec()
+        }
+
+        fn from_bytes(bytes: Cow<[u8]>) -> Self {
+            Self(u64::from_bytes(bytes))
+        }
+    }
+
+    impl RefCodec<Config> for ConfigCodec {
+        fn decode_ref<'a>(source: &'a ConfigCodec) -> Cow<'a, Config> {
+            DECODE_CALLS.with(|calls| calls.set(calls.get() + 1));
+            Cow::Owned(Config(source.0))
+        }
+
+        fn encode(dest: Config) -> ConfigCodec {
+            ConfigCodec(dest.0)
+        }
+    }
+
+    #[test]
+    fn should_read_the_stable_memory_only_on_init() {
+        let memory = VectorMemory::default();
+        {
+            let mut cell = CachedCell::init(memory.clone(), Config(1));
+            cell.set(Config(2));
+        }
+
+        FROM_BYTES_CALLS.with(|calls| calls.set(0));
+        let mut cell = CachedCell::init(memory, Config(1));
+        assert_eq!(FROM_BYTES_CALLS.with(Cell::get), 1);
+
+        for _ in 0..10 {
+            assert_eq!(cell.get().as_ref(), &Config(2));
+        }
+        cell.set(Config(3));
+        assert_eq!(cell.get().as_ref(), &Config(3));
+
+        assert_eq!(FROM_BYTES_CALLS.with(Cell::get), 1);
+    }
+
+    #[test]
+    fn should_decode_the_value_only_on_init() {
+        let memory = VectorMemory::default();
+        {
+            let mut cell = VersionedStableCell::<ConfigCodec, ConfigCodec, _>::init(
+                memory.clone(),
+                ConfigCodec(1),
+            );
+            cell.set(ConfigCodec(2));
+        }
+
+        DECODE_CALLS.with(|calls| calls.set(0));
+        let mut cell =
+            CachedVersionedCell::<Config, ConfigCodec, _>::init(memory.clone(), Config(1));
+        for _ in 0..10 {
+            assert_eq!(cell.get().as_ref(), &Config(2));
+        }
+        assert_eq!(DECODE_CALLS.with(Cell::get), 1);
+
+        cell.set(Config(3));
+        assert_eq!(cell.get().as_ref(), &Config(3));
+        assert_eq!(DECODE_CALLS.with(Cell::get), 1);
+
+        // the value is persisted
+        let cell = VersionedStableCell::<ConfigCodec, ConfigCodec, _>::init(memory, ConfigCodec(1));
+        assert_eq!(cell.get().as_ref(), &ConfigCodec(3));
+    }
+
+    #[test]
+    fn should_keep_the_cached_value_if_the_write_fails() {
+        let mut cell =
+            CachedCell::init(crate::test_utils::CappedMemory::new(1), "test".to_string());
+
+        assert!(cell.try_set("a".repeat(70_000)).is_err());
+        assert_eq!(cell.get().as_ref(), "test");
+
+        assert_eq!(cell.try_set("test2".to_string()), Ok(()));
+        assert_eq!(cell.get().as_ref(), "test2");
+    }
+
+    #[test]
+    fn should_erase_existing_data_on_new() {
+        let memory = VectorMemory::default();
+        {
+            let mut cell = CachedCell::init(memory.clone(), 1u64);
+            cell.set(2);
+        }
+
+        let cell = CachedCell::new(memory, 1u64);
+        assert_eq!(*cell.get(), 1);
+    }
+}
@@ -2,8 +2,11 @@ use std::borrow::Cow;
 
 use ic_stable_structures::{Memory, StableCell, Storable};
 
+mod cached;
 mod versioned;
 
+pub use cached::{CachedCell, CachedVersionedCell};
+pub(crate) use versioned::CELL_HEADER_SIZE;
 pub use versioned::VersionedStableCell;
 
 pub trait CellStructure<T: Clone> {
@@ -11,7 +14,25 @@ pub trait CellStructure<T: Clone> {
     fn get(&self) -> Cow<'_, T>;
 
     /// Updates the current value in the cell.
+    /// WARN: this panics if the stable memory cannot grow. The `VersionedStableCell` and
+    /// the `CachedVersionedCell` offer a `try_set` to handle the failure.
     fn set(&mut self, value: T);
+
+    /// Applies `f` to the current value in the cell, writes the updated value back
+    /// and returns the result of `f`.
+    fn update<R>(&mut self, f: impl FnOnce(&mut T) -> R) -> R {
+        let mut value = self.get().into_owned();
+        let result = f(&mut value);
+        self.set(value);
+        result
+    }
+
+    /// Sets the value in the cell and returns the previous one.
+    fn replace(&mut self, value: T) -> T {
+        let previous = self.get().into_owned();
+        self.set(value);
+        previous
+    }
 }
 
 impl<T: Storable + Clone, M: Memory> CellStructure<T> for StableCell<T, M> {
@@ -23,3 +44,32 @@ impl<T: Storable + Clone, M: Memory> CellStructure<T> for StableCell<T, M> {
         self.set(value);
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use ic_stable_structures::VectorMemory;
+
+    use super::*;
+
+    #[test]
+    fn should_update_the_value() {
+        let mut cell = StableCell::init(VectorMemory::default(), 1u64);
+
+        let result = CellStructure::update(&mut cell, |value| {
+            *value += 10;
+            *value * 2
+        });
+
+        assert_eq!(result, 22);
+        assert_eq!(*CellStructure::get(&cell), 11);
+    }
+
+    #[test]
+    fn should_replace_the_value() {
+        let mut cell = StableCell::init(VectorMemory::default(), 1u64);
+
+        assert_eq!(CellStructure::replace(&mut cell, 2), 1);
+        assert_eq!(CellStructure::replace(&mut cell, 3), 2);
+        assert_eq!(*CellStructure::get(&cell), 3);
+    }
+}
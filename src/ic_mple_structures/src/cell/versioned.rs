@@ -1,12 +1,18 @@
 use std::borrow::Cow;
 
-use ic_stable_structures::{Memory, StableCell};
+use ic_stable_structures::{Memory, StableCell, Storable};
 
-use crate::{CellStructure, common::RefCodec};
+use crate::{
+    CellStructure,
+    common::{CodecError, RefCodec, StructureError, TryCodec, ensure_capacity},
+};
+
+/// The size of the header of a stable cell: magic (3 bytes), version (1 byte), value length (4 bytes).
+pub(crate) const CELL_HEADER_SIZE: u64 = 8;
 
 /// A versioned stable cell.
 pub struct VersionedStableCell<T: Clone, C: RefCodec<T>, M: Memory> {
-    inner: StableCell<C, M>,
+    inner: Option<StableCell<C, M>>,
     phantom_t: std::marker::PhantomData<T>,
 }
 
@@ -17,7 +23,7 @@ impl<T: Clone, C: RefCodec<T>, M: Memory> VersionedStableCell<T, C, M> {
     /// VersionedStableCell.
     pub fn init(memory: M, default_value: T) -> Self {
         Self {
-            inner: StableCell::init(memory, C::encode(default_value)),
+            inner: Some(StableCell::init(memory, C::encode(default_value))),
             phantom_t: std::marker::PhantomData,
         }
     }
@@ -27,19 +33,83 @@ impl<T: Clone, C: RefCodec<T>, M: Memory> VersionedStableCell<T, C, M> {
     /// contained previously.
     pub fn new(memory: M, default_value: T) -> Self {
         Self {
-            inner: StableCell::new(memory, C::encode(default_value)),
+            inner: Some(StableCell::new(memory, C::encode(default_value))),
             phantom_t: std::marker::PhantomData,
         }
     }
+
+    /// Initializes a VersionedStableCell in the specified memory, like `init`,
+    /// and re-encodes the stored value in the latest version if it is not already in it.
+    ///
+    /// PRECONDITION: the memory is either empty or contains a valid
+    /// VersionedStableCell.
+    pub fn init_and_migrate(memory: M, default_value: T) -> Self {
+        let mut cell = Self::init(memory, default_value);
+        cell.migrate();
+        cell
+    }
+
+    /// Re-encodes the stored value in the latest version, according to `RefCodec::is_latest`,
+    /// so that it is not decoded from an old version on every `get`.
+    ///
+    /// Returns true if the value was re-encoded and written back.
+    pub fn migrate(&mut self) -> bool {
+        let stored = self.get_inner().get();
+        if C::is_latest(stored) {
+            return false;
+        }
+
+        let migrated = C::encode(C::decode_ref(stored).into_owned());
+        self.mut_inner().set(migrated);
+        true
+    }
+
+    /// Returns the value in the cell, or an error if it cannot be decoded.
+    pub fn try_get(&self) -> Result<T, CodecError>
+    where
+        C: TryCodec<T> + Clone,
+    {
+        <C as TryCodec<T>>::try_decode(self.get_inner().get().clone())
+    }
+
+    /// Updates the current value in the cell, or returns an error if the stable memory cannot grow.
+    ///
+    /// The memory is grown before writing the value, so that the failure can be reported.
+    /// This requires to reload the cell, so the current value is read again from the stable memory.
+    pub fn try_set(&mut self, value: T) -> Result<(), StructureError> {
+        let encoded = C::encode(value);
+        let size = CELL_HEADER_SIZE + encoded.to_bytes().len() as u64;
+
+        if let Some(cell) = self.inner.take() {
+            let memory = cell.into_memory();
+            let result = ensure_capacity(&memory, size);
+            // The memory contains a cell, so the default value is ignored
+            self.inner = Some(StableCell::init(memory, C::from_bytes(encoded.to_bytes())));
+            result?;
+        }
+
+        self.mut_inner().set(encoded);
+        Ok(())
+    }
+
+    #[inline(always)]
+    fn get_inner(&self) -> &StableCell<C, M> {
+        self.inner.as_ref().expect("inner cell is always present")
+    }
+
+    #[inline(always)]
+    fn mut_inner(&mut self) -> &mut StableCell<C, M> {
+        self.inner.as_mut().expect("inner cell is always present")
+    }
 }
 
 impl<T: Clone, C: RefCodec<T>, M: Memory> CellStructure<T> for VersionedStableCell<T, C, M> {
     fn get(&self) -> Cow<'_, T> {
-        C::decode_ref(self.inner.get())
+        C::decode_ref(self.get_inner().get())
     }
 
     fn set(&mut self, value: T) {
-        self.inner.set(C::encode(value));
+        self.mut_inner().set(C::encode(value));
     }
 }
 
@@ -47,7 +117,7 @@ impl<T: Clone, C: RefCodec<T>, M: Memory> CellStructure<T> for VersionedStableCe
 mod tests {
     use ic_stable_structures::VectorMemory;
 
-    use crate::test_utils::{UserCodec, UserV1, UserV2};
+    use crate::test_utils::{CappedMemory, StringCodec, UserCodec, UserV1, UserV2};
 
     use super::*;
 
@@ -99,6 +169,147 @@ mod tests {
         );
     }
 
+    #[test]
+    fn should_migrate_the_stored_value() {
+        let memory = VectorMemory::default();
+
+        {
+            VersionedStableCell::<UserCodec, UserCodec, _>::init(
+                memory.clone(),
+                UserCodec::V1(UserV1("test".to_string())),
+            );
+        }
+        let stored_value = |memory: &VectorMemory| {
+            VersionedStableCell::<UserCodec, UserCodec, _>::init(
+                memory.clone(),
+                UserCodec::V1(UserV1(String::new())),
+            )
+            .get()
+            .into_owned()
+        };
+        assert_eq!(
+            stored_value(&memory),
+            UserCodec::V1(UserV1("test".to_string()))
+        );
+
+        let default = UserV2 {
+            name: "default".to_string(),
+            age: None,
+        };
+        let expected = UserV2 {
+            name: "test".to_string(),
+            age: None,
+        };
+
+        let mut cell =
+            VersionedStableCell::<UserV2, UserCodec, _>::init_and_migrate(memory.clone(), default);
+        assert_eq!(cell.get().as_ref(), &expected);
+        assert_eq!(stored_value(&memory), UserCodec::V2(expected.clone()));
+
+        // the value is already in the latest version
+        assert!(!cell.migrate());
+        assert_eq!(cell.get().as_ref(), &expected);
+    }
+
+    #[test]
+    fn should_report_a_value_that_cannot_be_decoded() {
+        let memory = VectorMemory::default();
+
+        {
+            let cell = VersionedStableCell::<String, StringCodec, _>::init(
+                memory.clone(),
+                "test".to_string(),
+            );
+            assert_eq!(cell.try_get(), Ok("test".to_string()));
+        }
+
+        // a value written by a newer version
+        {
+            let mut raw_cell = VersionedStableCell::<StringCodec, StringCodec, _>::init(
+                memory.clone(),
+                StringCodec {
+                    version: 1,
+                    bytes: vec![],
+                },
+            );
+            raw_cell.set(StringCodec {
+                version: 2,
+                bytes: b"test".to_vec(),
+            });
+        }
+
+        let cell = VersionedStableCell::<String, StringCodec, _>::init(memory, "test".to_string());
+        assert_eq!(
+            cell.try_get(),
+            Err(CodecError::UnknownVersion("2".to_string()))
+        );
+    }
+
+    #[test]
+    fn should_update_and_replace_a_decoded_value() {
+        let memory = VectorMemory::default();
+
+        {
+            let mut v1_cell = VersionedStableCell::<UserCodec, UserCodec, _>::init(
+                memory.clone(),
+                UserCodec::V1(UserV1("test".to_string())),
+            );
+            v1_cell.set(UserCodec::V1(UserV1("test2".to_string())));
+        }
+
+        let mut v2_cell = VersionedStableCell::<UserV2, UserCodec, _>::init(
+            memory,
+            UserV2 {
+                name: "test".to_string(),
+                age: None,
+            },
+        );
+
+        let previous_age = v2_cell.update(|user| user.age.replace(42));
+        assert_eq!(previous_age, None);
+        assert_eq!(
+            v2_cell.get().as_ref(),
+            &UserV2 {
+                name: "test2".to_string(),
+                age: Some(42)
+            }
+        );
+
+        let previous = v2_cell.replace(UserV2 {
+            name: "test3".to_string(),
+            age: None,
+        });
+        assert_eq!(
+            previous,
+            UserV2 {
+                name: "test2".to_string(),
+                age: Some(42)
+            }
+        );
+        assert_eq!(v2_cell.get().name, "test3");
+    }
+
+    #[test]
+    fn should_return_an_error_if_the_memory_cannot_grow() {
+        let mut cell = VersionedStableCell::<String, String, _>::init(
+            CappedMemory::new(1),
+            "test".to_string(),
+        );
+
+        assert_eq!(cell.try_set("test2".to_string()), Ok(()));
+        assert_eq!(cell.get().as_ref(), "test2");
+
+        let too_big = "a".repeat(70_000);
+        assert_eq!(
+            cell.try_set(too_big),
+            Err(StructureError::GrowFailed {
+                current_size: 1,
+                delta: 1
+            })
+        );
+        assert_eq!(cell.get().as_ref(), "test2");
+    }
+
     #[test]
     fn should_reuse_existing_data_on_init() {
         let memory = VectorMemory::default();
@@ -9,6 +9,37 @@ pub trait Codec<D>: Storable {
 
     /// Encodes a `Destination` value into a `Source` value.
     fn encode(dest: D) -> Self;
+
+    /// Returns true if the `Source` value is already encoded in the latest version,
+    /// that is, the one produced by `encode`.
+    ///
+    /// It is used to find the values to migrate; codecs with more than one version should override it,
+    /// by default all values are considered up to date.
+    fn is_latest(_source: &Self) -> bool {
+        true
+    }
+}
+
+/// The error returned when a value cannot be decoded by a `TryCodec`.
+#[derive(Debug, Clone, PartialEq, Eq, thiserror::Error)]
+pub enum CodecError {
+    /// The value is encoded in a version unknown to the codec,
+    /// for example because it was written by a newer version of the canister.
+    #[error("unknown version: {0}")]
+    UnknownVersion(String),
+    /// The value is corrupted.
+    #[error("invalid value: {0}")]
+    InvalidValue(String),
+}
+
+/// A codec for a data type that can fail to decode a value.
+///
+/// The `Source` type should be able to hold any stored value, e.g. by keeping
+/// the raw bytes of the unknown versions, so that `Storable::from_bytes` never panics
+/// and the errors are reported by `try_decode` instead.
+pub trait TryCodec<D>: Storable {
+    /// Decodes a `Source` value into a `Destination` value.
+    fn try_decode(source: Self) -> Result<D, CodecError>;
 }
 
 /// A codec for a data type.
@@ -18,6 +49,15 @@ pub trait RefCodec<D: Clone>: Storable {
 
     /// Encodes a `Destination` value into a `Source` value.
     fn encode(dest: D) -> Self;
+
+    /// Returns true if the `Source` value is already encoded in the latest version,
+    /// that is, the one produced by `encode`.
+    ///
+    /// It is used to find the values to migrate; codecs with more than one version should override it,
+    /// by default all values are considered up to date.
+    fn is_latest(_source: &Self) -> bool {
+        true
+    }
 }
 
 impl<D: Storable> Codec<D> for D {
@@ -30,6 +70,12 @@ impl<D: Storable> Codec<D> for D {
     }
 }
 
+impl<D: Storable> TryCodec<D> for D {
+    fn try_decode(source: D) -> Result<D, CodecError> {
+        Ok(source)
+    }
+}
+
 impl<D: Storable + Clone> RefCodec<D> for D {
     fn decode_ref<'a>(source: &'a D) -> Cow<'a, D> {
         Cow::Borrowed(source)
@@ -39,3 +85,53 @@ impl<D: Storable + Clone> RefCodec<D> for D {
         dest
     }
 }
+
+#[cfg(all(test, feature = "derive"))]
+mod tests {
+    use super::*;
+    use crate::test_utils::{UserCodec, UserV1, UserV2};
+
+    fn user(name: &str, age: Option<u8>) -> UserV2 {
+        UserV2 {
+            name: name.to_string(),
+            age,
+        }
+    }
+
+    #[test]
+    fn should_derive_the_versioned_codec() {
+        let v1 = UserCodec::V1(UserV1("roger".to_string()));
+        let v2 = UserCodec::V2(user("brian", Some(42)));
+
+        assert!(!<UserCodec as Codec<UserV2>>::is_latest(&v1));
+        assert!(<UserCodec as Codec<UserV2>>::is_latest(&v2));
+
+        assert_eq!(
+            <UserCodec as Codec<UserV2>>::decode(v1.clone()),
+            user("roger", None)
+        );
+        assert_eq!(
+            <UserCodec as Codec<UserV2>>::decode(v2.clone()),
+            user("brian", Some(42))
+        );
+        assert_eq!(
+            <UserCodec as Codec<UserV2>>::encode(user("john", None)),
+            UserCodec::V2(user("john", None))
+        );
+
+        assert!(matches!(
+            <UserCodec as RefCodec<UserV2>>::decode_ref(&v1),
+            Cow::Owned(value) if value == user("roger", None)
+        ));
+        assert!(matches!(
+            <UserCodec as RefCodec<UserV2>>::decode_ref(&v2),
+            Cow::Borrowed(value) if value == &user("brian", Some(42))
+        ));
+        assert!(!<UserCodec as RefCodec<UserV2>>::is_latest(&v1));
+        assert!(<UserCodec as RefCodec<UserV2>>::is_latest(&v2));
+        assert_eq!(
+            <UserCodec as RefCodec<UserV2>>::encode(user("john", None)),
+            UserCodec::V2(user("john", None))
+        );
+    }
+}
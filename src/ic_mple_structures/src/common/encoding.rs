@@ -0,0 +1,238 @@
+use std::borrow::Cow;
+use std::ops::{Deref, DerefMut};
+
+use candid::{CandidType, Decode, Encode};
+use ic_stable_structures::{Storable, storable::Bound};
+use serde::de::DeserializeOwned;
+
+use crate::common::{Codec, RefCodec};
+
+/// Implements the traits shared by all the encoding wrappers.
+macro_rules! encoding_wrapper {
+    ($name:ident, $($bound:tt)+) => {
+        impl<T: $($bound)+> $name<T> {
+            /// Returns the wrapped value.
+            pub fn into_inner(self) -> T {
+                self.0
+            }
+        }
+
+        impl<T: $($bound)+> Deref for $name<T> {
+            type Target = T;
+
+            fn deref(&self) -> &T {
+                &self.0
+            }
+        }
+
+        impl<T: $($bound)+> DerefMut for $name<T> {
+            fn deref_mut(&mut self) -> &mut T {
+                &mut self.0
+            }
+        }
+
+        impl<T: $($bound)+> From<T> for $name<T> {
+            fn from(value: T) -> Self {
+                Self(value)
+            }
+        }
+
+        impl<T: $($bound)+> Codec<T> for $name<T> {
+            fn decode(source: Self) -> T {
+                source.0
+            }
+
+            fn encode(dest: T) -> Self {
+                Self(dest)
+            }
+        }
+
+        impl<T: Clone + $($bound)+> RefCodec<T> for $name<T> {
+            fn decode_ref<'a>(source: &'a Self) -> Cow<'a, T> {
+                Cow::Borrowed(&source.0)
+            }
+
+            fn encode(dest: T) -> Self {
+                Self(dest)
+            }
+        }
+    };
+}
+
+/// A value stored with the Candid encoding.
+///
+/// The Candid encoding includes the type of the value, so it is larger than the other encodings
+/// but it can be decoded without knowing the type.
+#[derive(Debug, Clone, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Candid<T: CandidType + DeserializeOwned>(pub T);
+
+impl<T: CandidType + DeserializeOwned> Storable for Candid<T> {
+    const BOUND: Bound = Bound::Unbounded;
+
+    fn to_bytes(&self) -> Cow<'_, [u8]> {
+        Encode!(&self.0)
+            .expect("failed to encode the value with Candid")
+            .into()
+    }
+
+    fn into_bytes(self) -> Vec<u8> {
+        Encode!(&self.0).expect("failed to encode the value with Candid")
+    }
+
+    fn from_bytes(bytes: Cow<[u8]>) -> Self {
+        Self(Decode!(&bytes, T).expect("failed to decode the value with Candid"))
+    }
+}
+
+encoding_wrapper!(Candid, CandidType + DeserializeOwned);
+
+/// A value stored with the CBOR encoding.
+///
+/// The CBOR encoding is compact and fast, so it fits the values that are written often.
+#[cfg(feature = "cbor")]
+#[derive(Debug, Clone, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Cbor<T: serde::Serialize + DeserializeOwned>(pub T);
+
+#[cfg(feature = "cbor")]
+impl<T: serde::Serialize + DeserializeOwned> Storable for Cbor<T> {
+    const BOUND: Bound = Bound::Unbounded;
+
+    fn to_bytes(&self) -> Cow<'_, [u8]> {
+        let mut bytes = Vec::new();
+        ciborium::into_writer(&self.0, &mut bytes).expect("failed to encode the value with CBOR");
+        bytes.into()
+    }
+
+    fn into_bytes(self) -> Vec<u8> {
+        self.to_bytes().into_owned()
+    }
+
+    fn from_bytes(bytes: Cow<[u8]>) -> Self {
+        Self(ciborium::from_reader(bytes.as_ref()).expect("failed to decode the value with CBOR"))
+    }
+}
+
+#[cfg(feature = "cbor")]
+encoding_wrapper!(Cbor, serde::Serialize + DeserializeOwned);
+
+/// A value stored with the JSON encoding.
+///
+/// The JSON encoding is the largest one, but the stored values are human readable.
+#[cfg(feature = "json")]
+#[derive(Debug, Clone, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Json<T: serde::Serialize + DeserializeOwned>(pub T);
+
+#[cfg(feature = "json")]
+impl<T: serde::Serialize + DeserializeOwned> Storable for Json<T> {
+    const BOUND: Bound = Bound::Unbounded;
+
+    fn to_bytes(&self) -> Cow<'_, [u8]> {
+        serde_json::to_vec(&self.0)
+            .expect("failed to encode the value with JSON")
+            .into()
+    }
+
+    fn into_bytes(self) -> Vec<u8> {
+        serde_json::to_vec(&self.0).expect("failed to encode the value with JSON")
+    }
+
+    fn from_bytes(bytes: Cow<[u8]>) -> Self {
+        Self(serde_json::from_slice(&bytes).expect("failed to decode the value with JSON"))
+    }
+}
+
+#[cfg(feature = "json")]
+encoding_wrapper!(Json, serde::Serialize + DeserializeOwned);
+
+#[cfg(test)]
+mod tests {
+
+    use candid::Deserialize;
+    use ic_stable_structures::{BTreeMap, VectorMemory};
+
+    use super::*;
+    use crate::btreemap::{BTreeMapStructure, VersionedBTreeMap};
+
+    #[derive(Debug, Clone, PartialEq, Eq, CandidType, Deserialize, serde::Serialize)]
+    struct Order {
+        id: u64,
+        customer: String,
+        items: Vec<String>,
+        paid: bool,
+    }
+
+    fn order() -> Order {
+        Order {
+            id: 42,
+            customer: "alice".to_string(),
+            items: vec!["book".to_string(), "pen".to_string()],
+            paid: true,
+        }
+    }
+
+    fn check_roundtrip<S: Storable + Deref<Target = Order> + From<Order>>() -> usize {
+        let value = S::from(order());
+        let bytes = value.to_bytes().into_owned();
+        assert_eq!(*S::from_bytes(Cow::Borrowed(&bytes)), order());
+        assert_eq!(*S::from_bytes(Cow::Owned(value.into_bytes())), order());
+        bytes.len()
+    }
+
+    #[test]
+    fn should_roundtrip_candid() {
+        check_roundtrip::<Candid<Order>>();
+    }
+
+    #[cfg(feature = "cbor")]
+    #[test]
+    fn should_roundtrip_cbor() {
+        check_roundtrip::<Cbor<Order>>();
+    }
+
+    #[cfg(feature = "json")]
+    #[test]
+    fn should_roundtrip_json() {
+        check_roundtrip::<Json<Order>>();
+
+        // The stored bytes are human readable
+        assert_eq!(
+            Json(order()).to_bytes().as_ref(),
+            br#"{"id":42,"customer":"alice","items":["book","pen"],"paid":true}"#
+        );
+    }
+
+    /// Documents the size of the encodings of the same value:
+    /// CBOR is the most compact one, JSON repeats the field names as text
+    /// and Candid starts with the description of the type.
+    #[cfg(all(feature = "cbor", feature = "json"))]
+    #[test]
+    fn should_compare_the_encoded_sizes() {
+        let candid = check_roundtrip::<Candid<Order>>();
+        let cbor = check_roundtrip::<Cbor<Order>>();
+        let json = check_roundtrip::<Json<Order>>();
+
+        assert!(cbor < json, "cbor: {cbor}, json: {json}");
+        assert!(cbor < candid, "cbor: {cbor}, candid: {candid}");
+    }
+
+    #[test]
+    fn should_deref_and_convert() {
+        let mut value = Candid::from(order());
+        assert_eq!(value.id, 42);
+
+        value.paid = false;
+        assert!(!value.into_inner().paid);
+    }
+
+    #[test]
+    fn should_be_used_as_codec() {
+        let memory = VectorMemory::default();
+        let mut map = VersionedBTreeMap::<u32, Order, Candid<Order>, _>::new(memory.clone());
+
+        map.insert(1, order());
+        assert_eq!(map.get(&1), Some(order()));
+
+        let raw_map = BTreeMap::<u32, Candid<Order>, _>::init(memory);
+        assert_eq!(raw_map.get(&1), Some(Candid(order())));
+    }
+}
@@ -0,0 +1,145 @@
+use ic_stable_structures::{Memory, log::WriteError, storable::Bound};
+
+use crate::ringbuffer::RingBufferError;
+
+/// The size of a WASM page in bytes.
+const WASM_PAGE_SIZE: u64 = 65536;
+
+/// The error returned when a structure cannot be written.
+#[derive(Debug, Clone, PartialEq, Eq, thiserror::Error)]
+pub enum StructureError {
+    /// The stable memory cannot grow, e.g. because it is exhausted.
+    #[error("failed to grow the memory of {current_size} pages by {delta} pages")]
+    GrowFailed { current_size: u64, delta: u64 },
+    /// The data found in the stable memory is not consistent.
+    #[error("corrupted data: {0}")]
+    Corrupted(String),
+    /// A memory with the same name has already been allocated.
+    #[error("the memory {0} is already allocated")]
+    MemoryAlreadyAllocated(String),
+    /// The memory id to allocate is already used outside of the memory registry.
+    #[error("the memory id {0} is already in use")]
+    MemoryIdInUse(u8),
+    /// All the memory ids have been allocated.
+    #[error("no memory id is available")]
+    MemoryIdsExhausted,
+    /// The timestamp of a sample is older than the last stored one.
+    #[error("the timestamp {timestamp} is older than the last one {last}")]
+    OutOfOrder { last: u64, timestamp: u64 },
+    /// The state of a ring buffer is not consistent.
+    #[error(transparent)]
+    RingBuffer(#[from] RingBufferError),
+}
+
+impl From<WriteError> for StructureError {
+    fn from(error: WriteError) -> Self {
+        match error {
+            WriteError::GrowFailed {
+                current_size,
+                delta,
+            } => StructureError::GrowFailed {
+                current_size,
+                delta,
+            },
+        }
+    }
+}
+
+/// Grows the memory, if needed, so that it can hold at least `size` bytes.
+pub(crate) fn ensure_capacity(memory: &impl Memory, size: u64) -> Result<(), StructureError> {
+    let required_pages = size.div_ceil(WASM_PAGE_SIZE);
+    let current_size = memory.size();
+    if current_size < required_pages {
+        let delta = required_pages - current_size;
+        if memory.grow(delta) < 0 {
+            return Err(StructureError::GrowFailed {
+                current_size,
+                delta,
+            });
+        }
+    }
+    Ok(())
+}
+
+/// Returns the size in bytes of a slot of a stable vector of values with the given bound,
+/// that is, the max size of the value plus the bytes used to store its actual size.
+pub(crate) fn vec_slot_size(bound: Bound) -> u64 {
+    match bound {
+        Bound::Bounded {
+            max_size,
+            is_fixed_size: true,
+        } => max_size as u64,
+        Bound::Bounded { max_size, .. } => {
+            let size_len = if max_size <= u8::MAX as u32 {
+                1
+            } else if max_size <= u16::MAX as u32 {
+                2
+            } else {
+                4
+            };
+            max_size as u64 + size_len
+        }
+        Bound::Unbounded => panic!("the values of a stable vector must be bounded"),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use ic_stable_structures::VectorMemory;
+
+    use super::*;
+    use crate::test_utils::CappedMemory;
+
+    #[test]
+    fn should_grow_the_memory_if_needed() {
+        let memory = VectorMemory::default();
+
+        ensure_capacity(&memory, 1).unwrap();
+        assert_eq!(memory.size(), 1);
+
+        ensure_capacity(&memory, WASM_PAGE_SIZE).unwrap();
+        assert_eq!(memory.size(), 1);
+
+        ensure_capacity(&memory, WASM_PAGE_SIZE * 2 + 1).unwrap();
+        assert_eq!(memory.size(), 3);
+    }
+
+    #[test]
+    fn should_fail_if_the_memory_cannot_grow() {
+        let memory = CappedMemory::new(2);
+
+        assert_eq!(
+            ensure_capacity(&memory, WASM_PAGE_SIZE * 3),
+            Err(StructureError::GrowFailed {
+                current_size: 0,
+                delta: 3
+            })
+        );
+        assert_eq!(memory.size(), 0);
+    }
+
+    #[test]
+    fn should_compute_the_slot_size() {
+        assert_eq!(
+            vec_slot_size(Bound::Bounded {
+                max_size: 8,
+                is_fixed_size: true
+            }),
+            8
+        );
+        assert_eq!(
+            vec_slot_size(Bound::Bounded {
+                max_size: 9,
+                is_fixed_size: false
+            }),
+            10
+        );
+        assert_eq!(
+            vec_slot_size(Bound::Bounded {
+                max_size: 1000,
+                is_fixed_size: false
+            }),
+            1002
+        );
+    }
+}
@@ -1,11 +1,81 @@
 use std::hash::Hash;
-use std::{cell::RefCell, convert::Infallible};
+use std::{
+    cell::{Cell, RefCell},
+    convert::Infallible,
+};
 
+use candid::CandidType;
 use schnellru::{ByLength, LruMap};
 
+/// The usage statistics of a `LruCache`.
+///
+/// The counters are kept in the heap only, so they are reset when the canister is upgraded.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, CandidType)]
+pub struct CacheStats {
+    /// The number of lookups that found the key in the cache.
+    pub hits: u64,
+    /// The number of lookups that did not find the key in the cache.
+    pub misses: u64,
+    /// The number of values put into the cache.
+    pub insertions: u64,
+    /// The number of values removed from the cache to make room for new ones.
+    pub evictions: u64,
+}
+
+/// The weight of a cached value, used by the caches bounded by a byte budget.
+///
+/// The weight should approximate the heap size of the value in bytes;
+/// by default every value weighs 1.
+pub trait CacheWeight {
+    /// Returns the weight of the value.
+    fn weight(&self) -> u64 {
+        1
+    }
+}
+
+macro_rules! fixed_cache_weight {
+    ($($ty:ty),*) => {
+        $(
+            impl CacheWeight for $ty {
+                fn weight(&self) -> u64 {
+                    std::mem::size_of::<$ty>() as u64
+                }
+            }
+        )*
+    };
+}
+
+fixed_cache_weight!(bool, u8, u16, u32, u64, u128, i8, i16, i32, i64, i128);
+
+impl CacheWeight for String {
+    fn weight(&self) -> u64 {
+        self.len() as u64
+    }
+}
+
+impl CacheWeight for Vec<u8> {
+    fn weight(&self) -> u64 {
+        self.len() as u64
+    }
+}
+
+/// The bound of a cache.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CacheCapacity {
+    /// The cache holds at most the given number of entries.
+    Entries(u32),
+    /// The cache holds entries until the total `CacheWeight` of the values exceeds the given budget.
+    Bytes(u64),
+}
+
 /// A wrapper around `LruMap`.
 pub struct LruCache<K, V> {
     inner: RefCell<LruMap<K, V>>,
+    stats: Cell<CacheStats>,
+    /// The weight function and the budget of a cache bounded by weight
+    weigher: Option<(fn(&V) -> u64, u64)>,
+    /// The total weight of the cached values, tracked only if the cache is bounded by weight
+    weight: Cell<u64>,
 }
 
 impl<K, V> LruCache<K, V>
@@ -18,9 +88,48 @@ where
         Self {
             // Creating an inner LruMap with a fixed hasher
             inner: RefCell::new(LruMap::<K, V>::with_seed(ByLength::new(cap), [0, 1, 3, 4])),
+            stats: Cell::new(CacheStats::default()),
+            weigher: None,
+            weight: Cell::new(0),
+        }
+    }
+
+    /// Creates a new `LRU` cache with the given bound.
+    ///
+    /// When the cache is bounded by `CacheCapacity::Bytes`, the least recently used entries
+    /// are evicted until the total weight of the values is within the budget.
+    pub fn with_capacity(capacity: CacheCapacity) -> Self
+    where
+        V: CacheWeight,
+    {
+        match capacity {
+            CacheCapacity::Entries(cap) => Self::new(cap),
+            CacheCapacity::Bytes(budget) => Self {
+                weigher: Some((V::weight, budget)),
+                ..Self::new(u32::MAX)
+            },
+        }
+    }
+
+    /// Returns the total weight of the cached values if the cache is bounded by weight,
+    /// otherwise the number of the cached values.
+    pub fn weight(&self) -> u64 {
+        match self.weigher {
+            Some(_) => self.weight.get(),
+            None => self.inner.borrow().len() as u64,
         }
     }
 
+    /// Returns the usage statistics of the cache.
+    pub fn stats(&self) -> CacheStats {
+        self.stats.get()
+    }
+
+    /// Resets the usage statistics of the cache.
+    pub fn reset_stats(&self) {
+        self.stats.set(CacheStats::default());
+    }
+
     // /// Returns the number of key-value pairs that are currently in the the cache.
     // pub fn len(&self) -> usize {
     //     self.inner.borrow().len()
@@ -61,7 +170,7 @@ where
         let val = f(key)?;
         if let Some(val) = val.as_ref() {
             let val_clone = val.clone();
-            self.inner.borrow_mut().insert(key.clone(), val_clone);
+            self.insert(key.clone(), val_clone);
         }
         Ok(val)
     }
@@ -69,29 +178,130 @@ where
     /// Puts a key-value pair into cache. If the key already exists in the cache,
     /// then it updates the key's value.
     pub fn insert(&self, key: K, value: V) {
-        self.inner.borrow_mut().insert(key, value);
+        self.insert_into(&mut self.inner.borrow_mut(), key, value);
+    }
+
+    /// Returns the values of the keys, in the same order of the keys.
+    /// The values not in the cache are computed using the provided closure and inserted into the cache.
+    ///
+    /// The cache is borrowed only once for all the keys.
+    pub fn get_or_insert_many_with<F>(&self, keys: &[K], mut f: F) -> Vec<Option<V>>
+    where
+        F: FnMut(&K) -> Option<V>,
+    {
+        let mut inner = self.inner.borrow_mut();
+        keys.iter()
+            .map(|key| {
+                if let Some(value) = inner.get(key) {
+                    self.update_stats(|stats| stats.hits += 1);
+                    return Some(value.clone());
+                }
+                self.update_stats(|stats| stats.misses += 1);
+                let value = f(key);
+                if let Some(value) = value.as_ref() {
+                    self.insert_into(&mut inner, key.clone(), value.clone());
+                }
+                value
+            })
+            .collect()
+    }
+
+    /// Puts the key-value pairs into cache, borrowing it only once.
+    /// If a key already exists in the cache, then it updates the key's value.
+    pub fn insert_many(&self, entries: impl IntoIterator<Item = (K, V)>) {
+        let mut inner = self.inner.borrow_mut();
+        for (key, value) in entries {
+            self.insert_into(&mut inner, key, value);
+        }
     }
 
-    /// Returns whether the key is in the cache
+    /// Returns whether the key is in the cache.
+    /// It is not counted as a hit or a miss in the statistics.
     pub fn contains_key(&self, key: &K) -> bool {
         self.inner.borrow_mut().get(key).is_some()
     }
 
+    /// Returns the value of the key in the cache without moving it to the head of the LRU list.
+    /// It is not counted as a hit or a miss in the statistics.
+    pub fn peek(&self, key: &K) -> Option<V> {
+        self.inner.borrow().peek(key).cloned()
+    }
+
+    /// Returns the cached entries, from the most to the least recently used one.
+    ///
+    /// The entries are copied, so it is meant for diagnostics only.
+    pub fn iter(&self) -> impl Iterator<Item = (K, V)> {
+        self.inner
+            .borrow()
+            .iter()
+            .map(|(key, value)| (key.clone(), value.clone()))
+            .collect::<Vec<_>>()
+            .into_iter()
+    }
+
     /// Returns the value of the key in the cache or None if it is not present in the cache.
     /// Moves the key to the head of the LRU list if it exists.
     pub fn get(&self, key: &K) -> Option<V> {
-        self.inner.borrow_mut().get(key).cloned()
+        let value = self.inner.borrow_mut().get(key).cloned();
+        if value.is_some() {
+            self.update_stats(|stats| stats.hits += 1);
+        } else {
+            self.update_stats(|stats| stats.misses += 1);
+        }
+        value
     }
 
     /// Removes an element from the cache.
     pub fn remove(&self, key: &K) -> Option<V> {
-        self.inner.borrow_mut().remove(key)
+        let value = self.inner.borrow_mut().remove(key);
+        if let (Some((weigher, _)), Some(value)) = (self.weigher, value.as_ref()) {
+            self.weight.set(self.weight.get() - weigher(value));
+        }
+        value
     }
 
     /// Puts a key-value pair into cache. If the key already exists in the cache,
     /// then it updates the key's value.
     pub fn clear(&self) {
-        self.inner.borrow_mut().clear()
+        self.inner.borrow_mut().clear();
+        self.weight.set(0);
+    }
+
+    /// Inserts a key-value pair into the borrowed cache, counting the insertion
+    /// and the evicted entries.
+    fn insert_into(&self, inner: &mut LruMap<K, V>, key: K, value: V) {
+        let len = inner.len() + usize::from(inner.peek(&key).is_none());
+        let mut evictions = 0;
+
+        if let Some((weigher, budget)) = self.weigher {
+            let mut weight = self.weight.get() + weigher(&value);
+            if let Some(old_value) = inner.peek(&key) {
+                weight -= weigher(old_value);
+            }
+            inner.insert(key, value);
+
+            while weight > budget {
+                let Some((_, evicted)) = inner.pop_oldest() else {
+                    break;
+                };
+                weight -= weigher(&evicted);
+                evictions += 1;
+            }
+            self.weight.set(weight);
+        } else {
+            inner.insert(key, value);
+            evictions = len.saturating_sub(inner.len()) as u64;
+        }
+        self.update_stats(|stats| {
+            stats.insertions += 1;
+            stats.evictions += evictions;
+        });
+    }
+
+    fn update_stats(&self, f: impl FnOnce(&mut CacheStats)) {
+        let mut stats = self.stats.get();
+        f(&mut stats);
+        self.stats.set(stats);
     }
 }
 
@@ -115,4 +325,132 @@ mod tests {
         assert_eq!(cache.get(&0u64), None);
         assert!(!cache.contains_key(&0u64));
     }
+
+    #[test]
+    fn should_get_or_insert_many() {
+        let cache = LruCache::<u64, u64>::new(100);
+        cache.insert(1, 10);
+
+        let mut computed = vec![];
+        let values = cache.get_or_insert_many_with(&[3, 1, 2], |key| {
+            computed.push(*key);
+            (*key != 2).then_some(key * 100)
+        });
+
+        assert_eq!(values, vec![Some(300), Some(10), None]);
+        assert_eq!(computed, vec![3, 2]);
+        assert_eq!(cache.get(&3), Some(300));
+        assert!(!cache.contains_key(&2));
+    }
+
+    #[test]
+    fn should_count_hits_misses_insertions_and_evictions() {
+        let cache = LruCache::<u64, u64>::new(2);
+
+        cache.insert(1, 10);
+        cache.insert(2, 20);
+        cache.insert(2, 21);
+        assert_eq!(cache.get(&1), Some(10));
+        assert_eq!(cache.get(&3), None);
+
+        // inserting a third key evicts the least recently used one
+        cache.insert(3, 30);
+        assert_eq!(cache.get(&2), None);
+        assert_eq!(cache.get_or_insert_with(&1, |_| None), Some(10));
+        assert_eq!(
+            cache.get_or_insert_many_with(&[3, 4], |_| Some(40)),
+            vec![Some(30), Some(40)]
+        );
+
+        assert_eq!(
+            cache.stats(),
+            CacheStats {
+                hits: 3,
+                misses: 3,
+                insertions: 5,
+                evictions: 2,
+            }
+        );
+
+        cache.reset_stats();
+        assert_eq!(cache.stats(), CacheStats::default());
+    }
+
+    #[test]
+    fn should_peek_without_promoting() {
+        let cache = LruCache::<u64, u64>::new(2);
+        cache.insert(1, 10);
+        cache.insert(2, 20);
+
+        assert_eq!(cache.peek(&1), Some(10));
+        assert_eq!(cache.peek(&3), None);
+        assert_eq!(
+            cache.stats(),
+            CacheStats {
+                insertions: 2,
+                ..Default::default()
+            }
+        );
+
+        // 1 is still the least recently used key
+        cache.insert(3, 30);
+        assert!(!cache.contains_key(&1));
+        assert_eq!(cache.iter().collect::<Vec<_>>(), vec![(3, 30), (2, 20)]);
+    }
+
+    #[test]
+    fn should_evict_by_weight() {
+        let cache = LruCache::<u64, Vec<u8>>::with_capacity(CacheCapacity::Bytes(100));
+
+        cache.insert(1, vec![0; 40]);
+        cache.insert(2, vec![0; 30]);
+        cache.insert(3, vec![0; 20]);
+        assert_eq!(cache.weight(), 90);
+
+        // 1 becomes the most recently used key
+        assert!(cache.get(&1).is_some());
+
+        // 2 and 3 are evicted to make room for 50 bytes
+        cache.insert(4, vec![0; 50]);
+        assert_eq!(
+            cache.iter().map(|(key, _)| key).collect::<Vec<_>>(),
+            vec![4, 1]
+        );
+        assert_eq!(cache.weight(), 90);
+        assert_eq!(cache.stats().evictions, 2);
+
+        // replacing a value updates the weight
+        cache.insert(1, vec![0; 10]);
+        assert_eq!(cache.weight(), 60);
+
+        cache.remove(&4);
+        assert_eq!(cache.weight(), 10);
+
+        // a value heavier than the budget is not cached
+        cache.insert(5, vec![0; 101]);
+        assert!(cache.is_empty());
+        assert_eq!(cache.weight(), 0);
+    }
+
+    #[test]
+    fn should_bound_by_entries() {
+        let cache = LruCache::<u64, Vec<u8>>::with_capacity(CacheCapacity::Entries(2));
+
+        cache.insert(1, vec![0; 1000]);
+        cache.insert(2, vec![0; 1000]);
+        cache.insert(3, vec![0; 1000]);
+
+        assert_eq!(cache.weight(), 2);
+        assert!(!cache.contains_key(&1));
+    }
+
+    #[test]
+    fn should_insert_many() {
+        let cache = LruCache::<u64, u64>::new(100);
+
+        cache.insert_many(vec![(1, 10), (2, 20), (1, 11)]);
+
+        assert_eq!(cache.get(&1), Some(11));
+        assert_eq!(cache.get(&2), Some(20));
+    }
 }
@@ -1,10 +1,24 @@
 mod bound;
 mod codec;
+mod encoding;
+mod error;
 #[cfg(feature = "cached")]
 mod lru;
+mod pagination;
+mod usage;
 
 pub use bound::Bounded;
 pub use codec::*;
+pub use encoding::Candid;
+#[cfg(feature = "cbor")]
+pub use encoding::Cbor;
+#[cfg(feature = "json")]
+pub use encoding::Json;
+pub use error::StructureError;
+pub(crate) use error::{ensure_capacity, vec_slot_size};
+pub use pagination::{Paginate, Pagination};
+pub(crate) use usage::fixed_size;
+pub use usage::{MemoryPages, MemoryUsage, memory_report};
 
 #[cfg(feature = "cached")]
-pub use lru::LruCache;
+pub use lru::{CacheCapacity, CacheStats, CacheWeight, LruCache};
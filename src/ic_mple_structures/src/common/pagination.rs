@@ -0,0 +1,79 @@
+use candid::{CandidType, Deserialize};
+
+/// Specifies the page to take from a long list of items.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, CandidType, Deserialize)]
+pub struct Pagination {
+    /// The number of items to skip.
+    pub offset: u64,
+    /// The max number of items to take.
+    pub count: u64,
+}
+
+impl Pagination {
+    /// Creates a new pagination.
+    pub fn new(offset: u64, count: u64) -> Self {
+        Self { offset, count }
+    }
+
+    /// Returns the pagination with the count clamped to `max_count`,
+    /// so that a caller cannot request pages bigger than the canister can return.
+    pub fn validate(self, max_count: u64) -> Self {
+        Self {
+            offset: self.offset,
+            count: self.count.min(max_count),
+        }
+    }
+
+    /// Returns the offset as `usize`, saturating on 32-bit targets.
+    pub(crate) fn offset_usize(&self) -> usize {
+        usize::try_from(self.offset).unwrap_or(usize::MAX)
+    }
+
+    /// Returns the count as `usize`, saturating on 32-bit targets.
+    pub(crate) fn count_usize(&self) -> usize {
+        usize::try_from(self.count).unwrap_or(usize::MAX)
+    }
+}
+
+/// Applies a `Pagination` to any iterator.
+pub trait Paginate: Iterator + Sized {
+    /// Skips the first `offset` items and takes at most `count` of the following ones.
+    fn paginate(self, pagination: Pagination) -> impl Iterator<Item = Self::Item> {
+        self.skip(pagination.offset_usize())
+            .take(pagination.count_usize())
+    }
+}
+
+impl<I: Iterator> Paginate for I {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn should_paginate_an_iterator() {
+        let page = |offset, count| {
+            (0..10)
+                .paginate(Pagination::new(offset, count))
+                .collect::<Vec<_>>()
+        };
+
+        assert_eq!(page(0, 3), vec![0, 1, 2]);
+        assert_eq!(page(8, 5), vec![8, 9]);
+        assert!(page(3, 0).is_empty());
+        assert!(page(10, 5).is_empty());
+        assert!(page(u64::MAX, u64::MAX).is_empty());
+    }
+
+    #[test]
+    fn should_clamp_the_count() {
+        assert_eq!(Pagination::new(5, 100).validate(10), Pagination::new(5, 10));
+        assert_eq!(Pagination::new(5, 3).validate(10), Pagination::new(5, 3));
+        assert_eq!(
+            (0..100)
+                .paginate(Pagination::new(90, 50).validate(5))
+                .collect::<Vec<_>>(),
+            vec![90, 91, 92, 93, 94]
+        );
+    }
+}
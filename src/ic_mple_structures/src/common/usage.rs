@@ -0,0 +1,111 @@
+use candid::{CandidType, Deserialize};
+use ic_stable_structures::Memory;
+use ic_stable_structures::memory_manager::{MemoryId, MemoryManager};
+use ic_stable_structures::storable::Bound;
+
+/// The greatest id of a memory of a `MemoryManager`.
+const MAX_MEMORY_ID: u8 = 254;
+
+/// Reports the stable memory taken by a structure.
+///
+/// The values are derived from the length of the structure.
+/// The memory grows in whole pages and it is never shrunk, so the underlying memory can be larger:
+/// `memory_report` returns the pages actually allocated.
+pub trait MemoryUsage {
+    /// Returns the number of bytes taken by the structure in the stable memory, headers included.
+    fn allocated_bytes(&self) -> u64;
+
+    /// Returns the approximate number of bytes taken by the values,
+    /// or `None` if it cannot be derived without reading them.
+    fn used_bytes(&self) -> Option<u64>;
+}
+
+/// The number of pages allocated to a memory of a `MemoryManager`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, CandidType, Deserialize)]
+pub struct MemoryPages {
+    /// The id of the memory.
+    pub memory_id: u8,
+    /// The number of WASM pages of 64 KiB allocated to the memory.
+    pub pages: u64,
+}
+
+/// Returns the number of pages of every memory of the `MemoryManager` that has been allocated,
+/// in ascending order of memory id.
+pub fn memory_report<M: Memory>(memory_manager: &MemoryManager<M>) -> Vec<MemoryPages> {
+    (0..=MAX_MEMORY_ID)
+        .filter_map(|memory_id| {
+            let pages = memory_manager.get(MemoryId::new(memory_id)).size();
+            (pages > 0).then_some(MemoryPages { memory_id, pages })
+        })
+        .collect()
+}
+
+/// Returns the size of the values with the given bound, if all of them have the same size.
+pub(crate) fn fixed_size(bound: Bound) -> Option<u64> {
+    match bound {
+        Bound::Bounded {
+            max_size,
+            is_fixed_size: true,
+        } => Some(max_size as u64),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use ic_stable_structures::{Storable, VectorMemory};
+
+    use super::*;
+    use crate::{BTreeMapStructure, VersionedBTreeMap};
+
+    #[test]
+    fn should_report_the_allocated_memories() {
+        let memory_manager = MemoryManager::init(VectorMemory::default());
+        assert_eq!(memory_report(&memory_manager), vec![]);
+
+        memory_manager.get(MemoryId::new(3)).grow(2);
+        memory_manager.get(MemoryId::new(1)).grow(1);
+        assert_eq!(
+            memory_report(&memory_manager),
+            vec![
+                MemoryPages {
+                    memory_id: 1,
+                    pages: 1
+                },
+                MemoryPages {
+                    memory_id: 3,
+                    pages: 2
+                }
+            ]
+        );
+
+        memory_manager.get(MemoryId::new(1)).grow(3);
+        assert_eq!(memory_report(&memory_manager)[0].pages, 4);
+    }
+
+    #[test]
+    fn should_report_the_memory_of_a_map() {
+        let memory_manager = MemoryManager::init(VectorMemory::default());
+        let mut map =
+            VersionedBTreeMap::<u64, u64, u64, _>::new(memory_manager.get(MemoryId::new(2)));
+        assert_eq!(map.used_bytes(), Some(0));
+
+        for key in 0..10_000 {
+            map.insert(key, key);
+        }
+
+        assert_eq!(map.used_bytes(), Some(10_000 * 16));
+        let report = memory_report(&memory_manager);
+        assert_eq!(report.len(), 1);
+        assert_eq!(report[0].memory_id, 2);
+        // the allocated bytes are a lower bound of the memory actually taken by the map
+        assert!(report[0].pages * 65536 >= map.allocated_bytes());
+        assert!(map.allocated_bytes() > map.used_bytes().unwrap());
+    }
+
+    #[test]
+    fn should_return_the_size_of_fixed_size_values() {
+        assert_eq!(fixed_size(u64::BOUND), Some(8));
+        assert_eq!(fixed_size(String::BOUND), None);
+    }
+}
@@ -0,0 +1,496 @@
+use std::mem::size_of;
+
+use ic_stable_structures::storable::Bound;
+use ic_stable_structures::{Memory, StableCell, Storable};
+
+use crate::cell::CELL_HEADER_SIZE;
+use crate::common::{MemoryUsage, StructureError, fixed_size};
+use crate::vec::VecExt;
+use crate::vec::VecStructure;
+
+/// Deque indices state.
+///
+/// The elements are stored in the data vector from `start`, wrapping at the end of the data.
+/// The length of the data is the current capacity of the deque.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+struct StableDequeIndices {
+    /// Index of the first element in the data
+    start: u64,
+    /// Number of elements in the deque
+    len: u64,
+}
+
+impl StableDequeIndices {
+    /// Converts an offset from the first element into an index of a data with the given `capacity`.
+    ///
+    /// PRECONDITION: `capacity > 0`
+    fn offset_to_index(&self, offset: u64, capacity: u64) -> u64 {
+        (self.start + offset) % capacity
+    }
+
+    /// Checks that the indices are consistent with a data vector of length `data_len`.
+    fn validate(&self, data_len: u64) -> Result<(), StructureError> {
+        let start_is_valid = self.start < data_len || (self.start == 0 && data_len == 0);
+        if !start_is_valid || self.len > data_len {
+            return Err(StructureError::Corrupted(format!(
+                "deque indices out of the data: start {}, len {}, data length {data_len}",
+                self.start, self.len
+            )));
+        }
+        Ok(())
+    }
+}
+
+const STABLE_DEQUE_INDICES_SIZE: usize = 2 * size_of::<u64>();
+
+impl Storable for StableDequeIndices {
+    const BOUND: Bound = Bound::Bounded {
+        max_size: STABLE_DEQUE_INDICES_SIZE as u32,
+        is_fixed_size: true,
+    };
+
+    fn to_bytes(&self) -> std::borrow::Cow<'_, [u8]> {
+        let mut buf = Vec::with_capacity(STABLE_DEQUE_INDICES_SIZE);
+        buf.extend_from_slice(&self.start.to_le_bytes());
+        buf.extend_from_slice(&self.len.to_le_bytes());
+        buf.into()
+    }
+
+    fn from_bytes(bytes: std::borrow::Cow<[u8]>) -> Self {
+        Self {
+            start: u64::from_le_bytes(bytes[..8].try_into().expect("start: expected 8 bytes")),
+            len: u64::from_le_bytes(bytes[8..16].try_into().expect("len: expected 8 bytes")),
+        }
+    }
+
+    fn into_bytes(self) -> Vec<u8> {
+        self.to_bytes().into()
+    }
+}
+
+/// Stable double-ended queue.
+///
+/// Like the `StableRingBuffer`, the elements are stored in a vector wrapping at its end,
+/// but the vector grows when the deque is full instead of displacing the elements.
+pub struct StableDeque<T: Storable + Clone, DataMemory: Memory, IndicesMemory: Memory> {
+    /// Vector with elements
+    data: VecExt<T, DataMemory>,
+    /// Indices that specify where are the elements in the data
+    indices: StableCell<StableDequeIndices, IndicesMemory>,
+}
+
+impl<T: Storable + Clone, DataMemory: Memory, IndicesMemory: Memory>
+    StableDeque<T, DataMemory, IndicesMemory>
+{
+    /// Creates new deque,
+    /// overwriting any data structures the memory might have
+    /// contained previously
+    pub fn new(data_memory: DataMemory, indices_memory: IndicesMemory) -> Self {
+        Self {
+            data: VecExt::new(data_memory),
+            indices: StableCell::new(indices_memory, StableDequeIndices::default()),
+        }
+    }
+
+    /// Creates new deque, preserving the existing elements if the memories already contain a deque.
+    ///
+    /// Returns an error if the existing indices are not consistent with the existing data.
+    pub fn init(
+        data_memory: DataMemory,
+        indices_memory: IndicesMemory,
+    ) -> Result<Self, StructureError> {
+        let deque = Self {
+            data: VecExt::init(data_memory),
+            indices: StableCell::init(indices_memory, StableDequeIndices::default()),
+        };
+        deque.indices.get().validate(deque.data.len())?;
+        Ok(deque)
+    }
+
+    /// Removes all elements in the deque
+    pub fn clear(&mut self) {
+        self.with_indices_data_mut(|indices, data| {
+            *indices = StableDequeIndices::default();
+            data.clear();
+        });
+    }
+
+    /// Number of elements in the deque
+    pub fn len(&self) -> u64 {
+        self.indices.get().len
+    }
+
+    /// Returns whether is empty
+    pub fn is_empty(&self) -> bool {
+        self.indices.get().len == 0
+    }
+
+    /// Appends an element to the back of the deque.
+    pub fn push_back(&mut self, val: &T) {
+        self.with_indices_data_mut(|indices, data| {
+            if indices.len == data.len() {
+                if indices.start == 0 {
+                    // The elements are not wrapped, so the back of the deque is the end of the data
+                    data.push(val);
+                    indices.len += 1;
+                    return;
+                }
+                Self::unwrap_elements(indices, data);
+            }
+
+            let index = indices.offset_to_index(indices.len, data.len());
+            data.set(index, val);
+            indices.len += 1;
+        });
+    }
+
+    /// Prepends an element to the front of the deque.
+    pub fn push_front(&mut self, val: &T) {
+        self.with_indices_data_mut(|indices, data| {
+            if indices.len == data.len() {
+                if indices.start == 0 {
+                    // The new element is placed at the end of the data and the others follow it wrapping
+                    data.push(val);
+                    indices.start = data.len() - 1;
+                    indices.len += 1;
+                    return;
+                }
+                Self::unwrap_elements(indices, data);
+            }
+
+            let capacity = data.len();
+            indices.start = (indices.start + capacity - 1) % capacity;
+            data.set(indices.start, val);
+            indices.len += 1;
+        });
+    }
+
+    /// Removes the first element and returns it, or `None` if the deque is empty.
+    pub fn pop_front(&mut self) -> Option<T> {
+        self.with_indices_data_mut(|indices, data| {
+            if indices.len == 0 {
+                return None;
+            }
+
+            let element = data.get(indices.start);
+            indices.start = indices.offset_to_index(1, data.len());
+            indices.len -= 1;
+            element
+        })
+    }
+
+    /// Removes the last element and returns it, or `None` if the deque is empty.
+    pub fn pop_back(&mut self) -> Option<T> {
+        self.with_indices_data_mut(|indices, data| {
+            let new_len = indices.len.checked_sub(1)?;
+            let element = data.get(indices.offset_to_index(new_len, data.len()));
+            indices.len = new_len;
+            element
+        })
+    }
+
+    /// Get the first element if it exists.
+    pub fn front(&self) -> Option<T> {
+        self.nth_element(0)
+    }
+
+    /// Get the last element if it exists.
+    pub fn back(&self) -> Option<T> {
+        let indices = self.indices.get();
+        self.nth_element(indices.len.checked_sub(1)?)
+    }
+
+    /// Get the `n`-th element from the front.
+    fn nth_element(&self, n: u64) -> Option<T> {
+        let indices = self.indices.get();
+        if n >= indices.len {
+            return None;
+        }
+        self.data.get(indices.offset_to_index(n, self.data.len()))
+    }
+
+    /// Copies the elements wrapped at the beginning of the data after its end,
+    /// so that the beginning of the data up to `start` can be reused.
+    ///
+    /// The cost of the copy is the number of slots made available,
+    /// so the pushes have a constant amortized cost.
+    ///
+    /// PRECONDITION: the deque is full
+    fn unwrap_elements(indices: &StableDequeIndices, data: &mut VecExt<T, DataMemory>) {
+        for index in 0..indices.start {
+            // This should never panic, because the deque is full, so all the indices are present in the data.
+            let element = data.get(index).expect("element should be present");
+            data.push(&element);
+        }
+    }
+
+    #[inline]
+    fn with_indices_data_mut<R>(
+        &mut self,
+        f: impl FnOnce(&mut StableDequeIndices, &mut VecExt<T, DataMemory>) -> R,
+    ) -> R {
+        let mut indices = self.indices.get().clone();
+        let result = f(&mut indices, &mut self.data);
+        self.indices.set(indices);
+        result
+    }
+}
+
+impl<T: Storable + Clone, DataMemory: Memory, IndicesMemory: Memory> MemoryUsage
+    for StableDeque<T, DataMemory, IndicesMemory>
+{
+    fn allocated_bytes(&self) -> u64 {
+        self.data.allocated_bytes() + CELL_HEADER_SIZE + STABLE_DEQUE_INDICES_SIZE as u64
+    }
+
+    /// Only the elements in the buffer are counted, not the removed ones still in the data.
+    fn used_bytes(&self) -> Option<u64> {
+        fixed_size(T::BOUND).map(|size| self.len() * size)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use std::collections::VecDeque;
+
+    use ic_stable_structures::VectorMemory;
+
+    use super::*;
+
+    fn check_deque<DataMemory: Memory, IndicesMemory: Memory>(
+        deque: &StableDeque<u64, DataMemory, IndicesMemory>,
+        expected: &VecDeque<u64>,
+    ) {
+        assert_eq!(deque.len(), expected.len() as u64);
+        assert_eq!(deque.is_empty(), expected.is_empty());
+        assert_eq!(deque.front(), expected.front().copied());
+        assert_eq!(deque.back(), expected.back().copied());
+        for (i, expected) in expected.iter().enumerate() {
+            assert_eq!(deque.nth_element(i as u64), Some(*expected));
+        }
+        assert_eq!(deque.nth_element(expected.len() as u64), None);
+    }
+
+    fn new_deque() -> StableDeque<u64, VectorMemory, VectorMemory> {
+        StableDeque::new(VectorMemory::default(), VectorMemory::default())
+    }
+
+    #[test]
+    fn indices_should_be_storable() {
+        let indices = StableDequeIndices { start: 3, len: 7 };
+        assert_eq!(StableDequeIndices::from_bytes(indices.to_bytes()), indices);
+    }
+
+    #[test]
+    fn should_push_and_pop_as_a_queue() {
+        let mut deque = new_deque();
+        assert_eq!(deque.pop_front(), None);
+        assert_eq!(deque.pop_back(), None);
+
+        for i in 0..5 {
+            deque.push_back(&i);
+        }
+        check_deque(&deque, &(0..5).collect());
+
+        for i in 0..5 {
+            assert_eq!(deque.pop_front(), Some(i));
+        }
+        assert!(deque.is_empty());
+        assert_eq!(deque.pop_front(), None);
+    }
+
+    #[test]
+    fn should_push_and_pop_as_a_stack() {
+        let mut deque = new_deque();
+
+        for i in 0..5 {
+            deque.push_front(&i);
+        }
+        check_deque(&deque, &(0..5).rev().collect());
+
+        for i in 0..5 {
+            assert_eq!(deque.pop_front(), Some(4 - i));
+        }
+        assert!(deque.is_empty());
+        assert_eq!(deque.pop_back(), None);
+    }
+
+    #[test]
+    fn should_reuse_the_slots_wrapping_around() {
+        let mut deque = new_deque();
+        for i in 0..4 {
+            deque.push_back(&i);
+        }
+
+        // The elements wrap around the end of the data without growing it
+        for i in 4..20 {
+            assert_eq!(deque.pop_front(), Some(i - 4));
+            deque.push_back(&i);
+            check_deque(&deque, &(i - 3..=i).collect());
+        }
+        assert_eq!(deque.data.len(), 4);
+
+        for i in (0..16).rev() {
+            assert_eq!(deque.pop_back(), Some(i + 4));
+            deque.push_front(&i);
+            check_deque(&deque, &(i..i + 4).collect());
+        }
+        assert_eq!(deque.data.len(), 4);
+    }
+
+    #[test]
+    fn should_grow_when_full_and_wrapped() {
+        let mut deque = new_deque();
+        let mut expected = VecDeque::new();
+        for i in 0..4 {
+            deque.push_back(&i);
+            expected.push_back(i);
+        }
+        for i in 4..6 {
+            deque.pop_front();
+            expected.pop_front();
+            deque.push_back(&i);
+            expected.push_back(i);
+        }
+
+        // The deque is full and wrapped
+        deque.push_back(&6);
+        expected.push_back(6);
+        check_deque(&deque, &expected);
+
+        deque.push_front(&100);
+        expected.push_front(100);
+        check_deque(&deque, &expected);
+
+        for i in 7..50 {
+            if i % 3 == 0 {
+                deque.push_front(&i);
+                expected.push_front(i);
+            } else {
+                deque.push_back(&i);
+                expected.push_back(i);
+            }
+            check_deque(&deque, &expected);
+        }
+
+        // The data grows at most to twice the number of elements
+        assert!(deque.data.len() <= 2 * deque.len());
+    }
+
+    #[test]
+    fn should_behave_like_a_vec_deque() {
+        let mut deque = new_deque();
+        let mut expected = VecDeque::new();
+
+        for i in 0..500u64 {
+            match i * 7 % 5 {
+                0 | 1 => {
+                    deque.push_back(&i);
+                    expected.push_back(i);
+                }
+                2 => {
+                    deque.push_front(&i);
+                    expected.push_front(i);
+                }
+                3 => assert_eq!(deque.pop_front(), expected.pop_front()),
+                _ => assert_eq!(deque.pop_back(), expected.pop_back()),
+            }
+            check_deque(&deque, &expected);
+        }
+    }
+
+    #[test]
+    fn should_clear() {
+        let mut deque = new_deque();
+        deque.clear();
+        assert!(deque.is_empty());
+
+        for i in 0..5 {
+            deque.push_front(&i);
+        }
+        deque.clear();
+        check_deque(&deque, &VecDeque::new());
+
+        deque.push_back(&1);
+        check_deque(&deque, &VecDeque::from([1]));
+    }
+
+    #[test]
+    fn should_recover_the_state_on_init() {
+        let data_memory = VectorMemory::default();
+        let indices_memory = VectorMemory::default();
+        let mut expected = VecDeque::new();
+        {
+            let mut deque =
+                StableDeque::<u64, _, _>::init(data_memory.clone(), indices_memory.clone())
+                    .unwrap();
+            for i in 0..6 {
+                deque.push_back(&i);
+                expected.push_back(i);
+            }
+            for _ in 0..3 {
+                deque.pop_front();
+                expected.pop_front();
+            }
+            for i in 6..9 {
+                deque.push_back(&i);
+                expected.push_back(i);
+            }
+            deque.push_front(&100);
+            expected.push_front(100);
+        }
+
+        {
+            let mut deque = StableDeque::<u64, _, _>::init(data_memory, indices_memory).unwrap();
+            check_deque(&deque, &expected);
+
+            deque.push_back(&9);
+            expected.push_back(9);
+            check_deque(&deque, &expected);
+        }
+    }
+
+    #[test]
+    fn should_return_an_error_on_init_if_the_indices_are_corrupted() {
+        let init_with_indices = |data_len: u64, start: u64, len: u64| {
+            let data_memory = VectorMemory::default();
+            let indices_memory = VectorMemory::default();
+            let mut data = VecExt::<u64, _>::init(data_memory.clone());
+            for i in 0..data_len {
+                data.push(&i);
+            }
+            StableCell::init(indices_memory.clone(), StableDequeIndices { start, len });
+            StableDeque::<u64, _, _>::init(data_memory, indices_memory)
+        };
+
+        assert!(init_with_indices(0, 0, 0).is_ok());
+        assert!(init_with_indices(3, 2, 3).is_ok());
+        assert!(init_with_indices(3, 0, 1).is_ok());
+
+        assert_eq!(
+            init_with_indices(3, 3, 1).err(),
+            Some(StructureError::Corrupted(
+                "deque indices out of the data: start 3, len 1, data length 3".to_string()
+            ))
+        );
+        assert!(init_with_indices(3, 0, 4).is_err());
+        assert!(init_with_indices(0, 1, 0).is_err());
+    }
+
+    #[test]
+    fn should_erase_existing_data_on_new() {
+        let data_memory = VectorMemory::default();
+        let indices_memory = VectorMemory::default();
+        {
+            let mut deque = StableDeque::new(data_memory.clone(), indices_memory.clone());
+            deque.push_back(&100u32);
+        }
+
+        {
+            let mut deque = StableDeque::<u32, _, _>::new(data_memory, indices_memory);
+            assert!(deque.is_empty());
+            assert_eq!(None, deque.pop_front());
+        }
+    }
+}
@@ -1,24 +1,39 @@
 mod btreemap;
+mod btreeset;
 mod cell;
 mod common;
+mod deque;
 mod log;
 mod multimap;
+mod registry;
 mod ringbuffer;
+mod timeseries;
 mod vec;
 
 #[cfg(test)]
 mod test_utils;
 
+// Allows the code generated by the derive macros to refer to this crate by name, also inside the crate itself.
+#[cfg(feature = "derive")]
+extern crate self as ic_mple_structures;
+
+#[cfg(feature = "derive")]
+pub use ic_mple_structures_derive::VersionedCodec;
+
 pub use {
     btreemap::*,
+    btreeset::*,
     cell::*,
     common::*,
+    deque::*,
     ic_stable_structures::{
         DefaultMemoryImpl, MAX_PAGES, Memory, StableBTreeMap, StableBTreeSet, StableCell,
         StableLog, StableVec, Storable, VectorMemory, memory_manager::*, storable::Bound,
     },
     log::*,
     multimap::*,
+    registry::*,
     ringbuffer::*,
+    timeseries::*,
     vec::*,
 };
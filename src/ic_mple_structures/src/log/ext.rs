@@ -3,6 +3,7 @@ use ic_stable_structures::{
     log::{self, WriteError},
 };
 
+use crate::common::MemoryUsage;
 use crate::log::LogStructure;
 
 /// An extended version of the log data structure
@@ -53,6 +54,11 @@ impl<T: Storable, M: Memory> LogStructure<T> for LogExt<T, M> {
         self.len() == 0
     }
 
+    fn size_bytes(&self) -> u64 {
+        let log = self.get_inner();
+        log.index_size_bytes() + log.data_size_bytes()
+    }
+
     fn clear(&mut self) {
         if let Some(log) = self.0.take() {
             let (index_mem, data_mem) = log.into_memories();
@@ -61,14 +67,95 @@ impl<T: Storable, M: Memory> LogStructure<T> for LogExt<T, M> {
     }
 }
 
+impl<T: Storable, M: Memory> MemoryUsage for LogExt<T, M> {
+    fn allocated_bytes(&self) -> u64 {
+        self.size_bytes()
+    }
+
+    fn used_bytes(&self) -> Option<u64> {
+        Some(self.get_inner().data_size_bytes())
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use ic_stable_structures::VectorMemory;
 
-    use crate::test_utils::Array;
+    use crate::common::Pagination;
+    use crate::log::AppendBatchError;
+    use crate::test_utils::{Array, CappedMemory};
 
     use super::*;
 
+    #[test]
+    fn should_read_ranges() {
+        let mut log = LogExt::new(VectorMemory::default(), VectorMemory::default());
+        assert!(log.get_range(0, 10).is_empty());
+        assert_eq!(log.iter_from(0).next(), None);
+
+        for i in 0..10u64 {
+            log.append(i).unwrap();
+        }
+
+        assert_eq!(log.get_range(0, 3), vec![0, 1, 2]);
+        assert_eq!(log.get_range(4, 3), vec![4, 5, 6]);
+        assert_eq!(log.get_range(8, 3), vec![8, 9]);
+        assert_eq!(log.get_range(4, 0), Vec::<u64>::new());
+        assert_eq!(log.get_range(10, 3), Vec::<u64>::new());
+        assert_eq!(log.get_range(20, u64::MAX), Vec::<u64>::new());
+        assert_eq!(log.get_range(9, u64::MAX), vec![9]);
+
+        assert_eq!(
+            log.iter_from(0).collect::<Vec<_>>(),
+            (0..10).collect::<Vec<_>>()
+        );
+        assert_eq!(log.iter_from(7).collect::<Vec<_>>(), vec![7, 8, 9]);
+        assert_eq!(log.iter_from(10).next(), None);
+        assert_eq!(log.iter_from(u64::MAX).next(), None);
+
+        assert_eq!(log.page(Pagination::new(4, 3)), vec![4, 5, 6]);
+        assert!(log.page(Pagination::new(4, 0)).is_empty());
+        assert!(log.page(Pagination::new(10, 3)).is_empty());
+        assert_eq!(log.page(Pagination::new(0, 100).validate(2)), vec![0, 1]);
+    }
+
+    #[test]
+    fn should_append_batch() {
+        let mut log = LogExt::new(VectorMemory::default(), VectorMemory::default());
+        let empty_size = log.size_bytes();
+
+        assert_eq!(log.append_batch(vec![]), Ok(0));
+        assert_eq!(log.size_bytes(), empty_size);
+
+        assert_eq!(log.append_batch(vec![1u64, 2, 3]), Ok(0));
+        let size = log.size_bytes();
+        assert!(size > empty_size);
+
+        assert_eq!(log.append_batch(vec![4, 5]), Ok(3));
+        assert!(log.size_bytes() > size);
+
+        assert_eq!(log.get_range(0, 10), vec![1, 2, 3, 4, 5]);
+        assert_eq!(log.append_batch(vec![]), Ok(5));
+    }
+
+    #[test]
+    fn should_stop_the_batch_at_the_first_error() {
+        let mut log = LogExt::new(CappedMemory::new(1), CappedMemory::new(1));
+
+        let values = vec![vec![1u8; 20_000]; 5];
+        let result = log.append_batch(values);
+
+        assert!(matches!(
+            result,
+            Err(AppendBatchError {
+                written: 3,
+                error: WriteError::GrowFailed { .. }
+            })
+        ));
+        assert_eq!(log.len(), 3);
+        assert_eq!(log.get(2), Some(vec![1u8; 20_000]));
+    }
+
     #[test]
     fn should_reuse_existing_data_on_init() {
         let memory_1 = VectorMemory::default();
@@ -102,4 +189,16 @@ mod tests {
             assert_eq!(None, log.get(index));
         }
     }
+
+    #[test]
+    fn should_report_the_memory_usage() {
+        let mut log = LogExt::new(VectorMemory::default(), VectorMemory::default());
+        let empty_allocated_bytes = log.allocated_bytes();
+        let empty_used_bytes = log.used_bytes().unwrap();
+
+        log.append("a long enough value".to_string()).unwrap();
+        assert!(log.allocated_bytes() > empty_allocated_bytes);
+        assert!(log.used_bytes().unwrap() >= empty_used_bytes + 19);
+        assert_eq!(log.allocated_bytes(), log.size_bytes());
+    }
 }
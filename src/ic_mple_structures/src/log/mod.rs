@@ -1,8 +1,24 @@
 use ic_stable_structures::log::WriteError;
 
+use crate::common::Pagination;
+
 mod ext;
+mod rotating;
+mod versioned;
 
 pub use ext::LogExt;
+pub use rotating::{RotatingLog, RotationPolicy};
+pub use versioned::VersionedLog;
+
+/// The error returned when a batch of values cannot be entirely appended to the log.
+#[derive(Debug, PartialEq, Eq, thiserror::Error)]
+#[error("failed to append the batch after {written} values: {error:?}")]
+pub struct AppendBatchError {
+    /// The number of values appended before the failure.
+    pub written: u64,
+    /// The error of the first value that could not be appended.
+    pub error: WriteError,
+}
 
 pub trait LogStructure<T> {
     /// Returns reference to value stored in stable memory.
@@ -19,4 +35,42 @@ pub trait LogStructure<T> {
 
     /// Remove all items from the log.
     fn clear(&mut self);
+
+    /// Returns the number of bytes of stable memory used by the index and the data of the log.
+    fn size_bytes(&self) -> u64;
+
+    /// Appends all the values, in order, and returns the index of the first one.
+    /// If `values` is empty, the returned index is the current length of the log.
+    ///
+    /// It stops at the first value that cannot be appended; the values before it stay in the log.
+    fn append_batch(&mut self, values: Vec<T>) -> Result<u64, AppendBatchError> {
+        let first_index = self.len();
+        for (written, value) in (0u64..).zip(values) {
+            self.append(value)
+                .map_err(|error| AppendBatchError { written, error })?;
+        }
+        Ok(first_index)
+    }
+
+    /// Returns up to `count` values starting from index `offset`.
+    /// Returns an empty vector if `offset` is out of range.
+    fn get_range(&self, offset: u64, count: u64) -> Vec<T> {
+        let len = self.len();
+        let end = offset.saturating_add(count).min(len);
+        (offset.min(len)..end)
+            .map_while(|index| self.get(index))
+            .collect()
+    }
+
+    /// Returns the values of the page, see `get_range`.
+    fn page(&self, pagination: Pagination) -> Vec<T> {
+        self.get_range(pagination.offset, pagination.count)
+    }
+
+    /// Returns an iterator over the values starting from index `offset`.
+    /// The iterator is empty if `offset` is out of range.
+    fn iter_from(&self, offset: u64) -> impl Iterator<Item = T> + '_ {
+        let len = self.len();
+        (offset.min(len)..len).map_while(|index| self.get(index))
+    }
 }
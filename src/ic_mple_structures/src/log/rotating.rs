@@ -0,0 +1,377 @@
+use std::borrow::Cow;
+use std::mem::size_of;
+
+use ic_stable_structures::storable::Bound;
+use ic_stable_structures::{Memory, StableCell, Storable, log::WriteError};
+
+use crate::cell::CELL_HEADER_SIZE;
+use crate::common::MemoryUsage;
+use crate::log::{LogExt, LogStructure};
+
+/// The limits of a segment of a `RotatingLog`.
+/// When the segment that receives the new values reaches any of them, the oldest segment is dropped.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RotationPolicy {
+    /// The max number of values of a segment.
+    pub max_segment_entries: u64,
+    /// The max number of bytes of stable memory used by a segment, as returned by `LogStructure::size_bytes`.
+    pub max_segment_bytes: u64,
+}
+
+impl Default for RotationPolicy {
+    /// Returns a policy without limits, so that the log never rotates.
+    fn default() -> Self {
+        Self {
+            max_segment_entries: u64::MAX,
+            max_segment_bytes: u64::MAX,
+        }
+    }
+}
+
+/// The state of a `RotatingLog`.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+struct RotatingLogState {
+    /// The index of the segment that receives the new values
+    active: u8,
+    /// The global index of the first value of the oldest segment
+    first_index: u64,
+}
+
+const ROTATING_LOG_STATE_SIZE: usize = size_of::<u8>() + size_of::<u64>();
+
+impl Storable for RotatingLogState {
+    const BOUND: Bound = Bound::Bounded {
+        max_size: ROTATING_LOG_STATE_SIZE as u32,
+        is_fixed_size: true,
+    };
+
+    fn to_bytes(&self) -> Cow<'_, [u8]> {
+        let mut buf = Vec::with_capacity(ROTATING_LOG_STATE_SIZE);
+        buf.push(self.active);
+        buf.extend_from_slice(&self.first_index.to_le_bytes());
+        buf.into()
+    }
+
+    fn from_bytes(bytes: Cow<[u8]>) -> Self {
+        Self {
+            active: bytes[0],
+            first_index: u64::from_le_bytes(
+                bytes[1..9]
+                    .try_into()
+                    .expect("first_index: expected 8 bytes"),
+            ),
+        }
+    }
+
+    fn into_bytes(self) -> Vec<u8> {
+        self.to_bytes().into()
+    }
+}
+
+/// A stable log that retains only the most recent values.
+///
+/// The values are appended to one of two segments. When that segment exceeds the budget of the `RotationPolicy`,
+/// the other segment, which contains the oldest values, is cleared and receives the next values.
+/// So the log retains between one and two segments of values.
+///
+/// Every value is addressed by a global index that keeps growing across the rotations;
+/// the values of a dropped segment are no longer addressable.
+pub struct RotatingLog<T: Storable, M: Memory> {
+    segments: [LogExt<T, M>; 2],
+    state: StableCell<RotatingLogState, M>,
+    policy: RotationPolicy,
+}
+
+impl<T: Storable, M: Memory> RotatingLog<T, M> {
+    /// Create new rotating log.
+    /// The segments memories are pairs of index and data memories.
+    ///
+    /// This overwrites any data structures the memories might have
+    /// contained previously.
+    pub fn new(
+        state_memory: M,
+        first_segment_memories: (M, M),
+        second_segment_memories: (M, M),
+        policy: RotationPolicy,
+    ) -> Self {
+        Self {
+            segments: [
+                LogExt::new(first_segment_memories.0, first_segment_memories.1),
+                LogExt::new(second_segment_memories.0, second_segment_memories.1),
+            ],
+            state: StableCell::new(state_memory, RotatingLogState::default()),
+            policy,
+        }
+    }
+
+    /// Create new rotating log.
+    /// The segments memories are pairs of index and data memories.
+    ///
+    /// PRECONDITION: the memories are either empty or contain valid
+    /// rotating log data.
+    pub fn init(
+        state_memory: M,
+        first_segment_memories: (M, M),
+        second_segment_memories: (M, M),
+        policy: RotationPolicy,
+    ) -> Self {
+        Self {
+            segments: [
+                LogExt::init(first_segment_memories.0, first_segment_memories.1),
+                LogExt::init(second_segment_memories.0, second_segment_memories.1),
+            ],
+            state: StableCell::init(state_memory, RotatingLogState::default()),
+            policy,
+        }
+    }
+
+    /// Appends the value and returns its global index.
+    /// If the active segment exceeds the budget of the policy, the oldest segment is dropped first.
+    pub fn append(&mut self, value: T) -> Result<u64, WriteError> {
+        if self.should_rotate() {
+            self.rotate();
+        }
+
+        let state = *self.state.get();
+        let index = self.segments[state.active as usize].append(value)?;
+        Ok(state.first_index + self.oldest().len() + index)
+    }
+
+    /// Returns the value with the given global index,
+    /// or `None` if it has been dropped or it has not been appended yet.
+    pub fn get(&self, index: u64) -> Option<T> {
+        let local_index = index.checked_sub(self.first_index())?;
+        let oldest = self.oldest();
+        match local_index.checked_sub(oldest.len()) {
+            None => oldest.get(local_index),
+            Some(active_index) => self.active().get(active_index),
+        }
+    }
+
+    /// Number of values retained by the log.
+    pub fn len(&self) -> u64 {
+        self.oldest().len() + self.active().len()
+    }
+
+    /// Returns true, if the log doesn't retain any values.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Returns the global index of the oldest value retained by the log.
+    pub fn first_index(&self) -> u64 {
+        self.state.get().first_index
+    }
+
+    /// Returns the global index that the next appended value will have.
+    pub fn next_index(&self) -> u64 {
+        self.first_index() + self.len()
+    }
+
+    /// Remove all values from the log.
+    /// The global indices keep growing, so the next appended value has the same index it would have had.
+    pub fn clear(&mut self) {
+        let next_index = self.next_index();
+        for segment in &mut self.segments {
+            segment.clear();
+        }
+        self.state.set(RotatingLogState {
+            active: 0,
+            first_index: next_index,
+        });
+    }
+
+    /// Returns the number of bytes of stable memory used by the segments.
+    pub fn size_bytes(&self) -> u64 {
+        self.segments
+            .iter()
+            .map(|segment| segment.size_bytes())
+            .sum()
+    }
+
+    fn should_rotate(&self) -> bool {
+        let active = self.active();
+        active.len() >= self.policy.max_segment_entries
+            || active.size_bytes() >= self.policy.max_segment_bytes
+    }
+
+    /// Drops the oldest segment and makes it the active one.
+    fn rotate(&mut self) {
+        let state = *self.state.get();
+        let oldest = 1 - state.active;
+        let dropped = self.segments[oldest as usize].len();
+        self.segments[oldest as usize].clear();
+        self.state.set(RotatingLogState {
+            active: oldest,
+            first_index: state.first_index + dropped,
+        });
+    }
+
+    fn active(&self) -> &LogExt<T, M> {
+        &self.segments[self.state.get().active as usize]
+    }
+
+    fn oldest(&self) -> &LogExt<T, M> {
+        &self.segments[1 - self.state.get().active as usize]
+    }
+}
+
+impl<T: Storable, M: Memory> MemoryUsage for RotatingLog<T, M> {
+    fn allocated_bytes(&self) -> u64 {
+        self.size_bytes() + CELL_HEADER_SIZE + ROTATING_LOG_STATE_SIZE as u64
+    }
+
+    fn used_bytes(&self) -> Option<u64> {
+        self.segments
+            .iter()
+            .map(|segment| segment.used_bytes())
+            .sum()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use ic_stable_structures::VectorMemory;
+
+    use super::*;
+
+    struct Memories {
+        state: VectorMemory,
+        first_segment: (VectorMemory, VectorMemory),
+        second_segment: (VectorMemory, VectorMemory),
+    }
+
+    impl Memories {
+        fn new() -> Self {
+            Self {
+                state: VectorMemory::default(),
+                first_segment: (VectorMemory::default(), VectorMemory::default()),
+                second_segment: (VectorMemory::default(), VectorMemory::default()),
+            }
+        }
+
+        fn init<T: Storable>(&self, policy: RotationPolicy) -> RotatingLog<T, VectorMemory> {
+            RotatingLog::init(
+                self.state.clone(),
+                self.first_segment.clone(),
+                self.second_segment.clone(),
+                policy,
+            )
+        }
+    }
+
+    fn entries_policy(max_segment_entries: u64) -> RotationPolicy {
+        RotationPolicy {
+            max_segment_entries,
+            ..Default::default()
+        }
+    }
+
+    fn retained(log: &RotatingLog<u64, VectorMemory>) -> Vec<u64> {
+        (log.first_index()..log.next_index())
+            .map(|index| log.get(index).unwrap())
+            .collect()
+    }
+
+    #[test]
+    fn should_append_and_get_without_rotation() {
+        let mut log = Memories::new().init(RotationPolicy::default());
+        assert!(log.is_empty());
+        assert_eq!(log.get(0), None);
+
+        for i in 0..10u64 {
+            assert_eq!(log.append(i * 10), Ok(i));
+        }
+
+        assert_eq!(log.len(), 10);
+        assert_eq!(log.first_index(), 0);
+        assert_eq!(log.get(3), Some(30));
+        assert_eq!(log.get(10), None);
+    }
+
+    #[test]
+    fn should_drop_the_oldest_segment_when_the_entries_budget_is_exceeded() {
+        let mut log = Memories::new().init(entries_policy(3));
+
+        for i in 0..3u64 {
+            assert_eq!(log.append(i), Ok(i));
+        }
+        // the first rotation drops the empty segment
+        assert_eq!(log.append(3), Ok(3));
+        assert_eq!(retained(&log), vec![0, 1, 2, 3]);
+
+        for i in 4..10u64 {
+            assert_eq!(log.append(i), Ok(i));
+        }
+
+        assert_eq!(log.first_index(), 6);
+        assert_eq!(log.next_index(), 10);
+        assert_eq!(log.len(), 4);
+        assert_eq!(retained(&log), vec![6, 7, 8, 9]);
+        assert_eq!(log.get(5), None);
+        assert_eq!(log.get(0), None);
+        assert_eq!(log.get(10), None);
+
+        for i in 10..100u64 {
+            assert_eq!(log.append(i), Ok(i));
+            assert!(log.len() > 3 && log.len() <= 6);
+            assert_eq!(log.get(i), Some(i));
+        }
+        assert_eq!(retained(&log), vec![96, 97, 98, 99]);
+    }
+
+    #[test]
+    fn should_drop_the_oldest_segment_when_the_bytes_budget_is_exceeded() {
+        let mut segment = LogExt::new(VectorMemory::default(), VectorMemory::default());
+        let empty_size = segment.size_bytes();
+        segment.append(vec![0u8; 100]).unwrap();
+        let value_size = segment.size_bytes() - empty_size;
+
+        let policy = RotationPolicy {
+            max_segment_bytes: empty_size + 3 * value_size,
+            ..Default::default()
+        };
+        let mut log = Memories::new().init(policy);
+
+        for i in 0..10u8 {
+            log.append(vec![i; 100]).unwrap();
+        }
+
+        assert_eq!(log.first_index(), 6);
+        assert_eq!(log.len(), 4);
+        assert_eq!(log.get(6), Some(vec![6u8; 100]));
+        assert_eq!(log.get(5), None);
+    }
+
+    #[test]
+    fn should_clear_keeping_the_global_indices() {
+        let mut log = Memories::new().init(entries_policy(2));
+        for i in 0..5u64 {
+            log.append(i).unwrap();
+        }
+
+        log.clear();
+        assert!(log.is_empty());
+        assert_eq!(log.get(4), None);
+        assert_eq!(log.first_index(), 5);
+        assert_eq!(log.append(5), Ok(5));
+        assert_eq!(retained(&log), vec![5]);
+    }
+
+    #[test]
+    fn should_reuse_existing_data_on_init() {
+        let memories = Memories::new();
+        {
+            let mut log = memories.init(entries_policy(3));
+            for i in 0..8u64 {
+                log.append(i).unwrap();
+            }
+        }
+
+        let mut log = memories.init::<u64>(entries_policy(3));
+        assert_eq!(retained(&log), vec![3, 4, 5, 6, 7]);
+        assert_eq!(log.append(8), Ok(8));
+        assert_eq!(log.append(9), Ok(9));
+        assert_eq!(retained(&log), vec![6, 7, 8, 9]);
+    }
+}
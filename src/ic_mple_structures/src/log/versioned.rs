@@ -0,0 +1,152 @@
+use std::marker::PhantomData;
+
+use ic_stable_structures::{Memory, log::WriteError};
+
+use crate::{
+    common::{Codec, MemoryUsage},
+    log::{LogExt, LogStructure},
+};
+
+/// A versioned stable log.
+///
+/// The values are stored as the codec type and decoded on read.
+pub struct VersionedLog<T, C: Codec<T>, M: Memory> {
+    inner: LogExt<C, M>,
+    phantom_t: PhantomData<T>,
+}
+
+impl<T, C: Codec<T>, M: Memory> VersionedLog<T, C, M> {
+    /// Create new storage for values with `T` type,
+    /// overwriting any data structures the memory might have
+    /// contained previously
+    pub fn new(index_memory: M, data_memory: M) -> Self {
+        Self::with_log(LogExt::new(index_memory, data_memory))
+    }
+
+    /// Create new storage for values with `T` type.
+    ///
+    /// PRECONDITION: the memories are either empty or contain valid
+    /// log data.
+    pub fn init(index_memory: M, data_memory: M) -> Self {
+        Self::with_log(LogExt::init(index_memory, data_memory))
+    }
+
+    /// Create new storage from a log of encoded values.
+    pub fn with_log(log: LogExt<C, M>) -> Self {
+        Self {
+            inner: log,
+            phantom_t: PhantomData,
+        }
+    }
+}
+
+impl<T, C: Codec<T>, M: Memory> LogStructure<T> for VersionedLog<T, C, M> {
+    fn get(&self, index: u64) -> Option<T> {
+        self.inner.get(index).map(C::decode)
+    }
+
+    fn append(&mut self, value: T) -> Result<u64, WriteError> {
+        self.inner.append(C::encode(value))
+    }
+
+    fn len(&self) -> u64 {
+        self.inner.len()
+    }
+
+    fn is_empty(&self) -> bool {
+        self.inner.is_empty()
+    }
+
+    fn size_bytes(&self) -> u64 {
+        self.inner.size_bytes()
+    }
+
+    fn clear(&mut self) {
+        self.inner.clear()
+    }
+}
+
+impl<T, C: Codec<T>, M: Memory> MemoryUsage for VersionedLog<T, C, M> {
+    fn allocated_bytes(&self) -> u64 {
+        self.inner.allocated_bytes()
+    }
+
+    fn used_bytes(&self) -> Option<u64> {
+        self.inner.used_bytes()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use ic_stable_structures::VectorMemory;
+
+    use crate::test_utils::{UserCodec, UserV1, UserV2};
+
+    use super::*;
+
+    fn user(name: &str, age: Option<u8>) -> UserV2 {
+        UserV2 {
+            name: name.to_string(),
+            age,
+        }
+    }
+
+    #[test]
+    fn should_read_old_and_new_values() {
+        let memory_1 = VectorMemory::default();
+        let memory_2 = VectorMemory::default();
+
+        {
+            let mut raw_log = LogExt::init(memory_1.clone(), memory_2.clone());
+            raw_log
+                .append(UserCodec::V1(UserV1("roger".to_string())))
+                .unwrap();
+        }
+        {
+            let mut log =
+                VersionedLog::<UserV2, UserCodec, _>::init(memory_1.clone(), memory_2.clone());
+            assert_eq!(log.append(user("brian", Some(42))).unwrap(), 1);
+        }
+        {
+            let mut raw_log = LogExt::init(memory_1.clone(), memory_2.clone());
+            raw_log
+                .append(UserCodec::V1(UserV1("freddie".to_string())))
+                .unwrap();
+            assert_eq!(raw_log.get(1), Some(UserCodec::V2(user("brian", Some(42)))));
+        }
+
+        let mut log = VersionedLog::<UserV2, UserCodec, _>::init(memory_1, memory_2);
+        assert_eq!(log.len(), 3);
+        assert_eq!(log.get(0), Some(user("roger", None)));
+        assert_eq!(log.get(1), Some(user("brian", Some(42))));
+        assert_eq!(log.get(2), Some(user("freddie", None)));
+        assert_eq!(log.get(3), None);
+        assert_eq!(
+            log.get_range(1, 5),
+            vec![user("brian", Some(42)), user("freddie", None)]
+        );
+        assert_eq!(
+            log.iter_from(2).collect::<Vec<_>>(),
+            vec![user("freddie", None)]
+        );
+
+        log.clear();
+        assert!(log.is_empty());
+    }
+
+    #[test]
+    fn should_erase_existing_data_on_new() {
+        let memory_1 = VectorMemory::default();
+        let memory_2 = VectorMemory::default();
+
+        {
+            let mut log =
+                VersionedLog::<UserV2, UserCodec, _>::new(memory_1.clone(), memory_2.clone());
+            log.append(user("roger", None)).unwrap();
+        }
+
+        let log = VersionedLog::<UserV2, UserCodec, _>::new(memory_1, memory_2);
+        assert!(log.is_empty());
+        assert_eq!(log.get(0), None);
+    }
+}
@@ -1,9 +1,10 @@
 use std::hash::Hash;
+use std::ops::RangeBounds;
 
 use ic_stable_structures::{Memory, Storable};
 
 use crate::{
-    common::{Bounded, LruCache},
+    common::{Bounded, CacheStats, LruCache},
     multimap::{MultimapStructure, StableMultimap},
 };
 
@@ -52,6 +53,32 @@ where
     pub fn inner(&self) -> &StableMultimap<K1, K2, V, M> {
         &self.inner
     }
+
+    /// Drops the cached value of the keys, so that the next read loads it from the inner map.
+    ///
+    /// Only the cache is touched: unlike `remove`, the entry is not removed from the inner map.
+    pub fn invalidate(&mut self, first_key: &K1, second_key: &K2) {
+        self.cache.remove(&(first_key.clone(), second_key.clone()));
+    }
+
+    /// Drops all the cached values, so that the next reads load them from the inner map.
+    ///
+    /// Only the cache is touched: unlike `clear`, which erases the stable memory,
+    /// the inner map is left untouched.
+    pub fn invalidate_all(&mut self) {
+        self.cache.clear();
+    }
+
+    /// Returns the hit, miss, insertion and eviction counters of the cache.
+    /// The counters are not persisted, so they restart from zero after an upgrade.
+    pub fn cache_stats(&self) -> CacheStats {
+        self.cache.stats()
+    }
+
+    /// Resets the counters of the cache.
+    pub fn reset_cache_stats(&self) {
+        self.cache.reset_stats();
+    }
 }
 
 impl<K1, K2, V, M> MultimapStructure<K1, K2, V> for CachedStableMultimap<K1, K2, V, M>
@@ -92,10 +119,19 @@ where
         self.inner.remove(first_key, second_key)
     }
 
-    fn remove_partial(&mut self, first_key: &K1) -> bool {
-        // Is it possible to remove only the partial keys?
-        self.cache.clear();
-        self.inner.remove_partial(first_key)
+    fn remove_partial(&mut self, first_key: &K1) -> Vec<(K2, V)> {
+        let removed = self.inner.remove_partial(first_key);
+        for (second_key, _) in &removed {
+            self.cache.remove(&(first_key.clone(), second_key.clone()));
+        }
+        removed
+    }
+
+    fn remove_partial_count(&mut self, first_key: &K1) -> u64 {
+        for key in self.inner.partial_keys(first_key) {
+            self.cache.remove(&key);
+        }
+        self.inner.remove_partial_count(first_key)
     }
 
     fn pop_first(&mut self) -> Option<((K1, K2), V)> {
@@ -127,9 +163,21 @@ where
         self.inner.range(first_key)
     }
 
+    fn range_partial(
+        &self,
+        first_key: &K1,
+        second_range: impl RangeBounds<K2>,
+    ) -> impl Iterator<Item = (K2, V)> + '_ {
+        self.inner.range_partial(first_key, second_range)
+    }
+
     fn iter(&self) -> Self::Iterator<'_> {
         self.inner.iter()
     }
+
+    fn keys(&self) -> impl Iterator<Item = K1> + '_ {
+        self.inner.keys()
+    }
 }
 
 #[cfg(test)]
@@ -176,8 +224,11 @@ mod test {
         assert_eq!(Some(Array([2u8, 10])), map.get(&2, &1));
         assert_eq!(None, map.get(&3, &1));
 
-        assert!(map.remove_partial(&1));
-        assert!(!map.remove_partial(&1));
+        assert_eq!(
+            map.remove_partial(&1),
+            vec![(1, Array([1u8, 10])), (2, Array([1u8, 2]))]
+        );
+        assert!(map.remove_partial(&1).is_empty());
 
         assert_eq!(None, map.get(&1, &1));
         assert_eq!(None, map.get(&1, &2));
@@ -185,6 +236,84 @@ mod test {
         assert_eq!(None, map.get(&3, &1));
     }
 
+    #[test]
+    fn should_evict_the_removed_partial_keys() {
+        let mut map =
+            CachedStableMultimap::<u32, u32, Array<2>, _>::new(VectorMemory::default(), 10);
+
+        for second_key in 0..5 {
+            map.insert(&1, &second_key, Array([1u8, second_key as u8]));
+        }
+        map.insert(&2, &0, Array([2u8, 0]));
+
+        assert_eq!(map.remove_partial_count(&1), 5);
+        assert_eq!(map.remove_partial_count(&1), 0);
+
+        for second_key in 0..5 {
+            assert_eq!(None, map.get(&1, &second_key));
+        }
+        assert_eq!(Some(Array([2u8, 0])), map.get(&2, &0));
+
+        map.insert(&1, &3, Array([1u8, 30]));
+        assert_eq!(Some(Array([1u8, 30])), map.get(&1, &3));
+        assert_eq!(None, map.get(&1, &1));
+        assert_eq!(map.remove_partial(&1), vec![(3, Array([1u8, 30]))]);
+        assert_eq!(None, map.get(&1, &3));
+    }
+
+    #[test]
+    fn should_count_cache_hits_and_misses() {
+        let cache_items = 2;
+        let mut inner = StableMultimap::new(VectorMemory::default());
+        inner.insert(&1u32, &1u32, Array([1u8, 1]));
+        inner.insert(&1u32, &2u32, Array([1u8, 2]));
+        let mut map = CachedStableMultimap::with_map(inner, cache_items);
+
+        // misses that load the values from the stable map
+        assert_eq!(map.get(&1, &1), Some(Array([1u8, 1])));
+        assert_eq!(map.get(&1, &2), Some(Array([1u8, 2])));
+        // a miss of a missing key
+        assert_eq!(map.get(&1, &3), None);
+        // hits
+        assert_eq!(map.get(&1, &1), Some(Array([1u8, 1])));
+        assert_eq!(map.get(&1, &2), Some(Array([1u8, 2])));
+        // an insertion that evicts the least recently used key
+        map.insert(&1, &3, Array([1u8, 3]));
+        // a miss of the evicted key
+        assert_eq!(map.get(&1, &1), Some(Array([1u8, 1])));
+
+        assert_eq!(
+            map.cache_stats(),
+            CacheStats {
+                hits: 2,
+                misses: 4,
+                insertions: 4,
+                evictions: 2,
+            }
+        );
+
+        map.reset_cache_stats();
+        assert_eq!(map.cache_stats(), CacheStats::default());
+    }
+
+    #[test]
+    fn should_invalidate_the_cached_values() {
+        let mut map =
+            CachedStableMultimap::<u32, u32, Array<2>, _>::new(VectorMemory::default(), 10);
+        map.insert(&1, &1, Array([1u8, 1]));
+        map.insert(&1, &2, Array([1u8, 2]));
+
+        map.invalidate(&1, &1);
+        assert!(!map.cache.contains_key(&(1, 1)));
+        assert!(map.cache.contains_key(&(1, 2)));
+        assert_eq!(map.get(&1, &1), Some(Array([1u8, 1])));
+
+        map.invalidate_all();
+        assert!(map.cache.is_empty());
+        assert_eq!(map.len(), 2);
+        assert_eq!(map.get(&1, &2), Some(Array([1u8, 2])));
+    }
+
     #[test]
     fn should_clear() {
         let cache_items = 2;
@@ -251,6 +380,9 @@ mod test {
         assert_eq!(iter.next(), Some((1, 1, Array([1u8, 1]))));
         assert_eq!(iter.next(), Some((1, 2, Array([2u8, 1]))));
         assert_eq!(iter.next(), Some((3, 1, Array([3u8, 1]))));
+        assert_eq!(iter.next(), None);
+
+        assert_eq!(map.keys().collect::<Vec<_>>(), vec![1, 3]);
     }
 
     #[test]
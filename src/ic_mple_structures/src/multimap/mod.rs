@@ -1,12 +1,16 @@
+use std::ops::{Bound, RangeBounds};
+
 use ic_stable_structures::{Memory, StableBTreeMap, Storable, btreemap};
 
 use crate::common::Bounded;
 
 #[cfg(feature = "cached")]
 mod cached;
+mod versioned;
 
 #[cfg(feature = "cached")]
 pub use cached::CachedStableMultimap;
+pub use versioned::VersionedStableMultimap;
 
 pub trait MultimapStructure<K1, K2, V> {
     /// iterator over the whole map
@@ -39,11 +43,18 @@ pub trait MultimapStructure<K1, K2, V> {
     ///   - `second_key.to_bytes().len() <= K2::MAX_SIZE`
     fn remove(&mut self, first_key: &K1, second_key: &K2) -> Option<V>;
 
-    /// Remove all values for the partial key
+    /// Remove all values for the partial key and return them, sorted by the second key.
+    ///
+    /// # Preconditions:
+    ///   - `first_key.to_bytes().len() <= K1::MAX_SIZE`
+    fn remove_partial(&mut self, first_key: &K1) -> Vec<(K2, V)>;
+
+    /// Remove all values for the partial key and return how many were removed.
+    /// Prefer this to `remove_partial` when the removed values are not needed.
     ///
     /// # Preconditions:
     ///   - `first_key.to_bytes().len() <= K1::MAX_SIZE`
-    fn remove_partial(&mut self, first_key: &K1) -> bool;
+    fn remove_partial_count(&mut self, first_key: &K1) -> u64;
 
     /// Removes and returns the first element in the map. The key of this element is the minimum key that was in the map
     fn pop_first(&mut self) -> Option<((K1, K2), V)>;
@@ -60,9 +71,20 @@ pub trait MultimapStructure<K1, K2, V> {
     /// Iterator over all the entries that korrespond to the `first_key`
     fn range(&self, first_key: &K1) -> Self::RangeIterator<'_>;
 
-    /// Iterator over all items in the map.
+    /// Iterator over the entries that correspond to the `first_key` and whose second key is in `second_range`,
+    /// sorted by the second key.
+    fn range_partial(
+        &self,
+        first_key: &K1,
+        second_range: impl RangeBounds<K2>,
+    ) -> impl Iterator<Item = (K2, V)> + '_;
+
+    /// Iterator over all items in the map, sorted by `(first_key, second_key)`.
     fn iter(&self) -> Self::Iterator<'_>;
 
+    /// Iterator over the distinct first keys in the map, in ascending order.
+    fn keys(&self) -> impl Iterator<Item = K1> + '_;
+
     /// Remove all entries from the map.
     fn clear(&mut self);
 }
@@ -101,6 +123,13 @@ where
     pub fn iter_from_prev_key(&self, key: &(K1, K2)) -> StableMultimapIter<'_, K1, K2, V, M> {
         StableMultimapIter::new(self.0.iter_from_prev_key(key))
     }
+
+    /// Returns all the keys that correspond to the `first_key`, without reading their values.
+    pub(crate) fn partial_keys(&self, first_key: &K1) -> Vec<(K1, K2)> {
+        self.0
+            .keys_range((first_key.clone(), K2::MIN)..=(first_key.clone(), K2::MAX))
+            .collect()
+    }
 }
 
 impl<K1, K2, V, M> MultimapStructure<K1, K2, V> for StableMultimap<K1, K2, V, M>
@@ -133,18 +162,21 @@ where
         self.0.remove(&(first_key.clone(), second_key.clone()))
     }
 
-    fn remove_partial(&mut self, first_key: &K1) -> bool {
-        let keys: Vec<_> = self
-            .0
-            .range((first_key.clone(), K2::MIN)..=(first_key.clone(), K2::MAX))
-            .map(|entry| entry.key().clone())
-            .collect();
+    fn remove_partial(&mut self, first_key: &K1) -> Vec<(K2, V)> {
+        self.partial_keys(first_key)
+            .into_iter()
+            .filter_map(|key| {
+                let value = self.0.remove(&key)?;
+                Some((key.1, value))
+            })
+            .collect()
+    }
 
-        let mut found = false;
-        for k in keys {
-            found = self.0.remove(&k).is_some() || found;
-        }
-        found
+    fn remove_partial_count(&mut self, first_key: &K1) -> u64 {
+        self.partial_keys(first_key)
+            .into_iter()
+            .filter(|key| self.0.remove(key).is_some())
+            .count() as u64
     }
 
     fn pop_first(&mut self) -> Option<((K1, K2), V)> {
@@ -174,9 +206,41 @@ where
         StableMultimapRangeIter::new(inner)
     }
 
+    /// The scan starts and stops at the bounds of `second_range` within the `first_key` entries.
+    fn range_partial(
+        &self,
+        first_key: &K1,
+        second_range: impl RangeBounds<K2>,
+    ) -> impl Iterator<Item = (K2, V)> + '_ {
+        let start = match second_range.start_bound().cloned() {
+            Bound::Included(second_key) => Bound::Included((first_key.clone(), second_key)),
+            Bound::Excluded(second_key) => Bound::Excluded((first_key.clone(), second_key)),
+            Bound::Unbounded => Bound::Included((first_key.clone(), K2::MIN)),
+        };
+        let end = match second_range.end_bound().cloned() {
+            Bound::Included(second_key) => Bound::Included((first_key.clone(), second_key)),
+            Bound::Excluded(second_key) => Bound::Excluded((first_key.clone(), second_key)),
+            Bound::Unbounded => Bound::Included((first_key.clone(), K2::MAX)),
+        };
+        StableMultimapRangeIter::new(self.0.range((start, end)))
+    }
+
     fn iter(&self) -> Self::Iterator<'_> {
         StableMultimapIter::new(self.0.iter())
     }
+
+    /// Each first key is found with a lookup that skips all the entries of the previous one.
+    fn keys(&self) -> impl Iterator<Item = K1> + '_ {
+        std::iter::successors(self.0.keys().next().map(|key| key.0), |first_key| {
+            self.0
+                .keys_range((
+                    Bound::Excluded((first_key.clone(), K2::MAX)),
+                    Bound::Unbounded,
+                ))
+                .next()
+                .map(|key| key.0)
+        })
+    }
 }
 
 /// Range iterator
@@ -368,11 +432,65 @@ mod test {
         let val = Array([123, 200u8, 200, 100, 100, 255]);
         mm.insert(&k1, &k2, val);
 
-        assert!(mm.remove_partial(&k1));
-        assert!(!mm.remove_partial(&k1));
+        assert_eq!(
+            mm.remove_partial(&k1),
+            vec![
+                (
+                    Array([11u8, 12, 13]),
+                    Array([200u8, 200, 200, 100, 100, 123])
+                ),
+                (
+                    Array([21u8, 22, 23]),
+                    Array([123, 200u8, 200, 100, 100, 255])
+                )
+            ]
+        );
+        assert!(mm.remove_partial(&k1).is_empty());
         assert!(mm.is_empty());
     }
 
+    #[test]
+    fn remove_partial_with_many_entries() {
+        let mut mm = StableMultimap::new(VectorMemory::default());
+        for second_key in 0..100u32 {
+            mm.insert(&1u32, &second_key, second_key * 10);
+            mm.insert(&2u32, &second_key, second_key);
+        }
+
+        let removed = mm.remove_partial(&1);
+        assert_eq!(removed.len(), 100);
+        assert_eq!(removed[0], (0, 0));
+        assert_eq!(removed[99], (99, 990));
+        assert_eq!(mm.len(), 100);
+        assert_eq!(mm.range(&1).next(), None);
+
+        assert_eq!(mm.remove_partial_count(&2), 100);
+        assert!(mm.is_empty());
+    }
+
+    #[test]
+    fn remove_partial_of_a_missing_key() {
+        let mut mm = make_map();
+
+        assert!(mm.remove_partial(&Array([3u8, 4])).is_empty());
+        assert_eq!(mm.remove_partial_count(&Array([3u8, 4])), 0);
+        assert_eq!(mm.len(), 2);
+    }
+
+    #[test]
+    fn insert_after_remove_partial() {
+        let mut mm = StableMultimap::new(VectorMemory::default());
+        mm.insert(&1u32, &1u32, 11u32);
+        mm.insert(&1u32, &2u32, 12u32);
+
+        assert_eq!(mm.remove_partial_count(&1), 2);
+
+        assert_eq!(mm.insert(&1, &2, 120), None);
+        assert_eq!(mm.get(&1, &1), None);
+        assert_eq!(mm.get(&1, &2), Some(120));
+        assert_eq!(mm.remove_partial(&1), vec![(2, 120)]);
+    }
+
     #[test]
     fn clear() {
         let mut mm = StableMultimap::new(VectorMemory::default());
@@ -409,6 +527,56 @@ mod test {
         assert!(iter.next().is_none());
     }
 
+    #[test]
+    fn range_partial() {
+        let mut map = StableMultimap::new(VectorMemory::default());
+        for timestamp in 1..=5u64 {
+            map.insert(&1u32, &timestamp, timestamp * 10);
+            map.insert(&2u32, &timestamp, timestamp * 100);
+        }
+
+        assert_eq!(
+            map.range_partial(&1, 2..4).collect::<Vec<_>>(),
+            vec![(2, 20), (3, 30)]
+        );
+        assert_eq!(
+            map.range_partial(&1, 2..=4).collect::<Vec<_>>(),
+            vec![(2, 20), (3, 30), (4, 40)]
+        );
+        assert_eq!(
+            map.range_partial(&1, (Bound::Excluded(2), Bound::Included(4)))
+                .collect::<Vec<_>>(),
+            vec![(3, 30), (4, 40)]
+        );
+        assert_eq!(
+            map.range_partial(&2, ..3).collect::<Vec<_>>(),
+            vec![(1, 100), (2, 200)]
+        );
+        assert_eq!(
+            map.range_partial(&2, 4..).collect::<Vec<_>>(),
+            vec![(4, 400), (5, 500)]
+        );
+        assert_eq!(map.range_partial(&1, ..).count(), 5);
+    }
+
+    #[test]
+    fn range_partial_should_not_return_the_entries_of_other_keys() {
+        let mut map = StableMultimap::new(VectorMemory::default());
+        map.insert(&1u32, &1u64, 10u64);
+        map.insert(&1u32, &2u64, 20u64);
+        map.insert(&3u32, &0u64, 300u64);
+        map.insert(&3u32, &1u64, 310u64);
+
+        assert_eq!(map.range_partial(&1, 3..).next(), None);
+        assert_eq!(map.range_partial(&1, 3..=u64::MAX).next(), None);
+        assert_eq!(map.range_partial(&2, ..).next(), None);
+        assert_eq!(
+            map.range_partial(&3, ..1).collect::<Vec<_>>(),
+            vec![(0, 300)]
+        );
+        assert_eq!(map.range_partial(&4, ..).next(), None);
+    }
+
     #[test]
     fn iter_from_prev_key() {
         let mm = make_map();
@@ -503,8 +671,8 @@ mod test {
         assert_eq!(range.next(), Some((1, 84)));
         assert_eq!(range.next(), None);
 
-        assert!(map.remove_partial(&0));
-        assert!(!map.remove_partial(&0));
+        assert_eq!(map.remove_partial(&0), vec![(0, 42), (1, 84)]);
+        assert_eq!(map.remove_partial_count(&0), 0);
         assert_eq!(map.len(), 2);
 
         assert_eq!(map.remove(&1, &0), Some(10));
@@ -512,6 +680,44 @@ mod test {
         assert_eq!(map.len(), 1);
     }
 
+    #[test]
+    fn should_iterate_sorted_by_both_keys() {
+        let mut map = StableMultimap::new(VectorMemory::default());
+        map.insert(&3u32, &1u32, 31u32);
+        map.insert(&1u32, &2u32, 12u32);
+        map.insert(&2u32, &1u32, 21u32);
+        map.insert(&1u32, &1u32, 11u32);
+        map.insert(&3u32, &0u32, 30u32);
+
+        assert_eq!(
+            map.iter().collect::<Vec<_>>(),
+            vec![(1, 1, 11), (1, 2, 12), (2, 1, 21), (3, 0, 30), (3, 1, 31)]
+        );
+        assert_eq!(map.len(), 5);
+    }
+
+    #[test]
+    fn should_iterate_distinct_first_keys() {
+        let mut map = StableMultimap::new(VectorMemory::default());
+        assert_eq!(map.keys().next(), None);
+
+        map.insert(&3u32, &1u32, 31u32);
+        map.insert(&1u32, &2u32, 12u32);
+        map.insert(&1u32, &1u32, 11u32);
+        map.insert(&3u32, &0u32, 30u32);
+        map.insert(&3u32, &u32::MAX, 0u32);
+        map.insert(&4u32, &0u32, 40u32);
+
+        assert_eq!(map.keys().collect::<Vec<_>>(), vec![1, 3, 4]);
+
+        map.remove_partial(&3);
+        assert_eq!(map.keys().collect::<Vec<_>>(), vec![1, 4]);
+
+        map.clear();
+        assert!(map.is_empty());
+        assert_eq!(map.keys().next(), None);
+    }
+
     #[test]
     fn test_pop_first() {
         let mut map = StableMultimap::new(VectorMemory::default());
@@ -0,0 +1,311 @@
+use std::marker::PhantomData;
+use std::ops::RangeBounds;
+
+use ic_stable_structures::{Memory, Storable};
+
+use crate::{
+    common::{Bounded, Codec},
+    multimap::{MultimapStructure, StableMultimap, StableMultimapIter, StableMultimapRangeIter},
+};
+
+/// A versioned `StableMultimap`.
+///
+/// The values are stored as the codec type and decoded on read.
+pub struct VersionedStableMultimap<K1, K2, V, C: Codec<V>, M>
+where
+    K1: Storable + Ord + Clone,
+    K2: Storable + Ord + Clone + Bounded,
+    M: Memory,
+{
+    inner: StableMultimap<K1, K2, C, M>,
+    phantom_v: PhantomData<V>,
+}
+
+impl<K1, K2, V, C: Codec<V>, M> VersionedStableMultimap<K1, K2, V, C, M>
+where
+    K1: Storable + Ord + Clone,
+    K2: Storable + Ord + Clone + Bounded,
+    M: Memory,
+{
+    /// Create new instance of the VersionedStableMultimap,
+    /// overwriting any data structures the memory might have
+    /// contained previously
+    pub fn new(memory: M) -> Self {
+        Self::with_map(StableMultimap::new(memory))
+    }
+
+    /// Create new instance of the VersionedStableMultimap.
+    ///
+    /// PRECONDITION: the memory is either empty or contains a valid
+    /// StableMultimap.
+    pub fn init(memory: M) -> Self {
+        Self::with_map(StableMultimap::init(memory))
+    }
+
+    /// Create new instance of the VersionedStableMultimap.
+    pub fn with_map(map: StableMultimap<K1, K2, C, M>) -> Self {
+        Self {
+            inner: map,
+            phantom_v: PhantomData,
+        }
+    }
+
+    /// Returns upper bound iterator for the given pair of keys.
+    pub fn iter_from_prev_key(
+        &self,
+        key: &(K1, K2),
+    ) -> VersionedStableMultimapIter<'_, K1, K2, V, C, M> {
+        VersionedStableMultimapIter(self.inner.iter_from_prev_key(key), PhantomData)
+    }
+}
+
+impl<K1, K2, V, C: Codec<V>, M> MultimapStructure<K1, K2, V>
+    for VersionedStableMultimap<K1, K2, V, C, M>
+where
+    K1: Storable + Ord + Clone,
+    K2: Storable + Ord + Clone + Bounded,
+    M: Memory,
+{
+    type Iterator<'a>
+        = VersionedStableMultimapIter<'a, K1, K2, V, C, M>
+    where
+        Self: 'a;
+
+    type RangeIterator<'a>
+        = VersionedStableMultimapRangeIter<'a, K1, K2, V, C, M>
+    where
+        Self: 'a;
+
+    fn get(&self, first_key: &K1, second_key: &K2) -> Option<V> {
+        self.inner.get(first_key, second_key).map(C::decode)
+    }
+
+    fn insert(&mut self, first_key: &K1, second_key: &K2, value: V) -> Option<V> {
+        self.inner
+            .insert(first_key, second_key, C::encode(value))
+            .map(C::decode)
+    }
+
+    fn remove(&mut self, first_key: &K1, second_key: &K2) -> Option<V> {
+        self.inner.remove(first_key, second_key).map(C::decode)
+    }
+
+    fn remove_partial(&mut self, first_key: &K1) -> Vec<(K2, V)> {
+        self.inner
+            .remove_partial(first_key)
+            .into_iter()
+            .map(|(second_key, value)| (second_key, C::decode(value)))
+            .collect()
+    }
+
+    fn remove_partial_count(&mut self, first_key: &K1) -> u64 {
+        self.inner.remove_partial_count(first_key)
+    }
+
+    fn pop_first(&mut self) -> Option<((K1, K2), V)> {
+        self.inner.pop_first().map(|(k, v)| (k, C::decode(v)))
+    }
+
+    fn pop_last(&mut self) -> Option<((K1, K2), V)> {
+        self.inner.pop_last().map(|(k, v)| (k, C::decode(v)))
+    }
+
+    fn len(&self) -> u64 {
+        self.inner.len()
+    }
+
+    fn is_empty(&self) -> bool {
+        self.inner.is_empty()
+    }
+
+    fn range(&self, first_key: &K1) -> Self::RangeIterator<'_> {
+        VersionedStableMultimapRangeIter(self.inner.range(first_key), PhantomData)
+    }
+
+    fn range_partial(
+        &self,
+        first_key: &K1,
+        second_range: impl RangeBounds<K2>,
+    ) -> impl Iterator<Item = (K2, V)> + '_ {
+        self.inner
+            .range_partial(first_key, second_range)
+            .map(|(second_key, value)| (second_key, C::decode(value)))
+    }
+
+    fn iter(&self) -> Self::Iterator<'_> {
+        VersionedStableMultimapIter(self.inner.iter(), PhantomData)
+    }
+
+    fn keys(&self) -> impl Iterator<Item = K1> + '_ {
+        self.inner.keys()
+    }
+
+    fn clear(&mut self) {
+        self.inner.clear()
+    }
+}
+
+/// Range iterator
+pub struct VersionedStableMultimapRangeIter<'a, K1, K2, V, C: Codec<V>, M>(
+    StableMultimapRangeIter<'a, K1, K2, C, M>,
+    PhantomData<V>,
+)
+where
+    K1: Storable + Ord + Clone,
+    K2: Storable + Ord + Clone,
+    M: Memory;
+
+impl<K1, K2, V, C: Codec<V>, M> Iterator for VersionedStableMultimapRangeIter<'_, K1, K2, V, C, M>
+where
+    K1: Storable + Ord + Clone,
+    K2: Storable + Ord + Clone,
+    M: Memory,
+{
+    type Item = (K2, V);
+
+    fn next(&mut self) -> Option<(K2, V)> {
+        self.0
+            .next()
+            .map(|(second_key, value)| (second_key, C::decode(value)))
+    }
+}
+
+pub struct VersionedStableMultimapIter<'a, K1, K2, V, C: Codec<V>, M>(
+    StableMultimapIter<'a, K1, K2, C, M>,
+    PhantomData<V>,
+)
+where
+    K1: Storable + Ord + Clone,
+    K2: Storable + Ord + Clone,
+    M: Memory;
+
+impl<K1, K2, V, C: Codec<V>, M> Iterator for VersionedStableMultimapIter<'_, K1, K2, V, C, M>
+where
+    K1: Storable + Ord + Clone,
+    K2: Storable + Ord + Clone,
+    M: Memory,
+{
+    type Item = (K1, K2, V);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.0
+            .next()
+            .map(|(first_key, second_key, value)| (first_key, second_key, C::decode(value)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use ic_stable_structures::VectorMemory;
+
+    use super::*;
+    use crate::test_utils::{UserCodec, UserV1, UserV2};
+
+    fn user(name: &str, age: Option<u8>) -> UserV2 {
+        UserV2 {
+            name: name.to_string(),
+            age,
+        }
+    }
+
+    fn make_map() -> VersionedStableMultimap<u32, u64, UserV2, UserCodec, VectorMemory> {
+        let mut multimap = StableMultimap::new(VectorMemory::default());
+        multimap.insert(&1u32, &10u64, UserCodec::V1(UserV1("roger".to_string())));
+        multimap.insert(&1, &11, UserCodec::V2(user("brian", Some(42))));
+        multimap.insert(&2, &20, UserCodec::V1(UserV1("freddie".to_string())));
+
+        VersionedStableMultimap::with_map(multimap)
+    }
+
+    #[test]
+    fn should_read_values_of_mixed_versions() {
+        let map = make_map();
+
+        assert_eq!(map.get(&1, &10), Some(user("roger", None)));
+        assert_eq!(map.get(&1, &11), Some(user("brian", Some(42))));
+        assert_eq!(map.get(&2, &20), Some(user("freddie", None)));
+        assert_eq!(map.get(&2, &21), None);
+        assert_eq!(map.len(), 3);
+    }
+
+    #[test]
+    fn should_insert_and_replace() {
+        let mut map = make_map();
+
+        assert_eq!(
+            map.insert(&1, &10, user("john", Some(24))),
+            Some(user("roger", None))
+        );
+        assert_eq!(map.insert(&3, &30, user("paul", None)), None);
+
+        assert_eq!(map.get(&1, &10), Some(user("john", Some(24))));
+        assert_eq!(map.get(&3, &30), Some(user("paul", None)));
+        assert_eq!(map.len(), 4);
+    }
+
+    #[test]
+    fn should_remove() {
+        let mut map = make_map();
+
+        assert_eq!(map.remove(&1, &10), Some(user("roger", None)));
+        assert_eq!(map.remove(&1, &10), None);
+        assert_eq!(map.remove_partial(&1), vec![(11, user("brian", Some(42)))]);
+        assert_eq!(map.remove_partial_count(&1), 0);
+        assert_eq!(map.len(), 1);
+
+        assert_eq!(map.pop_first(), Some(((2, 20), user("freddie", None))));
+        assert!(map.is_empty());
+    }
+
+    #[test]
+    fn should_pop_last_and_clear() {
+        let mut map = make_map();
+
+        assert_eq!(map.pop_last(), Some(((2, 20), user("freddie", None))));
+        map.clear();
+        assert!(map.is_empty());
+    }
+
+    #[test]
+    fn should_iterate() {
+        let map = make_map();
+
+        assert_eq!(
+            map.iter().collect::<Vec<_>>(),
+            vec![
+                (1, 10, user("roger", None)),
+                (1, 11, user("brian", Some(42))),
+                (2, 20, user("freddie", None))
+            ]
+        );
+        assert_eq!(
+            map.range(&1).collect::<Vec<_>>(),
+            vec![(10, user("roger", None)), (11, user("brian", Some(42)))]
+        );
+        assert_eq!(map.keys().collect::<Vec<_>>(), vec![1, 2]);
+        assert_eq!(
+            map.range_partial(&1, 11..).collect::<Vec<_>>(),
+            vec![(11, user("brian", Some(42)))]
+        );
+        assert_eq!(
+            map.iter_from_prev_key(&(2, 20))
+                .map(|(first_key, second_key, _)| (first_key, second_key))
+                .collect::<Vec<_>>(),
+            vec![(1, 11), (2, 20)]
+        );
+    }
+
+    #[test]
+    fn should_reuse_existing_data_on_init() {
+        let memory = VectorMemory::default();
+        {
+            let mut map =
+                VersionedStableMultimap::<u32, u64, UserV2, UserCodec, _>::new(memory.clone());
+            map.insert(&1, &10, user("john", None));
+        }
+
+        let map = VersionedStableMultimap::<u32, u64, UserV2, UserCodec, _>::init(memory);
+        assert_eq!(map.get(&1, &10), Some(user("john", None)));
+    }
+}
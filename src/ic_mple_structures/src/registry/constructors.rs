@@ -0,0 +1,219 @@
+//! Constructors of the structures over the virtual memories of a `MemoryManager`.
+//!
+//! The `new_*` functions overwrite any data structures the memories might have contained previously,
+//! the `init_*` functions preserve them.
+
+use std::num::NonZeroU64;
+
+use ic_stable_structures::memory_manager::{MemoryId, MemoryManager, VirtualMemory};
+use ic_stable_structures::{Memory, StableBTreeMap, StableCell, Storable};
+
+use crate::common::StructureError;
+use crate::log::LogExt;
+use crate::ringbuffer::StableRingBuffer;
+use crate::vec::VecExt;
+
+/// Creates a new `StableBTreeMap` in the memory with the given id.
+pub fn new_btreemap<K, V, M>(
+    memory_manager: &MemoryManager<M>,
+    id: MemoryId,
+) -> StableBTreeMap<K, V, VirtualMemory<M>>
+where
+    K: Storable + Ord + Clone,
+    V: Storable,
+    M: Memory,
+{
+    StableBTreeMap::new(memory_manager.get(id))
+}
+
+/// Initializes a `StableBTreeMap` in the memory with the given id.
+pub fn init_btreemap<K, V, M>(
+    memory_manager: &MemoryManager<M>,
+    id: MemoryId,
+) -> StableBTreeMap<K, V, VirtualMemory<M>>
+where
+    K: Storable + Ord + Clone,
+    V: Storable,
+    M: Memory,
+{
+    StableBTreeMap::init(memory_manager.get(id))
+}
+
+/// Creates a new `StableCell` with the given value in the memory with the given id.
+pub fn new_cell<T, M>(
+    memory_manager: &MemoryManager<M>,
+    id: MemoryId,
+    value: T,
+) -> StableCell<T, VirtualMemory<M>>
+where
+    T: Storable,
+    M: Memory,
+{
+    StableCell::new(memory_manager.get(id), value)
+}
+
+/// Initializes a `StableCell` in the memory with the given id.
+/// The `default_value` is used only if the memory is empty.
+pub fn init_cell<T, M>(
+    memory_manager: &MemoryManager<M>,
+    id: MemoryId,
+    default_value: T,
+) -> StableCell<T, VirtualMemory<M>>
+where
+    T: Storable,
+    M: Memory,
+{
+    StableCell::init(memory_manager.get(id), default_value)
+}
+
+/// Creates a new `VecExt` in the memory with the given id.
+pub fn new_vec<T: Storable, M: Memory>(
+    memory_manager: &MemoryManager<M>,
+    id: MemoryId,
+) -> VecExt<T, VirtualMemory<M>> {
+    VecExt::new(memory_manager.get(id))
+}
+
+/// Initializes a `VecExt` in the memory with the given id.
+pub fn init_vec<T: Storable, M: Memory>(
+    memory_manager: &MemoryManager<M>,
+    id: MemoryId,
+) -> VecExt<T, VirtualMemory<M>> {
+    VecExt::init(memory_manager.get(id))
+}
+
+/// Creates a new `LogExt` in the memories with the given ids.
+pub fn new_log<T: Storable, M: Memory>(
+    memory_manager: &MemoryManager<M>,
+    index_id: MemoryId,
+    data_id: MemoryId,
+) -> LogExt<T, VirtualMemory<M>> {
+    LogExt::new(memory_manager.get(index_id), memory_manager.get(data_id))
+}
+
+/// Initializes a `LogExt` in the memories with the given ids.
+pub fn init_log<T: Storable, M: Memory>(
+    memory_manager: &MemoryManager<M>,
+    index_id: MemoryId,
+    data_id: MemoryId,
+) -> LogExt<T, VirtualMemory<M>> {
+    LogExt::init(memory_manager.get(index_id), memory_manager.get(data_id))
+}
+
+/// Creates a new `StableRingBuffer` with the given capacity in the memories with the given ids.
+pub fn new_ring_buffer<T: Storable + Clone, M: Memory>(
+    memory_manager: &MemoryManager<M>,
+    data_id: MemoryId,
+    indices_id: MemoryId,
+    capacity: NonZeroU64,
+) -> StableRingBuffer<T, VirtualMemory<M>, VirtualMemory<M>> {
+    StableRingBuffer::new(
+        memory_manager.get(data_id),
+        memory_manager.get(indices_id),
+        capacity,
+    )
+}
+
+/// Initializes a `StableRingBuffer` in the memories with the given ids.
+/// The `default_capacity` is used only if the memories are empty.
+///
+/// Returns an error if the existing ring buffer is not consistent, see `StableRingBuffer::init`.
+pub fn init_ring_buffer<T: Storable + Clone, M: Memory>(
+    memory_manager: &MemoryManager<M>,
+    data_id: MemoryId,
+    indices_id: MemoryId,
+    default_capacity: NonZeroU64,
+) -> Result<StableRingBuffer<T, VirtualMemory<M>, VirtualMemory<M>>, StructureError> {
+    StableRingBuffer::init(
+        memory_manager.get(data_id),
+        memory_manager.get(indices_id),
+        default_capacity,
+    )
+}
+
+#[cfg(test)]
+mod tests {
+
+    use ic_stable_structures::DefaultMemoryImpl;
+
+    use super::*;
+    use crate::{LogStructure, VecStructure};
+
+    fn memory_manager() -> MemoryManager<DefaultMemoryImpl> {
+        MemoryManager::init(DefaultMemoryImpl::default())
+    }
+
+    #[test]
+    fn should_construct_working_structures() {
+        let memory_manager = memory_manager();
+
+        let mut map = new_btreemap::<u32, u64, _>(&memory_manager, MemoryId::new(0));
+        map.insert(1, 10);
+
+        let mut cell = new_cell(&memory_manager, MemoryId::new(1), 0u64);
+        cell.set(42);
+
+        let mut vec = new_vec::<u64, _>(&memory_manager, MemoryId::new(2));
+        vec.push(&7);
+
+        let mut log = new_log::<u64, _>(&memory_manager, MemoryId::new(3), MemoryId::new(4));
+        log.append(5).unwrap();
+
+        let mut ring_buffer = new_ring_buffer::<u64, _>(
+            &memory_manager,
+            MemoryId::new(5),
+            MemoryId::new(6),
+            NonZeroU64::new(2).unwrap(),
+        );
+        ring_buffer.push(&1);
+        ring_buffer.push(&2);
+        ring_buffer.push(&3);
+
+        assert_eq!(map.get(&1), Some(10));
+        assert_eq!(*cell.get(), 42);
+        assert_eq!(vec.get(0), Some(7));
+        assert_eq!(log.get(0), Some(5));
+        assert_eq!(ring_buffer.len(), 2);
+    }
+
+    #[test]
+    fn should_reuse_existing_data_on_init() {
+        let memory_manager = memory_manager();
+        {
+            let mut map = init_btreemap::<u32, u64, _>(&memory_manager, MemoryId::new(0));
+            map.insert(1, 10);
+            init_cell(&memory_manager, MemoryId::new(1), 0u64).set(42);
+            init_vec::<u64, _>(&memory_manager, MemoryId::new(2)).push(&7);
+            init_log::<u64, _>(&memory_manager, MemoryId::new(3), MemoryId::new(4))
+                .append(5)
+                .unwrap();
+            init_ring_buffer::<u64, _>(
+                &memory_manager,
+                MemoryId::new(5),
+                MemoryId::new(6),
+                NonZeroU64::new(2).unwrap(),
+            )
+            .unwrap()
+            .push(&1);
+        }
+
+        let map = init_btreemap::<u32, u64, _>(&memory_manager, MemoryId::new(0));
+        let cell = init_cell(&memory_manager, MemoryId::new(1), 0u64);
+        let vec = init_vec::<u64, _>(&memory_manager, MemoryId::new(2));
+        let log = init_log::<u64, _>(&memory_manager, MemoryId::new(3), MemoryId::new(4));
+        let ring_buffer = init_ring_buffer::<u64, _>(
+            &memory_manager,
+            MemoryId::new(5),
+            MemoryId::new(6),
+            NonZeroU64::new(10).unwrap(),
+        )
+        .unwrap();
+
+        assert_eq!(map.get(&1), Some(10));
+        assert_eq!(*cell.get(), 42);
+        assert_eq!(vec.get(0), Some(7));
+        assert_eq!(log.get(0), Some(5));
+        assert_eq!(ring_buffer.len(), 1);
+        assert_eq!(ring_buffer.capacity(), 2);
+    }
+}
@@ -0,0 +1,259 @@
+use std::cell::RefCell;
+use std::collections::BTreeSet;
+
+use ic_stable_structures::memory_manager::{MemoryId, MemoryManager, VirtualMemory};
+use ic_stable_structures::{Memory, StableBTreeMap};
+
+use crate::common::{MemoryPages, StructureError, memory_report};
+
+mod constructors;
+
+pub use constructors::*;
+
+/// The raw value of `REGISTRY_MEMORY_ID`, which is also the number of ids that can be allocated.
+const REGISTRY_MEMORY_ID_U8: u8 = 254;
+
+/// The id of the memory where the `MemoryRegistry` stores the name to id mapping.
+/// It is never allocated to a name.
+pub const REGISTRY_MEMORY_ID: MemoryId = MemoryId::new(REGISTRY_MEMORY_ID_U8);
+
+/// Allocates the virtual memories of a `MemoryManager` by name.
+///
+/// The ids are assigned sequentially, starting from 0, and the name to id mapping
+/// is stored in the memory with id `REGISTRY_MEMORY_ID`,
+/// so that the same name gets the same memory after an upgrade.
+///
+/// The registry owns the `MemoryManager`: the memories must not be obtained from
+/// another `MemoryManager` over the same memory.
+pub struct MemoryRegistry<M: Memory> {
+    memory_manager: MemoryManager<M>,
+    /// The persisted name to id mapping
+    ids: RefCell<StableBTreeMap<String, u8, VirtualMemory<M>>>,
+    /// The names allocated by this instance
+    allocated: RefCell<BTreeSet<String>>,
+}
+
+impl<M: Memory> MemoryRegistry<M> {
+    /// Creates a new registry, preserving the existing name to id mapping
+    /// if the memory already contains a registry.
+    ///
+    /// PRECONDITION: the memory is either empty or contains a valid
+    /// `MemoryManager`.
+    pub fn init(memory: M) -> Self {
+        let memory_manager = MemoryManager::init(memory);
+        let ids = StableBTreeMap::init(memory_manager.get(REGISTRY_MEMORY_ID));
+        Self {
+            memory_manager,
+            ids: RefCell::new(ids),
+            allocated: RefCell::new(BTreeSet::new()),
+        }
+    }
+
+    /// Returns the memory with the given name, assigning the next free id to it
+    /// if the name has never been allocated.
+    ///
+    /// Returns an error if:
+    /// - the name has already been allocated by this registry;
+    /// - the next free id is already in use, e.g. because it has been obtained from the `MemoryManager` directly;
+    /// - all the ids have already been assigned.
+    pub fn allocate(&self, name: &str) -> Result<VirtualMemory<M>, StructureError> {
+        if self.allocated.borrow().contains(name) {
+            return Err(StructureError::MemoryAlreadyAllocated(name.to_string()));
+        }
+
+        let mut ids = self.ids.borrow_mut();
+        let memory = match ids.get(&name.to_string()) {
+            Some(id) => self.memory_manager.get(MemoryId::new(id)),
+            None => {
+                if ids.len() >= REGISTRY_MEMORY_ID_U8 as u64 {
+                    return Err(StructureError::MemoryIdsExhausted);
+                }
+                let id = ids.len() as u8;
+
+                let memory = self.memory_manager.get(MemoryId::new(id));
+                if memory.size() > 0 {
+                    return Err(StructureError::MemoryIdInUse(id));
+                }
+                ids.insert(name.to_string(), id);
+                memory
+            }
+        };
+
+        self.allocated.borrow_mut().insert(name.to_string());
+        Ok(memory)
+    }
+
+    /// Returns the id assigned to the name, if any.
+    pub fn id_of(&self, name: &str) -> Option<MemoryId> {
+        self.ids.borrow().get(&name.to_string()).map(MemoryId::new)
+    }
+
+    /// Returns the number of pages of every allocated memory, the registry memory included.
+    pub fn memory_report(&self) -> Vec<MemoryPages> {
+        memory_report(&self.memory_manager)
+    }
+}
+
+/// Declares a struct with all the virtual memories of a canister,
+/// allocated from a `MemoryRegistry` with the names of the fields.
+///
+/// ```
+/// use ic_mple_structures::{DefaultMemoryImpl, MemoryRegistry, declare_memories};
+///
+/// declare_memories! {
+///     pub struct Memories<DefaultMemoryImpl> {
+///         pub tasks,
+///         pub sequence,
+///     }
+/// }
+///
+/// let registry = MemoryRegistry::init(DefaultMemoryImpl::default());
+/// let memories = Memories::allocate(&registry).unwrap();
+/// ```
+#[macro_export]
+macro_rules! declare_memories {
+    (
+        $(#[$meta:meta])*
+        $vis:vis struct $name:ident<$memory:ty> {
+            $($(#[$field_meta:meta])* $field_vis:vis $field:ident),* $(,)?
+        }
+    ) => {
+        $(#[$meta])*
+        $vis struct $name {
+            $($(#[$field_meta])* $field_vis $field: $crate::VirtualMemory<$memory>,)*
+        }
+
+        impl $name {
+            /// Allocates all the memories from the registry, using the field names as memory names.
+            $vis fn allocate(
+                registry: &$crate::MemoryRegistry<$memory>,
+            ) -> ::std::result::Result<Self, $crate::StructureError> {
+                ::std::result::Result::Ok(Self {
+                    $($field: registry.allocate(::std::stringify!($field))?,)*
+                })
+            }
+        }
+    };
+}
+
+#[cfg(test)]
+mod tests {
+
+    use ic_stable_structures::VectorMemory;
+
+    use super::*;
+
+    #[test]
+    fn should_assign_sequential_ids() {
+        let registry = MemoryRegistry::init(VectorMemory::default());
+
+        registry.allocate("tasks").unwrap();
+        registry.allocate("sequence").unwrap();
+
+        assert_eq!(registry.id_of("tasks"), Some(MemoryId::new(0)));
+        assert_eq!(registry.id_of("sequence"), Some(MemoryId::new(1)));
+        assert_eq!(registry.id_of("other"), None);
+    }
+
+    #[test]
+    fn should_return_the_same_memory_after_init() {
+        let memory = VectorMemory::default();
+        {
+            let registry = MemoryRegistry::init(memory.clone());
+            registry.allocate("tasks").unwrap();
+            let sequence = registry.allocate("sequence").unwrap();
+            sequence.grow(1);
+            sequence.write(0, &[1, 2, 3]);
+        }
+
+        let registry = MemoryRegistry::init(memory);
+        // The allocation order does not matter
+        let sequence = registry.allocate("sequence").unwrap();
+        let logs = registry.allocate("logs").unwrap();
+        assert_eq!(registry.id_of("sequence"), Some(MemoryId::new(1)));
+        assert_eq!(registry.id_of("logs"), Some(MemoryId::new(2)));
+
+        let mut bytes = [0; 3];
+        sequence.read(0, &mut bytes);
+        assert_eq!(bytes, [1, 2, 3]);
+        assert_eq!(logs.size(), 0);
+
+        let report = registry.memory_report();
+        assert!(report.contains(&MemoryPages {
+            memory_id: 1,
+            pages: 1
+        }));
+        assert!(
+            report
+                .iter()
+                .any(|pages| pages.memory_id == REGISTRY_MEMORY_ID_U8)
+        );
+    }
+
+    #[test]
+    fn should_return_an_error_if_the_name_is_allocated_twice() {
+        let registry = MemoryRegistry::init(VectorMemory::default());
+
+        registry.allocate("tasks").unwrap();
+        assert_eq!(
+            registry.allocate("tasks").err(),
+            Some(StructureError::MemoryAlreadyAllocated("tasks".to_string()))
+        );
+        assert!(registry.allocate("sequence").is_ok());
+    }
+
+    #[test]
+    fn should_return_an_error_if_the_id_is_used_out_of_band() {
+        let memory = VectorMemory::default();
+        {
+            let memory_manager = MemoryManager::init(memory.clone());
+            memory_manager.get(MemoryId::new(0)).grow(1);
+        }
+
+        let registry = MemoryRegistry::init(memory);
+        assert_eq!(
+            registry.allocate("tasks").err(),
+            Some(StructureError::MemoryIdInUse(0))
+        );
+        assert_eq!(registry.id_of("tasks"), None);
+    }
+
+    #[test]
+    fn should_return_an_error_if_the_ids_are_exhausted() {
+        let registry = MemoryRegistry::init(VectorMemory::default());
+
+        for i in 0..254 {
+            registry.allocate(&format!("memory_{i}")).unwrap();
+        }
+        assert_eq!(
+            registry.allocate("memory_254").err(),
+            Some(StructureError::MemoryIdsExhausted)
+        );
+    }
+
+    declare_memories! {
+        struct TestMemories<VectorMemory> {
+            tasks,
+            sequence,
+        }
+    }
+
+    #[test]
+    fn should_declare_the_memories() {
+        let memory = VectorMemory::default();
+        {
+            let registry = MemoryRegistry::init(memory.clone());
+            let memories = TestMemories::allocate(&registry).unwrap();
+            memories.sequence.grow(1);
+
+            assert_eq!(registry.id_of("tasks"), Some(MemoryId::new(0)));
+            assert_eq!(registry.id_of("sequence"), Some(MemoryId::new(1)));
+            assert!(TestMemories::allocate(&registry).is_err());
+        }
+
+        let registry = MemoryRegistry::init(memory);
+        let memories = TestMemories::allocate(&registry).unwrap();
+        assert_eq!(memories.tasks.size(), 0);
+        assert_eq!(memories.sequence.size(), 1);
+    }
+}
@@ -5,9 +5,31 @@ use std::num::NonZeroU64;
 use ic_stable_structures::storable::Bound;
 use ic_stable_structures::{Memory, StableCell, Storable};
 
+use crate::cell::CELL_HEADER_SIZE;
+use crate::common::{MemoryUsage, StructureError, fixed_size};
 use crate::vec::VecExt;
 use crate::vec::VecStructure;
 
+/// The invariant of the ring buffer state that does not hold.
+#[derive(Debug, Clone, PartialEq, Eq, thiserror::Error)]
+pub enum RingBufferError {
+    /// The capacity is zero.
+    #[error("ring buffer capacity is zero")]
+    ZeroCapacity,
+    /// The index of the first element is not less than the capacity.
+    #[error("ring buffer start {start} is out of the capacity {capacity}")]
+    StartOutOfCapacity { start: u64, capacity: u64 },
+    /// The number of elements is greater than the capacity.
+    #[error("ring buffer length {len} exceeds the capacity {capacity}")]
+    LenExceedsCapacity { len: u64, capacity: u64 },
+    /// The data vector is longer than the capacity.
+    #[error("ring buffer data length {data_len} exceeds the capacity {capacity}")]
+    DataExceedsCapacity { data_len: u64, capacity: u64 },
+    /// The data vector does not contain all the elements.
+    #[error("ring buffer data length {data_len} is less than the required {required}")]
+    MissingData { data_len: u64, required: u64 },
+}
+
 /// Ring buffer indices state
 #[derive(Clone, Debug, PartialEq, Eq)]
 pub struct StableRingBufferIndices {
@@ -38,7 +60,11 @@ impl StableRingBufferIndices {
 
     /// Index of the element placed with the `n` offset from start.
     fn nth_element(&self, n: u64) -> Option<u64> {
-        (n < self.len).then_some((self.start + n) % self.capacity)
+        if n < self.len {
+            (self.start + n).checked_rem(self.capacity)
+        } else {
+            None
+        }
     }
 
     /// Index of the element placed with the `n` offset from end.
@@ -79,6 +105,70 @@ impl StableRingBufferIndices {
     pub fn is_empty(&self) -> bool {
         self.len == 0
     }
+
+    /// Reserves the index for a new element after the last one.
+    /// If the buffer is full, the first element is displaced.
+    ///
+    /// Returns the reserved index and whether an element has been displaced.
+    fn reserve_next(&mut self) -> (u64, bool) {
+        let new_index = self.offset_to_index(self.len);
+        if self.len == self.capacity {
+            self.increase_start(1);
+            (new_index, true)
+        } else {
+            self.increase_len(1);
+            (new_index, false)
+        }
+    }
+
+    /// Number of elements that wrapped around the end of the buffer,
+    /// that is, the elements stored at the beginning of the data after the element at `capacity - 1`.
+    fn wrapped_len(&self) -> u64 {
+        (self.start + self.len).saturating_sub(self.capacity)
+    }
+
+    /// Checks that the indices are consistent with each other.
+    pub fn validate(&self) -> Result<(), RingBufferError> {
+        if self.capacity == 0 {
+            return Err(RingBufferError::ZeroCapacity);
+        }
+        if self.start >= self.capacity {
+            return Err(RingBufferError::StartOutOfCapacity {
+                start: self.start,
+                capacity: self.capacity,
+            });
+        }
+        if self.len > self.capacity {
+            return Err(RingBufferError::LenExceedsCapacity {
+                len: self.len,
+                capacity: self.capacity,
+            });
+        }
+        Ok(())
+    }
+
+    /// Checks that the indices are consistent with each other
+    /// and with a data vector of length `data_len`.
+    fn validate_with_data(&self, data_len: u64) -> Result<(), RingBufferError> {
+        self.validate()?;
+        if data_len > self.capacity {
+            return Err(RingBufferError::DataExceedsCapacity {
+                data_len,
+                capacity: self.capacity,
+            });
+        }
+
+        // The data must contain all the elements between `start` and `start + len`, wrapping at `capacity`
+        let required = if self.len == 0 {
+            0
+        } else {
+            min(self.start + self.len, self.capacity)
+        };
+        if data_len < required {
+            return Err(RingBufferError::MissingData { data_len, required });
+        }
+        Ok(())
+    }
 }
 
 const STABLE_RING_BUFFER_INDICES_SIZE: usize = 3 * size_of::<u64>();
@@ -98,14 +188,16 @@ impl Storable for StableRingBufferIndices {
     }
 
     fn from_bytes(bytes: std::borrow::Cow<[u8]>) -> Self {
+        // A truncated input is decoded with zero values, which `validate` reports
+        let read_u64 = |offset: usize| {
+            bytes
+                .get(offset..offset + 8)
+                .map_or(0, |chunk| u64::from_le_bytes(chunk.try_into().unwrap()))
+        };
         Self {
-            start: u64::from_le_bytes(bytes[..8].try_into().expect("first: expected 8 bytes")),
-            len: u64::from_le_bytes(bytes[8..16].try_into().expect("latest: expected 8 bytes")),
-            capacity: u64::from_le_bytes(
-                bytes[16..24]
-                    .try_into()
-                    .expect("capacity: expected 8 bytes"),
-            ),
+            start: read_u64(0),
+            len: read_u64(8),
+            capacity: read_u64(16),
         }
     }
 
@@ -142,43 +234,74 @@ impl<T: Storable + Clone, DataMemory: Memory, IndicesMemory: Memory>
         }
     }
 
-    /// Creates new ring buffer.
+    /// Creates new ring buffer, preserving the existing elements if the memories already contain a ring buffer.
+    /// The `default_history_size` is used as capacity only if the memories are empty.
     ///
-    /// PRECONDITION: the memories are either empty or contain valid
-    /// ring buffer data.
+    /// Returns an error if the existing indices are not consistent with each other
+    /// or with the existing data.
     pub fn init(
         data_memory: DataMemory,
         indices_memory: IndicesMemory,
         default_history_size: NonZeroU64,
-    ) -> Self {
-        Self {
+    ) -> Result<Self, StructureError> {
+        let buffer = Self {
             data: VecExt::init(data_memory),
             indices: StableCell::init(
                 indices_memory,
                 StableRingBufferIndices::new(default_history_size),
             ),
-        }
+        };
+        buffer.validate()?;
+        Ok(buffer)
     }
 
     /// Creates new ring buffer
+    ///
+    /// Returns an error if the indices are not consistent with each other
+    /// or with the data.
     pub fn new_with(
         data: VecExt<T, DataMemory>,
         indices: StableCell<StableRingBufferIndices, IndicesMemory>,
-    ) -> Self {
-        Self { data, indices }
+    ) -> Result<Self, StructureError> {
+        let buffer = Self { data, indices };
+        buffer.validate()?;
+        Ok(buffer)
+    }
+
+    /// Checks that the indices are consistent with each other and with the data.
+    pub fn validate(&self) -> Result<(), RingBufferError> {
+        self.indices.get().validate_with_data(self.data.len())
     }
 
-    /// Removes all elements in the buffer
+    /// Returns the indices if they are consistent, so that the readers
+    /// return no elements instead of panicking on an invalid state.
+    fn valid_indices(&self) -> Option<&StableRingBufferIndices> {
+        self.validate().is_ok().then(|| self.indices.get())
+    }
+
+    /// Removes all elements in the buffer, keeping its capacity.
+    ///
+    /// The state is not validated, so that an invalid buffer can be recovered.
+    ///
+    /// Panics if the stored capacity is zero: use [`Self::clear_with_capacity`] in that case.
     pub fn clear(&mut self) {
-        self.with_indices_data_mut(|indices, data| {
-            *indices = StableRingBufferIndices::new(
-                indices
-                    .capacity()
-                    .try_into()
-                    .expect("capacity should be non-zero"),
-            );
-            data.clear();
-        });
+        let capacity = NonZeroU64::new(self.indices.get().capacity)
+            .expect("capacity should be non-zero, use clear_with_capacity");
+        self.reset(capacity);
+    }
+
+    /// Removes all elements in the buffer, keeping its capacity or setting the
+    /// provided one if the stored capacity is zero.
+    ///
+    /// The state is not validated, so that an invalid buffer can be recovered.
+    pub fn clear_with_capacity(&mut self, capacity: NonZeroU64) {
+        let capacity = NonZeroU64::new(self.indices.get().capacity).unwrap_or(capacity);
+        self.reset(capacity);
+    }
+
+    fn reset(&mut self, capacity: NonZeroU64) {
+        self.data.clear();
+        self.indices.set(StableRingBufferIndices::new(capacity));
     }
 
     /// Number of elements in the buffer
@@ -199,7 +322,9 @@ impl<T: Storable + Clone, DataMemory: Memory, IndicesMemory: Memory>
     /// Update the ring buffer capacity to the given value.
     /// The elements that do not fit into new capacity will be deleted.
     ///
-    /// This operataion performs a copy of all elements that need to be preserved.
+    /// When the capacity increases, no element is copied if the buffer is not wrapped,
+    /// otherwise only the wrapped elements at the beginning of the data are copied.
+    /// When the capacity decreases, this operation performs a copy of all elements that need to be preserved.
     /// This may be inefficient if there are a lot of elements.
     pub fn resize(&mut self, new_capacity: NonZeroU64) {
         self.with_indices_data_mut(|indices, data| {
@@ -207,57 +332,147 @@ impl<T: Storable + Clone, DataMemory: Memory, IndicesMemory: Memory>
                 return;
             }
 
-            let elements_to_copy = min(indices.len, new_capacity.get());
-            // Copy to memory all the elements that need to be preserved
-            let elements = (0..elements_to_copy)
-                .rev()
-                .map(|offset| {
-                    // These panics should never happen, because `elements_to_copy < indices.len`.
-                    let idx = indices
-                        .nth_element_from_end(offset)
-                        .expect("element should be present");
-                    data.get(idx).expect("element should be present")
-                })
-                .collect::<Vec<_>>();
-
-            // clear the stable vector and fill with the elements
-            data.clear();
-            for element in elements {
-                data.push(&element);
+            if new_capacity.get() > indices.capacity() {
+                Self::grow(indices, data, new_capacity);
+            } else {
+                let elements_to_copy = min(indices.len, new_capacity.get());
+                Self::copy_latest(indices, data, new_capacity, elements_to_copy);
             }
-
-            *indices = StableRingBufferIndices::new(new_capacity);
-            indices.increase_len(elements_to_copy);
         });
     }
 
+    /// Update the ring buffer capacity to the given value, copying at most `max_copy` elements.
+    /// If `resize` would copy more than `max_copy` elements, only the latest `max_copy` elements are preserved
+    /// and the oldest ones are deleted.
+    ///
+    /// Returns the number of deleted elements.
+    pub fn resize_lossy(&mut self, new_capacity: NonZeroU64, max_copy: u64) -> u64 {
+        self.with_indices_data_mut(|indices, data| {
+            if new_capacity.get() == indices.capacity() {
+                return 0;
+            }
+
+            if new_capacity.get() > indices.capacity() && indices.wrapped_len() <= max_copy {
+                Self::grow(indices, data, new_capacity);
+                return 0;
+            }
+
+            let elements_to_copy = min(min(indices.len, new_capacity.get()), max_copy);
+            let deleted = indices.len - elements_to_copy;
+            Self::copy_latest(indices, data, new_capacity, elements_to_copy);
+            deleted
+        })
+    }
+
+    /// Increases the capacity moving the wrapped elements, if any, after the end of the old capacity.
+    ///
+    /// PRECONDITION: `new_capacity > indices.capacity()`
+    fn grow(
+        indices: &mut StableRingBufferIndices,
+        data: &mut VecExt<T, DataMemory>,
+        new_capacity: NonZeroU64,
+    ) {
+        let old_capacity = indices.capacity();
+
+        // The elements at the beginning of the data follow the element at `old_capacity - 1`,
+        // so they are moved to the next indices, wrapping at `new_capacity`.
+        // Every index is written only after its element has been moved.
+        for offset in 0..indices.wrapped_len() {
+            // This should never panic, because the wrapped elements are present in the data.
+            let element = data.get(offset).expect("element should be present");
+            Self::write_element(data, (old_capacity + offset) % new_capacity.get(), &element);
+        }
+
+        indices.capacity = new_capacity.get();
+    }
+
+    /// Replaces the buffer content with the latest `count` elements and sets the new capacity.
+    ///
+    /// PRECONDITION: `count <= min(indices.len(), new_capacity)`
+    fn copy_latest(
+        indices: &mut StableRingBufferIndices,
+        data: &mut VecExt<T, DataMemory>,
+        new_capacity: NonZeroU64,
+        count: u64,
+    ) {
+        // Copy to memory all the elements that need to be preserved
+        let elements = (0..count)
+            .rev()
+            .map(|offset| {
+                // These panics should never happen, because `count <= indices.len`.
+                let idx = indices
+                    .nth_element_from_end(offset)
+                    .expect("element should be present");
+                data.get(idx).expect("element should be present")
+            })
+            .collect::<Vec<_>>();
+
+        // clear the stable vector and fill with the elements
+        data.clear();
+        for element in elements {
+            data.push(&element);
+        }
+
+        *indices = StableRingBufferIndices::new(new_capacity);
+        indices.increase_len(count);
+    }
+
+    /// Writes the element at `index`, appending it if `index` is the length of the data.
+    fn write_element(data: &mut VecExt<T, DataMemory>, index: u64, val: &T) {
+        if index == data.len() {
+            data.push(val);
+        } else {
+            // This should never panic, because `index` is inside the `data.len()`.
+            data.set(index, val);
+        }
+    }
+
     /// Push new element to the buffer.
     ///
     /// Returns removed element if any
     pub fn push(&mut self, val: &T) -> Option<T> {
         self.with_indices_data_mut(|indices, data| {
-            let new_index = indices.offset_to_index(indices.len());
+            let (new_index, displaced) = indices.reserve_next();
 
-            let replaced = if indices.len() == indices.capacity() {
-                indices.increase_start(1);
-                // This should never panic, because all indices < capacity are present in the data.
-                Some(data.get(new_index).expect("element should be present"))
-            } else {
-                indices.increase_len(1);
-                None
-            };
+            // This should never panic, because all indices < capacity are present in the data.
+            let replaced =
+                displaced.then(|| data.get(new_index).expect("element should be present"));
 
-            if new_index == data.len() {
-                data.push(val);
-            } else {
-                // This should never panic, because `new_index` is inside the `data.len()`.
-                data.set(new_index, val);
-            }
+            Self::write_element(data, new_index, val);
 
             replaced
         })
     }
 
+    /// Push all the elements to the buffer, in order.
+    ///
+    /// Returns the number of removed elements.
+    pub fn extend<I: IntoIterator<Item = T>>(&mut self, items: I) -> u64 {
+        self.with_indices_data_mut(|indices, data| {
+            let mut removed = 0;
+            for item in items {
+                let (new_index, displaced) = indices.reserve_next();
+                if displaced {
+                    removed += 1;
+                }
+                Self::write_element(data, new_index, &item);
+            }
+            removed
+        })
+    }
+
+    /// Remove `n` first elements from the buffer.
+    ///
+    /// Returns the number of removed elements.
+    pub fn drop_oldest(&mut self, n: u64) -> u64 {
+        self.with_indices_data_mut(|indices, _| {
+            let removed = min(n, indices.len());
+            indices.increase_start(removed);
+            indices.decrease_len(removed);
+            removed
+        })
+    }
+
     /// Pop the last element from the buffer.
     pub fn pop(&mut self) -> Option<T> {
         self.with_indices_data_mut(|indices, data| {
@@ -287,21 +502,76 @@ impl<T: Storable + Clone, DataMemory: Memory, IndicesMemory: Memory>
 
     /// Get the `n`-th element from the start.
     pub fn nth_element(&self, n: u64) -> Option<T> {
-        let index = self.indices.get().nth_element(n)?;
+        let index = self.valid_indices()?.nth_element(n)?;
         self.data.get(index)
     }
 
     /// Get the `n`-th element from the end.
     pub fn nth_element_from_end(&self, n: u64) -> Option<T> {
-        let index = self.indices.get().nth_element_from_end(n)?;
+        let index = self.valid_indices()?.nth_element_from_end(n)?;
         self.data.get(index)
     }
 
+    /// Returns an iterator over the elements, from the oldest to the newest.
+    pub fn iter(&self) -> impl Iterator<Item = T> + '_ {
+        self.elements()
+    }
+
+    /// Returns an iterator over the elements, from the newest to the oldest.
+    pub fn iter_rev(&self) -> impl Iterator<Item = T> + '_ {
+        self.elements().rev()
+    }
+
+    /// Returns an iterator over the elements from `start` to the end of the data,
+    /// followed by the elements wrapped at the beginning of the data.
+    /// No element is returned if the indices are not consistent.
+    fn elements(&self) -> impl DoubleEndedIterator<Item = T> + '_ {
+        let (start, end, wrapped_len) = match self.valid_indices() {
+            Some(indices) => (
+                indices.start,
+                min(indices.start + indices.len, indices.capacity),
+                indices.wrapped_len(),
+            ),
+            None => (0, 0, 0),
+        };
+        self.data
+            .iter_range(start, end)
+            .chain(self.data.iter_range(0, wrapped_len))
+    }
+
+    /// Returns the latest `n` elements, from the oldest to the newest.
+    /// If the buffer contains less than `n` elements, all of them are returned.
+    pub fn latest_n(&self, n: u64) -> Vec<T> {
+        let Some(indices) = self.valid_indices() else {
+            return Vec::new();
+        };
+        let skip = indices.len().saturating_sub(n);
+        (skip..indices.len())
+            .map(|offset| self.element_at(indices, offset))
+            .collect()
+    }
+
+    /// Returns the element placed with the `n` offset from start.
+    ///
+    /// PRECONDITION: `n < indices.len()`
+    fn element_at(&self, indices: &StableRingBufferIndices, n: u64) -> T {
+        // This should never panic, because all the elements before `len` are present in the data.
+        let index = indices.nth_element(n).expect("element should be present");
+        self.data.get(index).expect("element should be present")
+    }
+
+    /// Updates the indices and the data.
+    ///
+    /// Panics if the indices are not consistent, because writing on an invalid state
+    /// would corrupt the buffer further.
     #[inline]
     fn with_indices_data_mut<R>(
         &mut self,
-        f: impl Fn(&mut StableRingBufferIndices, &mut VecExt<T, DataMemory>) -> R,
+        f: impl FnOnce(&mut StableRingBufferIndices, &mut VecExt<T, DataMemory>) -> R,
     ) -> R {
+        if let Err(error) = self.validate() {
+            panic!("invalid ring buffer state: {error}");
+        }
         let mut indices = self.indices.get().clone();
         let result = f(&mut indices, &mut self.data);
         self.indices.set(indices);
@@ -309,9 +579,23 @@ impl<T: Storable + Clone, DataMemory: Memory, IndicesMemory: Memory>
     }
 }
 
+impl<T: Storable + Clone, DataMemory: Memory, IndicesMemory: Memory> MemoryUsage
+    for StableRingBuffer<T, DataMemory, IndicesMemory>
+{
+    fn allocated_bytes(&self) -> u64 {
+        self.data.allocated_bytes() + CELL_HEADER_SIZE + STABLE_RING_BUFFER_INDICES_SIZE as u64
+    }
+
+    /// Only the elements in the buffer are counted, not the removed ones still in the data.
+    fn used_bytes(&self) -> Option<u64> {
+        fixed_size(T::BOUND).map(|size| self.len() * size)
+    }
+}
+
 #[cfg(test)]
 mod tests {
 
+    use std::borrow::Cow;
     use std::fmt::Debug;
 
     use ic_stable_structures::VectorMemory;
@@ -384,6 +668,12 @@ mod tests {
         }
 
         assert_eq!(None, buffer.nth_element(expected.len() as _));
+
+        assert_eq!(buffer.iter().collect::<Vec<_>>(), expected);
+        assert_eq!(
+            buffer.iter_rev().collect::<Vec<_>>(),
+            expected.iter().rev().cloned().collect::<Vec<_>>()
+        );
     }
 
     fn with_buffer(
@@ -467,6 +757,71 @@ mod tests {
         });
     }
 
+    #[test]
+    fn should_extend() {
+        with_buffer(3, |buffer| {
+            assert_eq!(buffer.extend(Vec::<u64>::new()), 0);
+            check_buffer(buffer, &[]);
+
+            assert_eq!(buffer.extend([1, 2]), 0);
+            check_buffer(buffer, &[1, 2]);
+
+            assert_eq!(buffer.extend([3, 4]), 1);
+            check_buffer(buffer, &[2, 3, 4]);
+
+            // Checks for wrapped buffer.
+            assert_eq!(buffer.extend(5..10), 5);
+            check_buffer(buffer, &[7, 8, 9]);
+
+            assert_eq!(buffer.pop(), Some(9));
+            assert_eq!(buffer.extend([10]), 0);
+            check_buffer(buffer, &[7, 8, 10]);
+            assert_eq!(buffer.push(&11), Some(7));
+            check_buffer(buffer, &[8, 10, 11]);
+        });
+    }
+
+    #[test]
+    fn should_drop_oldest() {
+        with_buffer(5, |buffer| {
+            assert_eq!(buffer.drop_oldest(1), 0);
+            check_buffer(buffer, &[]);
+
+            // Checks for not-wrapped buffer.
+            for i in 0..4 {
+                buffer.push(&i);
+            }
+            assert_eq!(buffer.drop_oldest(0), 0);
+            assert_eq!(buffer.drop_oldest(2), 2);
+            check_buffer(buffer, &[2, 3]);
+
+            assert_eq!(buffer.push(&4), None);
+            assert_eq!(buffer.push(&5), None);
+            assert_eq!(buffer.push(&6), None);
+            check_buffer(buffer, &[2, 3, 4, 5, 6]);
+
+            // Checks for wrapped buffer.
+            assert_eq!(buffer.push(&7), Some(2));
+            check_buffer(buffer, &[3, 4, 5, 6, 7]);
+            assert_eq!(buffer.drop_oldest(3), 3);
+            check_buffer(buffer, &[6, 7]);
+            assert_eq!(buffer.first(), Some(6));
+            assert_eq!(buffer.last(), Some(7));
+
+            // Check replacement of dropped elements returns None.
+            for i in 8..11 {
+                assert_eq!(buffer.push(&i), None);
+            }
+            check_buffer(buffer, &[6, 7, 8, 9, 10]);
+            assert_eq!(buffer.push(&11), Some(6));
+
+            assert_eq!(buffer.drop_oldest(10), 5);
+            check_buffer(buffer, &[]);
+            assert_eq!(buffer.push(&12), None);
+            check_buffer(buffer, &[12]);
+        });
+    }
+
     #[test]
     fn should_truncate() {
         with_buffer(5, |buffer| {
@@ -590,6 +945,142 @@ mod tests {
         });
     }
 
+    #[test]
+    fn should_return_the_latest_n_elements() {
+        with_buffer(3, |buffer| {
+            assert!(buffer.latest_n(2).is_empty());
+
+            buffer.push(&1);
+            buffer.push(&2);
+            assert_eq!(buffer.latest_n(0), Vec::<u64>::new());
+            assert_eq!(buffer.latest_n(1), vec![2]);
+            assert_eq!(buffer.latest_n(5), vec![1, 2]);
+
+            // wrapped buffer
+            buffer.push(&3);
+            buffer.push(&4);
+            buffer.push(&5);
+            check_buffer(buffer, &[3, 4, 5]);
+            assert_eq!(buffer.latest_n(2), vec![4, 5]);
+            assert_eq!(buffer.latest_n(3), vec![3, 4, 5]);
+            assert_eq!(buffer.latest_n(4), vec![3, 4, 5]);
+        });
+    }
+
+    fn data_of<T: Storable + Clone, DataMemory: Memory, IndicesMemory: Memory>(
+        buffer: &StableRingBuffer<T, DataMemory, IndicesMemory>,
+    ) -> Vec<T> {
+        buffer.data.iter().collect()
+    }
+
+    #[test]
+    fn should_grow_a_not_wrapped_buffer_without_copying() {
+        with_buffer(4, |buffer| {
+            for i in 1..=4 {
+                buffer.push(&i);
+            }
+            buffer.pop();
+            buffer.pop();
+            buffer.push(&5);
+            check_buffer(buffer, &[1, 2, 5]);
+
+            buffer.resize(6.try_into().unwrap());
+
+            assert_eq!(6, buffer.capacity());
+            assert_eq!(0, buffer.indices.get().start);
+            assert_eq!(data_of(buffer), vec![1, 2, 5, 4]);
+            check_buffer(buffer, &[1, 2, 5]);
+
+            for i in 6..=9 {
+                buffer.push(&i);
+            }
+            check_buffer(buffer, &[2, 5, 6, 7, 8, 9]);
+        });
+    }
+
+    #[test]
+    fn should_grow_a_wrapped_buffer_copying_the_wrapped_elements() {
+        with_buffer(3, |buffer| {
+            for i in 1..=4 {
+                buffer.push(&i);
+            }
+            assert_eq!(data_of(buffer), vec![4, 2, 3]);
+
+            buffer.resize(5.try_into().unwrap());
+
+            assert_eq!(1, buffer.indices.get().start);
+            assert_eq!(data_of(buffer), vec![4, 2, 3, 4]);
+            check_buffer(buffer, &[2, 3, 4]);
+
+            buffer.push(&5);
+            buffer.push(&6);
+            check_buffer(buffer, &[2, 3, 4, 5, 6]);
+            assert_eq!(buffer.push(&7), Some(2));
+            check_buffer(buffer, &[3, 4, 5, 6, 7]);
+        });
+
+        // the wrapped elements do not fit after the end of the old capacity
+        with_buffer(4, |buffer| {
+            for i in 1..=7 {
+                buffer.push(&i);
+            }
+            assert_eq!(data_of(buffer), vec![5, 6, 7, 4]);
+
+            buffer.resize(5.try_into().unwrap());
+
+            assert_eq!(3, buffer.indices.get().start);
+            assert_eq!(data_of(buffer), vec![6, 7, 7, 4, 5]);
+            check_buffer(buffer, &[4, 5, 6, 7]);
+
+            buffer.push(&8);
+            check_buffer(buffer, &[4, 5, 6, 7, 8]);
+            assert_eq!(buffer.push(&9), Some(4));
+            check_buffer(buffer, &[5, 6, 7, 8, 9]);
+        });
+    }
+
+    #[test]
+    fn should_resize_lossy() {
+        // grow without exceeding the copy limit
+        with_buffer(3, |buffer| {
+            for i in 1..=4 {
+                buffer.push(&i);
+            }
+            assert_eq!(0, buffer.resize_lossy(5.try_into().unwrap(), 1));
+            assert_eq!(5, buffer.capacity());
+            check_buffer(buffer, &[2, 3, 4]);
+        });
+
+        // grow exceeding the copy limit
+        with_buffer(4, |buffer| {
+            for i in 1..=7 {
+                buffer.push(&i);
+            }
+            assert_eq!(2, buffer.resize_lossy(8.try_into().unwrap(), 2));
+            assert_eq!(8, buffer.capacity());
+            check_buffer(buffer, &[6, 7]);
+        });
+
+        // shrink exceeding the copy limit
+        with_buffer(5, |buffer| {
+            for i in 1..=5 {
+                buffer.push(&i);
+            }
+            assert_eq!(3, buffer.resize_lossy(3.try_into().unwrap(), 2));
+            assert_eq!(3, buffer.capacity());
+            check_buffer(buffer, &[4, 5]);
+        });
+
+        // shrink without exceeding the copy limit
+        with_buffer(5, |buffer| {
+            for i in 1..=5 {
+                buffer.push(&i);
+            }
+            assert_eq!(2, buffer.resize_lossy(3.try_into().unwrap(), 10));
+            check_buffer(buffer, &[3, 4, 5]);
+        });
+    }
+
     #[test]
     fn should_clear() {
         with_buffer(2, |buffer| {
@@ -628,18 +1119,250 @@ mod tests {
                 memory_1.clone(),
                 memory_2.clone(),
                 NonZeroU64::new(2).unwrap(),
-            );
+            )
+            .unwrap();
             buffer.push(&100u32);
         }
 
         {
             let mut buffer =
-                StableRingBuffer::init(memory_1, memory_2, NonZeroU64::new(2).unwrap());
+                StableRingBuffer::init(memory_1, memory_2, NonZeroU64::new(2).unwrap()).unwrap();
             assert!(!buffer.is_empty());
             assert_eq!(Some(100u32), buffer.pop());
         }
     }
 
+    #[test]
+    fn should_preserve_wrapped_elements_on_init() {
+        let data_memory = VectorMemory::default();
+        let indices_memory = VectorMemory::default();
+        {
+            let mut buffer = StableRingBuffer::init(
+                data_memory.clone(),
+                indices_memory.clone(),
+                NonZeroU64::new(3).unwrap(),
+            )
+            .unwrap();
+            for i in 0..5u64 {
+                buffer.push(&i);
+            }
+        }
+
+        {
+            // The capacity of the existing buffer is preserved
+            let buffer =
+                StableRingBuffer::init(data_memory, indices_memory, NonZeroU64::new(10).unwrap())
+                    .unwrap();
+            assert_eq!(buffer.capacity(), 3);
+            check_buffer(&buffer, &[2, 3, 4]);
+        }
+    }
+
+    #[test]
+    fn should_return_an_error_on_init_if_the_indices_are_corrupted() {
+        let init_with_indices = |data_len: u64, indices: StableRingBufferIndices| {
+            let data_memory = VectorMemory::default();
+            let indices_memory = VectorMemory::default();
+            let mut data = VecExt::<u64, _>::init(data_memory.clone());
+            for i in 0..data_len {
+                data.push(&i);
+            }
+            StableCell::init(indices_memory.clone(), indices);
+            StableRingBuffer::<u64, _, _>::init(
+                data_memory,
+                indices_memory,
+                NonZeroU64::new(3).unwrap(),
+            )
+        };
+
+        let indices = |start, len, capacity| StableRingBufferIndices {
+            start,
+            len,
+            capacity,
+        };
+
+        assert!(init_with_indices(2, indices(1, 1, 3)).is_ok());
+        assert!(init_with_indices(3, indices(2, 3, 3)).is_ok());
+
+        assert_eq!(
+            init_with_indices(0, indices(0, 0, 0)).err(),
+            Some(StructureError::RingBuffer(RingBufferError::ZeroCapacity))
+        );
+        assert_eq!(
+            init_with_indices(3, indices(3, 1, 3)).err(),
+            Some(StructureError::RingBuffer(
+                RingBufferError::StartOutOfCapacity {
+                    start: 3,
+                    capacity: 3
+                }
+            ))
+        );
+        assert_eq!(
+            init_with_indices(3, indices(0, 4, 3)).err(),
+            Some(StructureError::RingBuffer(
+                RingBufferError::LenExceedsCapacity {
+                    len: 4,
+                    capacity: 3
+                }
+            ))
+        );
+        assert_eq!(
+            init_with_indices(4, indices(0, 1, 3)).err(),
+            Some(StructureError::RingBuffer(
+                RingBufferError::DataExceedsCapacity {
+                    data_len: 4,
+                    capacity: 3
+                }
+            ))
+        );
+        assert_eq!(
+            init_with_indices(2, indices(1, 2, 3)).err(),
+            Some(StructureError::RingBuffer(RingBufferError::MissingData {
+                data_len: 2,
+                required: 3
+            }))
+        );
+        assert!(init_with_indices(2, indices(2, 2, 3)).is_err());
+    }
+
+    /// Writes the indices bytes as a partially written cell would leave them.
+    fn buffer_with_indices_bytes(
+        data_len: u64,
+        bytes: [u64; 3],
+    ) -> StableRingBuffer<u64, VectorMemory, VectorMemory> {
+        let mut data = VecExt::<u64, _>::new(VectorMemory::default());
+        for i in 0..data_len {
+            data.push(&i);
+        }
+        let bytes: Vec<u8> = bytes.iter().flat_map(|value| value.to_le_bytes()).collect();
+        let indices = StableRingBufferIndices::from_bytes(Cow::Owned(bytes));
+
+        // The indices are written without validation, so that the readers see an invalid state
+        StableRingBuffer {
+            data,
+            indices: StableCell::new(VectorMemory::default(), indices),
+        }
+    }
+
+    #[test]
+    fn should_validate_the_indices_read_from_bytes() {
+        let indices = |start: u64, len: u64, capacity: u64| {
+            let bytes: Vec<u8> = [start, len, capacity]
+                .iter()
+                .flat_map(|value| value.to_le_bytes())
+                .collect();
+            StableRingBufferIndices::from_bytes(Cow::Owned(bytes))
+        };
+
+        assert_eq!(indices(1, 2, 3).validate(), Ok(()));
+        assert_eq!(
+            indices(0, 0, 0).validate(),
+            Err(RingBufferError::ZeroCapacity)
+        );
+        assert_eq!(
+            indices(5, 0, 3).validate(),
+            Err(RingBufferError::StartOutOfCapacity {
+                start: 5,
+                capacity: 3
+            })
+        );
+        assert_eq!(
+            indices(0, 7, 3).validate(),
+            Err(RingBufferError::LenExceedsCapacity {
+                len: 7,
+                capacity: 3
+            })
+        );
+    }
+
+    #[test]
+    fn should_not_panic_when_reading_an_invalid_state() {
+        for (data_len, bytes) in [
+            (0, [0, 2, 0]),
+            (3, [5, 2, 3]),
+            (3, [0, 7, 3]),
+            (1, [0, 3, 3]),
+        ] {
+            let buffer = buffer_with_indices_bytes(data_len, bytes);
+
+            assert!(buffer.validate().is_err());
+            assert_eq!(buffer.first(), None);
+            assert_eq!(buffer.last(), None);
+            assert_eq!(buffer.nth_element(1), None);
+            assert_eq!(buffer.nth_element_from_end(1), None);
+            assert_eq!(buffer.iter().count(), 0);
+            assert_eq!(buffer.iter_rev().count(), 0);
+            assert!(buffer.latest_n(2).is_empty());
+        }
+    }
+
+    #[test]
+    fn should_decode_truncated_indices_into_an_invalid_state() {
+        for len in [0, 7, 8, 16, 23] {
+            let indices = StableRingBufferIndices::from_bytes(Cow::Owned(vec![1; len]));
+            assert_eq!(indices.validate(), Err(RingBufferError::ZeroCapacity));
+        }
+    }
+
+    #[test]
+    fn should_clear_an_invalid_state() {
+        for (data_len, bytes) in [(3, [5, 2, 3]), (3, [0, 7, 3]), (1, [0, 3, 3])] {
+            let mut buffer = buffer_with_indices_bytes(data_len, bytes);
+            assert!(buffer.validate().is_err());
+
+            buffer.clear();
+
+            assert_eq!(buffer.validate(), Ok(()));
+            check_buffer(&buffer, &[]);
+            assert_eq!(3, buffer.capacity());
+        }
+    }
+
+    #[test]
+    fn should_clear_with_capacity_an_invalid_state() {
+        let mut buffer = buffer_with_indices_bytes(2, [0, 2, 0]);
+        assert_eq!(buffer.validate(), Err(RingBufferError::ZeroCapacity));
+
+        buffer.clear_with_capacity(NonZeroU64::new(4).unwrap());
+
+        assert_eq!(buffer.validate(), Ok(()));
+        check_buffer(&buffer, &[]);
+        assert_eq!(4, buffer.capacity());
+
+        // The stored capacity is kept when it is not zero
+        buffer.push(&1);
+        buffer.clear_with_capacity(NonZeroU64::new(2).unwrap());
+        check_buffer(&buffer, &[]);
+        assert_eq!(4, buffer.capacity());
+    }
+
+    #[test]
+    fn should_validate_on_new_with() {
+        let data = VecExt::<u64, _>::new(VectorMemory::default());
+        let valid = StableCell::new(
+            VectorMemory::default(),
+            StableRingBufferIndices::new(NonZeroU64::new(2).unwrap()),
+        );
+        assert!(StableRingBuffer::new_with(data, valid).is_ok());
+
+        let data = VecExt::<u64, _>::new(VectorMemory::default());
+        let invalid = StableCell::new(
+            VectorMemory::default(),
+            StableRingBufferIndices {
+                start: 0,
+                len: 1,
+                capacity: 2,
+            },
+        );
+        assert_eq!(
+            StableRingBuffer::new_with(data, invalid).err(),
+            Some(StructureError::RingBuffer(RingBufferError::MissingData {
+                data_len: 0,
+                required: 1
+            }))
+        );
+    }
+
     #[test]
     fn should_erase_existing_data_on_new() {
         let memory_1 = VectorMemory::default();
@@ -660,4 +1383,22 @@ mod tests {
             assert_eq!(None, buffer.pop());
         }
     }
+
+    #[test]
+    fn should_report_the_memory_usage() {
+        with_buffer(2, |buffer| {
+            let empty_allocated_bytes = buffer.allocated_bytes();
+            assert_eq!(buffer.used_bytes(), Some(0));
+
+            buffer.push(&1);
+            buffer.push(&2);
+            buffer.push(&3);
+            assert_eq!(buffer.allocated_bytes(), empty_allocated_bytes + 2 * 8);
+            assert_eq!(buffer.used_bytes(), Some(2 * 8));
+
+            buffer.pop();
+            assert_eq!(buffer.allocated_bytes(), empty_allocated_bytes + 2 * 8);
+            assert_eq!(buffer.used_bytes(), Some(8));
+        })
+    }
 }
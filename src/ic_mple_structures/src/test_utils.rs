@@ -1,16 +1,28 @@
 use std::borrow::Cow;
 
 use candid::{CandidType, Decode, Deserialize, Encode};
-use ic_stable_structures::{Storable, storable::Bound};
+use ic_stable_structures::{Memory, Storable, VectorMemory, storable::Bound};
 
-use crate::common::{Bounded, Codec, RefCodec};
+use crate::common::{Bounded, Codec, CodecError, RefCodec, TryCodec};
 
+/// When the `derive` feature is enabled, the codec is derived,
+/// so that all the tests using it run against the generated implementation.
 #[derive(Clone, CandidType, Deserialize, PartialEq, Eq, Debug)]
+#[cfg_attr(feature = "derive", derive(crate::VersionedCodec))]
 pub enum UserCodec {
+    #[cfg_attr(feature = "derive", upgrade(path = "user_v1_to_v2"))]
     V1(UserV1),
+    #[cfg_attr(feature = "derive", latest)]
     V2(UserV2),
 }
 
+fn user_v1_to_v2(user_v1: UserV1) -> UserV2 {
+    UserV2 {
+        name: user_v1.0,
+        age: None,
+    }
+}
+
 impl Storable for UserCodec {
     const BOUND: Bound = Bound::Unbounded;
 
@@ -68,13 +80,11 @@ impl Storable for UserV2 {
     }
 }
 
+#[cfg(not(feature = "derive"))]
 impl Codec<UserV2> for UserCodec {
     fn decode(source: UserCodec) -> UserV2 {
         match source {
-            UserCodec::V1(user_v1) => UserV2 {
-                name: user_v1.0,
-                age: None,
-            },
+            UserCodec::V1(user_v1) => user_v1_to_v2(user_v1),
             UserCodec::V2(user_v2) => user_v2,
         }
     }
@@ -82,15 +92,17 @@ impl Codec<UserV2> for UserCodec {
     fn encode(dest: UserV2) -> UserCodec {
         UserCodec::V2(dest)
     }
+
+    fn is_latest(source: &UserCodec) -> bool {
+        matches!(source, UserCodec::V2(_))
+    }
 }
 
+#[cfg(not(feature = "derive"))]
 impl RefCodec<UserV2> for UserCodec {
     fn decode_ref<'a>(source: &'a UserCodec) -> std::borrow::Cow<'a, UserV2> {
         match source {
-            UserCodec::V1(user_v1) => Cow::Owned(UserV2 {
-                name: user_v1.0.clone(),
-                age: None,
-            }),
+            UserCodec::V1(user_v1) => Cow::Owned(user_v1_to_v2(user_v1.clone())),
             UserCodec::V2(user_v2) => Cow::Borrowed(user_v2),
         }
     }
@@ -98,6 +110,72 @@ impl RefCodec<UserV2> for UserCodec {
     fn encode(dest: UserV2) -> UserCodec {
         UserCodec::V2(dest)
     }
+
+    fn is_latest(source: &UserCodec) -> bool {
+        matches!(source, UserCodec::V2(_))
+    }
+}
+
+/// A codec that stores the version in the first byte, so that it can hold values of unknown versions.
+/// Only the version `1`, an UTF-8 string, is known.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct StringCodec {
+    pub version: u8,
+    pub bytes: Vec<u8>,
+}
+
+impl Storable for StringCodec {
+    const BOUND: Bound = Bound::Unbounded;
+
+    fn to_bytes(&self) -> Cow<'_, [u8]> {
+        Cow::Owned(self.clone().into_bytes())
+    }
+
+    fn into_bytes(self) -> Vec<u8> {
+        let mut bytes = vec![self.version];
+        bytes.extend(self.bytes);
+        bytes
+    }
+
+    fn from_bytes(bytes: Cow<[u8]>) -> Self {
+        Self {
+            version: bytes[0],
+            bytes: bytes[1..].to_vec(),
+        }
+    }
+}
+
+impl Codec<String> for StringCodec {
+    fn decode(source: StringCodec) -> String {
+        <Self as TryCodec<String>>::try_decode(source).unwrap()
+    }
+
+    fn encode(dest: String) -> StringCodec {
+        StringCodec {
+            version: 1,
+            bytes: dest.into_bytes(),
+        }
+    }
+}
+
+impl RefCodec<String> for StringCodec {
+    fn decode_ref<'a>(source: &'a StringCodec) -> Cow<'a, String> {
+        Cow::Owned(<Self as TryCodec<String>>::try_decode(source.clone()).unwrap())
+    }
+
+    fn encode(dest: String) -> StringCodec {
+        <Self as Codec<String>>::encode(dest)
+    }
+}
+
+impl TryCodec<String> for StringCodec {
+    fn try_decode(source: StringCodec) -> Result<String, CodecError> {
+        match source.version {
+            1 => String::from_utf8(source.bytes)
+                .map_err(|err| CodecError::InvalidValue(err.to_string())),
+            version => Err(CodecError::UnknownVersion(version.to_string())),
+        }
+    }
 }
 
 /// New type pattern used to implement `Storable` trait for all arrays.
@@ -130,3 +208,40 @@ impl<const N: usize> Storable for Array<N> {
         self.0.to_vec()
     }
 }
+
+/// A memory that cannot grow beyond a max number of pages.
+#[derive(Clone)]
+pub struct CappedMemory {
+    inner: VectorMemory,
+    max_pages: u64,
+}
+
+impl CappedMemory {
+    pub fn new(max_pages: u64) -> Self {
+        Self {
+            inner: VectorMemory::default(),
+            max_pages,
+        }
+    }
+}
+
+impl Memory for CappedMemory {
+    fn size(&self) -> u64 {
+        self.inner.size()
+    }
+
+    fn grow(&self, pages: u64) -> i64 {
+        if self.inner.size() + pages > self.max_pages {
+            return -1;
+        }
+        self.inner.grow(pages)
+    }
+
+    fn read(&self, offset: u64, dst: &mut [u8]) {
+        self.inner.read(offset, dst)
+    }
+
+    fn write(&self, offset: u64, src: &[u8]) {
+        self.inner.write(offset, src)
+    }
+}
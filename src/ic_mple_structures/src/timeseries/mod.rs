@@ -0,0 +1,233 @@
+use ic_stable_structures::{BTreeMap, Memory, Storable};
+
+use crate::btreemap::remove_range_in_batches;
+use crate::common::StructureError;
+
+/// A series of samples ordered by timestamp.
+///
+/// The samples are stored in a BTreeMap keyed by the timestamp in nanoseconds and by a sequence number,
+/// so that the samples appended with the same timestamp are all kept, in the order they were appended.
+///
+/// Optionally, the series keeps at most a maximum number of samples, dropping the oldest ones on `append`.
+pub struct StableTimeseries<V: Storable, M: Memory> {
+    samples: BTreeMap<(u64, u32), V, M>,
+    max_entries: Option<u64>,
+}
+
+impl<V: Storable, M: Memory> StableTimeseries<V, M> {
+    /// Create new instance of the StableTimeseries,
+    /// overwriting any data structures the memory might have
+    /// contained previously.
+    pub fn new(memory: M) -> Self {
+        Self {
+            samples: BTreeMap::new(memory),
+            max_entries: None,
+        }
+    }
+
+    /// Create new instance of the StableTimeseries.
+    ///
+    /// PRECONDITION: the memory is either empty or contains a valid
+    /// StableTimeseries.
+    pub fn init(memory: M) -> Self {
+        Self {
+            samples: BTreeMap::init(memory),
+            max_entries: None,
+        }
+    }
+
+    /// Sets the max number of samples kept by the series:
+    /// when a sample is appended to a full series, the oldest samples are dropped.
+    ///
+    /// The retention is not persisted, so it should be set every time the series is created.
+    pub fn with_max_entries(mut self, max_entries: u64) -> Self {
+        self.max_entries = Some(max_entries);
+        self
+    }
+
+    /// Appends a sample taken at `now`, in nanoseconds.
+    ///
+    /// Returns an error if `now` is older than the timestamp of the last sample.
+    pub fn append(&mut self, now: u64, value: V) -> Result<(), StructureError> {
+        let sequence = match self.samples.keys().next_back() {
+            Some((last, _)) if now < last => {
+                return Err(StructureError::OutOfOrder {
+                    last,
+                    timestamp: now,
+                });
+            }
+            Some((last, sequence)) if now == last => sequence
+                .checked_add(1)
+                .expect("too many samples with the same timestamp"),
+            _ => 0,
+        };
+        self.samples.insert((now, sequence), value);
+
+        if let Some(max_entries) = self.max_entries {
+            while self.samples.len() > max_entries {
+                self.samples.pop_first();
+            }
+        }
+        Ok(())
+    }
+
+    /// Returns the samples taken from `from` included to `to` excluded, in chronological order.
+    pub fn range(&self, from: u64, to: u64) -> impl Iterator<Item = (u64, V)> + '_ {
+        self.samples
+            .range((from, 0)..(to.max(from), 0))
+            .map(|entry| {
+                let ((timestamp, _), value) = entry.into_pair();
+                (timestamp, value)
+            })
+    }
+
+    /// Returns the latest `n` samples, in chronological order.
+    pub fn latest(&self, n: usize) -> Vec<(u64, V)> {
+        let mut latest: Vec<_> = self
+            .samples
+            .iter()
+            .rev()
+            .take(n)
+            .map(|entry| {
+                let ((timestamp, _), value) = entry.into_pair();
+                (timestamp, value)
+            })
+            .collect();
+        latest.reverse();
+        latest
+    }
+
+    /// Removes the samples taken before `cutoff`, up to `limit` samples, and returns the number of removed samples.
+    ///
+    /// Pruning a long series can be split across messages by calling it until it returns less than `limit`.
+    pub fn prune_older_than(&mut self, cutoff: u64, limit: u64) -> u64 {
+        remove_range_in_batches(&mut self.samples, ..(cutoff, 0), Some(limit), |_| ())
+    }
+
+    /// Returns the timestamp of the last sample.
+    pub fn last_timestamp(&self) -> Option<u64> {
+        self.samples
+            .keys()
+            .next_back()
+            .map(|(timestamp, _)| timestamp)
+    }
+
+    /// Returns the number of samples.
+    pub fn len(&self) -> u64 {
+        self.samples.len()
+    }
+
+    /// Returns true if there are no samples.
+    pub fn is_empty(&self) -> bool {
+        self.samples.is_empty()
+    }
+
+    /// Removes all the samples.
+    pub fn clear(&mut self) {
+        self.samples.clear_new();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use ic_stable_structures::VectorMemory;
+
+    use super::*;
+
+    fn timestamps(samples: Vec<(u64, u32)>) -> Vec<u64> {
+        samples
+            .into_iter()
+            .map(|(timestamp, _)| timestamp)
+            .collect()
+    }
+
+    #[test]
+    fn should_append_and_read_a_range() {
+        let mut series = StableTimeseries::<u32, _>::new(VectorMemory::default());
+        for (value, now) in [10, 20, 30, 40].into_iter().enumerate() {
+            series.append(now, value as u32).unwrap();
+        }
+
+        assert_eq!(
+            series.range(20, 40).collect::<Vec<_>>(),
+            vec![(20, 1), (30, 2)]
+        );
+        assert_eq!(series.range(0, 15).collect::<Vec<_>>(), vec![(10, 0)]);
+        assert_eq!(series.range(40, 20).count(), 0);
+        assert_eq!(series.last_timestamp(), Some(40));
+    }
+
+    #[test]
+    fn should_keep_the_samples_with_the_same_timestamp() {
+        let mut series = StableTimeseries::<u32, _>::new(VectorMemory::default());
+        series.append(10, 1).unwrap();
+        series.append(10, 2).unwrap();
+        series.append(10, 3).unwrap();
+
+        assert_eq!(series.len(), 3);
+        assert_eq!(
+            series.range(10, 11).collect::<Vec<_>>(),
+            vec![(10, 1), (10, 2), (10, 3)]
+        );
+    }
+
+    #[test]
+    fn should_reject_out_of_order_samples() {
+        let mut series = StableTimeseries::<u32, _>::new(VectorMemory::default());
+        series.append(10, 1).unwrap();
+        series.append(20, 2).unwrap();
+
+        assert_eq!(
+            series.append(15, 3),
+            Err(StructureError::OutOfOrder {
+                last: 20,
+                timestamp: 15
+            })
+        );
+        assert_eq!(series.len(), 2);
+    }
+
+    #[test]
+    fn should_prune_in_bounded_batches() {
+        let mut series = StableTimeseries::<u32, _>::new(VectorMemory::default());
+        for now in 0..2500 {
+            series.append(now, now as u32).unwrap();
+        }
+
+        assert_eq!(series.prune_older_than(2000, 1500), 1500);
+        assert_eq!(series.range(0, 2500).next(), Some((1500, 1500)));
+        assert_eq!(series.prune_older_than(2000, 1500), 500);
+        assert_eq!(series.prune_older_than(2000, 1500), 0);
+        assert_eq!(series.len(), 500);
+        assert_eq!(series.range(0, 2500).next(), Some((2000, 2000)));
+    }
+
+    #[test]
+    fn should_return_the_latest_samples_with_retention() {
+        let mut series =
+            StableTimeseries::<u32, _>::new(VectorMemory::default()).with_max_entries(5);
+        for now in 0..12 {
+            series.append(now * 10, now as u32).unwrap();
+        }
+
+        assert_eq!(series.len(), 5);
+        assert_eq!(timestamps(series.latest(3)), vec![90, 100, 110]);
+        assert_eq!(timestamps(series.latest(10)), vec![70, 80, 90, 100, 110]);
+        assert!(series.latest(0).is_empty());
+    }
+
+    #[test]
+    fn should_reuse_existing_data_on_init() {
+        let memory = VectorMemory::default();
+        {
+            let mut series = StableTimeseries::<u32, _>::init(memory.clone());
+            series.append(10, 1).unwrap();
+        }
+
+        let mut series = StableTimeseries::<u32, _>::init(memory);
+        assert_eq!(series.latest(1), vec![(10, 1)]);
+        assert!(series.append(5, 2).is_err());
+        series.append(10, 2).unwrap();
+        assert_eq!(series.latest(2), vec![(10, 1), (10, 2)]);
+    }
+}
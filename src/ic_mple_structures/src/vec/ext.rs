@@ -1,6 +1,10 @@
 use ic_stable_structures::{Memory, Storable, vec};
 
-use crate::vec::VecStructure;
+use crate::common::{MemoryUsage, StructureError, ensure_capacity, fixed_size, vec_slot_size};
+use crate::vec::{VecIter, VecStructure};
+
+/// The size of the header of a stable vector.
+const VEC_HEADER_SIZE: u64 = 64;
 
 pub struct VecExt<T: Storable, M: Memory>(Option<vec::Vec<T, M>>);
 
@@ -29,6 +33,18 @@ impl<T: Storable, M: Memory> VecExt<T, M> {
         self.get_inner().iter()
     }
 
+    /// Grows the memory, if needed, so that it can hold `additional` more values.
+    fn reserve(&mut self, additional: u64) -> Result<(), StructureError> {
+        if let Some(vector) = self.0.take() {
+            let size = VEC_HEADER_SIZE + (vector.len() + additional) * vec_slot_size(T::BOUND);
+            let memory = vector.into_memory();
+            let result = ensure_capacity(&memory, size);
+            self.0 = Some(vec::Vec::init(memory));
+            result?;
+        }
+        Ok(())
+    }
+
     #[inline(always)]
     fn mut_inner(&mut self) -> &mut vec::Vec<T, M> {
         self.0.as_mut().expect("vector is always initialized")
@@ -41,6 +57,19 @@ impl<T: Storable, M: Memory> VecExt<T, M> {
 }
 
 impl<T: Storable, M: Memory> VecStructure<T> for VecExt<T, M> {
+    type Iterator<'a>
+        = VecIter<'a, T, Self>
+    where
+        Self: 'a;
+
+    fn iter(&self) -> Self::Iterator<'_> {
+        VecIter::new(self, 0, self.len())
+    }
+
+    fn iter_range(&self, start: u64, end: u64) -> Self::Iterator<'_> {
+        VecIter::new(self, start, end)
+    }
+
     fn is_empty(&self) -> bool {
         self.get_inner().is_empty()
     }
@@ -68,9 +97,83 @@ impl<T: Storable, M: Memory> VecStructure<T> for VecExt<T, M> {
         self.mut_inner().push(item)
     }
 
+    /// The memory is grown before appending the value, so that the failure can be reported.
+    fn try_push(&mut self, item: &T) -> Result<(), StructureError> {
+        self.reserve(1)?;
+        self.mut_inner().push(item);
+        Ok(())
+    }
+
     fn pop(&mut self) -> Option<T> {
         self.mut_inner().pop()
     }
+
+    fn truncate(&mut self, len: u64) {
+        if len == 0 {
+            self.clear();
+            return;
+        }
+
+        let vector = self.mut_inner();
+        while vector.len() > len {
+            vector.pop();
+        }
+    }
+
+    fn swap_remove(&mut self, index: u64) -> Option<T> {
+        let len = self.len();
+        if index >= len {
+            return None;
+        }
+
+        let last = self.mut_inner().pop()?;
+        if index == len - 1 {
+            return Some(last);
+        }
+
+        let removed = self.get_inner().get(index);
+        self.mut_inner().set(index, &last);
+        removed
+    }
+
+    /// The memory is grown once for all the values, if the iterator provides a lower bound of its length.
+    fn extend_from_iter(&mut self, items: impl IntoIterator<Item = T>) {
+        let items = items.into_iter();
+        // If the memory cannot grow, the failure is reported by the first push that needs more memory
+        let _ = self.reserve(items.size_hint().0 as u64);
+
+        let vector = self.mut_inner();
+        for item in items {
+            vector.push(&item);
+        }
+    }
+
+    fn retain(&mut self, mut f: impl FnMut(&T) -> bool) {
+        let len = self.len();
+        let mut kept = 0;
+        for index in 0..len {
+            // This should never panic, because `index` is inside the `len`.
+            let item = self.get_inner().get(index).expect("item should be present");
+            if f(&item) {
+                if kept != index {
+                    self.mut_inner().set(kept, &item);
+                }
+                kept += 1;
+            }
+        }
+        self.truncate(kept);
+    }
+}
+
+impl<T: Storable, M: Memory> MemoryUsage for VecExt<T, M> {
+    fn allocated_bytes(&self) -> u64 {
+        VEC_HEADER_SIZE + self.len() * vec_slot_size(T::BOUND)
+    }
+
+    /// The size of the values can be derived only if they have a fixed size.
+    fn used_bytes(&self) -> Option<u64> {
+        fixed_size(T::BOUND).map(|size| self.len() * size)
+    }
 }
 
 #[cfg(test)]
@@ -78,7 +181,7 @@ mod tests {
 
     use ic_stable_structures::VectorMemory;
 
-    use crate::test_utils::Array;
+    use crate::test_utils::{Array, CappedMemory};
 
     use super::*;
 
@@ -149,6 +252,128 @@ mod tests {
         assert_eq!(Some(item), vec.get(0));
     }
 
+    #[test]
+    fn should_return_an_error_if_the_memory_cannot_grow() {
+        let mut vec = VecExt::<Array<1024>, _>::init(CappedMemory::new(1));
+
+        let mut pushed = 0;
+        while vec.try_push(&Array([1u8; 1024])).is_ok() {
+            pushed += 1;
+        }
+
+        assert_eq!(
+            vec.try_push(&Array([1u8; 1024])),
+            Err(StructureError::GrowFailed {
+                current_size: 1,
+                delta: 1
+            })
+        );
+        assert!(pushed > 0);
+        assert_eq!(vec.len(), pushed);
+        assert_eq!(vec.get(0), Some(Array([1u8; 1024])));
+        assert_eq!(vec.pop(), Some(Array([1u8; 1024])));
+    }
+
+    fn vec_of(items: &[u64]) -> VecExt<u64, VectorMemory> {
+        let mut vec = VecExt::new(VectorMemory::default());
+        for item in items {
+            vec.push(item);
+        }
+        vec
+    }
+
+    #[test]
+    fn should_truncate() {
+        let mut vec = vec_of(&[]);
+        vec.truncate(0);
+        assert!(vec.is_empty());
+
+        let mut vec = vec_of(&[1, 2, 3, 4]);
+        vec.truncate(5);
+        assert_eq!(vec.iter().collect::<Vec<_>>(), vec![1, 2, 3, 4]);
+        vec.truncate(4);
+        assert_eq!(vec.iter().collect::<Vec<_>>(), vec![1, 2, 3, 4]);
+        vec.truncate(1);
+        assert_eq!(vec.iter().collect::<Vec<_>>(), vec![1]);
+        vec.truncate(0);
+        assert!(vec.is_empty());
+
+        vec.push(&5);
+        assert_eq!(vec.iter().collect::<Vec<_>>(), vec![5]);
+    }
+
+    #[test]
+    fn should_swap_remove() {
+        let mut vec = vec_of(&[]);
+        assert_eq!(vec.swap_remove(0), None);
+
+        let mut vec = vec_of(&[1, 2, 3, 4]);
+        assert_eq!(vec.swap_remove(4), None);
+
+        assert_eq!(vec.swap_remove(0), Some(1));
+        assert_eq!(vec.iter().collect::<Vec<_>>(), vec![4, 2, 3]);
+
+        assert_eq!(vec.swap_remove(2), Some(3));
+        assert_eq!(vec.iter().collect::<Vec<_>>(), vec![4, 2]);
+
+        assert_eq!(vec.swap_remove(1), Some(2));
+        assert_eq!(vec.swap_remove(0), Some(4));
+        assert!(vec.is_empty());
+    }
+
+    #[test]
+    fn should_retain() {
+        let mut vec = vec_of(&[]);
+        vec.retain(|_| true);
+        assert!(vec.is_empty());
+
+        let mut vec = vec_of(&[1, 2, 3, 4, 5, 6]);
+        vec.retain(|item| item % 2 == 0);
+        assert_eq!(vec.iter().collect::<Vec<_>>(), vec![2, 4, 6]);
+
+        vec.retain(|_| true);
+        assert_eq!(vec.iter().collect::<Vec<_>>(), vec![2, 4, 6]);
+
+        vec.retain(|item| *item != 6);
+        assert_eq!(vec.iter().collect::<Vec<_>>(), vec![2, 4]);
+
+        vec.retain(|_| false);
+        assert!(vec.is_empty());
+        assert_eq!(vec.get(0), None);
+    }
+
+    #[test]
+    fn should_extend_and_drain() {
+        let memory = VectorMemory::default();
+        let mut vec = VecExt::<u64, _>::new(memory.clone());
+        let mut reference = Vec::new();
+
+        vec.extend_from_iter(0..30_000);
+        reference.extend(0..30_000);
+        assert_eq!(
+            memory.size(),
+            (VEC_HEADER_SIZE + 30_000 * 8).div_ceil(65536)
+        );
+
+        vec.extend_from_slice(&[7, 8, 9]);
+        reference.extend_from_slice(&[7, 8, 9]);
+        vec.extend_from_iter((0..2000).filter(|item| item % 3 == 0));
+        reference.extend((0..2000).filter(|item| item % 3 == 0));
+
+        assert_eq!(vec.len(), reference.len() as u64);
+        assert_eq!(vec.iter().collect::<Vec<_>>(), reference);
+
+        let drained = vec.drain_to_vec(1000);
+        let expected = reference.split_off(reference.len() - 1000);
+        assert_eq!(drained, expected.into_iter().rev().collect::<Vec<_>>());
+        assert_eq!(vec.iter().collect::<Vec<_>>(), reference);
+
+        assert_eq!(vec.drain_to_vec(0), Vec::<u64>::new());
+        assert_eq!(vec.drain_to_vec(10_000).len(), reference.len());
+        assert!(vec.is_empty());
+        assert!(vec.drain_to_vec(1).is_empty());
+    }
+
     #[test]
     fn should_reuse_existing_data_on_init() {
         let memory = VectorMemory::default();
@@ -180,4 +405,19 @@ mod tests {
             assert_eq!(None, log.get(0));
         }
     }
+
+    #[test]
+    fn should_report_the_memory_usage() {
+        let mut vec = VecExt::<u64, _>::new(VectorMemory::default());
+        assert_eq!(vec.allocated_bytes(), VEC_HEADER_SIZE);
+        assert_eq!(vec.used_bytes(), Some(0));
+
+        vec.extend_from_slice(&[1, 2, 3]);
+        assert_eq!(vec.allocated_bytes(), VEC_HEADER_SIZE + 3 * 8);
+        assert_eq!(vec.used_bytes(), Some(3 * 8));
+
+        vec.pop();
+        assert_eq!(vec.allocated_bytes(), VEC_HEADER_SIZE + 2 * 8);
+        assert_eq!(vec.used_bytes(), Some(2 * 8));
+    }
 }
@@ -1,8 +1,31 @@
 mod ext;
+mod priority_queue;
+mod sorted;
+mod versioned;
 
 pub use ext::VecExt;
+pub use priority_queue::StablePriorityQueue;
+pub use sorted::{SortedVec, SortedVecStructure};
+pub use versioned::VersionedVec;
+
+use std::marker::PhantomData;
+use std::ops::Range;
+
+use crate::common::{Pagination, StructureError};
 
 pub trait VecStructure<T> {
+    /// Vector iterator type
+    type Iterator<'a>: DoubleEndedIterator<Item = T>
+    where
+        Self: 'a;
+
+    /// Returns iterator over the elements in the vector
+    fn iter(&self) -> Self::Iterator<'_>;
+
+    /// Returns iterator over the elements from index `start` included to index `end` excluded.
+    /// The indices out of range are ignored.
+    fn iter_range(&self, start: u64, end: u64) -> Self::Iterator<'_>;
+
     /// Returns if vector is empty
     fn is_empty(&self) -> bool;
 
@@ -20,8 +43,149 @@ pub trait VecStructure<T> {
     fn get(&self, index: u64) -> Option<T>;
 
     /// Appends new value to the vector
+    /// WARN: this panics if the stable memory cannot grow, use `try_push` to handle the failure.
     fn push(&mut self, item: &T);
 
+    /// Appends new value to the vector, or returns an error if the stable memory cannot grow.
+    fn try_push(&mut self, item: &T) -> Result<(), StructureError>;
+
     /// Pops the last value from the vector
     fn pop(&mut self) -> Option<T>;
+
+    /// Shortens the vector, keeping the first `len` values and dropping the rest.
+    /// It has no effect if `len` is greater or equal to the vector length.
+    fn truncate(&mut self, len: u64);
+
+    /// Removes the value at `index` and returns it, replacing it with the last value of the vector.
+    /// Returns `None` if `index` is out of range.
+    ///
+    /// This does not preserve the ordering, but it is O(1).
+    fn swap_remove(&mut self, index: u64) -> Option<T>;
+
+    /// Retains only the values for which `f` returns true, preserving their order.
+    ///
+    /// This reads all the values and rewrites all the values after the first removed one, so it is O(n).
+    fn retain(&mut self, f: impl FnMut(&T) -> bool);
+
+    /// Appends all the values to the vector, in order.
+    /// WARN: this panics if the stable memory cannot grow.
+    fn extend_from_iter(&mut self, items: impl IntoIterator<Item = T>) {
+        for item in items {
+            self.push(&item);
+        }
+    }
+
+    /// Appends all the values of the slice to the vector, in order.
+    /// WARN: this panics if the stable memory cannot grow.
+    fn extend_from_slice(&mut self, items: &[T])
+    where
+        T: Clone,
+    {
+        self.extend_from_iter(items.iter().cloned())
+    }
+
+    /// Returns the values of the page, in order.
+    fn page(&self, pagination: Pagination) -> Vec<T> {
+        let end = pagination.offset.saturating_add(pagination.count);
+        self.iter_range(pagination.offset, end).collect()
+    }
+
+    /// Pops up to `max` values from the vector and returns them in the order they are popped,
+    /// that is, starting from the last value of the vector.
+    fn drain_to_vec(&mut self, max: u64) -> Vec<T> {
+        (0..max).map_while(|_| self.pop()).collect()
+    }
+}
+
+/// Iterator over a range of indices of a `VecStructure`.
+pub struct VecIter<'a, T, V: VecStructure<T>> {
+    vec: &'a V,
+    indices: Range<u64>,
+    phantom_t: PhantomData<T>,
+}
+
+impl<'a, T, V: VecStructure<T>> VecIter<'a, T, V> {
+    /// Creates an iterator over the elements of `vec` from index `start` included to index `end` excluded.
+    /// The end is limited to the length of the vector.
+    pub fn new(vec: &'a V, start: u64, end: u64) -> Self {
+        let end = end.min(vec.len());
+        Self {
+            vec,
+            indices: start.min(end)..end,
+            phantom_t: PhantomData,
+        }
+    }
+}
+
+impl<T, V: VecStructure<T>> Iterator for VecIter<'_, T, V> {
+    type Item = T;
+
+    fn next(&mut self) -> Option<T> {
+        self.indices.next().and_then(|index| self.vec.get(index))
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.indices.size_hint()
+    }
+
+    /// Skips the elements without reading them.
+    fn nth(&mut self, n: usize) -> Option<T> {
+        self.indices.nth(n).and_then(|index| self.vec.get(index))
+    }
+}
+
+impl<T, V: VecStructure<T>> DoubleEndedIterator for VecIter<'_, T, V> {
+    fn next_back(&mut self) -> Option<T> {
+        self.indices
+            .next_back()
+            .and_then(|index| self.vec.get(index))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use ic_stable_structures::VectorMemory;
+
+    use super::*;
+
+    fn check_iteration<V: VecStructure<u64>>(vec: &mut V) {
+        assert_eq!(vec.iter().next(), None);
+        assert_eq!(vec.iter_range(0, 10).next(), None);
+
+        vec.extend_from_iter(0..10);
+
+        assert_eq!(vec.iter().collect::<Vec<_>>(), (0..10).collect::<Vec<_>>());
+        assert_eq!(
+            vec.iter().rev().collect::<Vec<_>>(),
+            (0..10).rev().collect::<Vec<_>>()
+        );
+        assert_eq!(vec.iter().nth(3), Some(3));
+        assert_eq!(vec.iter().size_hint(), (10, Some(10)));
+
+        // paginated reads
+        assert_eq!(vec.iter_range(0, 4).collect::<Vec<_>>(), vec![0, 1, 2, 3]);
+        assert_eq!(vec.iter_range(4, 8).collect::<Vec<_>>(), vec![4, 5, 6, 7]);
+        assert_eq!(vec.iter_range(8, 12).collect::<Vec<_>>(), vec![8, 9]);
+        assert_eq!(vec.iter_range(12, 16).next(), None);
+        assert_eq!(vec.iter_range(5, 2).next(), None);
+
+        assert_eq!(vec.page(Pagination::new(8, 4)), vec![8, 9]);
+        assert!(vec.page(Pagination::new(3, 0)).is_empty());
+        assert!(vec.page(Pagination::new(12, 4)).is_empty());
+        assert!(vec.page(Pagination::new(u64::MAX, u64::MAX)).is_empty());
+        assert_eq!(vec.page(Pagination::new(2, 100).validate(3)), vec![2, 3, 4]);
+    }
+
+    #[test]
+    fn should_iterate_a_vec_ext() {
+        check_iteration(&mut VecExt::<u64, _>::new(VectorMemory::default()));
+    }
+
+    #[test]
+    fn should_iterate_a_versioned_vec() {
+        check_iteration(&mut VersionedVec::<u64, u64, _>::new(
+            VectorMemory::default(),
+        ));
+    }
 }
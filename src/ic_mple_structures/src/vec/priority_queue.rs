@@ -0,0 +1,286 @@
+use ic_stable_structures::{Memory, Storable};
+
+use crate::common::MemoryUsage;
+use crate::vec::{VecExt, VecStructure};
+
+/// A stable priority queue that returns the smallest value first.
+///
+/// The values are stored in a stable vector laid out as a binary min-heap.
+/// Every `push` and `pop` moves up to O(log n) values,
+/// and every moved value is written to the stable memory,
+/// so the cost of a write is proportional to the logarithm of the number of values.
+pub struct StablePriorityQueue<T: Storable + Ord, M: Memory>(VecExt<T, M>);
+
+impl<T: Storable + Ord, M: Memory> StablePriorityQueue<T, M> {
+    /// Initializes a priority queue in the specified memory.
+    ///
+    /// If the values in the memory do not satisfy the heap property,
+    /// they are reordered, with a cost of O(n).
+    ///
+    /// PRECONDITION: the memory is either empty or contains a valid
+    /// stable vector.
+    pub fn init(memory: M) -> Self {
+        let mut queue = Self(VecExt::init(memory));
+        if !queue.is_heap() {
+            queue.heapify();
+        }
+        queue
+    }
+
+    /// Creates a new empty priority queue in the specified memory,
+    /// overwriting any data structures the memory might have
+    /// contained previously.
+    pub fn new(memory: M) -> Self {
+        Self(VecExt::new(memory))
+    }
+
+    /// Returns the number of values in the queue
+    pub fn len(&self) -> u64 {
+        self.0.len()
+    }
+
+    /// Returns if the queue is empty
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    /// Removes all the values from the queue
+    pub fn clear(&mut self) {
+        self.0.clear()
+    }
+
+    /// Returns the smallest value without removing it.
+    pub fn peek(&self) -> Option<T> {
+        self.0.get(0)
+    }
+
+    /// Adds a value to the queue.
+    ///
+    /// Complexity: O(log n)
+    pub fn push(&mut self, item: &T) {
+        self.0.push(item);
+        self.sift_up(self.0.len() - 1, item);
+    }
+
+    /// Removes the smallest value from the queue and returns it.
+    /// If multiple values are the smallest, any one of them can be returned.
+    ///
+    /// Complexity: O(log n)
+    pub fn pop(&mut self) -> Option<T> {
+        let last = self.0.pop()?;
+        if self.0.is_empty() {
+            return Some(last);
+        }
+
+        let smallest = self.item(0);
+        self.sift_down(0, last);
+        Some(smallest)
+    }
+
+    /// Moves `item` from `index` towards the root until its parent is not greater than it.
+    /// Only the final position of `item` and the moved parents are written.
+    fn sift_up(&mut self, mut index: u64, item: &T) {
+        let start = index;
+        while index > 0 {
+            let parent_index = (index - 1) / 2;
+            let parent = self.item(parent_index);
+            if &parent <= item {
+                break;
+            }
+            self.0.set(index, &parent);
+            index = parent_index;
+        }
+        if index != start {
+            self.0.set(index, item);
+        }
+    }
+
+    /// Places `item` at `index` and moves it towards the leaves until its children are not less than it.
+    fn sift_down(&mut self, mut index: u64, item: T) {
+        let len = self.0.len();
+        loop {
+            let mut child_index = 2 * index + 1;
+            if child_index >= len {
+                break;
+            }
+            let mut child = self.item(child_index);
+            if child_index + 1 < len {
+                let right = self.item(child_index + 1);
+                if right < child {
+                    child_index += 1;
+                    child = right;
+                }
+            }
+            if item <= child {
+                break;
+            }
+            self.0.set(index, &child);
+            index = child_index;
+        }
+        self.0.set(index, &item);
+    }
+
+    /// Checks that no value is less than its parent.
+    fn is_heap(&self) -> bool {
+        (1..self.0.len()).all(|index| self.item((index - 1) / 2) <= self.item(index))
+    }
+
+    /// Reorders the values so that they satisfy the heap property.
+    fn heapify(&mut self) {
+        for index in (0..self.0.len() / 2).rev() {
+            let item = self.item(index);
+            self.sift_down(index, item);
+        }
+    }
+
+    /// Returns the value at `index`.
+    ///
+    /// PRECONDITION: `index < self.len()`
+    fn item(&self, index: u64) -> T {
+        // This should never panic, because `index` is inside the `len`.
+        self.0.get(index).expect("item should be present")
+    }
+}
+
+impl<T: Storable + Ord, M: Memory> MemoryUsage for StablePriorityQueue<T, M> {
+    fn allocated_bytes(&self) -> u64 {
+        self.0.allocated_bytes()
+    }
+
+    fn used_bytes(&self) -> Option<u64> {
+        self.0.used_bytes()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use ic_stable_structures::VectorMemory;
+    use rand::random;
+
+    use super::*;
+
+    fn pop_all<M: Memory>(queue: &mut StablePriorityQueue<u32, M>) -> Vec<u32> {
+        std::iter::from_fn(|| queue.pop()).collect()
+    }
+
+    #[test]
+    fn should_push_and_pop() {
+        let mut queue = StablePriorityQueue::new(VectorMemory::default());
+        assert!(queue.is_empty());
+        assert_eq!(queue.peek(), None);
+        assert_eq!(queue.pop(), None);
+
+        queue.push(&5u32);
+        queue.push(&1);
+        queue.push(&9);
+        queue.push(&1);
+        queue.push(&3);
+
+        assert_eq!(queue.len(), 5);
+        assert_eq!(queue.peek(), Some(1));
+        assert_eq!(queue.pop(), Some(1));
+        assert_eq!(queue.pop(), Some(1));
+        assert_eq!(queue.peek(), Some(3));
+
+        queue.push(&0);
+        assert_eq!(pop_all(&mut queue), vec![0, 3, 5, 9]);
+        assert!(queue.is_empty());
+    }
+
+    #[test]
+    fn should_pop_randomized_values_sorted() {
+        for len in [1, 2, 3, 10, 100, 1000] {
+            let mut queue = StablePriorityQueue::new(VectorMemory::default());
+            let mut expected = (0..len).map(|_| random::<u32>() % 500).collect::<Vec<_>>();
+            for item in &expected {
+                queue.push(item);
+            }
+            expected.sort();
+
+            assert_eq!(queue.len(), len as u64);
+            assert_eq!(pop_all(&mut queue), expected);
+        }
+    }
+
+    #[test]
+    fn should_interleave_pushes_and_pops() {
+        let mut queue = StablePriorityQueue::new(VectorMemory::default());
+        let mut expected = Vec::new();
+
+        for _ in 0..2000 {
+            if random::<u8>() % 3 == 0 {
+                let smallest = expected.iter().min().copied();
+                if let Some(smallest) = smallest {
+                    let index = expected.iter().position(|item| *item == smallest).unwrap();
+                    expected.swap_remove(index);
+                }
+                assert_eq!(queue.pop(), smallest);
+            } else {
+                let item = random::<u32>() % 100;
+                queue.push(&item);
+                expected.push(item);
+            }
+            assert_eq!(queue.peek(), expected.iter().min().copied());
+        }
+    }
+
+    #[test]
+    fn should_clear() {
+        let mut queue = StablePriorityQueue::new(VectorMemory::default());
+        queue.push(&1u32);
+        queue.push(&2);
+
+        queue.clear();
+        assert!(queue.is_empty());
+        assert_eq!(queue.pop(), None);
+
+        queue.push(&3);
+        assert_eq!(pop_all(&mut queue), vec![3]);
+    }
+
+    #[test]
+    fn should_reuse_existing_data_on_init() {
+        let memory = VectorMemory::default();
+        let mut expected = (0..100).map(|_| random::<u32>()).collect::<Vec<_>>();
+        {
+            let mut queue = StablePriorityQueue::init(memory.clone());
+            for item in &expected {
+                queue.push(item);
+            }
+        }
+
+        let mut queue = StablePriorityQueue::init(memory);
+        expected.sort();
+        assert_eq!(queue.peek(), expected.first().copied());
+        assert_eq!(pop_all(&mut queue), expected);
+    }
+
+    #[test]
+    fn should_repair_the_heap_on_init() {
+        let memory = VectorMemory::default();
+        {
+            let mut vec = VecExt::new(memory.clone());
+            for item in [9u32, 8, 7, 1, 5, 3, 2, 6] {
+                vec.push(&item);
+            }
+        }
+
+        let mut queue = StablePriorityQueue::init(memory);
+        assert_eq!(queue.len(), 8);
+        assert_eq!(queue.peek(), Some(1));
+        assert_eq!(pop_all(&mut queue), vec![1, 2, 3, 5, 6, 7, 8, 9]);
+    }
+
+    #[test]
+    fn should_erase_existing_data_on_new() {
+        let memory = VectorMemory::default();
+        {
+            let mut queue = StablePriorityQueue::new(memory.clone());
+            queue.push(&1u32);
+        }
+
+        let queue = StablePriorityQueue::<u32, _>::new(memory);
+        assert!(queue.is_empty());
+    }
+}
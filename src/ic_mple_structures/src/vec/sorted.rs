@@ -0,0 +1,324 @@
+use std::ops::{Bound, RangeBounds};
+
+use ic_stable_structures::{Memory, Storable};
+
+use crate::common::{MemoryUsage, StructureError};
+use crate::vec::{VecExt, VecStructure};
+
+pub trait SortedVecStructure<T: Ord> {
+    /// Returns if vector is empty
+    fn is_empty(&self) -> bool;
+
+    /// Returns the number of elements in the vector
+    fn len(&self) -> u64;
+
+    /// Returns the value at `index`
+    fn get(&self, index: u64) -> Option<T>;
+
+    /// Removes al the values from the vector
+    fn clear(&mut self);
+
+    /// Inserts the value keeping the vector sorted and returns its index.
+    /// The value is inserted after the values equal to it.
+    ///
+    /// All the values after the returned index are moved, so this is O(n).
+    fn insert_sorted(&mut self, item: &T) -> u64;
+
+    /// Removes the value at `index` and returns it.
+    /// Returns `None` if `index` is out of range.
+    ///
+    /// All the values after `index` are moved, so this is O(n).
+    fn remove(&mut self, index: u64) -> Option<T>;
+
+    /// Binary searches the value.
+    ///
+    /// If the value is found, returns `Ok` with its index; if there are multiple matches,
+    /// any one of them can be returned.
+    /// If the value is not found, returns `Err` with the index where it could be inserted keeping the vector sorted.
+    fn binary_search(&self, item: &T) -> Result<u64, u64>;
+
+    /// Returns the indices `(start, end)` of the values in `range`,
+    /// that is, the values from index `start` included to index `end` excluded.
+    /// If no value is in the range, `start == end`.
+    fn range_indices(&self, range: impl RangeBounds<T>) -> (u64, u64);
+
+    /// Returns true if the vector contains the value
+    fn contains(&self, item: &T) -> bool {
+        self.binary_search(item).is_ok()
+    }
+}
+
+/// A stable vector whose values are always sorted in ascending order.
+pub struct SortedVec<T: Storable + Ord, M: Memory>(VecExt<T, M>);
+
+impl<T: Storable + Ord, M: Memory> SortedVec<T, M> {
+    /// Initializes a sorted vector in the specified memory.
+    ///
+    /// Returns an error if the memory contains a stable vector whose values are not sorted.
+    ///
+    /// PRECONDITION: the memory is either empty or contains a valid
+    /// stable vector.
+    pub fn init(memory: M) -> Result<Self, StructureError> {
+        let vec = Self(VecExt::init(memory));
+        vec.validate()?;
+        Ok(vec)
+    }
+
+    /// Creates a new empty sorted vector in the specified memory,
+    /// overwriting any data structures the memory might have
+    /// contained previously.
+    pub fn new(memory: M) -> Self {
+        Self(VecExt::new(memory))
+    }
+
+    /// Returns iterator over the elements in the vector, in ascending order
+    pub fn iter(&self) -> impl Iterator<Item = T> + '_ {
+        self.0.iter()
+    }
+
+    /// Returns the index of the first value for which `pred` returns false.
+    ///
+    /// PRECONDITION: `pred` returns true for all the values before that index and false for all the others.
+    fn partition_point(&self, pred: impl Fn(&T) -> bool) -> u64 {
+        let mut low = 0;
+        let mut high = self.0.len();
+        while low < high {
+            let mid = low + (high - low) / 2;
+            if pred(&self.item(mid)) {
+                low = mid + 1;
+            } else {
+                high = mid;
+            }
+        }
+        low
+    }
+
+    /// Checks that the values are sorted.
+    fn validate(&self) -> Result<(), StructureError> {
+        let mut values = self.0.iter();
+        let Some(mut previous) = values.next() else {
+            return Ok(());
+        };
+        for (index, value) in values.enumerate() {
+            if value < previous {
+                return Err(StructureError::Corrupted(format!(
+                    "sorted vector value at index {} is less than the previous one",
+                    index + 1
+                )));
+            }
+            previous = value;
+        }
+        Ok(())
+    }
+
+    /// Returns the value at `index`.
+    ///
+    /// PRECONDITION: `index < self.len()`
+    fn item(&self, index: u64) -> T {
+        // This should never panic, because `index` is inside the `len`.
+        self.0.get(index).expect("item should be present")
+    }
+}
+
+impl<T: Storable + Ord, M: Memory> SortedVecStructure<T> for SortedVec<T, M> {
+    fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    fn len(&self) -> u64 {
+        self.0.len()
+    }
+
+    fn get(&self, index: u64) -> Option<T> {
+        self.0.get(index)
+    }
+
+    fn clear(&mut self) {
+        self.0.clear()
+    }
+
+    fn insert_sorted(&mut self, item: &T) -> u64 {
+        let index = self.partition_point(|value| value <= item);
+
+        let len = self.0.len();
+        if index == len {
+            self.0.push(item);
+            return index;
+        }
+
+        // Move all the values after `index` by one position, starting from the last one
+        let last = self.item(len - 1);
+        self.0.push(&last);
+        for moved in (index..len - 1).rev() {
+            let value = self.item(moved);
+            self.0.set(moved + 1, &value);
+        }
+        self.0.set(index, item);
+        index
+    }
+
+    fn remove(&mut self, index: u64) -> Option<T> {
+        let removed = self.0.get(index)?;
+
+        // Move all the values after `index` by one position, starting from the first one
+        for moved in index + 1..self.0.len() {
+            let value = self.item(moved);
+            self.0.set(moved - 1, &value);
+        }
+        self.0.pop();
+        Some(removed)
+    }
+
+    fn binary_search(&self, item: &T) -> Result<u64, u64> {
+        let index = self.partition_point(|value| value < item);
+        match self.0.get(index) {
+            Some(value) if &value == item => Ok(index),
+            _ => Err(index),
+        }
+    }
+
+    fn range_indices(&self, range: impl RangeBounds<T>) -> (u64, u64) {
+        let start = match range.start_bound() {
+            Bound::Included(bound) => self.partition_point(|value| value < bound),
+            Bound::Excluded(bound) => self.partition_point(|value| value <= bound),
+            Bound::Unbounded => 0,
+        };
+        let end = match range.end_bound() {
+            Bound::Included(bound) => self.partition_point(|value| value <= bound),
+            Bound::Excluded(bound) => self.partition_point(|value| value < bound),
+            Bound::Unbounded => self.0.len(),
+        };
+        (start, end.max(start))
+    }
+}
+
+impl<T: Storable + Ord, M: Memory> MemoryUsage for SortedVec<T, M> {
+    fn allocated_bytes(&self) -> u64 {
+        self.0.allocated_bytes()
+    }
+
+    fn used_bytes(&self) -> Option<u64> {
+        self.0.used_bytes()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use ic_stable_structures::VectorMemory;
+
+    use super::*;
+
+    fn sorted_vec_of(items: &[u64]) -> SortedVec<u64, VectorMemory> {
+        let mut vec = SortedVec::new(VectorMemory::default());
+        for item in items {
+            vec.insert_sorted(item);
+        }
+        vec
+    }
+
+    #[test]
+    fn should_insert_sorted() {
+        let mut vec = SortedVec::new(VectorMemory::default());
+        assert!(vec.is_empty());
+
+        assert_eq!(vec.insert_sorted(&5u64), 0);
+        assert_eq!(vec.insert_sorted(&1), 0);
+        assert_eq!(vec.insert_sorted(&9), 2);
+        assert_eq!(vec.insert_sorted(&5), 2);
+        assert_eq!(vec.insert_sorted(&3), 1);
+        assert_eq!(vec.insert_sorted(&0), 0);
+
+        assert_eq!(vec.len(), 6);
+        assert_eq!(vec.iter().collect::<Vec<_>>(), vec![0, 1, 3, 5, 5, 9]);
+    }
+
+    #[test]
+    fn should_remove() {
+        let mut vec = sorted_vec_of(&[1, 3, 3, 7]);
+
+        assert_eq!(vec.remove(4), None);
+        assert_eq!(vec.remove(1), Some(3));
+        assert_eq!(vec.iter().collect::<Vec<_>>(), vec![1, 3, 7]);
+        assert_eq!(vec.remove(2), Some(7));
+        assert_eq!(vec.remove(0), Some(1));
+        assert_eq!(vec.iter().collect::<Vec<_>>(), vec![3]);
+        assert_eq!(vec.remove(0), Some(3));
+        assert!(vec.is_empty());
+        assert_eq!(vec.remove(0), None);
+    }
+
+    #[test]
+    fn should_binary_search() {
+        let vec = sorted_vec_of(&[]);
+        assert_eq!(vec.binary_search(&1), Err(0));
+        assert!(!vec.contains(&1));
+
+        let vec = sorted_vec_of(&[10, 20, 20, 20, 30]);
+        assert_eq!(vec.binary_search(&10), Ok(0));
+        assert_eq!(vec.binary_search(&30), Ok(4));
+        assert!(matches!(vec.binary_search(&20), Ok(1..=3)));
+        assert_eq!(vec.binary_search(&15), Err(1));
+        assert_eq!(vec.binary_search(&25), Err(4));
+
+        // absent values at both ends
+        assert_eq!(vec.binary_search(&0), Err(0));
+        assert_eq!(vec.binary_search(&40), Err(5));
+
+        assert!(vec.contains(&20));
+        assert!(!vec.contains(&0));
+        assert!(!vec.contains(&40));
+    }
+
+    #[test]
+    fn should_return_the_range_indices() {
+        let vec = sorted_vec_of(&[10, 20, 20, 20, 30]);
+
+        assert_eq!(vec.range_indices(..), (0, 5));
+        assert_eq!(vec.range_indices(20..=20), (1, 4));
+        assert_eq!(vec.range_indices(20..30), (1, 4));
+        assert_eq!(vec.range_indices(15..), (1, 5));
+        assert_eq!(vec.range_indices(..20), (0, 1));
+        assert_eq!(
+            vec.range_indices((Bound::Excluded(20), Bound::Unbounded)),
+            (4, 5)
+        );
+
+        // empty ranges
+        assert_eq!(vec.range_indices(21..29), (4, 4));
+        assert_eq!(vec.range_indices(..10), (0, 0));
+        assert_eq!(vec.range_indices(31..), (5, 5));
+        assert_eq!(vec.range_indices(30..10), (4, 4));
+    }
+
+    #[test]
+    fn should_reuse_existing_data_on_init() {
+        let memory = VectorMemory::default();
+        {
+            let mut vec = SortedVec::init(memory.clone()).unwrap();
+            vec.insert_sorted(&2u64);
+            vec.insert_sorted(&1);
+        }
+
+        let vec = SortedVec::<u64, _>::init(memory).unwrap();
+        assert_eq!(vec.iter().collect::<Vec<_>>(), vec![1, 2]);
+    }
+
+    #[test]
+    fn should_return_an_error_on_init_if_not_sorted() {
+        let memory = VectorMemory::default();
+        {
+            let mut vec = VecExt::init(memory.clone());
+            vec.push(&1u64);
+            vec.push(&3);
+            vec.push(&2);
+        }
+
+        assert_eq!(
+            SortedVec::<u64, _>::init(memory).err(),
+            Some(StructureError::Corrupted(
+                "sorted vector value at index 2 is less than the previous one".to_string()
+            ))
+        );
+    }
+}
@@ -0,0 +1,272 @@
+use std::marker::PhantomData;
+
+use ic_stable_structures::Memory;
+
+use crate::{
+    common::{Codec, MemoryUsage, StructureError},
+    vec::{VecExt, VecIter, VecStructure},
+};
+
+/// A versioned stable vector.
+///
+/// The values are stored as the codec type and decoded on read.
+/// As for the `VecExt`, the codec type must be bounded.
+pub struct VersionedVec<T, C: Codec<T>, M: Memory> {
+    inner: VecExt<C, M>,
+    phantom_t: PhantomData<T>,
+}
+
+impl<T, C: Codec<T>, M: Memory> VersionedVec<T, C, M> {
+    /// Initializes a VersionedVec in the specified memory.
+    ///
+    /// PRECONDITION: the memory is either empty or contains a valid
+    /// stable vector.
+    pub fn init(memory: M) -> Self {
+        Self::with_vec(VecExt::init(memory))
+    }
+
+    /// Creates a new empty VersionedVec in the specified memory,
+    /// overwriting any data structures the memory might have
+    /// contained previously.
+    pub fn new(memory: M) -> Self {
+        Self::with_vec(VecExt::new(memory))
+    }
+
+    /// Creates a new VersionedVec from a vector of encoded values.
+    pub fn with_vec(vec: VecExt<C, M>) -> Self {
+        Self {
+            inner: vec,
+            phantom_t: PhantomData,
+        }
+    }
+
+    /// Returns iterator over the elements in the vector
+    pub fn iter(&self) -> impl Iterator<Item = T> + '_ {
+        self.inner.iter().map(C::decode)
+    }
+}
+
+impl<T: Clone, C: Codec<T>, M: Memory> VecStructure<T> for VersionedVec<T, C, M> {
+    type Iterator<'a>
+        = VecIter<'a, T, Self>
+    where
+        Self: 'a;
+
+    fn iter(&self) -> Self::Iterator<'_> {
+        VecIter::new(self, 0, self.len())
+    }
+
+    fn iter_range(&self, start: u64, end: u64) -> Self::Iterator<'_> {
+        VecIter::new(self, start, end)
+    }
+
+    fn is_empty(&self) -> bool {
+        self.inner.is_empty()
+    }
+
+    fn clear(&mut self) {
+        self.inner.clear()
+    }
+
+    fn len(&self) -> u64 {
+        self.inner.len()
+    }
+
+    fn set(&mut self, index: u64, item: &T) {
+        self.inner.set(index, &C::encode(item.clone()))
+    }
+
+    fn get(&self, index: u64) -> Option<T> {
+        self.inner.get(index).map(C::decode)
+    }
+
+    fn push(&mut self, item: &T) {
+        self.inner.push(&C::encode(item.clone()))
+    }
+
+    fn try_push(&mut self, item: &T) -> Result<(), StructureError> {
+        self.inner.try_push(&C::encode(item.clone()))
+    }
+
+    fn pop(&mut self) -> Option<T> {
+        self.inner.pop().map(C::decode)
+    }
+
+    fn truncate(&mut self, len: u64) {
+        self.inner.truncate(len)
+    }
+
+    fn extend_from_iter(&mut self, items: impl IntoIterator<Item = T>) {
+        self.inner
+            .extend_from_iter(items.into_iter().map(C::encode))
+    }
+
+    fn swap_remove(&mut self, index: u64) -> Option<T> {
+        self.inner.swap_remove(index).map(C::decode)
+    }
+
+    /// The retained values are moved without being encoded again,
+    /// so they keep the version they are stored with.
+    fn retain(&mut self, mut f: impl FnMut(&T) -> bool) {
+        let len = self.inner.len();
+        let mut kept = 0;
+        for index in 0..len {
+            // This should never panic, because `index` is inside the `len`.
+            let item = self.inner.get(index).expect("item should be present");
+            if f(&C::decode(item)) {
+                if kept != index {
+                    let item = self.inner.get(index).expect("item should be present");
+                    self.inner.set(kept, &item);
+                }
+                kept += 1;
+            }
+        }
+        self.inner.truncate(kept);
+    }
+}
+
+impl<T, C: Codec<T>, M: Memory> MemoryUsage for VersionedVec<T, C, M> {
+    fn allocated_bytes(&self) -> u64 {
+        self.inner.allocated_bytes()
+    }
+
+    fn used_bytes(&self) -> Option<u64> {
+        self.inner.used_bytes()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use std::borrow::Cow;
+
+    use ic_stable_structures::storable::Bound;
+    use ic_stable_structures::{Storable, VectorMemory};
+
+    use super::*;
+
+    /// An amount stored as `u32` in the first version and as `u64` in the second one.
+    #[derive(Clone, Copy, Debug, PartialEq, Eq)]
+    enum AmountCodec {
+        V1(u32),
+        V2(u64),
+    }
+
+    impl Storable for AmountCodec {
+        const BOUND: Bound = Bound::Bounded {
+            max_size: 9,
+            is_fixed_size: false,
+        };
+
+        fn to_bytes(&self) -> Cow<'_, [u8]> {
+            Cow::Owned(self.into_bytes())
+        }
+
+        fn into_bytes(self) -> Vec<u8> {
+            match self {
+                AmountCodec::V1(amount) => [&[1u8][..], &amount.to_be_bytes()].concat(),
+                AmountCodec::V2(amount) => [&[2u8][..], &amount.to_be_bytes()].concat(),
+            }
+        }
+
+        fn from_bytes(bytes: Cow<[u8]>) -> Self {
+            match bytes[0] {
+                1 => AmountCodec::V1(u32::from_be_bytes(bytes[1..].try_into().unwrap())),
+                _ => AmountCodec::V2(u64::from_be_bytes(bytes[1..].try_into().unwrap())),
+            }
+        }
+    }
+
+    impl Codec<u64> for AmountCodec {
+        fn decode(source: AmountCodec) -> u64 {
+            match source {
+                AmountCodec::V1(amount) => u64::from(amount),
+                AmountCodec::V2(amount) => amount,
+            }
+        }
+
+        fn encode(dest: u64) -> AmountCodec {
+            AmountCodec::V2(dest)
+        }
+    }
+
+    #[test]
+    fn should_read_old_and_new_values() {
+        let memory = VectorMemory::default();
+
+        {
+            let mut raw_vec = VecExt::init(memory.clone());
+            raw_vec.push(&AmountCodec::V1(1));
+        }
+        {
+            let mut vec = VersionedVec::<u64, AmountCodec, _>::init(memory.clone());
+            vec.push(&(u32::MAX as u64 + 2));
+        }
+        {
+            let mut raw_vec = VecExt::init(memory.clone());
+            raw_vec.push(&AmountCodec::V1(3));
+            assert_eq!(raw_vec.get(1), Some(AmountCodec::V2(u32::MAX as u64 + 2)));
+        }
+
+        let mut vec = VersionedVec::<u64, AmountCodec, _>::init(memory);
+        assert_eq!(vec.len(), 3);
+        assert_eq!(
+            vec.iter().collect::<Vec<_>>(),
+            vec![1, u32::MAX as u64 + 2, 3]
+        );
+
+        vec.set(0, &10);
+        assert_eq!(vec.get(0), Some(10));
+        assert_eq!(vec.pop(), Some(3));
+        assert_eq!(vec.get(2), None);
+
+        vec.clear();
+        assert!(vec.is_empty());
+    }
+
+    #[test]
+    fn should_retain_without_encoding_again() {
+        let memory = VectorMemory::default();
+        {
+            let mut raw_vec = VecExt::init(memory.clone());
+            raw_vec.push(&AmountCodec::V1(1));
+            raw_vec.push(&AmountCodec::V2(2));
+            raw_vec.push(&AmountCodec::V1(3));
+            raw_vec.push(&AmountCodec::V2(4));
+        }
+
+        let mut vec = VersionedVec::<u64, AmountCodec, _>::init(memory.clone());
+        vec.retain(|amount| *amount != 2);
+        assert_eq!(vec.iter().collect::<Vec<_>>(), vec![1, 3, 4]);
+        assert_eq!(vec.swap_remove(0), Some(1));
+        assert_eq!(vec.iter().collect::<Vec<_>>(), vec![4, 3]);
+
+        let raw_vec = VecExt::<AmountCodec, _>::init(memory);
+        assert_eq!(
+            raw_vec.iter().collect::<Vec<_>>(),
+            vec![AmountCodec::V2(4), AmountCodec::V1(3)]
+        );
+
+        vec.truncate(1);
+        assert_eq!(vec.iter().collect::<Vec<_>>(), vec![4]);
+
+        vec.extend_from_iter(5..8);
+        vec.extend_from_slice(&[8, 9]);
+        assert_eq!(vec.iter().collect::<Vec<_>>(), vec![4, 5, 6, 7, 8, 9]);
+        assert_eq!(vec.drain_to_vec(2), vec![9, 8]);
+    }
+
+    #[test]
+    fn should_erase_existing_data_on_new() {
+        let memory = VectorMemory::default();
+
+        {
+            let mut vec = VersionedVec::<u64, AmountCodec, _>::new(memory.clone());
+            vec.push(&1);
+        }
+
+        let vec = VersionedVec::<u64, AmountCodec, _>::new(memory);
+        assert!(vec.is_empty());
+        assert_eq!(vec.get(0), None);
+    }
+}
@@ -0,0 +1,175 @@
+use proc_macro::TokenStream;
+use proc_macro2::Span;
+use quote::quote;
+use syn::{Data, DeriveInput, Fields, Ident, LitStr, Path, Type, parse_macro_input};
+
+/// Derives `Codec<Latest>` and `RefCodec<Latest>` for an enum with a variant for each version of a type.
+///
+/// Every variant must have a single unnamed field with the value of that version.
+/// The newest variant is marked with `#[latest]`, the older ones with `#[upgrade(path = "fn_name")]`,
+/// where `fn_name` is a function converting the value of that version into the latest one.
+///
+/// The values of the older versions must implement `Clone`, because `RefCodec::decode_ref` upgrades a copy of them.
+///
+/// ```ignore
+/// #[derive(VersionedCodec)]
+/// enum UserCodec {
+///     #[upgrade(path = "user_v1_to_v2")]
+///     V1(UserV1),
+///     #[latest]
+///     V2(UserV2),
+/// }
+///
+/// fn user_v1_to_v2(user: UserV1) -> UserV2 { ... }
+/// ```
+#[proc_macro_derive(VersionedCodec, attributes(latest, upgrade))]
+pub fn derive_versioned_codec(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+    expand(input)
+        .unwrap_or_else(syn::Error::into_compile_error)
+        .into()
+}
+
+/// A variant of the versioned enum.
+struct Variant {
+    ident: Ident,
+    ty: Type,
+    /// The upgrade function of an older variant, `None` for the latest one.
+    upgrade: Option<Path>,
+}
+
+fn expand(input: DeriveInput) -> syn::Result<proc_macro2::TokenStream> {
+    let Data::Enum(data) = &input.data else {
+        return Err(syn::Error::new_spanned(
+            &input.ident,
+            "VersionedCodec can be derived only for enums",
+        ));
+    };
+
+    let mut variants = Vec::with_capacity(data.variants.len());
+    for variant in &data.variants {
+        variants.push(parse_variant(variant)?);
+    }
+
+    let mut latest = variants.iter().filter(|variant| variant.upgrade.is_none());
+    let latest_variant = latest.next().ok_or_else(|| {
+        syn::Error::new(
+            Span::call_site(),
+            "VersionedCodec requires a variant marked with #[latest]",
+        )
+    })?;
+    if let Some(other) = latest.next() {
+        return Err(syn::Error::new_spanned(
+            &other.ident,
+            "only one variant can be marked with #[latest]",
+        ));
+    }
+
+    let name = &input.ident;
+    let (impl_generics, ty_generics, where_clause) = input.generics.split_for_impl();
+    let latest_ident = &latest_variant.ident;
+    let latest_ty = &latest_variant.ty;
+
+    let decode_arms = variants.iter().map(|variant| {
+        let ident = &variant.ident;
+        match &variant.upgrade {
+            Some(upgrade) => quote! { #name::#ident(value) => #upgrade(value), },
+            None => quote! { #name::#ident(value) => value, },
+        }
+    });
+    let decode_ref_arms = variants.iter().map(|variant| {
+        let ident = &variant.ident;
+        match &variant.upgrade {
+            Some(upgrade) => quote! {
+                #name::#ident(value) => ::std::borrow::Cow::Owned(#upgrade(::std::clone::Clone::clone(value))),
+            },
+            None => quote! { #name::#ident(value) => ::std::borrow::Cow::Borrowed(value), },
+        }
+    });
+
+    Ok(quote! {
+        impl #impl_generics ::ic_mple_structures::Codec<#latest_ty> for #name #ty_generics #where_clause {
+            fn decode(source: Self) -> #latest_ty {
+                match source {
+                    #(#decode_arms)*
+                }
+            }
+
+            fn encode(dest: #latest_ty) -> Self {
+                #name::#latest_ident(dest)
+            }
+
+            fn is_latest(source: &Self) -> bool {
+                ::std::matches!(source, #name::#latest_ident(_))
+            }
+        }
+
+        impl #impl_generics ::ic_mple_structures::RefCodec<#latest_ty> for #name #ty_generics #where_clause {
+            fn decode_ref<'a>(source: &'a Self) -> ::std::borrow::Cow<'a, #latest_ty> {
+                match source {
+                    #(#decode_ref_arms)*
+                }
+            }
+
+            fn encode(dest: #latest_ty) -> Self {
+                #name::#latest_ident(dest)
+            }
+
+            fn is_latest(source: &Self) -> bool {
+                ::std::matches!(source, #name::#latest_ident(_))
+            }
+        }
+    })
+}
+
+fn parse_variant(variant: &syn::Variant) -> syn::Result<Variant> {
+    let ty = match &variant.fields {
+        Fields::Unnamed(fields) if fields.unnamed.len() == 1 => fields.unnamed[0].ty.clone(),
+        _ => {
+            return Err(syn::Error::new_spanned(
+                &variant.ident,
+                "VersionedCodec variants must have a single unnamed field",
+            ));
+        }
+    };
+
+    let mut is_latest = false;
+    let mut upgrade = None;
+    for attr in &variant.attrs {
+        if attr.path().is_ident("latest") {
+            attr.meta.require_path_only()?;
+            is_latest = true;
+        } else if attr.path().is_ident("upgrade") {
+            attr.parse_nested_meta(|meta| {
+                if meta.path.is_ident("path") {
+                    let path: LitStr = meta.value()?.parse()?;
+                    upgrade = Some(path.parse::<Path>()?);
+                    Ok(())
+                } else {
+                    Err(meta.error("expected `path = \"fn_name\"`"))
+                }
+            })?;
+        }
+    }
+
+    match (is_latest, upgrade) {
+        (true, None) => Ok(Variant {
+            ident: variant.ident.clone(),
+            ty,
+            upgrade: None,
+        }),
+        (false, Some(upgrade)) => Ok(Variant {
+            ident: variant.ident.clone(),
+            ty,
+            upgrade: Some(upgrade),
+        }),
+        (true, Some(_)) => Err(syn::Error::new_spanned(
+            &variant.ident,
+            "the #[latest] variant cannot have an #[upgrade] attribute",
+        )),
+        (false, None) => Err(syn::Error::new_spanned(
+            &variant.ident,
+            "VersionedCodec variants must be marked with either #[latest] or #[upgrade(path = \"fn_name\")]",
+        )),
+    }
+}
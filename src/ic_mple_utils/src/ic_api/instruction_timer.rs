@@ -0,0 +1,57 @@
+use crate::ic_api::IcTrait;
+
+/// A guard that measures the instructions executed while it is alive,
+/// and prints them with `IcTrait::print` when it is dropped.
+///
+/// ```ignore
+/// let _timer = InstructionTimer::new(ic(), "rebuild_index");
+/// rebuild_index();
+/// // prints "rebuild_index: <n> instructions" here
+/// ```
+pub struct InstructionTimer<I: IcTrait> {
+    ic: I,
+    label: String,
+    start: u64,
+}
+
+impl<I: IcTrait> InstructionTimer<I> {
+    /// Starts measuring the instructions executed from now on.
+    pub fn new(ic: I, label: impl Into<String>) -> Self {
+        let start = ic.instruction_counter();
+        Self {
+            ic,
+            label: label.into(),
+            start,
+        }
+    }
+
+    /// Returns the instructions executed since the timer was created.
+    pub fn elapsed(&self) -> u64 {
+        self.ic.instruction_counter().saturating_sub(self.start)
+    }
+}
+
+impl<I: IcTrait> Drop for InstructionTimer<I> {
+    fn drop(&mut self) {
+        self.ic
+            .print(format!("{}: {} instructions", self.label, self.elapsed()));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ic_api::mock::IcMock;
+
+    #[test]
+    fn should_measure_the_executed_instructions() {
+        let mut ic = IcMock::default();
+        ic.advance_instructions(1_000);
+
+        let timer = InstructionTimer::new(ic.clone(), "test");
+        assert_eq!(timer.elapsed(), 0);
+
+        ic.advance_instructions(250);
+        assert_eq!(timer.elapsed(), 250);
+    }
+}
@@ -0,0 +1,106 @@
+use candid::CandidType;
+use serde::Deserialize;
+
+use crate::ic_api::IcTrait;
+
+const GIB: u64 = 1024 * 1024 * 1024;
+
+/// The memory pressure of the canister, from the lowest to the highest.
+#[derive(Clone, Copy, Debug, CandidType, Deserialize, PartialEq, Eq, PartialOrd, Ord)]
+pub enum MemoryPressure {
+    Ok,
+    Warning,
+    Critical,
+}
+
+/// The sizes at which the stable and the wasm memories are under pressure.
+#[derive(Clone, Debug, CandidType, Deserialize, PartialEq, Eq)]
+pub struct MemoryPressureThresholds {
+    pub stable_warning_bytes: u64,
+    pub stable_critical_bytes: u64,
+    pub wasm_warning_bytes: u64,
+    pub wasm_critical_bytes: u64,
+}
+
+impl Default for MemoryPressureThresholds {
+    /// The defaults are the 80% and 90% of the 500 GiB stable memory
+    /// and of the 4 GiB wasm memory limits.
+    fn default() -> Self {
+        Self {
+            stable_warning_bytes: 400 * GIB,
+            stable_critical_bytes: 450 * GIB,
+            wasm_warning_bytes: 4 * GIB / 10 * 8,
+            wasm_critical_bytes: 4 * GIB / 10 * 9,
+        }
+    }
+}
+
+impl MemoryPressureThresholds {
+    /// Returns the highest pressure of the stable and the wasm memories of the canister.
+    pub fn check(&self, ic: &impl IcTrait) -> MemoryPressure {
+        let stable = Self::pressure(
+            ic.stable_memory_size_bytes(),
+            self.stable_warning_bytes,
+            self.stable_critical_bytes,
+        );
+        let wasm = Self::pressure(
+            ic.wasm_memory_size_bytes(),
+            self.wasm_warning_bytes,
+            self.wasm_critical_bytes,
+        );
+        stable.max(wasm)
+    }
+
+    fn pressure(size: u64, warning: u64, critical: u64) -> MemoryPressure {
+        if size >= critical {
+            MemoryPressure::Critical
+        } else if size >= warning {
+            MemoryPressure::Warning
+        } else {
+            MemoryPressure::Ok
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ic_api::mock::IcMock;
+
+    fn thresholds() -> MemoryPressureThresholds {
+        MemoryPressureThresholds {
+            stable_warning_bytes: 1_000,
+            stable_critical_bytes: 2_000,
+            wasm_warning_bytes: 100,
+            wasm_critical_bytes: 200,
+        }
+    }
+
+    #[test]
+    fn should_report_the_highest_memory_pressure() {
+        let mut ic = IcMock::default();
+        assert_eq!(thresholds().check(&ic), MemoryPressure::Ok);
+
+        ic.set_stable_memory_size_bytes(1_000);
+        assert_eq!(thresholds().check(&ic), MemoryPressure::Warning);
+
+        ic.set_wasm_memory_size_bytes(250);
+        assert_eq!(thresholds().check(&ic), MemoryPressure::Critical);
+
+        ic.set_wasm_memory_size_bytes(0);
+        ic.set_stable_memory_size_bytes(1_999);
+        assert_eq!(thresholds().check(&ic), MemoryPressure::Warning);
+    }
+
+    #[test]
+    fn should_not_report_pressure_with_the_default_thresholds() {
+        let mut ic = IcMock::default();
+        ic.set_stable_memory_size_bytes(10 * GIB);
+        ic.set_wasm_memory_size_bytes(GIB);
+
+        assert_eq!(
+            MemoryPressureThresholds::default().check(&ic),
+            MemoryPressure::Ok
+        );
+    }
+}
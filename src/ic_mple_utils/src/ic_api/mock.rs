@@ -1,11 +1,98 @@
 use std::{
+    cell::{Cell, RefCell},
+    collections::VecDeque,
     sync::{Arc, Mutex},
-    time::SystemTime,
+    time::{Duration, SystemTime},
 };
 
 use candid::{CandidType, Deserialize, Principal};
 
-use crate::ic_api::IcTrait;
+use crate::ic_api::{IcTrait, SubnetType};
+
+/// The max number of printed lines kept by the IcMock
+const MAX_PRINTED_LINES: usize = 1024;
+
+thread_local! {
+    /// The lines printed by all the IcMock instances of the thread, so that the lines
+    /// printed through `ic()` can be inspected with any instance.
+    static PRINTED: RefCell<VecDeque<String>> = const { RefCell::new(VecDeque::new()) };
+
+    /// The counters of the futures spawned by all the IcMock instances of the thread,
+    /// so that the futures spawned through `ic()` can be awaited with any instance.
+    static SPAWNED_TASKS: Cell<SpawnedTasksStats> = const { Cell::new(SpawnedTasksStats {
+        spawned: 0,
+        completed: 0,
+        panicked: 0,
+    }) };
+}
+
+/// The counters of the futures spawned with `IcMock::spawn` and `IcMock::spawn_detached`.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct SpawnedTasksStats {
+    /// The number of spawned futures.
+    pub spawned: u64,
+    /// The number of futures that completed.
+    pub completed: u64,
+    /// The number of futures that panicked, or were dropped before completing.
+    pub panicked: u64,
+}
+
+impl SpawnedTasksStats {
+    /// Returns the number of spawned futures that did not finish yet.
+    pub fn running(&self) -> u64 {
+        self.spawned - self.completed - self.panicked
+    }
+}
+
+#[cfg(feature = "tokio")]
+fn update_spawned_tasks(f: impl FnOnce(&mut SpawnedTasksStats)) {
+    // the thread-local could be already destroyed if the futures are dropped when the thread exits
+    let _ = SPAWNED_TASKS.try_with(|stats| {
+        let mut updated = stats.get();
+        f(&mut updated);
+        stats.set(updated);
+    });
+}
+
+/// Restores the previous caller of the current message when dropped.
+struct CallerGuard {
+    msg_caller: Arc<Mutex<Principal>>,
+    previous: Principal,
+}
+
+impl Drop for CallerGuard {
+    fn drop(&mut self) {
+        *self.msg_caller.lock().unwrap() = self.previous;
+    }
+}
+
+/// Counts a spawned future, which is completed only if the guard is marked as such before being dropped.
+#[cfg(feature = "tokio")]
+struct SpawnedTaskGuard {
+    completed: bool,
+}
+
+#[cfg(feature = "tokio")]
+impl SpawnedTaskGuard {
+    fn new() -> Self {
+        update_spawned_tasks(|stats| stats.spawned += 1);
+        Self { completed: false }
+    }
+}
+
+#[cfg(feature = "tokio")]
+impl Drop for SpawnedTaskGuard {
+    fn drop(&mut self) {
+        let completed = self.completed;
+        update_spawned_tasks(|stats| {
+            if completed {
+                stats.completed += 1;
+            } else {
+                stats.panicked += 1;
+            }
+        });
+    }
+}
 
 /// The time strategy to use for the mocked IC API
 #[derive(Clone, Debug, CandidType, Deserialize, PartialEq, Eq)]
@@ -24,6 +111,16 @@ pub enum TimeStrategy {
 pub struct IcMock {
     canister_id: Arc<Mutex<candid::Principal>>,
     canister_cycle_balance: Arc<Mutex<u128>>,
+    msg_caller: Arc<Mutex<candid::Principal>>,
+    incoming_cycles: Arc<Mutex<u128>>,
+    controllers: Arc<Mutex<Vec<candid::Principal>>>,
+    canister_version: Arc<Mutex<u64>>,
+    subnet_self: Arc<Mutex<Option<candid::Principal>>>,
+    subnet_type: Arc<Mutex<Option<SubnetType>>>,
+    rand_state: Arc<Mutex<u64>>,
+    instructions: Arc<Mutex<u64>>,
+    stable_memory_size_bytes: Arc<Mutex<u64>>,
+    wasm_memory_size_bytes: Arc<Mutex<u64>>,
     time_strategy: Arc<Mutex<TimeStrategy>>,
 }
 
@@ -32,16 +129,41 @@ impl Default for IcMock {
         Self {
             canister_id: Arc::new(Mutex::new(Principal::anonymous())),
             canister_cycle_balance: Default::default(),
+            msg_caller: Arc::new(Mutex::new(Principal::anonymous())),
+            incoming_cycles: Default::default(),
+            controllers: Default::default(),
+            canister_version: Default::default(),
+            subnet_self: Default::default(),
+            subnet_type: Default::default(),
+            rand_state: Arc::new(Mutex::new(system_time_nanos())),
+            instructions: Default::default(),
+            stable_memory_size_bytes: Default::default(),
+            wasm_memory_size_bytes: Default::default(),
             time_strategy: Arc::new(Mutex::new(TimeStrategy::System)),
         }
     }
 }
 
 impl IcMock {
+    /// Returns a builder of an IcMock, starting from the default values.
+    pub fn builder() -> IcMockBuilder {
+        IcMockBuilder::default()
+    }
+
     pub fn new(canister_id: Principal, canister_cycle_balance: u128) -> Self {
         Self {
             canister_id: Arc::new(Mutex::new(canister_id)),
             canister_cycle_balance: Arc::new(Mutex::new(canister_cycle_balance)),
+            msg_caller: Arc::new(Mutex::new(Principal::anonymous())),
+            incoming_cycles: Default::default(),
+            controllers: Default::default(),
+            canister_version: Default::default(),
+            subnet_self: Default::default(),
+            subnet_type: Default::default(),
+            rand_state: Arc::new(Mutex::new(system_time_nanos())),
+            instructions: Default::default(),
+            stable_memory_size_bytes: Default::default(),
+            wasm_memory_size_bytes: Default::default(),
             time_strategy: Arc::new(Mutex::new(TimeStrategy::System)),
         }
     }
@@ -56,10 +178,135 @@ impl IcMock {
         *self.canister_cycle_balance.lock().unwrap() = canister_cycle_balance;
     }
 
+    /// Sets the Principal returned as the caller of the current message.
+    /// The default caller is the anonymous Principal.
+    pub fn set_msg_caller(&mut self, msg_caller: Principal) {
+        *self.msg_caller.lock().unwrap() = msg_caller;
+    }
+
+    /// Executes `f` with `msg_caller` as the caller of the current message,
+    /// then restores the previous caller, even if `f` panics.
+    pub fn with_caller<R>(&self, msg_caller: Principal, f: impl FnOnce() -> R) -> R {
+        let previous = std::mem::replace(&mut *self.msg_caller.lock().unwrap(), msg_caller);
+        let _guard = CallerGuard {
+            msg_caller: self.msg_caller.clone(),
+            previous,
+        };
+        f()
+    }
+
+    /// Sets the amount of cycles sent with the current message.
+    /// The accepted cycles are moved to the canister cycle balance.
+    pub fn set_incoming_cycles(&mut self, incoming_cycles: u128) {
+        *self.incoming_cycles.lock().unwrap() = incoming_cycles;
+    }
+
+    /// Sets the controllers of the canister.
+    /// By default the canister has no controllers.
+    pub fn set_controllers(&mut self, controllers: Vec<Principal>) {
+        *self.controllers.lock().unwrap() = controllers;
+    }
+
+    /// Sets the version of the canister.
+    pub fn set_canister_version(&mut self, canister_version: u64) {
+        *self.canister_version.lock().unwrap() = canister_version;
+    }
+
+    /// Sets the id of the subnet the canister is running on.
+    /// By default the subnet is unknown.
+    pub fn set_subnet_self(&mut self, subnet_self: Option<Principal>) {
+        *self.subnet_self.lock().unwrap() = subnet_self;
+    }
+
+    /// Sets the type of the subnet the canister is running on.
+    /// By default the subnet type is unknown.
+    pub fn set_subnet_type(&mut self, subnet_type: Option<SubnetType>) {
+        *self.subnet_type.lock().unwrap() = subnet_type;
+    }
+
+    /// Sets the seed of the deterministic generator used by `raw_rand`.
+    /// By default the generator is seeded with the current system time.
+    pub fn set_rand_seed(&mut self, seed: u64) {
+        *self.rand_state.lock().unwrap() = seed;
+    }
+
+    /// Sets the size of the stable memory, in bytes.
+    pub fn set_stable_memory_size_bytes(&mut self, size: u64) {
+        *self.stable_memory_size_bytes.lock().unwrap() = size;
+    }
+
+    /// Sets the size of the wasm memory, in bytes.
+    pub fn set_wasm_memory_size_bytes(&mut self, size: u64) {
+        *self.wasm_memory_size_bytes.lock().unwrap() = size;
+    }
+
+    /// Advances the fake instruction counter by `instructions`.
+    /// The counter starts from zero and never decreases.
+    pub fn advance_instructions(&mut self, instructions: u64) {
+        let mut counter = self.instructions.lock().unwrap();
+        *counter = counter.saturating_add(instructions);
+    }
+
+    /// Returns the last lines printed by the IcMock instances in the current thread,
+    /// from the oldest to the newest. At most 1024 lines are kept.
+    pub fn printed(&self) -> Vec<String> {
+        PRINTED.with_borrow(|printed| printed.iter().cloned().collect())
+    }
+
+    /// Forgets the lines printed in the current thread.
+    pub fn clear_printed(&mut self) {
+        PRINTED.with_borrow_mut(|printed| printed.clear());
+    }
+
+    /// Panics if none of the lines printed in the current thread contains `substring`.
+    pub fn assert_printed_contains(&self, substring: &str) {
+        let printed = self.printed();
+        assert!(
+            printed.iter().any(|line| line.contains(substring)),
+            "no printed line contains [{substring}], printed lines: {printed:?}"
+        );
+    }
+
+    /// Returns the counters of the futures spawned by the IcMock instances in the current thread.
+    pub fn spawned_tasks_stats(&self) -> SpawnedTasksStats {
+        SPAWNED_TASKS.get()
+    }
+
+    /// Yields until all the futures spawned by the IcMock instances in the current thread finish,
+    /// including the ones spawned while waiting.
+    ///
+    /// It must be awaited in the `LocalSet` running the spawned futures,
+    /// and it never returns if one of them never finishes.
+    #[cfg(feature = "tokio")]
+    pub async fn await_spawned(&self) {
+        while SPAWNED_TASKS.get().running() > 0 {
+            tokio::task::yield_now().await;
+        }
+    }
+
     /// Sets the time strategy to use for the IC API.
     pub fn set_time_strategy(&mut self, time_strategy: TimeStrategy) {
         *self.time_strategy.lock().unwrap() = time_strategy;
     }
+
+    /// Sets the current time, in nanoseconds since the epoch.
+    /// The time does not change until it is set or advanced again.
+    pub fn set_time(&mut self, timestamp_nanos: u64) {
+        self.set_time_strategy(TimeStrategy::Fixed { timestamp_nanos });
+    }
+
+    /// Advances the current time by `duration`.
+    /// If the time strategy is `System`, the time is fixed to the current system time plus `duration`.
+    pub fn advance_time(&mut self, duration: Duration) {
+        let mut time_strategy = self.time_strategy.lock().unwrap();
+        let now = match *time_strategy {
+            TimeStrategy::Fixed { timestamp_nanos } => timestamp_nanos,
+            TimeStrategy::System => system_time_nanos(),
+        };
+        *time_strategy = TimeStrategy::Fixed {
+            timestamp_nanos: now.saturating_add(duration.as_nanos() as u64),
+        };
+    }
 }
 
 impl IcTrait for IcMock {
@@ -71,19 +318,80 @@ impl IcTrait for IcMock {
         *self.canister_cycle_balance.lock().unwrap()
     }
 
+    fn msg_caller(&self) -> candid::Principal {
+        *self.msg_caller.lock().unwrap()
+    }
+
+    fn msg_cycles_available(&self) -> u128 {
+        *self.incoming_cycles.lock().unwrap()
+    }
+
+    fn msg_cycles_accept(&self, max: u128) -> u128 {
+        let mut incoming_cycles = self.incoming_cycles.lock().unwrap();
+        let accepted = max.min(*incoming_cycles);
+        *incoming_cycles -= accepted;
+        *self.canister_cycle_balance.lock().unwrap() += accepted;
+        accepted
+    }
+
+    fn is_controller(&self, principal: &candid::Principal) -> bool {
+        self.controllers.lock().unwrap().contains(principal)
+    }
+
+    fn canister_version(&self) -> u64 {
+        *self.canister_version.lock().unwrap()
+    }
+
+    fn subnet_self(&self) -> Option<candid::Principal> {
+        *self.subnet_self.lock().unwrap()
+    }
+
+    fn subnet_type(&self) -> Option<SubnetType> {
+        *self.subnet_type.lock().unwrap()
+    }
+
     fn time_nanos(&self) -> u64 {
         match *self.time_strategy.lock().unwrap() {
             TimeStrategy::Fixed { timestamp_nanos } => timestamp_nanos,
-            TimeStrategy::System => SystemTime::now()
-                .duration_since(std::time::SystemTime::UNIX_EPOCH)
-                .expect("get current timestamp error")
-                .as_nanos() as u64,
+            TimeStrategy::System => system_time_nanos(),
         }
     }
 
+    fn stable_memory_size_bytes(&self) -> u64 {
+        *self.stable_memory_size_bytes.lock().unwrap()
+    }
+
+    fn wasm_memory_size_bytes(&self) -> u64 {
+        *self.wasm_memory_size_bytes.lock().unwrap()
+    }
+
+    /// All the counter types share the same fake instruction counter.
+    fn performance_counter(&self, _counter_type: u32) -> u64 {
+        *self.instructions.lock().unwrap()
+    }
+
+    fn instruction_counter(&self) -> u64 {
+        *self.instructions.lock().unwrap()
+    }
+
+    async fn raw_rand(&self) -> Result<[u8; 32], String> {
+        let mut state = self.rand_state.lock().unwrap();
+        let mut bytes = [0; 32];
+        for chunk in bytes.chunks_exact_mut(8) {
+            chunk.copy_from_slice(&splitmix64(&mut state).to_le_bytes());
+        }
+        Ok(bytes)
+    }
+
     fn spawn<F: 'static + Future<Output = ()>>(&self, _future: F) {
         #[cfg(feature = "tokio")]
-        tokio::task::spawn_local(_future);
+        {
+            let mut guard = SpawnedTaskGuard::new();
+            tokio::task::spawn_local(async move {
+                _future.await;
+                guard.completed = true;
+            });
+        }
 
         #[cfg(not(feature = "tokio"))]
         {
@@ -93,11 +401,457 @@ impl IcTrait for IcMock {
         }
     }
 
+    /// The line is echoed to stdout and kept for the assertions, see `printed`.
     fn print<S: std::convert::AsRef<str>>(&self, s: S) {
-        println!("{}", s.as_ref())
+        let line = s.as_ref();
+        println!("{line}");
+        PRINTED.with_borrow_mut(|printed| {
+            if printed.len() == MAX_PRINTED_LINES {
+                printed.pop_front();
+            }
+            printed.push_back(line.to_string());
+        });
     }
 
     fn spawn_detached<F: 'static + Future<Output = ()>>(&self, future: F) {
         self.spawn(future);
     }
 }
+
+/// A builder of an `IcMock`, as an alternative to calling the setters one by one.
+///
+/// ```
+/// use candid::Principal;
+/// use ic_mple_utils::ic_api::{IcTrait, SubnetType, mock::IcMock};
+///
+/// let ic = IcMock::builder()
+///     .canister_id(Principal::management_canister())
+///     .cycle_balance(1_000_000)
+///     .subnet_type(SubnetType::Application)
+///     .build();
+///
+/// assert_eq!(ic.canister_cycle_balance(), 1_000_000);
+/// ```
+#[derive(Clone, Debug, Default)]
+pub struct IcMockBuilder {
+    ic: IcMock,
+}
+
+impl IcMockBuilder {
+    /// Sets the Principal of the canister.
+    pub fn canister_id(mut self, canister_id: Principal) -> Self {
+        self.ic.set_canister_id(canister_id);
+        self
+    }
+
+    /// Sets the cycle balance of the canister.
+    pub fn cycle_balance(mut self, cycle_balance: u128) -> Self {
+        self.ic.set_canister_cycle_balance(cycle_balance);
+        self
+    }
+
+    /// Sets the time strategy.
+    pub fn time_strategy(mut self, time_strategy: TimeStrategy) -> Self {
+        self.ic.set_time_strategy(time_strategy);
+        self
+    }
+
+    /// Sets the caller of the current message.
+    pub fn caller(mut self, caller: Principal) -> Self {
+        self.ic.set_msg_caller(caller);
+        self
+    }
+
+    /// Sets the controllers of the canister.
+    pub fn controllers(mut self, controllers: Vec<Principal>) -> Self {
+        self.ic.set_controllers(controllers);
+        self
+    }
+
+    /// Sets the data budget of the canister, that is, the size of its stable and wasm memories in bytes.
+    pub fn data_budget(
+        mut self,
+        stable_memory_size_bytes: u64,
+        wasm_memory_size_bytes: u64,
+    ) -> Self {
+        self.ic
+            .set_stable_memory_size_bytes(stable_memory_size_bytes);
+        self.ic.set_wasm_memory_size_bytes(wasm_memory_size_bytes);
+        self
+    }
+
+    /// Sets the id of the subnet the canister is running on.
+    pub fn subnet_self(mut self, subnet_self: Principal) -> Self {
+        self.ic.set_subnet_self(Some(subnet_self));
+        self
+    }
+
+    /// Sets the type of the subnet the canister is running on.
+    pub fn subnet_type(mut self, subnet_type: SubnetType) -> Self {
+        self.ic.set_subnet_type(Some(subnet_type));
+        self
+    }
+
+    /// Builds the IcMock.
+    pub fn build(self) -> IcMock {
+        self.ic
+    }
+}
+
+fn system_time_nanos() -> u64 {
+    SystemTime::now()
+        .duration_since(std::time::SystemTime::UNIX_EPOCH)
+        .expect("get current timestamp error")
+        .as_nanos() as u64
+}
+
+/// The SplitMix64 generator: it is not cryptographically secure, but it is fast
+/// and its sequence depends only on the seed.
+fn splitmix64(state: &mut u64) -> u64 {
+    *state = state.wrapping_add(0x9E37_79B9_7F4A_7C15);
+    let mut z = *state;
+    z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+    z ^ (z >> 31)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn greet(name: &str) {
+        crate::ic_api::ic().print(format!("hello {name}"));
+    }
+
+    #[test]
+    fn should_capture_the_printed_lines() {
+        let mut ic = IcMock::default();
+        ic.clear_printed();
+
+        greet("alice");
+        greet("bob");
+
+        assert_eq!(ic.printed(), vec!["hello alice", "hello bob"]);
+        ic.assert_printed_contains("bob");
+
+        ic.clear_printed();
+        assert!(ic.printed().is_empty());
+    }
+
+    #[test]
+    fn should_keep_the_last_printed_lines() {
+        let mut ic = IcMock::default();
+        ic.clear_printed();
+
+        for i in 0..MAX_PRINTED_LINES + 10 {
+            ic.print(i.to_string());
+        }
+
+        let printed = ic.printed();
+        assert_eq!(printed.len(), MAX_PRINTED_LINES);
+        assert_eq!(printed[0], "10");
+    }
+
+    #[test]
+    #[should_panic(expected = "no printed line contains [missing]")]
+    fn should_fail_the_printed_assertion() {
+        let mut ic = IcMock::default();
+        ic.clear_printed();
+        ic.print("something");
+
+        ic.assert_printed_contains("missing");
+    }
+
+    /// Prints the greeting from a background task.
+    #[cfg(feature = "tokio")]
+    fn greet_in_background(name: &str) {
+        let line = format!("hello {name}");
+        crate::ic_api::ic().spawn(async move {
+            tokio::task::yield_now().await;
+            crate::ic_api::ic().print(line);
+        });
+    }
+
+    #[cfg(feature = "tokio")]
+    fn run_local<F: Future>(future: F) -> F::Output {
+        let runtime = tokio::runtime::Builder::new_current_thread()
+            .build()
+            .unwrap();
+        tokio::task::LocalSet::new().block_on(&runtime, future)
+    }
+
+    #[cfg(feature = "tokio")]
+    #[test]
+    fn should_await_the_spawned_futures() {
+        run_local(async {
+            let mut ic = IcMock::default();
+            ic.clear_printed();
+            let before = ic.spawned_tasks_stats();
+
+            greet_in_background("alice");
+            greet_in_background("bob");
+            assert_eq!(ic.spawned_tasks_stats().running(), before.running() + 2);
+            assert!(ic.printed().is_empty());
+
+            ic.await_spawned().await;
+
+            assert_eq!(ic.printed(), vec!["hello alice", "hello bob"]);
+            let after = ic.spawned_tasks_stats();
+            assert_eq!(after.spawned - before.spawned, 2);
+            assert_eq!(after.completed - before.completed, 2);
+            assert_eq!(after.panicked, before.panicked);
+        });
+    }
+
+    #[cfg(feature = "tokio")]
+    #[test]
+    fn should_track_the_detached_and_panicking_futures() {
+        run_local(async {
+            let ic = IcMock::default();
+            let before = ic.spawned_tasks_stats();
+
+            ic.spawn_detached(async { tokio::task::yield_now().await });
+            ic.spawn(async {
+                tokio::task::yield_now().await;
+                panic!("the background task panics");
+            });
+            // a future spawned while waiting is awaited too
+            ic.spawn(async {
+                crate::ic_api::ic().spawn(async {});
+            });
+
+            ic.await_spawned().await;
+
+            let after = ic.spawned_tasks_stats();
+            assert_eq!(after.spawned - before.spawned, 4);
+            assert_eq!(after.completed - before.completed, 3);
+            assert_eq!(after.panicked - before.panicked, 1);
+            assert_eq!(after.running(), 0);
+        });
+    }
+
+    #[test]
+    fn should_return_consistent_time_units() {
+        let ic = IcMock::default();
+
+        let nanos = ic.time_nanos();
+        let secs = ic.time_secs();
+
+        // the system time can move forward between the two calls
+        assert!(secs >= nanos / 1_000_000_000);
+        assert!(secs - nanos / 1_000_000_000 <= 1);
+    }
+
+    #[test]
+    fn should_advance_the_time() {
+        let mut ic = IcMock::default();
+        ic.set_time(5_000_000_000);
+
+        ic.advance_time(Duration::from_secs(10));
+        ic.advance_time(Duration::from_millis(500));
+
+        assert_eq!(ic.time_nanos(), 15_500_000_000);
+        assert_eq!(ic.time_secs(), 15);
+        assert_eq!(
+            ic.current_system_time(),
+            SystemTime::UNIX_EPOCH + Duration::from_millis(15_500)
+        );
+    }
+
+    #[test]
+    fn should_advance_the_system_time() {
+        let mut ic = IcMock::default();
+        let before = system_time_nanos();
+
+        ic.advance_time(Duration::from_secs(60));
+
+        let now = ic.time_nanos();
+        assert!(now >= before + 60_000_000_000);
+        // the time is now fixed
+        assert_eq!(ic.time_nanos(), now);
+    }
+
+    #[test]
+    fn should_check_the_controllers() {
+        let mut ic = IcMock::default();
+        let controller = Principal::from_slice(&[1; 29]);
+        let stranger = Principal::from_slice(&[2; 29]);
+
+        assert!(!ic.is_controller(&controller));
+
+        ic.set_controllers(vec![controller]);
+        assert!(ic.is_controller(&controller));
+        assert!(!ic.is_controller(&stranger));
+
+        ic.with_caller(stranger, || assert!(!ic.is_controller(&ic.msg_caller())));
+    }
+
+    #[test]
+    fn should_restore_the_caller_if_the_closure_panics() {
+        let mut ic = IcMock::default();
+        let caller = Principal::from_slice(&[1; 29]);
+        let stranger = Principal::from_slice(&[2; 29]);
+        ic.set_msg_caller(caller);
+
+        let result = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+            ic.with_caller(stranger, || panic!("the closure panics"))
+        }));
+
+        assert!(result.is_err());
+        assert_eq!(ic.msg_caller(), caller);
+    }
+
+    #[test]
+    fn should_return_the_canister_version() {
+        let mut ic = IcMock::default();
+        assert_eq!(ic.canister_version(), 0);
+
+        ic.set_canister_version(3);
+        assert_eq!(ic.canister_version(), 3);
+        // the clones share the state
+        assert_eq!(ic.clone().canister_version(), 3);
+    }
+
+    #[test]
+    fn should_build_with_the_default_values() {
+        let ic = IcMock::builder().build();
+
+        assert_eq!(ic.canister_self(), Principal::anonymous());
+        assert_eq!(ic.canister_cycle_balance(), 0);
+        assert_eq!(ic.msg_caller(), Principal::anonymous());
+        assert!(!ic.is_controller(&Principal::anonymous()));
+        assert_eq!(ic.stable_memory_size_bytes(), 0);
+        assert_eq!(ic.wasm_memory_size_bytes(), 0);
+        assert_eq!(ic.subnet_self(), None);
+        assert_eq!(ic.subnet_type(), None);
+        assert_eq!(*ic.time_strategy.lock().unwrap(), TimeStrategy::System);
+    }
+
+    #[test]
+    fn should_build_with_the_given_values() {
+        let canister_id = Principal::from_slice(&[1; 29]);
+        let caller = Principal::from_slice(&[2; 29]);
+        let subnet = Principal::from_slice(&[3; 29]);
+
+        let ic = IcMock::builder()
+            .canister_id(canister_id)
+            .cycle_balance(1_000)
+            .time_strategy(TimeStrategy::Fixed {
+                timestamp_nanos: 42,
+            })
+            .caller(caller)
+            .controllers(vec![caller])
+            .data_budget(2048, 1024)
+            .subnet_self(subnet)
+            .subnet_type(SubnetType::System)
+            .build();
+
+        assert_eq!(ic.canister_self(), canister_id);
+        assert_eq!(ic.canister_cycle_balance(), 1_000);
+        assert_eq!(ic.time_nanos(), 42);
+        assert_eq!(ic.msg_caller(), caller);
+        assert!(ic.is_controller(&caller));
+        assert_eq!(ic.stable_memory_size_bytes(), 2048);
+        assert_eq!(ic.wasm_memory_size_bytes(), 1024);
+        assert_eq!(ic.subnet_self(), Some(subnet));
+        assert_eq!(ic.subnet_type(), Some(SubnetType::System));
+    }
+
+    /// Returns the fee of a call, which is not charged on the system subnets.
+    fn call_fee(ic: &impl IcTrait) -> u128 {
+        match ic.subnet_type() {
+            Some(SubnetType::System) => 0,
+            _ => 590_000,
+        }
+    }
+
+    #[test]
+    fn should_branch_on_the_subnet_type() {
+        let mut ic = IcMock::default();
+        assert_eq!(call_fee(&ic), 590_000);
+
+        ic.set_subnet_type(Some(SubnetType::System));
+        assert_eq!(call_fee(&ic), 0);
+    }
+
+    /// Accepts the price of a paid endpoint, or nothing if the caller did not send enough cycles.
+    fn charge(ic: &impl IcTrait, price: u128) -> Result<u128, String> {
+        let available = ic.msg_cycles_available();
+        if available < price {
+            return Err(format!("{price} cycles required, {available} sent"));
+        }
+        Ok(ic.msg_cycles_accept(price))
+    }
+
+    #[test]
+    fn should_accept_all_the_incoming_cycles() {
+        let mut ic = IcMock::new(Principal::anonymous(), 1_000);
+        ic.set_incoming_cycles(500);
+
+        assert_eq!(charge(&ic, 500), Ok(500));
+        assert_eq!(ic.msg_cycles_available(), 0);
+        assert_eq!(ic.canister_cycle_balance(), 1_500);
+    }
+
+    #[test]
+    fn should_accept_part_of_the_incoming_cycles() {
+        let mut ic = IcMock::new(Principal::anonymous(), 1_000);
+        ic.set_incoming_cycles(500);
+
+        assert_eq!(charge(&ic, 200), Ok(200));
+        assert_eq!(ic.msg_cycles_available(), 300);
+        assert_eq!(ic.canister_cycle_balance(), 1_200);
+
+        assert!(charge(&ic, 400).is_err());
+        assert_eq!(ic.msg_cycles_available(), 300);
+        assert_eq!(ic.msg_cycles_accept(u128::MAX), 300);
+        assert_eq!(ic.canister_cycle_balance(), 1_500);
+    }
+
+    #[test]
+    fn should_advance_the_instruction_counter() {
+        let mut ic = IcMock::default();
+        assert_eq!(ic.instruction_counter(), 0);
+
+        ic.advance_instructions(100);
+        ic.advance_instructions(50);
+
+        assert_eq!(ic.instruction_counter(), 150);
+        assert_eq!(ic.performance_counter(0), 150);
+    }
+
+    #[test]
+    fn should_generate_a_reproducible_sequence() {
+        let mut ic = IcMock::default();
+        ic.set_rand_seed(42);
+        let first = (
+            block_on(ic.raw_rand()).unwrap(),
+            block_on(ic.raw_rand()).unwrap(),
+            block_on(ic.random_u64()).unwrap(),
+        );
+
+        ic.set_rand_seed(42);
+        let second = (
+            block_on(ic.raw_rand()).unwrap(),
+            block_on(ic.raw_rand()).unwrap(),
+            block_on(ic.random_u64()).unwrap(),
+        );
+
+        assert_eq!(first, second);
+        assert_ne!(first.0, first.1);
+
+        ic.set_rand_seed(43);
+        assert_ne!(block_on(ic.raw_rand()).unwrap(), first.0);
+    }
+
+    /// Polls a future that never waits until it completes.
+    fn block_on<F: Future>(future: F) -> F::Output {
+        let mut future = std::pin::pin!(future);
+        let mut cx = std::task::Context::from_waker(std::task::Waker::noop());
+        loop {
+            if let std::task::Poll::Ready(output) = future.as_mut().poll(&mut cx) {
+                return output;
+            }
+        }
+    }
+}
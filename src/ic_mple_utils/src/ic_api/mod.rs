@@ -2,20 +2,77 @@ use std::time::SystemTime;
 
 use candid::{CandidType, Principal};
 use ic_cdk::{
-    api::{canister_cycle_balance, canister_self},
+    api::{
+        canister_cycle_balance, canister_self, canister_version, instruction_counter,
+        is_controller, msg_caller, msg_cycles_accept, msg_cycles_available, performance_counter,
+    },
     futures::spawn,
 };
 use serde::Deserialize;
 
+use crate::timer::{IcTimers, TimerTrait};
+use crate::units::Cycles;
+
+mod instruction_timer;
+mod memory_pressure;
 pub mod mock;
 
+pub use instruction_timer::InstructionTimer;
+pub use memory_pressure::{MemoryPressure, MemoryPressureThresholds};
+
 const E_9: u64 = 1_000_000_000;
 
-/// Returns the IC API, or the TokioIcApi if non in wasm and the ic_mock feature is enabled
+/// The size of a wasm memory page, for both the stable and the wasm memory.
+const WASM_PAGE_SIZE_BYTES: u64 = 65536;
+
+#[cfg(not(target_family = "wasm"))]
+thread_local! {
+    static IC_OVERRIDE: std::cell::RefCell<Option<IcApi>> = const { std::cell::RefCell::new(None) };
+}
+
+/// Returns the IC API, or the IcMock if not in wasm.
+/// Outside wasm, the IcMock set with `set_ic_override` or `with_ic` is returned, if any.
 pub fn ic() -> IcApi {
+    #[cfg(not(target_family = "wasm"))]
+    {
+        if let Some(ic) = IC_OVERRIDE.with_borrow(|ic| ic.clone()) {
+            return ic;
+        }
+    }
     IcApi::default()
 }
 
+/// Sets the IcMock returned by `ic()` in the current thread, until it is cleared.
+/// The IcMock clones share their state, so the changes to `ic` are visible through `ic()`.
+#[cfg(not(target_family = "wasm"))]
+pub fn set_ic_override(ic: IcApi) {
+    IC_OVERRIDE.with_borrow_mut(|ic_override| *ic_override = Some(ic));
+}
+
+/// Restores the default IcMock returned by `ic()` in the current thread.
+#[cfg(not(target_family = "wasm"))]
+pub fn clear_ic_override() {
+    IC_OVERRIDE.with_borrow_mut(|ic_override| *ic_override = None);
+}
+
+/// Executes `f` with `ic` returned by `ic()`, then restores the previous value,
+/// even if `f` panics.
+#[cfg(not(target_family = "wasm"))]
+pub fn with_ic<R>(ic: IcApi, f: impl FnOnce() -> R) -> R {
+    struct RestoreGuard(Option<IcApi>);
+
+    impl Drop for RestoreGuard {
+        fn drop(&mut self) {
+            let previous = self.0.take();
+            IC_OVERRIDE.with_borrow_mut(|ic_override| *ic_override = previous);
+        }
+    }
+
+    let previous = IC_OVERRIDE.with_borrow_mut(|ic_override| ic_override.replace(ic));
+    let _guard = RestoreGuard(previous);
+    f()
+}
+
 #[cfg(target_family = "wasm")]
 pub type IcApi = IcPlatform;
 
@@ -31,6 +88,34 @@ pub trait IcTrait: Clone {
     /// Gets the current cycle balance of the canister.
     fn canister_cycle_balance(&self) -> u128;
 
+    /// Gets the current cycle balance of the canister as `Cycles`.
+    fn cycle_balance(&self) -> Cycles {
+        Cycles(self.canister_cycle_balance())
+    }
+
+    /// Gets the identity of the caller of the current message.
+    fn msg_caller(&self) -> Principal;
+
+    /// Gets the amount of cycles sent with the current message and not accepted yet.
+    fn msg_cycles_available(&self) -> u128;
+
+    /// Moves up to `max` cycles sent with the current message to the canister balance,
+    /// and returns the amount actually accepted.
+    fn msg_cycles_accept(&self, max: u128) -> u128;
+
+    /// Returns true if the principal is a controller of the canister.
+    fn is_controller(&self, principal: &Principal) -> bool;
+
+    /// Gets the version of the canister, incremented at every code or settings change.
+    fn canister_version(&self) -> u64;
+
+    /// Gets the id of the subnet the canister is running on, if known.
+    fn subnet_self(&self) -> Option<Principal>;
+
+    /// Gets the type of the subnet the canister is running on, if known.
+    /// The cycles fees depend on it, e.g. they are not charged on the system subnets.
+    fn subnet_type(&self) -> Option<SubnetType>;
+
     /// Gets current timestamp, in nanoseconds since the epoch (1970-01-01)
     fn time_nanos(&self) -> u64;
 
@@ -45,6 +130,32 @@ pub trait IcTrait: Clone {
         std::time::SystemTime::UNIX_EPOCH + std::time::Duration::from_nanos(timestamp_in_nanos)
     }
 
+    /// Gets the size of the stable memory of the canister, in bytes.
+    fn stable_memory_size_bytes(&self) -> u64;
+
+    /// Gets the size of the wasm (heap) memory of the canister, in bytes.
+    fn wasm_memory_size_bytes(&self) -> u64;
+
+    /// Gets the value of the performance counter of the given type,
+    /// e.g. 0 for the instructions executed in the current message.
+    fn performance_counter(&self, counter_type: u32) -> u64;
+
+    /// Gets the number of instructions executed in the current message.
+    fn instruction_counter(&self) -> u64;
+
+    /// Gets 32 pseudo-random bytes from the management canister.
+    fn raw_rand(&self) -> impl Future<Output = Result<[u8; 32], String>>;
+
+    /// Gets a pseudo-random u64 from the first 8 bytes returned by `raw_rand`.
+    fn random_u64(&self) -> impl Future<Output = Result<u64, String>> {
+        async {
+            let bytes = self.raw_rand().await?;
+            let mut head = [0; 8];
+            head.copy_from_slice(&bytes[..8]);
+            Ok(u64::from_le_bytes(head))
+        }
+    }
+
     /// Spawn an asynchronous task to run in the background.
     fn spawn<F: 'static + Future<Output = ()>>(&self, future: F);
 
@@ -56,6 +167,14 @@ pub trait IcTrait: Clone {
     fn print<S: std::convert::AsRef<str>>(&self, s: S);
 }
 
+/// The type of a subnet.
+#[derive(Clone, Copy, Debug, CandidType, Deserialize, PartialEq, Eq, Hash)]
+pub enum SubnetType {
+    Application,
+    VerifiedApplication,
+    System,
+}
+
 /// The default implementation of the IC API
 #[derive(Clone, Debug, Default, CandidType, Deserialize, PartialEq, Eq)]
 pub struct IcPlatform;
@@ -69,6 +188,39 @@ impl IcTrait for IcPlatform {
         ic_cdk::api::time()
     }
 
+    fn stable_memory_size_bytes(&self) -> u64 {
+        ic_cdk::stable::stable_size() * WASM_PAGE_SIZE_BYTES
+    }
+
+    fn wasm_memory_size_bytes(&self) -> u64 {
+        #[cfg(target_arch = "wasm32")]
+        {
+            core::arch::wasm32::memory_size::<0>() as u64 * WASM_PAGE_SIZE_BYTES
+        }
+
+        #[cfg(not(target_arch = "wasm32"))]
+        {
+            0
+        }
+    }
+
+    fn performance_counter(&self, counter_type: u32) -> u64 {
+        performance_counter(counter_type)
+    }
+
+    fn instruction_counter(&self) -> u64 {
+        instruction_counter()
+    }
+
+    async fn raw_rand(&self) -> Result<[u8; 32], String> {
+        let bytes = ic_cdk::management_canister::raw_rand()
+            .await
+            .map_err(|err| err.to_string())?;
+        bytes
+            .try_into()
+            .map_err(|bytes: Vec<u8>| format!("raw_rand returned {} bytes", bytes.len()))
+    }
+
     fn spawn<F: 'static + Future<Output = ()>>(&self, future: F) {
         spawn(future)
     }
@@ -77,13 +229,89 @@ impl IcTrait for IcPlatform {
         canister_cycle_balance()
     }
 
+    fn msg_caller(&self) -> Principal {
+        msg_caller()
+    }
+
+    fn msg_cycles_available(&self) -> u128 {
+        msg_cycles_available()
+    }
+
+    fn msg_cycles_accept(&self, max: u128) -> u128 {
+        msg_cycles_accept(max)
+    }
+
+    fn is_controller(&self, principal: &Principal) -> bool {
+        is_controller(principal)
+    }
+
+    fn canister_version(&self) -> u64 {
+        canister_version()
+    }
+
+    /// WARN: the cdk does not expose the subnet of the canister, so this always returns `None`.
+    fn subnet_self(&self) -> Option<Principal> {
+        None
+    }
+
+    /// WARN: the cdk does not expose the subnet of the canister, so this always returns `None`.
+    fn subnet_type(&self) -> Option<SubnetType> {
+        None
+    }
+
     fn print<S: std::convert::AsRef<str>>(&self, s: S) {
         ic_cdk::api::debug_print(s)
     }
 
     fn spawn_detached<F: 'static + Future<Output = ()>>(&self, future: F) {
-        ic_cdk_timers::set_timer(std::time::Duration::from_millis(0), async {
-            spawn(future);
+        IcTimers.set_timer(std::time::Duration::ZERO, move || spawn(future));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn canister_id(byte: u8) -> Principal {
+        Principal::from_slice(&[byte; 29])
+    }
+
+    #[test]
+    fn should_override_the_ic_api() {
+        assert_eq!(ic().canister_self(), Principal::anonymous());
+
+        let mut mock = IcApi::default();
+        mock.set_canister_id(canister_id(1));
+        set_ic_override(mock.clone());
+        assert_eq!(ic().canister_self(), canister_id(1));
+
+        // the override shares the state with the mock
+        mock.set_canister_id(canister_id(2));
+        assert_eq!(ic().canister_self(), canister_id(2));
+
+        clear_ic_override();
+        assert_eq!(ic().canister_self(), Principal::anonymous());
+    }
+
+    #[test]
+    fn should_restore_the_previous_ic_api() {
+        let outer = IcApi::new(canister_id(1), 0);
+        let inner = IcApi::new(canister_id(2), 0);
+
+        with_ic(outer, || {
+            assert_eq!(ic().canister_self(), canister_id(1));
+            with_ic(inner.clone(), || {
+                assert_eq!(ic().canister_self(), canister_id(2));
+            });
+            assert_eq!(ic().canister_self(), canister_id(1));
+
+            let result = std::panic::catch_unwind(|| {
+                with_ic(inner, || panic!("the scoped function panics"));
+            });
+            assert!(result.is_err());
+            assert_eq!(ic().canister_self(), canister_id(1));
         });
+
+        assert_eq!(ic().canister_self(), Principal::anonymous());
     }
 }
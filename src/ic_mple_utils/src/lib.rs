@@ -1,2 +1,6 @@
 pub mod ic_api;
+pub mod rate_limiter;
+pub mod stable_state;
 pub mod store;
+pub mod timer;
+pub mod units;
@@ -0,0 +1,322 @@
+use std::borrow::Cow;
+
+use candid::{CandidType, Principal};
+use ic_mple_structures::{Bound, DefaultMemoryImpl, StableBTreeMap, Storable, VirtualMemory};
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+use crate::store::Storage;
+
+/// The requests of a principal counted in the current window.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, CandidType, Serialize, Deserialize)]
+pub struct WindowCounter {
+    /// The time of the first request of the window, in seconds since the epoch.
+    pub window_start_secs: u64,
+    /// The number of requests in the window.
+    pub count: u32,
+}
+
+impl WindowCounter {
+    const SIZE: usize = 12;
+
+    /// Returns true if the window is over at `now_secs`.
+    fn is_expired(&self, window_secs: u64, now_secs: u64) -> bool {
+        now_secs >= self.window_start_secs.saturating_add(window_secs)
+    }
+}
+
+impl Storable for WindowCounter {
+    const BOUND: Bound = Bound::Bounded {
+        max_size: Self::SIZE as u32,
+        is_fixed_size: true,
+    };
+
+    fn to_bytes(&self) -> Cow<'_, [u8]> {
+        Cow::Owned(self.into_bytes())
+    }
+
+    fn into_bytes(self) -> Vec<u8> {
+        let mut bytes = Vec::with_capacity(Self::SIZE);
+        bytes.extend_from_slice(&self.window_start_secs.to_le_bytes());
+        bytes.extend_from_slice(&self.count.to_le_bytes());
+        bytes
+    }
+
+    fn from_bytes(bytes: Cow<[u8]>) -> Self {
+        Self {
+            window_start_secs: u64::from_le_bytes(bytes[..8].try_into().unwrap()),
+            count: u32::from_le_bytes(bytes[8..Self::SIZE].try_into().unwrap()),
+        }
+    }
+}
+
+/// The error returned when a principal exceeded its quota.
+#[derive(Debug, Error, Clone, Copy, PartialEq, Eq, CandidType, Serialize, Deserialize)]
+#[error("rate limit exceeded, retry after {retry_after_secs} seconds")]
+pub struct RateLimitError {
+    /// The seconds until the current window is over.
+    pub retry_after_secs: u64,
+}
+
+pub type RateLimiterStorage =
+    StableBTreeMap<Principal, WindowCounter, VirtualMemory<DefaultMemoryImpl>>;
+
+/// A service limiting the requests of every principal to a quota per fixed window,
+/// e.g. at most 10 updates per minute.
+///
+/// The window of a principal starts with its first request, and the counters are kept
+/// in the stable memory so that they survive the upgrades.
+/// The times are in seconds since the epoch, e.g. `ic().time_secs()`.
+pub struct RateLimiterService<S: Storage<RateLimiterStorage>> {
+    storage: S,
+}
+
+impl<S: Storage<RateLimiterStorage>> RateLimiterService<S> {
+    /// Instantiates a new RateLimiterService
+    pub fn new(storage: S) -> Self {
+        Self { storage }
+    }
+
+    /// Counts a request of the principal, or returns an error if the principal
+    /// already made `limit` requests in the current window of `window_secs` seconds.
+    /// The rejected requests are not counted.
+    pub fn check_and_increment(
+        &mut self,
+        principal: Principal,
+        limit: u32,
+        window_secs: u64,
+        now_secs: u64,
+    ) -> Result<(), RateLimitError> {
+        self.storage.with_borrow_mut(|counters| {
+            let counter = match counters.get(&principal) {
+                Some(counter) if !counter.is_expired(window_secs, now_secs) => counter,
+                _ => WindowCounter {
+                    window_start_secs: now_secs,
+                    count: 0,
+                },
+            };
+
+            if counter.count >= limit {
+                return Err(RateLimitError {
+                    retry_after_secs: counter
+                        .window_start_secs
+                        .saturating_add(window_secs)
+                        .saturating_sub(now_secs),
+                });
+            }
+
+            counters.insert(
+                principal,
+                WindowCounter {
+                    count: counter.count + 1,
+                    ..counter
+                },
+            );
+            Ok(())
+        })
+    }
+
+    /// Returns the counter of the principal, if any; the window could be already over.
+    pub fn get(&self, principal: &Principal) -> Option<WindowCounter> {
+        self.storage.with_borrow(|counters| counters.get(principal))
+    }
+
+    /// Forgets the requests of the principal.
+    /// Returns true if the principal had a counter.
+    pub fn reset(&mut self, principal: &Principal) -> bool {
+        self.storage
+            .with_borrow_mut(|counters| counters.remove(principal).is_some())
+    }
+
+    /// Removes up to `limit` counters whose window of `window_secs` seconds is over at `now_secs`,
+    /// and returns the number of removed counters.
+    ///
+    /// It is meant to be called periodically, e.g. by a timer, to bound the size of the storage.
+    /// The `limit` bounds only the number of removals: all the counters may still be scanned
+    /// to find the stale ones, so the instructions used grow with the size of the storage.
+    pub fn purge_stale(&mut self, now_secs: u64, window_secs: u64, limit: u64) -> u64 {
+        self.storage.with_borrow_mut(|counters| {
+            let stale: Vec<Principal> = counters
+                .iter()
+                .map(|entry| entry.into_pair())
+                .filter(|(_, counter)| counter.is_expired(window_secs, now_secs))
+                .map(|(principal, _)| principal)
+                .take(limit as usize)
+                .collect();
+
+            for principal in &stale {
+                counters.remove(principal);
+            }
+            stale.len() as u64
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use std::cell::RefCell;
+    use std::time::Duration;
+
+    use ic_mple_structures::{MemoryId, MemoryManager};
+
+    use super::*;
+    use crate::ic_api::IcTrait;
+    use crate::ic_api::mock::IcMock;
+
+    const LIMIT: u32 = 3;
+    const WINDOW_SECS: u64 = 60;
+
+    fn new_rate_limiter() -> RateLimiterService<RefCell<RateLimiterStorage>> {
+        RateLimiterService::new(RefCell::new(StableBTreeMap::new(
+            MemoryManager::init(DefaultMemoryImpl::default()).get(MemoryId::new(0)),
+        )))
+    }
+
+    fn principal(byte: u8) -> Principal {
+        Principal::from_slice(&[byte; 29])
+    }
+
+    fn mock_ic() -> IcMock {
+        let mut ic = IcMock::default();
+        ic.set_time(1_000 * 1_000_000_000);
+        ic
+    }
+
+    #[test]
+    fn should_limit_the_requests_in_a_window() {
+        let ic = mock_ic();
+        let mut rate_limiter = new_rate_limiter();
+        let user = principal(1);
+
+        for _ in 0..LIMIT {
+            assert_eq!(
+                rate_limiter.check_and_increment(user, LIMIT, WINDOW_SECS, ic.time_secs()),
+                Ok(())
+            );
+        }
+
+        assert_eq!(
+            rate_limiter.check_and_increment(user, LIMIT, WINDOW_SECS, ic.time_secs()),
+            Err(RateLimitError {
+                retry_after_secs: 60
+            })
+        );
+        assert_eq!(
+            rate_limiter.get(&user),
+            Some(WindowCounter {
+                window_start_secs: 1_000,
+                count: LIMIT,
+            })
+        );
+    }
+
+    #[test]
+    fn should_start_a_new_window_when_the_previous_one_is_over() {
+        let mut ic = mock_ic();
+        let mut rate_limiter = new_rate_limiter();
+        let user = principal(1);
+
+        for _ in 0..LIMIT {
+            rate_limiter
+                .check_and_increment(user, LIMIT, WINDOW_SECS, ic.time_secs())
+                .unwrap();
+        }
+
+        ic.advance_time(Duration::from_secs(45));
+        assert_eq!(
+            rate_limiter.check_and_increment(user, LIMIT, WINDOW_SECS, ic.time_secs()),
+            Err(RateLimitError {
+                retry_after_secs: 15
+            })
+        );
+
+        ic.advance_time(Duration::from_secs(15));
+        assert_eq!(
+            rate_limiter.check_and_increment(user, LIMIT, WINDOW_SECS, ic.time_secs()),
+            Ok(())
+        );
+        assert_eq!(
+            rate_limiter.get(&user),
+            Some(WindowCounter {
+                window_start_secs: 1_060,
+                count: 1,
+            })
+        );
+    }
+
+    #[test]
+    fn should_count_the_principals_separately() {
+        let ic = mock_ic();
+        let mut rate_limiter = new_rate_limiter();
+        let alice = principal(1);
+        let bob = principal(2);
+
+        for _ in 0..LIMIT {
+            rate_limiter
+                .check_and_increment(alice, LIMIT, WINDOW_SECS, ic.time_secs())
+                .unwrap();
+        }
+
+        assert!(
+            rate_limiter
+                .check_and_increment(alice, LIMIT, WINDOW_SECS, ic.time_secs())
+                .is_err()
+        );
+        assert_eq!(
+            rate_limiter.check_and_increment(bob, LIMIT, WINDOW_SECS, ic.time_secs()),
+            Ok(())
+        );
+        assert_eq!(rate_limiter.get(&bob).map(|counter| counter.count), Some(1));
+    }
+
+    #[test]
+    fn should_reset_a_principal() {
+        let ic = mock_ic();
+        let mut rate_limiter = new_rate_limiter();
+        let user = principal(1);
+
+        for _ in 0..LIMIT {
+            rate_limiter
+                .check_and_increment(user, LIMIT, WINDOW_SECS, ic.time_secs())
+                .unwrap();
+        }
+
+        assert!(rate_limiter.reset(&user));
+        assert!(!rate_limiter.reset(&user));
+        assert_eq!(
+            rate_limiter.check_and_increment(user, LIMIT, WINDOW_SECS, ic.time_secs()),
+            Ok(())
+        );
+    }
+
+    #[test]
+    fn should_purge_the_stale_counters() {
+        let mut ic = mock_ic();
+        let mut rate_limiter = new_rate_limiter();
+
+        for byte in 1..=3 {
+            rate_limiter
+                .check_and_increment(principal(byte), LIMIT, WINDOW_SECS, ic.time_secs())
+                .unwrap();
+        }
+        ic.advance_time(Duration::from_secs(30));
+        rate_limiter
+            .check_and_increment(principal(4), LIMIT, WINDOW_SECS, ic.time_secs())
+            .unwrap();
+
+        ic.advance_time(Duration::from_secs(30));
+        assert_eq!(rate_limiter.purge_stale(ic.time_secs(), WINDOW_SECS, 2), 2);
+        assert_eq!(rate_limiter.purge_stale(ic.time_secs(), WINDOW_SECS, 2), 1);
+        assert_eq!(rate_limiter.purge_stale(ic.time_secs(), WINDOW_SECS, 2), 0);
+
+        assert_eq!(rate_limiter.get(&principal(1)), None);
+        assert_eq!(
+            rate_limiter.get(&principal(4)),
+            Some(WindowCounter {
+                window_start_secs: 1_030,
+                count: 1,
+            })
+        );
+    }
+}
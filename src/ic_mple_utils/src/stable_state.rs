@@ -0,0 +1,174 @@
+use std::marker::PhantomData;
+
+use ic_mple_structures::{CellStructure, Memory, StableCell, Storable};
+
+/// Persists a heap value in a stable cell across the canister upgrades.
+///
+/// The value is written with `save` in the `pre_upgrade` hook and read back with `restore`
+/// in the `post_upgrade` hook, see `register_stable_state!`.
+///
+/// Any `CellStructure` can back the state, so a `VersionedStableCell` can be used
+/// to change the type of the value between two versions of the canister.
+pub struct StableState<T: Clone, C: CellStructure<T>> {
+    cell: C,
+    phantom_t: PhantomData<T>,
+}
+
+impl<T, M> StableState<T, StableCell<T, M>>
+where
+    T: Storable + Default + Clone,
+    M: Memory,
+{
+    /// Initializes the state in the specified memory.
+    /// The default value is restored if nothing was saved yet.
+    ///
+    /// PRECONDITION: the memory is either empty or contains a valid
+    /// StableCell.
+    pub fn init(memory: M) -> Self {
+        Self::with_cell(StableCell::init(memory, T::default()))
+    }
+}
+
+impl<T: Clone, C: CellStructure<T>> StableState<T, C> {
+    /// Creates the state backed by the given cell.
+    pub fn with_cell(cell: C) -> Self {
+        Self {
+            cell,
+            phantom_t: PhantomData,
+        }
+    }
+
+    /// Writes the value to the stable memory.
+    /// WARN: this panics if the stable memory cannot grow.
+    pub fn save(&mut self, value: &T) {
+        self.cell.set(value.clone());
+    }
+
+    /// Reads the last saved value from the stable memory.
+    pub fn restore(&self) -> T {
+        self.cell.get().into_owned()
+    }
+}
+
+/// Generates the `pre_upgrade` and `post_upgrade` hooks of the canister, saving
+/// the value of a heap thread-local into a `StableState` thread-local before the upgrade
+/// and restoring it after the upgrade.
+///
+/// The `heap` thread-local must be a `RefCell<T>` and the `stable` one a `RefCell<StableState<T, _>>`.
+/// As the canister can have only one `post_upgrade` hook, any other initialization can be passed
+/// as `post_upgrade`; it is called after the value is restored.
+///
+/// The canister must depend on `ic-cdk`.
+///
+/// ```ignore
+/// thread_local! {
+///     static CONFIG: RefCell<Config> = RefCell::new(Config::default());
+///     static CONFIG_STATE: RefCell<StableState<Config, StableCell<Config, Memory>>> =
+///         RefCell::new(StableState::init(memory()));
+/// }
+///
+/// register_stable_state! {
+///     heap: CONFIG,
+///     stable: CONFIG_STATE,
+///     post_upgrade: set_timers,
+/// }
+/// ```
+#[macro_export]
+macro_rules! register_stable_state {
+    (heap: $heap:path, stable: $stable:path $(, post_upgrade: $hook:path)? $(,)?) => {
+        #[::ic_cdk::pre_upgrade]
+        fn pre_upgrade() {
+            $heap.with_borrow(|value| $stable.with_borrow_mut(|stable| stable.save(value)));
+        }
+
+        #[::ic_cdk::post_upgrade]
+        fn post_upgrade() {
+            let restored = $stable.with_borrow(|stable| stable.restore());
+            $heap.set(restored);
+            $( $hook(); )?
+        }
+    };
+}
+
+#[cfg(test)]
+mod tests {
+
+    use std::borrow::Cow;
+
+    use ic_mple_structures::{Bound, RefCodec, VectorMemory, VersionedStableCell};
+
+    use super::*;
+
+    #[test]
+    fn should_restore_the_default_value_if_nothing_was_saved() {
+        let state = StableState::<u64, _>::init(VectorMemory::default());
+        assert_eq!(state.restore(), 0);
+    }
+
+    #[test]
+    fn should_restore_the_saved_value_after_an_upgrade() {
+        let memory = VectorMemory::default();
+        {
+            let mut state = StableState::<(u64, u32), _>::init(memory.clone());
+            state.save(&(42, 7));
+        }
+
+        let state = StableState::<(u64, u32), _>::init(memory);
+        assert_eq!(state.restore(), (42, 7));
+    }
+
+    /// A counter that was an `u32` in the first version of the canister.
+    #[derive(Clone)]
+    enum CounterCodec {
+        V1(u32),
+        V2(u64),
+    }
+
+    impl Storable for CounterCodec {
+        const BOUND: Bound = Bound::Unbounded;
+
+        fn to_bytes(&self) -> Cow<'_, [u8]> {
+            self.clone().into_bytes().into()
+        }
+
+        fn into_bytes(self) -> Vec<u8> {
+            match self {
+                Self::V1(value) => [vec![1], value.to_le_bytes().to_vec()].concat(),
+                Self::V2(value) => [vec![2], value.to_le_bytes().to_vec()].concat(),
+            }
+        }
+
+        fn from_bytes(bytes: Cow<[u8]>) -> Self {
+            match bytes[0] {
+                1 => Self::V1(u32::from_le_bytes(bytes[1..].try_into().unwrap())),
+                _ => Self::V2(u64::from_le_bytes(bytes[1..].try_into().unwrap())),
+            }
+        }
+    }
+
+    impl RefCodec<u64> for CounterCodec {
+        fn decode_ref(source: &Self) -> Cow<'_, u64> {
+            match source {
+                Self::V1(value) => Cow::Owned(*value as u64),
+                Self::V2(value) => Cow::Borrowed(value),
+            }
+        }
+
+        fn encode(dest: u64) -> Self {
+            Self::V2(dest)
+        }
+    }
+
+    #[test]
+    fn should_restore_a_value_saved_by_a_previous_version() {
+        let memory = VectorMemory::default();
+        StableCell::init(memory.clone(), CounterCodec::V1(0)).set(CounterCodec::V1(42));
+
+        let mut state =
+            StableState::with_cell(VersionedStableCell::<u64, CounterCodec, _>::init(memory, 0));
+        assert_eq!(state.restore(), 42);
+
+        state.save(&(u32::MAX as u64 + 1));
+        assert_eq!(state.restore(), u32::MAX as u64 + 1);
+    }
+}
@@ -1,10 +1,49 @@
 use std::{
-    cell::RefCell,
+    cell::{BorrowError, BorrowMutError, RefCell},
+    marker::PhantomData,
     rc::Rc,
-    sync::{Arc, Mutex, RwLock},
+    sync::{Arc, Mutex, PoisonError, RwLock},
     thread::LocalKey,
 };
 
+use candid::CandidType;
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+/// The error returned when the value of a `Storage` cannot be accessed.
+#[derive(Debug, Error, Clone, Copy, PartialEq, Eq, CandidType, Serialize, Deserialize)]
+pub enum StorageAccessError {
+    /// The value is already borrowed, e.g. a `RefCell` borrowed mutably by a caller up in the stack.
+    #[error("the storage is already borrowed")]
+    AlreadyBorrowed,
+
+    /// The lock was poisoned by a panic of a previous holder.
+    #[error("the storage lock is poisoned")]
+    Poisoned,
+
+    /// The value of a lazily initialized storage was not set, see `init_storage`.
+    #[error("storage not initialized: call init_storage first")]
+    Uninitialized,
+}
+
+impl From<BorrowError> for StorageAccessError {
+    fn from(_: BorrowError) -> Self {
+        Self::AlreadyBorrowed
+    }
+}
+
+impl From<BorrowMutError> for StorageAccessError {
+    fn from(_: BorrowMutError) -> Self {
+        Self::AlreadyBorrowed
+    }
+}
+
+impl<G> From<PoisonError<G>> for StorageAccessError {
+    fn from(_: PoisonError<G>) -> Self {
+        Self::Poisoned
+    }
+}
+
 /// An abstract storage interface that allows creating services that can
 /// use both thread-local and owned plain object storage.
 /// This simplifies unit testing.
@@ -18,6 +57,102 @@ pub trait Storage<T> {
     fn with_borrow<F, R>(&self, f: F) -> R
     where
         F: FnOnce(&T) -> R;
+
+    /// Acquires a mutable reference to the contained value,
+    /// or returns an error if the value cannot be accessed.
+    ///
+    /// As `with_borrow_mut`, it waits for a lock held by someone else:
+    /// only a poisoned lock is reported as an error.
+    /// The default implementation panics as `with_borrow_mut` does.
+    fn try_with_borrow_mut<F, R>(&mut self, f: F) -> Result<R, StorageAccessError>
+    where
+        F: FnOnce(&mut T) -> R,
+    {
+        Ok(self.with_borrow_mut(f))
+    }
+
+    /// Acquires a reference to the contained value,
+    /// or returns an error if the value cannot be accessed.
+    ///
+    /// As `with_borrow`, it waits for a lock held by someone else:
+    /// only a poisoned lock is reported as an error.
+    /// The default implementation panics as `with_borrow` does.
+    fn try_with_borrow<F, R>(&self, f: F) -> Result<R, StorageAccessError>
+    where
+        F: FnOnce(&T) -> R,
+    {
+        Ok(self.with_borrow(f))
+    }
+
+    /// Returns a `Storage` of a part of the contained value, e.g. a field of a larger state struct.
+    ///
+    /// This allows a single state to back several services that require different storages.
+    fn map<U>(self, get: fn(&T) -> &U, get_mut: fn(&mut T) -> &mut U) -> MappedStorage<Self, T, U>
+    where
+        Self: Sized,
+    {
+        MappedStorage::new(self, get, get_mut)
+    }
+}
+
+/// A `Storage` of a part of the value of another `Storage`,
+/// projected by the `get` and `get_mut` functions.
+pub struct MappedStorage<S, T, U> {
+    storage: S,
+    get: fn(&T) -> &U,
+    get_mut: fn(&mut T) -> &mut U,
+    phantom: PhantomData<fn(T) -> U>,
+}
+
+impl<S: Storage<T>, T, U> MappedStorage<S, T, U> {
+    /// Creates a storage that projects the value of `storage` with `get` and `get_mut`.
+    pub fn new(storage: S, get: fn(&T) -> &U, get_mut: fn(&mut T) -> &mut U) -> Self {
+        Self {
+            storage,
+            get,
+            get_mut,
+            phantom: PhantomData,
+        }
+    }
+
+    /// Returns the inner storage.
+    pub fn into_inner(self) -> S {
+        self.storage
+    }
+}
+
+impl<S: Storage<T>, T, U> Storage<U> for MappedStorage<S, T, U> {
+    fn with_borrow_mut<F, R>(&mut self, f: F) -> R
+    where
+        F: FnOnce(&mut U) -> R,
+    {
+        let get_mut = self.get_mut;
+        self.storage.with_borrow_mut(|value| f(get_mut(value)))
+    }
+
+    fn with_borrow<F, R>(&self, f: F) -> R
+    where
+        F: FnOnce(&U) -> R,
+    {
+        let get = self.get;
+        self.storage.with_borrow(|value| f(get(value)))
+    }
+
+    fn try_with_borrow_mut<F, R>(&mut self, f: F) -> Result<R, StorageAccessError>
+    where
+        F: FnOnce(&mut U) -> R,
+    {
+        let get_mut = self.get_mut;
+        self.storage.try_with_borrow_mut(|value| f(get_mut(value)))
+    }
+
+    fn try_with_borrow<F, R>(&self, f: F) -> Result<R, StorageAccessError>
+    where
+        F: FnOnce(&U) -> R,
+    {
+        let get = self.get;
+        self.storage.try_with_borrow(|value| f(get(value)))
+    }
 }
 
 //
@@ -53,6 +188,20 @@ impl<T> Storage<T> for RefCell<T> {
     {
         f(&self.borrow())
     }
+
+    fn try_with_borrow_mut<F, R>(&mut self, f: F) -> Result<R, StorageAccessError>
+    where
+        F: FnOnce(&mut T) -> R,
+    {
+        Ok(f(&mut self.try_borrow_mut()?))
+    }
+
+    fn try_with_borrow<F, R>(&self, f: F) -> Result<R, StorageAccessError>
+    where
+        F: FnOnce(&T) -> R,
+    {
+        Ok(f(&self.try_borrow()?))
+    }
 }
 
 impl<T> Storage<T> for Rc<RefCell<T>> {
@@ -69,6 +218,20 @@ impl<T> Storage<T> for Rc<RefCell<T>> {
     {
         f(&self.borrow())
     }
+
+    fn try_with_borrow_mut<F, R>(&mut self, f: F) -> Result<R, StorageAccessError>
+    where
+        F: FnOnce(&mut T) -> R,
+    {
+        Ok(f(&mut self.try_borrow_mut()?))
+    }
+
+    fn try_with_borrow<F, R>(&self, f: F) -> Result<R, StorageAccessError>
+    where
+        F: FnOnce(&T) -> R,
+    {
+        Ok(f(&self.try_borrow()?))
+    }
 }
 
 impl<T> Storage<T> for Mutex<T> {
@@ -85,6 +248,20 @@ impl<T> Storage<T> for Mutex<T> {
     {
         f(&self.lock().unwrap())
     }
+
+    fn try_with_borrow_mut<F, R>(&mut self, f: F) -> Result<R, StorageAccessError>
+    where
+        F: FnOnce(&mut T) -> R,
+    {
+        Ok(f(self.get_mut()?))
+    }
+
+    fn try_with_borrow<F, R>(&self, f: F) -> Result<R, StorageAccessError>
+    where
+        F: FnOnce(&T) -> R,
+    {
+        Ok(f(&self.lock()?))
+    }
 }
 
 impl<T> Storage<T> for Arc<Mutex<T>> {
@@ -101,6 +278,20 @@ impl<T> Storage<T> for Arc<Mutex<T>> {
     {
         f(&self.lock().unwrap())
     }
+
+    fn try_with_borrow_mut<F, R>(&mut self, f: F) -> Result<R, StorageAccessError>
+    where
+        F: FnOnce(&mut T) -> R,
+    {
+        Ok(f(&mut self.lock()?))
+    }
+
+    fn try_with_borrow<F, R>(&self, f: F) -> Result<R, StorageAccessError>
+    where
+        F: FnOnce(&T) -> R,
+    {
+        Ok(f(&self.lock()?))
+    }
 }
 
 impl<T> Storage<T> for RwLock<T> {
@@ -117,6 +308,20 @@ impl<T> Storage<T> for RwLock<T> {
     {
         f(&self.read().unwrap())
     }
+
+    fn try_with_borrow_mut<F, R>(&mut self, f: F) -> Result<R, StorageAccessError>
+    where
+        F: FnOnce(&mut T) -> R,
+    {
+        Ok(f(&mut self.write()?))
+    }
+
+    fn try_with_borrow<F, R>(&self, f: F) -> Result<R, StorageAccessError>
+    where
+        F: FnOnce(&T) -> R,
+    {
+        Ok(f(&self.read()?))
+    }
 }
 
 impl<T> Storage<T> for Arc<RwLock<T>> {
@@ -133,6 +338,20 @@ impl<T> Storage<T> for Arc<RwLock<T>> {
     {
         f(&self.read().unwrap())
     }
+
+    fn try_with_borrow_mut<F, R>(&mut self, f: F) -> Result<R, StorageAccessError>
+    where
+        F: FnOnce(&mut T) -> R,
+    {
+        Ok(f(&mut self.write()?))
+    }
+
+    fn try_with_borrow<F, R>(&self, f: F) -> Result<R, StorageAccessError>
+    where
+        F: FnOnce(&T) -> R,
+    {
+        Ok(f(&self.read()?))
+    }
 }
 
 impl<T: 'static> Storage<T> for &'static LocalKey<RefCell<T>> {
@@ -149,4 +368,229 @@ impl<T: 'static> Storage<T> for &'static LocalKey<RefCell<T>> {
     {
         LocalKey::with_borrow(self, f)
     }
+
+    fn try_with_borrow_mut<F, R>(&mut self, f: F) -> Result<R, StorageAccessError>
+    where
+        F: FnOnce(&mut T) -> R,
+    {
+        LocalKey::with(self, |cell| Ok(f(&mut cell.try_borrow_mut()?)))
+    }
+
+    fn try_with_borrow<F, R>(&self, f: F) -> Result<R, StorageAccessError>
+    where
+        F: FnOnce(&T) -> R,
+    {
+        LocalKey::with(self, |cell| Ok(f(&cell.try_borrow()?)))
+    }
+}
+
+/// The message of the panic raised when a lazily initialized storage is accessed before `init_storage`.
+const UNINITIALIZED_STORAGE: &str = "storage not initialized: call init_storage first";
+
+/// A thread-local storage whose value is set after the thread-local is constructed,
+/// e.g. because it depends on the init args of the canister.
+///
+/// WARN: `with_borrow` and `with_borrow_mut` panic if the value was not set with `init_storage`,
+/// the `try_` variants return `StorageAccessError::Uninitialized` instead.
+impl<T: 'static> Storage<T> for &'static LocalKey<RefCell<Option<T>>> {
+    fn with_borrow_mut<F, R>(&mut self, f: F) -> R
+    where
+        F: FnOnce(&mut T) -> R,
+    {
+        LocalKey::with_borrow_mut(self, |value| {
+            f(value.as_mut().expect(UNINITIALIZED_STORAGE))
+        })
+    }
+
+    fn with_borrow<F, R>(&self, f: F) -> R
+    where
+        F: FnOnce(&T) -> R,
+    {
+        LocalKey::with_borrow(self, |value| {
+            f(value.as_ref().expect(UNINITIALIZED_STORAGE))
+        })
+    }
+
+    fn try_with_borrow_mut<F, R>(&mut self, f: F) -> Result<R, StorageAccessError>
+    where
+        F: FnOnce(&mut T) -> R,
+    {
+        LocalKey::with(self, |cell| {
+            let mut value = cell.try_borrow_mut()?;
+            let value = value.as_mut().ok_or(StorageAccessError::Uninitialized)?;
+            Ok(f(value))
+        })
+    }
+
+    fn try_with_borrow<F, R>(&self, f: F) -> Result<R, StorageAccessError>
+    where
+        F: FnOnce(&T) -> R,
+    {
+        LocalKey::with(self, |cell| {
+            let value = cell.try_borrow()?;
+            let value = value.as_ref().ok_or(StorageAccessError::Uninitialized)?;
+            Ok(f(value))
+        })
+    }
+}
+
+/// Sets the value of a lazily initialized thread-local storage, replacing the previous one if any.
+pub fn init_storage<T: 'static>(storage: &'static LocalKey<RefCell<Option<T>>>, value: T) {
+    storage.set(Some(value));
+}
+
+/// Returns true if the value of a lazily initialized thread-local storage was set.
+pub fn is_initialized<T: 'static>(storage: &'static LocalKey<RefCell<Option<T>>>) -> bool {
+    storage.with_borrow(Option::is_some)
+}
+
+/// The tokio locks are acquired with the blocking functions, so that they can be used by the
+/// synchronous services.
+///
+/// WARN: the blocking functions panic if called from an asynchronous execution context,
+/// e.g. from an `async` function executed by the tokio runtime; use `spawn_blocking` in that case.
+#[cfg(feature = "tokio")]
+mod tokio_impls {
+    use std::sync::Arc;
+
+    use tokio::sync::{Mutex, RwLock};
+
+    use super::Storage;
+
+    impl<T> Storage<T> for Mutex<T> {
+        fn with_borrow_mut<F, R>(&mut self, f: F) -> R
+        where
+            F: FnOnce(&mut T) -> R,
+        {
+            f(self.get_mut())
+        }
+
+        fn with_borrow<F, R>(&self, f: F) -> R
+        where
+            F: FnOnce(&T) -> R,
+        {
+            f(&self.blocking_lock())
+        }
+    }
+
+    impl<T> Storage<T> for Arc<Mutex<T>> {
+        fn with_borrow_mut<F, R>(&mut self, f: F) -> R
+        where
+            F: FnOnce(&mut T) -> R,
+        {
+            f(&mut self.blocking_lock())
+        }
+
+        fn with_borrow<F, R>(&self, f: F) -> R
+        where
+            F: FnOnce(&T) -> R,
+        {
+            f(&self.blocking_lock())
+        }
+    }
+
+    impl<T> Storage<T> for RwLock<T> {
+        fn with_borrow_mut<F, R>(&mut self, f: F) -> R
+        where
+            F: FnOnce(&mut T) -> R,
+        {
+            f(self.get_mut())
+        }
+
+        fn with_borrow<F, R>(&self, f: F) -> R
+        where
+            F: FnOnce(&T) -> R,
+        {
+            f(&self.blocking_read())
+        }
+    }
+
+    impl<T> Storage<T> for Arc<RwLock<T>> {
+        fn with_borrow_mut<F, R>(&mut self, f: F) -> R
+        where
+            F: FnOnce(&mut T) -> R,
+        {
+            f(&mut self.blocking_write())
+        }
+
+        fn with_borrow<F, R>(&self, f: F) -> R
+        where
+            F: FnOnce(&T) -> R,
+        {
+            f(&self.blocking_read())
+        }
+    }
+}
+
+#[cfg(feature = "parking_lot")]
+mod parking_lot_impls {
+    use std::sync::Arc;
+
+    use parking_lot::{Mutex, RwLock};
+
+    use super::Storage;
+
+    impl<T> Storage<T> for Mutex<T> {
+        fn with_borrow_mut<F, R>(&mut self, f: F) -> R
+        where
+            F: FnOnce(&mut T) -> R,
+        {
+            f(self.get_mut())
+        }
+
+        fn with_borrow<F, R>(&self, f: F) -> R
+        where
+            F: FnOnce(&T) -> R,
+        {
+            f(&self.lock())
+        }
+    }
+
+    impl<T> Storage<T> for Arc<Mutex<T>> {
+        fn with_borrow_mut<F, R>(&mut self, f: F) -> R
+        where
+            F: FnOnce(&mut T) -> R,
+        {
+            f(&mut self.lock())
+        }
+
+        fn with_borrow<F, R>(&self, f: F) -> R
+        where
+            F: FnOnce(&T) -> R,
+        {
+            f(&self.lock())
+        }
+    }
+
+    impl<T> Storage<T> for RwLock<T> {
+        fn with_borrow_mut<F, R>(&mut self, f: F) -> R
+        where
+            F: FnOnce(&mut T) -> R,
+        {
+            f(self.get_mut())
+        }
+
+        fn with_borrow<F, R>(&self, f: F) -> R
+        where
+            F: FnOnce(&T) -> R,
+        {
+            f(&self.read())
+        }
+    }
+
+    impl<T> Storage<T> for Arc<RwLock<T>> {
+        fn with_borrow_mut<F, R>(&mut self, f: F) -> R
+        where
+            F: FnOnce(&mut T) -> R,
+        {
+            f(&mut self.write())
+        }
+
+        fn with_borrow<F, R>(&self, f: F) -> R
+        where
+            F: FnOnce(&T) -> R,
+        {
+            f(&self.read())
+        }
+    }
 }
@@ -0,0 +1,258 @@
+use std::{cell::RefCell, collections::BTreeMap, rc::Rc, time::Duration};
+
+use crate::timer::TimerTrait;
+
+/// The identifier of a timer of the `MockTimers`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct MockTimerId(u64);
+
+enum Callback {
+    Once(Box<dyn FnOnce()>),
+    Interval {
+        interval_nanos: u64,
+        callback: Box<dyn FnMut()>,
+    },
+}
+
+struct MockTimer {
+    due_nanos: u64,
+    callback: Callback,
+}
+
+#[derive(Default)]
+struct MockTimersState {
+    now_nanos: u64,
+    next_id: u64,
+    timers: BTreeMap<MockTimerId, MockTimer>,
+    /// The interval timer being executed, and whether it was cleared by its own callback
+    firing: Option<(MockTimerId, bool)>,
+}
+
+/// A mocked implementation of the timers for testing.
+///
+/// The time of the timers starts from zero and it is advanced only manually,
+/// by `advance` and `fire_due`, which execute the due callbacks synchronously.
+/// The clones share the same timers.
+#[derive(Clone, Default)]
+pub struct MockTimers {
+    state: Rc<RefCell<MockTimersState>>,
+}
+
+impl MockTimers {
+    /// Returns the current time of the timers, as elapsed since their creation.
+    pub fn now(&self) -> Duration {
+        Duration::from_nanos(self.state.borrow().now_nanos)
+    }
+
+    /// Returns the number of the active timers.
+    pub fn len(&self) -> usize {
+        self.state.borrow().timers.len()
+    }
+
+    /// Returns true if there are no active timers.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Advances the time by `duration` and executes the due callbacks.
+    /// Returns the number of the executed callbacks.
+    pub fn advance(&self, duration: Duration) -> usize {
+        let now = self.now() + duration;
+        self.fire_due(now)
+    }
+
+    /// Moves the time forward to `now` and executes the callbacks due by then, in the order
+    /// of their due time. An interval timer is executed once for each interval elapsed.
+    /// Returns the number of the executed callbacks.
+    ///
+    /// The callbacks can set and clear timers.
+    pub fn fire_due(&self, now: Duration) -> usize {
+        let now_nanos = now.as_nanos() as u64;
+        {
+            let mut state = self.state.borrow_mut();
+            state.now_nanos = state.now_nanos.max(now_nanos);
+        }
+
+        let mut fired = 0;
+        while let Some((timer_id, timer)) = self.pop_due(now_nanos) {
+            fired += 1;
+            match timer.callback {
+                Callback::Once(callback) => callback(),
+                Callback::Interval {
+                    interval_nanos,
+                    mut callback,
+                } => {
+                    self.state.borrow_mut().firing = Some((timer_id, false));
+                    callback();
+                    let mut state = self.state.borrow_mut();
+                    if let Some((_, false)) = state.firing.take() {
+                        state.timers.insert(
+                            timer_id,
+                            MockTimer {
+                                due_nanos: timer.due_nanos + interval_nanos,
+                                callback: Callback::Interval {
+                                    interval_nanos,
+                                    callback,
+                                },
+                            },
+                        );
+                    }
+                }
+            }
+        }
+        fired
+    }
+
+    /// Removes the timer with the lowest due time, if it is due by `now_nanos`.
+    fn pop_due(&self, now_nanos: u64) -> Option<(MockTimerId, MockTimer)> {
+        let mut state = self.state.borrow_mut();
+        let timer_id = state
+            .timers
+            .iter()
+            .filter(|(_, timer)| timer.due_nanos <= now_nanos)
+            .min_by_key(|(timer_id, timer)| (timer.due_nanos, **timer_id))
+            .map(|(timer_id, _)| *timer_id)?;
+        state
+            .timers
+            .remove(&timer_id)
+            .map(|timer| (timer_id, timer))
+    }
+
+    fn insert(&self, delay: Duration, callback: Callback) -> MockTimerId {
+        let mut state = self.state.borrow_mut();
+        let timer_id = MockTimerId(state.next_id);
+        state.next_id += 1;
+        let due_nanos = state.now_nanos.saturating_add(delay.as_nanos() as u64);
+        state.timers.insert(
+            timer_id,
+            MockTimer {
+                due_nanos,
+                callback,
+            },
+        );
+        timer_id
+    }
+}
+
+impl TimerTrait for MockTimers {
+    type TimerId = MockTimerId;
+
+    fn set_timer(&self, delay: Duration, callback: impl FnOnce() + 'static) -> Self::TimerId {
+        self.insert(delay, Callback::Once(Box::new(callback)))
+    }
+
+    /// A zero interval is executed as an interval of one nanosecond.
+    fn set_timer_interval(
+        &self,
+        interval: Duration,
+        callback: impl FnMut() + 'static,
+    ) -> Self::TimerId {
+        let interval_nanos = (interval.as_nanos() as u64).max(1);
+        self.insert(
+            interval,
+            Callback::Interval {
+                interval_nanos,
+                callback: Box::new(callback),
+            },
+        )
+    }
+
+    fn clear_timer(&self, timer_id: Self::TimerId) {
+        let mut state = self.state.borrow_mut();
+        if state.timers.remove(&timer_id).is_none()
+            && let Some((firing_id, cleared)) = state.firing.as_mut()
+            && *firing_id == timer_id
+        {
+            *cleared = true;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::cell::Cell;
+
+    use super::*;
+
+    #[test]
+    fn should_fire_an_interval_three_times() {
+        let timers = MockTimers::default();
+        let counter = Rc::new(Cell::new(0));
+        let counter_cb = counter.clone();
+        timers.set_timer_interval(Duration::from_secs(10), move || {
+            counter_cb.set(counter_cb.get() + 1)
+        });
+
+        assert_eq!(timers.advance(Duration::from_secs(9)), 0);
+        assert_eq!(timers.advance(Duration::from_secs(1)), 1);
+        assert_eq!(timers.advance(Duration::from_secs(10)), 1);
+        assert_eq!(timers.advance(Duration::from_secs(10)), 1);
+
+        assert_eq!(counter.get(), 3);
+        assert_eq!(timers.now(), Duration::from_secs(30));
+        assert_eq!(timers.len(), 1);
+    }
+
+    #[test]
+    fn should_fire_a_timer_once() {
+        let timers = MockTimers::default();
+        let counter = Rc::new(Cell::new(0));
+        let counter_cb = counter.clone();
+        timers.set_timer(Duration::from_secs(5), move || {
+            counter_cb.set(counter_cb.get() + 1)
+        });
+
+        assert_eq!(timers.fire_due(Duration::from_secs(100)), 1);
+        assert_eq!(timers.fire_due(Duration::from_secs(200)), 0);
+        assert_eq!(counter.get(), 1);
+        assert!(timers.is_empty());
+    }
+
+    #[test]
+    fn should_fire_the_elapsed_intervals_in_order() {
+        let timers = MockTimers::default();
+        let calls = Rc::new(RefCell::new(Vec::new()));
+        let calls_a = calls.clone();
+        let calls_b = calls.clone();
+        timers.set_timer_interval(Duration::from_secs(2), move || {
+            calls_a.borrow_mut().push('a')
+        });
+        timers.set_timer(Duration::from_secs(3), move || {
+            calls_b.borrow_mut().push('b')
+        });
+
+        assert_eq!(timers.advance(Duration::from_secs(6)), 4);
+        assert_eq!(*calls.borrow(), vec!['a', 'b', 'a', 'a']);
+    }
+
+    #[test]
+    fn should_clear_timers() {
+        let timers = MockTimers::default();
+        let counter = Rc::new(Cell::new(0));
+
+        let counter_cb = counter.clone();
+        let timer_id = timers.set_timer(Duration::from_secs(1), move || {
+            counter_cb.set(counter_cb.get() + 1)
+        });
+        timers.clear_timer(timer_id);
+
+        // an interval that clears itself at the second execution
+        let counter_cb = counter.clone();
+        let timers_cb = timers.clone();
+        let interval_id = Rc::new(Cell::new(None));
+        let interval_id_cb = interval_id.clone();
+        interval_id.set(Some(timers.set_timer_interval(
+            Duration::from_secs(1),
+            move || {
+                counter_cb.set(counter_cb.get() + 1);
+                if counter_cb.get() == 2 {
+                    timers_cb.clear_timer(interval_id_cb.get().unwrap());
+                }
+            },
+        )));
+
+        assert_eq!(timers.advance(Duration::from_secs(10)), 2);
+        assert_eq!(counter.get(), 2);
+        assert!(timers.is_empty());
+    }
+}
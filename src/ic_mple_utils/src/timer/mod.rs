@@ -0,0 +1,50 @@
+use std::time::Duration;
+
+pub mod mock;
+
+/// A wrapper trait for the IC timers.
+/// It allows us to use a mock implementation that is triggered manually in tests.
+pub trait TimerTrait {
+    /// The identifier of a timer.
+    type TimerId: Copy;
+
+    /// Sets `callback` to be executed once after `delay`.
+    fn set_timer(&self, delay: Duration, callback: impl FnOnce() + 'static) -> Self::TimerId;
+
+    /// Sets `callback` to be executed every `interval`, until the timer is cleared.
+    fn set_timer_interval(
+        &self,
+        interval: Duration,
+        callback: impl FnMut() + 'static,
+    ) -> Self::TimerId;
+
+    /// Cancels the timer. It has no effect if the timer was already executed or cleared.
+    fn clear_timer(&self, timer_id: Self::TimerId);
+}
+
+/// The implementation of the timers based on `ic_cdk_timers`
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct IcTimers;
+
+impl TimerTrait for IcTimers {
+    type TimerId = ic_cdk_timers::TimerId;
+
+    fn set_timer(&self, delay: Duration, callback: impl FnOnce() + 'static) -> Self::TimerId {
+        ic_cdk_timers::set_timer(delay, async move { callback() })
+    }
+
+    fn set_timer_interval(
+        &self,
+        interval: Duration,
+        mut callback: impl FnMut() + 'static,
+    ) -> Self::TimerId {
+        ic_cdk_timers::set_timer_interval(interval, move || {
+            callback();
+            async {}
+        })
+    }
+
+    fn clear_timer(&self, timer_id: Self::TimerId) {
+        ic_cdk_timers::clear_timer(timer_id)
+    }
+}
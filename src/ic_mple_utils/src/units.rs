@@ -0,0 +1,334 @@
+use std::fmt;
+use std::str::FromStr;
+
+use candid::CandidType;
+use candid::types::{Serializer, Type};
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+const E_12: u128 = 1_000_000_000_000;
+
+/// An amount of cycles.
+#[derive(
+    Clone, Copy, Debug, Default, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize,
+)]
+#[serde(transparent)]
+pub struct Cycles(pub u128);
+
+impl Cycles {
+    pub const ZERO: Cycles = Cycles(0);
+
+    /// Returns the cycles of an amount of trillion cycles (TC).
+    /// The amount is rounded to the nearest cycle, and negative amounts are zero.
+    pub fn from_tc(tc: f64) -> Self {
+        Self((tc * E_12 as f64).round() as u128)
+    }
+
+    /// Returns the amount in trillion cycles (TC).
+    pub fn as_tc(self) -> f64 {
+        self.0 as f64 / E_12 as f64
+    }
+
+    pub fn checked_add(self, other: Cycles) -> Option<Cycles> {
+        self.0.checked_add(other.0).map(Cycles)
+    }
+
+    pub fn checked_sub(self, other: Cycles) -> Option<Cycles> {
+        self.0.checked_sub(other.0).map(Cycles)
+    }
+
+    pub fn checked_mul(self, factor: u128) -> Option<Cycles> {
+        self.0.checked_mul(factor).map(Cycles)
+    }
+
+    pub fn saturating_add(self, other: Cycles) -> Cycles {
+        Cycles(self.0.saturating_add(other.0))
+    }
+
+    pub fn saturating_sub(self, other: Cycles) -> Cycles {
+        Cycles(self.0.saturating_sub(other.0))
+    }
+}
+
+impl From<u128> for Cycles {
+    fn from(cycles: u128) -> Self {
+        Self(cycles)
+    }
+}
+
+impl From<Cycles> for u128 {
+    fn from(cycles: Cycles) -> Self {
+        cycles.0
+    }
+}
+
+/// Formats the cycles in TC with three decimals, truncated, e.g. "1.234 TC".
+impl fmt::Display for Cycles {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let whole = self.0 / E_12;
+        let millis = self.0 % E_12 / 1_000_000_000;
+        write!(f, "{whole}.{millis:03} TC")
+    }
+}
+
+impl CandidType for Cycles {
+    fn _ty() -> Type {
+        u128::ty()
+    }
+
+    fn idl_serialize<S: Serializer>(&self, serializer: S) -> Result<(), S::Error> {
+        self.0.idl_serialize(serializer)
+    }
+}
+
+/// The error returned when a string is not a valid amount of `Tokens`.
+#[derive(Debug, Error, Clone, PartialEq, Eq)]
+pub enum TokensParseError {
+    #[error("the amount is empty")]
+    Empty,
+
+    #[error("the amount must contain only digits and an optional decimal point")]
+    InvalidCharacter,
+
+    #[error("the amount has more than {max} decimals")]
+    TooManyDecimals { max: u8 },
+
+    #[error("the amount does not fit in 128 bits")]
+    Overflow,
+}
+
+/// An amount of tokens, stored as the number of subunits of a token with `DECIMALS` decimals,
+/// e.g. `Tokens<8>(100_000_000)` is 1 token with 8 decimals.
+#[derive(
+    Clone, Copy, Debug, Default, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize,
+)]
+#[serde(transparent)]
+pub struct Tokens<const DECIMALS: u8>(pub u128);
+
+impl<const DECIMALS: u8> Tokens<DECIMALS> {
+    pub const DECIMALS: u8 = DECIMALS;
+    pub const ZERO: Self = Self(0);
+
+    /// Returns the amount of subunits.
+    pub fn subunits(self) -> u128 {
+        self.0
+    }
+
+    pub fn checked_add(self, other: Self) -> Option<Self> {
+        self.0.checked_add(other.0).map(Self)
+    }
+
+    pub fn checked_sub(self, other: Self) -> Option<Self> {
+        self.0.checked_sub(other.0).map(Self)
+    }
+
+    pub fn checked_mul(self, factor: u128) -> Option<Self> {
+        self.0.checked_mul(factor).map(Self)
+    }
+}
+
+impl<const DECIMALS: u8> From<u128> for Tokens<DECIMALS> {
+    fn from(subunits: u128) -> Self {
+        Self(subunits)
+    }
+}
+
+/// Parses a decimal amount of tokens, e.g. "12.5", into subunits.
+impl<const DECIMALS: u8> FromStr for Tokens<DECIMALS> {
+    type Err = TokensParseError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if s.is_empty() {
+            return Err(TokensParseError::Empty);
+        }
+        let (whole, fraction) = s.split_once('.').unwrap_or((s, ""));
+        if whole.is_empty()
+            || !whole
+                .bytes()
+                .chain(fraction.bytes())
+                .all(|b| b.is_ascii_digit())
+        {
+            return Err(TokensParseError::InvalidCharacter);
+        }
+        if fraction.len() > DECIMALS as usize {
+            return Err(TokensParseError::TooManyDecimals { max: DECIMALS });
+        }
+
+        let digits = format!("{whole}{fraction:0<width$}", width = DECIMALS as usize);
+        let digits = digits.trim_start_matches('0');
+        if digits.is_empty() {
+            return Ok(Self::ZERO);
+        }
+        // the digits are validated, so the parsing fails only on overflow
+        digits
+            .parse()
+            .map(Self)
+            .map_err(|_| TokensParseError::Overflow)
+    }
+}
+
+/// Formats the amount of tokens without the trailing zeros of the decimals, e.g. "12.5".
+impl<const DECIMALS: u8> fmt::Display for Tokens<DECIMALS> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let decimals = DECIMALS as usize;
+        let digits = format!("{:0>width$}", self.0, width = decimals + 1);
+        let (whole, fraction) = digits.split_at(digits.len() - decimals);
+        let fraction = fraction.trim_end_matches('0');
+        if fraction.is_empty() {
+            write!(f, "{whole}")
+        } else {
+            write!(f, "{whole}.{fraction}")
+        }
+    }
+}
+
+impl<const DECIMALS: u8> CandidType for Tokens<DECIMALS> {
+    fn _ty() -> Type {
+        u128::ty()
+    }
+
+    fn idl_serialize<S: Serializer>(&self, serializer: S) -> Result<(), S::Error> {
+        self.0.idl_serialize(serializer)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use candid::{Decode, Encode};
+
+    use super::*;
+
+    #[test]
+    fn should_convert_cycles_to_and_from_tc() {
+        assert_eq!(Cycles::from_tc(1.0), Cycles(E_12));
+        assert_eq!(Cycles::from_tc(0.5), Cycles(500_000_000_000));
+        assert_eq!(Cycles::from_tc(1.234), Cycles(1_234_000_000_000));
+        assert_eq!(Cycles::from_tc(0.0), Cycles::ZERO);
+        assert_eq!(Cycles::from_tc(-1.0), Cycles::ZERO);
+        assert_eq!(Cycles(2 * E_12).as_tc(), 2.0);
+        assert_eq!(Cycles(250_000_000_000).as_tc(), 0.25);
+
+        for tc in [0.001, 0.1, 1.5, 3.0, 42.125, 1_000.0] {
+            assert_eq!(Cycles::from_tc(tc).as_tc(), tc);
+        }
+    }
+
+    #[test]
+    fn should_check_the_cycles_arithmetic() {
+        assert_eq!(Cycles(1).checked_add(Cycles(2)), Some(Cycles(3)));
+        assert_eq!(Cycles(u128::MAX).checked_add(Cycles(1)), None);
+        assert_eq!(Cycles(3).checked_sub(Cycles(2)), Some(Cycles(1)));
+        assert_eq!(Cycles(2).checked_sub(Cycles(3)), None);
+        assert_eq!(Cycles(E_12).checked_mul(3), Some(Cycles(3 * E_12)));
+        assert_eq!(Cycles(u128::MAX / 2 + 1).checked_mul(2), None);
+        assert_eq!(
+            Cycles(u128::MAX).saturating_add(Cycles(1)),
+            Cycles(u128::MAX)
+        );
+        assert_eq!(Cycles(1).saturating_sub(Cycles(2)), Cycles::ZERO);
+    }
+
+    #[test]
+    fn should_display_cycles_in_tc() {
+        assert_eq!(Cycles(1_234_567_890_123).to_string(), "1.234 TC");
+        assert_eq!(Cycles(E_12).to_string(), "1.000 TC");
+        assert_eq!(Cycles(999_999_999).to_string(), "0.000 TC");
+        assert_eq!(Cycles(5_000_000_000).to_string(), "0.005 TC");
+        assert_eq!(Cycles(42 * E_12).to_string(), "42.000 TC");
+    }
+
+    #[test]
+    fn should_parse_tokens() {
+        assert_eq!("1".parse(), Ok(Tokens::<8>(100_000_000)));
+        assert_eq!("1.5".parse(), Ok(Tokens::<8>(150_000_000)));
+        assert_eq!("0.00000001".parse(), Ok(Tokens::<8>(1)));
+        assert_eq!("000.10".parse(), Ok(Tokens::<8>(10_000_000)));
+        assert_eq!("0".parse(), Ok(Tokens::<8>::ZERO));
+        assert_eq!("12.".parse(), Ok(Tokens::<2>(1_200)));
+        assert_eq!("7".parse(), Ok(Tokens::<0>(7)));
+    }
+
+    #[test]
+    fn should_reject_invalid_tokens() {
+        assert_eq!("".parse::<Tokens<8>>(), Err(TokensParseError::Empty));
+        assert_eq!(
+            ".5".parse::<Tokens<8>>(),
+            Err(TokensParseError::InvalidCharacter)
+        );
+        assert_eq!(
+            "-1".parse::<Tokens<8>>(),
+            Err(TokensParseError::InvalidCharacter)
+        );
+        assert_eq!(
+            "1.2.3".parse::<Tokens<8>>(),
+            Err(TokensParseError::InvalidCharacter)
+        );
+        assert_eq!(
+            "1e8".parse::<Tokens<8>>(),
+            Err(TokensParseError::InvalidCharacter)
+        );
+        assert_eq!(
+            "0.001".parse::<Tokens<2>>(),
+            Err(TokensParseError::TooManyDecimals { max: 2 })
+        );
+        assert_eq!(
+            "1.5".parse::<Tokens<0>>(),
+            Err(TokensParseError::TooManyDecimals { max: 0 })
+        );
+    }
+
+    #[test]
+    fn should_reject_overflowing_tokens() {
+        let max = u128::MAX.to_string();
+        assert_eq!(max.parse(), Ok(Tokens::<0>(u128::MAX)));
+        assert_eq!(
+            format!("{max}0").parse::<Tokens<0>>(),
+            Err(TokensParseError::Overflow)
+        );
+        // the subunits of 1 token with 39 decimals do not fit in 128 bits
+        assert_eq!("1".parse::<Tokens<39>>(), Err(TokensParseError::Overflow));
+        assert_eq!(
+            "0.000000000000000000000000000000000000001".parse(),
+            Ok(Tokens::<39>(1))
+        );
+    }
+
+    #[test]
+    fn should_display_tokens() {
+        assert_eq!(Tokens::<8>(150_000_000).to_string(), "1.5");
+        assert_eq!(Tokens::<8>(100_000_000).to_string(), "1");
+        assert_eq!(Tokens::<8>(1).to_string(), "0.00000001");
+        assert_eq!(Tokens::<8>::ZERO.to_string(), "0");
+        assert_eq!(Tokens::<0>(42).to_string(), "42");
+        assert_eq!(
+            Tokens::<39>(u128::MAX).to_string(),
+            format!("0.{}", u128::MAX)
+        );
+
+        for value in [0, 1, 10, 99, 123_456_789, u128::MAX] {
+            let tokens = Tokens::<6>(value);
+            assert_eq!(tokens.to_string().parse(), Ok(tokens));
+        }
+    }
+
+    #[test]
+    fn should_check_the_tokens_arithmetic() {
+        assert_eq!(Tokens::<8>(1).checked_add(Tokens(2)), Some(Tokens(3)));
+        assert_eq!(Tokens::<8>(u128::MAX).checked_add(Tokens(1)), None);
+        assert_eq!(Tokens::<8>(1).checked_sub(Tokens(2)), None);
+        assert_eq!(Tokens::<8>(u128::MAX).checked_mul(2), None);
+    }
+
+    #[test]
+    fn should_encode_the_units_as_candid_nat() {
+        let cycles = Cycles(1_234);
+        let bytes = Encode!(&cycles).unwrap();
+        assert_eq!(bytes, Encode!(&1_234u128).unwrap());
+        assert_eq!(Decode!(&bytes, Cycles).unwrap(), cycles);
+
+        let tokens = Tokens::<8>(5);
+        let bytes = Encode!(&tokens).unwrap();
+        assert_eq!(bytes, Encode!(&5u128).unwrap());
+        assert_eq!(Decode!(&bytes, Tokens<8>).unwrap(), tokens);
+    }
+}